@@ -0,0 +1,108 @@
+use crate::needle::Needle;
+use crate::needle_matcher::NeedleMatcher;
+use crate::process_data::find_matches_in_chunk;
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use std::time::{Duration, Instant};
+
+const BENCHMARK_SYNTHETIC_NEEDLE_COUNT: usize = 8;
+
+/// Same as the chunk size for uncompressed images.
+pub const BENCHMARK_CHUNK_SIZE_BYTES: usize = 8 * 1024 * 1024;
+
+pub struct BenchmarkResult {
+    pub bytes_searched: u64,
+    /// Time spent searching only (not generating the synthetic data).
+    pub search_duration: Duration,
+    pub match_count: u64,
+}
+
+impl BenchmarkResult {
+    pub fn mib_per_sec(&self) -> f64 {
+        self.bytes_searched as f64 / 1024.0 / 1024.0 / self.search_duration.as_secs_f64()
+    }
+}
+
+/// Random needles of 4 to 32 bytes, for benchmarking without a needle config.
+pub fn make_synthetic_needles(seed: u64) -> Vec<Needle> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..BENCHMARK_SYNTHETIC_NEEDLE_COUNT)
+        .map(|needle_idx| {
+            let mut val = vec![0u8; rng.gen_range(4..=32)];
+            rng.fill_bytes(&mut val);
+            Needle {
+                name: format!("benchmark_{}", needle_idx),
+                val,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Measures the throughput of the matching engine (`find_matches_in_chunk`) on `total_size_bytes`
+/// of pseudo-random data, in chunks of `chunk_size_bytes`, with each needle planted once per chunk
+/// at a random position (in its own slot of the chunk, so they don't overwrite each other).
+/// No image is read, and no output is written.
+pub fn run_benchmark(
+    needles: &[Needle],
+    total_size_bytes: usize,
+    chunk_size_bytes: usize,
+    chunk_search_threads: usize,
+) -> BenchmarkResult {
+    let needle_matcher = NeedleMatcher::new(needles).expect("Could not build the needle matcher");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut haystack_chunk = vec![0u8; chunk_size_bytes];
+    let mut bytes_searched: u64 = 0;
+    let mut search_duration = Duration::ZERO;
+    let mut match_count: u64 = 0;
+
+    while (bytes_searched as usize) < total_size_bytes {
+        let chunk_len = chunk_size_bytes.min(total_size_bytes - bytes_searched as usize);
+        let haystack_chunk = &mut haystack_chunk[..chunk_len];
+        rng.fill_bytes(haystack_chunk);
+        let slot_len = chunk_len / needles.len().max(1);
+        for (needle_idx, needle) in needles.iter().enumerate() {
+            if needle.val.len() <= slot_len {
+                let pos = needle_idx * slot_len + rng.gen_range(0..=(slot_len - needle.val.len()));
+                haystack_chunk[pos..(pos + needle.val.len())].copy_from_slice(&needle.val);
+            }
+        }
+
+        let search_start_time = Instant::now();
+        let chunk_matches = find_matches_in_chunk(
+            haystack_chunk,
+            needles,
+            &needle_matcher,
+            chunk_search_threads,
+            0,
+        );
+        search_duration += search_start_time.elapsed();
+
+        match_count += chunk_matches.len() as u64;
+        bytes_searched += chunk_len as u64;
+    }
+
+    BenchmarkResult {
+        bytes_searched,
+        search_duration,
+        match_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_benchmark() {
+        let needles = make_synthetic_needles(0);
+        let benchmark_result = run_benchmark(&needles, 1024 * 1024, 256 * 1024, 1);
+
+        assert_eq!(benchmark_result.bytes_searched, 1024 * 1024);
+        // every planted needle is found (in each of the 4 chunks)
+        assert!(benchmark_result.match_count >= 4 * needles.len() as u64);
+        assert!(benchmark_result.mib_per_sec() > 0.0);
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+use std::ops::Range;
+
+const JPEG_SOI: [u8; 3] = [0xFF, 0xD8, 0xFF];
+const ZIP_LOCAL_FILE_HEADER: [u8; 4] = *b"PK\x03\x04";
+const ZIP_END_OF_CENTRAL_DIR: [u8; 4] = *b"PK\x05\x06";
+const ZIP_END_OF_CENTRAL_DIR_LEN: usize = 22;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CarvedFileType {
+    Jpeg,
+    Zip,
+}
+
+impl fmt::Display for CarvedFileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CarvedFileType::Jpeg => write!(f, "JPEG"),
+            CarvedFileType::Zip => write!(f, "ZIP"),
+        }
+    }
+}
+
+/// A well-known file embedded in the haystack chunk, found by its signature and structure.
+#[derive(Debug, PartialEq)]
+pub struct CarvedFile {
+    pub file_type: CarvedFileType,
+    /// Extent of the file within the haystack chunk.
+    pub range_in_chunk: Range<usize>,
+}
+
+/// Finds complete JPEG and ZIP files within the haystack chunk (carve-and-exclude pre-pass).
+///
+/// Only files that start and end within the chunk are found. Files are walked by structure (JPEG
+/// segments, ZIP central directory), rather than just looking for an end marker, so a random
+/// signature match doesn't swallow the rest of the chunk.
+pub fn find_carved_files(haystack: &[u8]) -> Vec<CarvedFile> {
+    let zip_end_of_central_dir_positions = find_all(haystack, &ZIP_END_OF_CENTRAL_DIR, 0);
+
+    let mut carved_files = Vec::new();
+    let mut pos = 0;
+    while pos < haystack.len() {
+        let carved_file = if haystack[pos..].starts_with(&JPEG_SOI) {
+            find_jpeg_end(haystack, pos).map(|end| CarvedFile {
+                file_type: CarvedFileType::Jpeg,
+                range_in_chunk: pos..end,
+            })
+        } else if haystack[pos..].starts_with(&ZIP_LOCAL_FILE_HEADER) {
+            find_zip_end(haystack, pos, &zip_end_of_central_dir_positions).map(|end| CarvedFile {
+                file_type: CarvedFileType::Zip,
+                range_in_chunk: pos..end,
+            })
+        } else {
+            None
+        };
+
+        match carved_file {
+            Some(carved_file) => {
+                // files nested inside this one (e.g., EXIF thumbnails) are already covered
+                pos = carved_file.range_in_chunk.end;
+                carved_files.push(carved_file);
+            }
+            None => pos += 1,
+        }
+    }
+    carved_files
+}
+
+/// Returns the carved file that `match_range` lies entirely within, if any.
+pub fn find_carved_file_containing<'a>(
+    carved_files: &'a [CarvedFile],
+    match_range: &Range<usize>,
+) -> Option<&'a CarvedFile> {
+    carved_files.iter().find(|carved_file| {
+        carved_file.range_in_chunk.start <= match_range.start
+            && match_range.end <= carved_file.range_in_chunk.end
+    })
+}
+
+fn find_all(haystack: &[u8], needle: &[u8], start: usize) -> Vec<usize> {
+    haystack[start..]
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(pos, _)| start + pos)
+        .collect()
+}
+
+/// Walks the JPEG's marker segments from the SOI at `start`, and returns the end of its EOI marker.
+fn find_jpeg_end(haystack: &[u8], start: usize) -> Option<usize> {
+    let mut pos = start + 2; // after SOI
+    loop {
+        if *haystack.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *haystack.get(pos + 1)?;
+        match marker {
+            0xFF => pos += 1,               // fill byte
+            0xD9 => return Some(pos + 2),   // EOI
+            0x01 | 0xD0..=0xD7 => pos += 2, // standalone markers
+            0xC0..=0xFE => {
+                let segment_len =
+                    u16::from_be_bytes([*haystack.get(pos + 2)?, *haystack.get(pos + 3)?]) as usize;
+                if segment_len < 2 {
+                    return None;
+                }
+                pos += 2 + segment_len;
+                if marker == 0xDA {
+                    // SOS: skip the entropy-coded data, up to the next marker that isn't a restart marker
+                    while haystack.get(pos)? != &0xFF
+                        || matches!(haystack.get(pos + 1)?, 0x00 | 0xD0..=0xD7)
+                    {
+                        pos += 1;
+                    }
+                }
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Finds the end of central directory record for the ZIP starting at `start`, and returns the end
+/// of the record (including its comment). The record must point back to a central directory that
+/// ends right before it, relative to `start`.
+fn find_zip_end(
+    haystack: &[u8],
+    start: usize,
+    zip_end_of_central_dir_positions: &[usize],
+) -> Option<usize> {
+    zip_end_of_central_dir_positions
+        .iter()
+        .filter(|&&eocd_pos| eocd_pos > start)
+        .find_map(|&eocd_pos| {
+            let eocd = haystack.get(eocd_pos..(eocd_pos + ZIP_END_OF_CENTRAL_DIR_LEN))?;
+            let central_dir_size = u32::from_le_bytes([eocd[12], eocd[13], eocd[14], eocd[15]]);
+            let central_dir_offset = u32::from_le_bytes([eocd[16], eocd[17], eocd[18], eocd[19]]);
+            let comment_len = u16::from_le_bytes([eocd[20], eocd[21]]) as usize;
+            let end = eocd_pos + ZIP_END_OF_CENTRAL_DIR_LEN + comment_len;
+            match (central_dir_offset as u64 + central_dir_size as u64 == (eocd_pos - start) as u64)
+                && end <= haystack.len()
+            {
+                true => Some(end),
+                false => None,
+            }
+        })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// A minimal but structurally valid JPEG, with `payload` in its entropy-coded data.
+    pub fn make_jpeg(payload: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8]; // SOI
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0x00]); // APP0
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02]); // SOS (empty header)
+        jpeg.extend_from_slice(payload);
+        jpeg.extend_from_slice(&[0xFF, 0x00, 0x12, 0xFF, 0xD3, 0x34]); // stuffed byte, restart marker
+        jpeg.extend_from_slice(&[0xFF, 0xD9]); // EOI
+        jpeg
+    }
+
+    /// A minimal ZIP with no files in it, except a local file header at the start.
+    fn make_zip(payload: &[u8]) -> Vec<u8> {
+        let mut zip = ZIP_LOCAL_FILE_HEADER.to_vec();
+        zip.extend_from_slice(payload);
+        let central_dir_offset = zip.len() as u32;
+        let central_dir = b"PK\x01\x02central_dir";
+        zip.extend_from_slice(central_dir);
+        zip.extend_from_slice(&ZIP_END_OF_CENTRAL_DIR);
+        zip.extend_from_slice(&[0u8; 8]);
+        zip.extend_from_slice(&(central_dir.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&central_dir_offset.to_le_bytes());
+        zip.extend_from_slice(&3u16.to_le_bytes());
+        zip.extend_from_slice(b"abc"); // comment
+        zip
+    }
+
+    #[test]
+    fn test_find_carved_files() {
+        let jpeg = make_jpeg(b"inside the jpeg");
+        let zip = make_zip(b"inside the zip");
+        let mut haystack = b"junk \xFF\xD8\xFF not a jpeg PK\x03\x04 not a zip".to_vec();
+        let jpeg_start = haystack.len();
+        haystack.extend_from_slice(&jpeg);
+        haystack.extend_from_slice(b"between");
+        let zip_start = haystack.len();
+        haystack.extend_from_slice(&zip);
+        haystack.extend_from_slice(b"trailing junk");
+
+        assert_eq!(
+            find_carved_files(&haystack),
+            vec![
+                CarvedFile {
+                    file_type: CarvedFileType::Jpeg,
+                    range_in_chunk: jpeg_start..(jpeg_start + jpeg.len()),
+                },
+                CarvedFile {
+                    file_type: CarvedFileType::Zip,
+                    range_in_chunk: zip_start..(zip_start + zip.len()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_carved_files_ignores_truncated_jpeg() {
+        let jpeg = make_jpeg(b"inside the jpeg");
+        assert_eq!(find_carved_files(&jpeg[..(jpeg.len() - 1)]), vec![]);
+    }
+
+    #[test]
+    fn test_find_carved_file_containing() {
+        let carved_files = vec![CarvedFile {
+            file_type: CarvedFileType::Jpeg,
+            range_in_chunk: 100..200,
+        }];
+        assert_eq!(
+            find_carved_file_containing(&carved_files, &(100..105)),
+            Some(&carved_files[0])
+        );
+        assert!(find_carved_file_containing(&carved_files, &(195..200)).is_some());
+        assert!(find_carved_file_containing(&carved_files, &(98..103)).is_none());
+        assert!(find_carved_file_containing(&carved_files, &(198..203)).is_none());
+    }
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Seconds between checkpoints, during a search.
+pub const CHECKPOINT_INTERVAL_SEC: f32 = 30.0;
+
+/// How far a search got, written to the output directory during the run, so an interrupted search
+/// can be continued with `--resume <results_dir>`, instead of starting over.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub drive_image_searcher_version: String,
+    /// Hash of the run's config (see `config_hash`). A run can only be resumed with the same config.
+    pub config_hash: String,
+    /// Every match that ends at or before this global offset has been recorded.
+    pub searched_end_global_offset: u64,
+    /// True once the whole stream was searched, so there's nothing left to resume.
+    pub is_complete: bool,
+}
+
+impl Checkpoint {
+    pub fn file_path(results_dir: &Path) -> PathBuf {
+        results_dir.join("08_checkpoint.json")
+    }
+
+    /// Writes the checkpoint to a temporary file, then renames it over the previous checkpoint, so
+    /// a crash mid-write never leaves a broken checkpoint behind.
+    pub fn write_to_json_file(&self, json_file_path: &Path) -> io::Result<()> {
+        let tmp_file_path = json_file_path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_file_path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_file_path, json_file_path)
+    }
+
+    pub fn read_from_json_file(json_file_path: &Path) -> Result<Self, String> {
+        let json_str = fs::read_to_string(json_file_path).map_err(|e| {
+            format!(
+                "Could not read checkpoint file {}: {}",
+                json_file_path.display(),
+                e
+            )
+        })?;
+        serde_json::from_str(&json_str).map_err(|e| {
+            format!(
+                "Could not parse checkpoint file {}: {}",
+                json_file_path.display(),
+                e
+            )
+        })
+    }
+
+    /// Checks that a search with `config_hash` can continue from this checkpoint.
+    pub fn check_resumable(&self, config_hash: &str) -> Result<(), String> {
+        if self.is_complete {
+            return Err("The search already finished, so there's nothing to resume".to_string());
+        }
+        if self.config_hash != config_hash {
+            return Err(format!(
+                "The config doesn't match the interrupted run's (config hash {}, expected {}). Use the same input file, compression format, needles, and needle filters.",
+                config_hash, self.config_hash
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Hash of everything that decides which matches a run finds: the input file, how it's
+/// decompressed, the needle config, and which of its needles are searched for.
+pub fn config_hash(
+    input_file_name: &str,
+    compression_format: &str,
+    needle_config_text: &str,
+    needle_names: &[&str],
+) -> String {
+    let mut hasher = Sha256::new();
+    for part in [input_file_name, compression_format, needle_config_text]
+        .into_iter()
+        .chain(needle_names.iter().copied())
+    {
+        // length-prefixed, so the parts can't run into each other
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trip_and_check_resumable() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let run_config_hash = config_hash("img.bin", "xz", "- name: a", &["a"]);
+        let checkpoint = Checkpoint {
+            drive_image_searcher_version: "1.0.0".to_string(),
+            config_hash: run_config_hash.clone(),
+            searched_end_global_offset: 123_456,
+            is_complete: false,
+        };
+        let checkpoint_file_path = Checkpoint::file_path(tmp_dir.path());
+        checkpoint
+            .write_to_json_file(&checkpoint_file_path)
+            .unwrap();
+        let read_checkpoint = Checkpoint::read_from_json_file(&checkpoint_file_path).unwrap();
+        assert_eq!(read_checkpoint, checkpoint);
+        assert!(read_checkpoint.check_resumable(&run_config_hash).is_ok());
+
+        // a different needle selection is a different config
+        let other_config_hash = config_hash("img.bin", "xz", "- name: a", &[]);
+        assert_ne!(other_config_hash, run_config_hash);
+        assert!(read_checkpoint.check_resumable(&other_config_hash).is_err());
+
+        let complete_checkpoint = Checkpoint {
+            is_complete: true,
+            ..checkpoint
+        };
+        assert!(complete_checkpoint
+            .check_resumable(&run_config_hash)
+            .is_err());
+    }
+}
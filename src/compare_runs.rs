@@ -0,0 +1,154 @@
+use std::error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+
+/// Matches present in one run's results but not the other, keyed on `(name, match_start_global_offset)`.
+pub struct RunComparison {
+    /// Matches only in run A (i.e., "removed" going from A to B)
+    pub only_in_a: DataFrame,
+    /// Matches only in run B (i.e., "added" going from A to B)
+    pub only_in_b: DataFrame,
+}
+
+/// Accepts either a results directory, or the path to its JSONL file directly.
+pub fn resolve_results_jsonl_path(results_path: &Path) -> PathBuf {
+    match results_path.is_dir() {
+        true => results_path.join("00_all_output_record.jsonl"),
+        false => results_path.to_path_buf(),
+    }
+}
+
+fn load_match_keys(
+    jsonl_file_path: &Path,
+) -> std::result::Result<LazyFrame, Box<dyn error::Error>> {
+    let mut file = File::open(jsonl_file_path)
+        .map_err(|e| format!("Could not open {}: {}", jsonl_file_path.display(), e))?;
+    let df = JsonLineReader::new(&mut file).finish()?;
+    Ok(df
+        .lazy()
+        .select([
+            col("name"),
+            col("match_start_global_offset"),
+            col("happiness_level"),
+        ])
+        .unique(None, UniqueKeepStrategy::First))
+}
+
+pub fn compare_runs(
+    jsonl_file_path_a: &Path,
+    jsonl_file_path_b: &Path,
+) -> std::result::Result<RunComparison, Box<dyn error::Error>> {
+    let matches_a = load_match_keys(jsonl_file_path_a)?;
+    let matches_b = load_match_keys(jsonl_file_path_b)?;
+    let join_cols = [col("name"), col("match_start_global_offset")];
+    let sort_cols = ["name", "match_start_global_offset"];
+
+    let only_in_a = matches_a
+        .clone()
+        .join(
+            matches_b.clone(),
+            join_cols.clone(),
+            join_cols.clone(),
+            JoinArgs::new(JoinType::Anti),
+        )
+        .sort(sort_cols, SortMultipleOptions::default())
+        .collect()?;
+    let only_in_b = matches_b
+        .join(
+            matches_a,
+            join_cols.clone(),
+            join_cols,
+            JoinArgs::new(JoinType::Anti),
+        )
+        .sort(sort_cols, SortMultipleOptions::default())
+        .collect()?;
+
+    Ok(RunComparison {
+        only_in_a,
+        only_in_b,
+    })
+}
+
+impl RunComparison {
+    pub fn make_summary_message(&self) -> String {
+        format!(
+            "{} match(es) only in run A (removed):\n{}\n{} match(es) only in run B (added):\n{}",
+            self.only_in_a.height(),
+            self.only_in_a,
+            self.only_in_b.height(),
+            self.only_in_b
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::found_needle::LineEnding;
+    use crate::found_needle::NeedleValFound;
+    use crate::needle::Needle;
+
+    fn write_results_jsonl(jsonl_file_path: &Path, matches: &[(&str, u64)]) {
+        for (name, offset) in matches {
+            let needle = Needle {
+                name: name.to_string(),
+                val: b"Hello".to_vec(),
+                happiness_level: 2,
+                ..Default::default()
+            };
+            NeedleValFound::from_needle_val(&needle, *offset, Path::new("/images/disk.img"))
+                .append_to_jsonl_file(&jsonl_file_path.to_path_buf(), LineEnding::Lf)
+                .unwrap();
+        }
+    }
+
+    fn get_keys(df: &DataFrame) -> Vec<(String, u64)> {
+        let names = df.column("name").unwrap().str().unwrap();
+        let offsets = df.column("match_start_global_offset").unwrap();
+        let offsets = offsets.cast(&DataType::UInt64).unwrap();
+        let offsets = offsets.u64().unwrap();
+        names
+            .into_iter()
+            .zip(offsets)
+            .map(|(name, offset)| (name.unwrap().to_string(), offset.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_runs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir_a = tmp_dir.path().join("results_a");
+        let dir_b = tmp_dir.path().join("results_b");
+        std::fs::create_dir(&dir_a).unwrap();
+        std::fs::create_dir(&dir_b).unwrap();
+
+        write_results_jsonl(
+            &resolve_results_jsonl_path(&dir_a),
+            &[("needle_1", 100), ("needle_1", 200), ("needle_2", 100)],
+        );
+        write_results_jsonl(
+            &resolve_results_jsonl_path(&dir_b),
+            &[("needle_1", 100), ("needle_2", 100), ("needle_2", 300)],
+        );
+
+        let run_comparison = compare_runs(
+            &resolve_results_jsonl_path(&dir_a),
+            &resolve_results_jsonl_path(&dir_b),
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_keys(&run_comparison.only_in_a),
+            vec![("needle_1".to_string(), 200)]
+        );
+        assert_eq!(
+            get_keys(&run_comparison.only_in_b),
+            vec![("needle_2".to_string(), 300)]
+        );
+        assert!(run_comparison
+            .make_summary_message()
+            .starts_with("1 match(es) only in run A"));
+    }
+}
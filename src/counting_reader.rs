@@ -0,0 +1,71 @@
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Wraps a reader, and counts the bytes read through it. The count is shared, so it can still be
+/// read once the reader is owned by a decoder (like the compressed bytes consumed by an lz4
+/// decoder, which doesn't count them itself).
+pub struct CountingReader<R: Read> {
+    inner: R,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            bytes_read: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The shared count of the bytes read so far.
+    pub fn bytes_read_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.bytes_read)
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        self.bytes_read
+            .fetch_add(bytes_read as u64, Ordering::Relaxed);
+        Ok(bytes_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multi_frame_lz4::tests::lz4_compress;
+    use crate::multi_frame_lz4::MultiFrameLz4Decoder;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn test_counting_reader_counts_lz4_input() {
+        let image: Vec<u8> = (0..1_000_000).map(|i| (i % 251) as u8).collect();
+        let compressed = lz4_compress(&image);
+        assert!(compressed.len() < image.len());
+
+        let counting_reader = CountingReader::new(Cursor::new(compressed.clone()));
+        let compressed_bytes_read = counting_reader.bytes_read_counter();
+        let mut lz4_decoder = MultiFrameLz4Decoder::new(BufReader::new(counting_reader));
+
+        let mut first_bytes = vec![0u8; 1000];
+        lz4_decoder.read_exact(&mut first_bytes).unwrap();
+        let partial_bytes_read = compressed_bytes_read.load(Ordering::Relaxed);
+        assert!(partial_bytes_read > 0);
+
+        let mut decompressed = first_bytes;
+        lz4_decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, image);
+        assert_eq!(
+            compressed_bytes_read.load(Ordering::Relaxed),
+            compressed.len() as u64
+        );
+        assert!(partial_bytes_read <= compressed.len() as u64);
+    }
+}
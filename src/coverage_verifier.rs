@@ -0,0 +1,258 @@
+use crate::display_hex::display_hex_offset;
+
+use std::fmt;
+
+/// A problem found by the `CoverageVerifier`, in global (uncompressed stream) offsets.
+#[derive(Debug, PartialEq)]
+pub enum CoverageProblem {
+    /// Bytes in `start..end` were read from the stream, but never searched.
+    Gap { start: u64, end: u64 },
+    /// Bytes in `start..end` were searched more than once, beyond the expected carry-forward overlap.
+    Overlap { start: u64, end: u64 },
+    /// The chunk claimed to start at `claimed_start`, but its bytes don't match the stream at that offset.
+    /// This means matches in the chunk are reported at the wrong offsets.
+    ContentMismatch { claimed_start: u64, len: u64 },
+}
+
+impl CoverageProblem {
+    pub fn start_global_offset(&self) -> u64 {
+        match self {
+            CoverageProblem::Gap { start, .. } | CoverageProblem::Overlap { start, .. } => *start,
+            CoverageProblem::ContentMismatch { claimed_start, .. } => *claimed_start,
+        }
+    }
+}
+
+impl fmt::Display for CoverageProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoverageProblem::Gap { start, end } => write!(
+                f,
+                "Gap: bytes 0x{}..0x{} were never searched",
+                display_hex_offset(*start, 1),
+                display_hex_offset(*end, 1)
+            ),
+            CoverageProblem::Overlap { start, end } => write!(
+                f,
+                "Overlap: bytes 0x{}..0x{} were searched more than once (beyond the carry-forward)",
+                display_hex_offset(*start, 1),
+                display_hex_offset(*end, 1)
+            ),
+            CoverageProblem::ContentMismatch { claimed_start, len } => write!(
+                f,
+                "Content mismatch: chunk claimed to be at 0x{}..0x{}, but its bytes differ from the stream there",
+                display_hex_offset(*claimed_start, 1),
+                display_hex_offset(*claimed_start + *len, 1)
+            ),
+        }
+    }
+}
+
+/// Diagnostic for the chunked search loop (`--single-pass-verify`).
+///
+/// It keeps its own copy of the raw stream bytes (fed straight from each read), and compares every
+/// searched chunk against the stream at the offset the chunk claims to start at. This proves that
+/// the carry-forward and offset math are right: every byte is searched, nothing is searched twice
+/// (except the carry-forward overlap), and every chunk is reported at its true offset.
+pub struct CoverageVerifier {
+    carry_forward_len: u64,
+
+    /// Raw stream bytes, starting at global offset `stream_window_start`.
+    stream_window: Vec<u8>,
+    stream_window_start: u64,
+
+    /// End of the searched region so far (exclusive).
+    searched_end: u64,
+
+    pub problems: Vec<CoverageProblem>,
+}
+
+impl CoverageVerifier {
+    pub fn new(carry_forward_len: usize) -> Self {
+        Self {
+            carry_forward_len: carry_forward_len as u64,
+            stream_window: Vec::new(),
+            stream_window_start: 0,
+            searched_end: 0,
+            problems: Vec::new(),
+        }
+    }
+
+    fn stream_len(&self) -> u64 {
+        self.stream_window_start + self.stream_window.len() as u64
+    }
+
+    /// Call before any bytes are recorded, when the stream starts partway through (`--resume-from-offset`).
+    pub fn start_at(&mut self, global_offset: u64) {
+        self.stream_window_start = global_offset;
+        self.searched_end = global_offset;
+    }
+
+    /// Call with the exact bytes returned by each read of the input stream.
+    pub fn record_stream_bytes(&mut self, bytes: &[u8]) {
+        self.stream_window.extend_from_slice(bytes);
+    }
+
+    /// Call with each chunk passed to the search, and the global offset the search reports it at.
+    pub fn check_searched_chunk(&mut self, chunk_start_global_offset: u64, chunk: &[u8]) {
+        let chunk_end_global_offset = chunk_start_global_offset + chunk.len() as u64;
+
+        // check coverage against the previous chunks
+        if chunk_start_global_offset > self.searched_end {
+            self.problems.push(CoverageProblem::Gap {
+                start: self.searched_end,
+                end: chunk_start_global_offset,
+            });
+        } else if chunk_start_global_offset + self.carry_forward_len < self.searched_end {
+            self.problems.push(CoverageProblem::Overlap {
+                start: chunk_start_global_offset,
+                end: self.searched_end - self.carry_forward_len,
+            });
+        }
+
+        // check the chunk's contents are really the stream bytes at the claimed offset
+        // (bytes from before the retained window were already reported as an overlap, so only check the rest)
+        let verifiable_start = chunk_start_global_offset.max(self.stream_window_start);
+        let content_matches = if chunk_end_global_offset > self.stream_len() {
+            false // claims bytes that haven't been read yet
+        } else if verifiable_start >= chunk_end_global_offset {
+            true
+        } else {
+            self.stream_window[((verifiable_start - self.stream_window_start) as usize)
+                ..((chunk_end_global_offset - self.stream_window_start) as usize)]
+                == chunk[((verifiable_start - chunk_start_global_offset) as usize)..]
+        };
+        if !content_matches {
+            self.problems.push(CoverageProblem::ContentMismatch {
+                claimed_start: chunk_start_global_offset,
+                len: chunk.len() as u64,
+            });
+        }
+
+        self.searched_end = self.searched_end.max(chunk_end_global_offset);
+
+        // drop stream bytes that can't be part of any later chunk
+        let keep_from = self
+            .searched_end
+            .saturating_sub(self.carry_forward_len)
+            .clamp(self.stream_window_start, self.stream_len());
+        self.stream_window
+            .drain(..((keep_from - self.stream_window_start) as usize));
+        self.stream_window_start = keep_from;
+    }
+
+    /// Call once the stream is exhausted, to catch any trailing bytes that were never searched.
+    pub fn finish(&mut self) {
+        if self.searched_end < self.stream_len() {
+            self.problems.push(CoverageProblem::Gap {
+                start: self.searched_end,
+                end: self.stream_len(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_stream(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_correct_chunking_has_no_problems() {
+        let stream = make_stream(1000);
+        let mut verifier = CoverageVerifier::new(10);
+
+        verifier.record_stream_bytes(&stream[0..400]);
+        verifier.check_searched_chunk(0, &stream[0..400]);
+        verifier.record_stream_bytes(&stream[400..790]);
+        verifier.check_searched_chunk(390, &stream[390..790]);
+        verifier.record_stream_bytes(&stream[790..1000]);
+        verifier.check_searched_chunk(780, &stream[780..1000]);
+        verifier.finish();
+
+        assert_eq!(verifier.problems, vec![]);
+    }
+
+    #[test]
+    fn test_detects_gap() {
+        let stream = make_stream(1000);
+        let mut verifier = CoverageVerifier::new(10);
+
+        verifier.record_stream_bytes(&stream[0..400]);
+        verifier.check_searched_chunk(0, &stream[0..400]);
+        // artificially skip 100 bytes of the stream
+        verifier.record_stream_bytes(&stream[400..1000]);
+        verifier.check_searched_chunk(500, &stream[500..1000]);
+        verifier.finish();
+
+        assert_eq!(
+            verifier.problems,
+            vec![CoverageProblem::Gap {
+                start: 400,
+                end: 500
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detects_trailing_gap() {
+        let stream = make_stream(1000);
+        let mut verifier = CoverageVerifier::new(10);
+
+        verifier.record_stream_bytes(&stream[0..1000]);
+        verifier.check_searched_chunk(0, &stream[0..900]);
+        verifier.finish();
+
+        assert_eq!(
+            verifier.problems,
+            vec![CoverageProblem::Gap {
+                start: 900,
+                end: 1000
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detects_overlap() {
+        let stream = make_stream(1000);
+        let mut verifier = CoverageVerifier::new(10);
+
+        verifier.record_stream_bytes(&stream[0..400]);
+        verifier.check_searched_chunk(0, &stream[0..400]);
+        verifier.record_stream_bytes(&stream[400..1000]);
+        verifier.check_searched_chunk(300, &stream[300..1000]);
+        verifier.finish();
+
+        assert_eq!(
+            verifier.problems,
+            vec![CoverageProblem::Overlap {
+                start: 300,
+                end: 390
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detects_misattributed_offset() {
+        // the carry-forward is searched, but the chunk is reported as if it started after it
+        let stream = make_stream(1000);
+        let mut verifier = CoverageVerifier::new(10);
+
+        verifier.record_stream_bytes(&stream[0..400]);
+        verifier.check_searched_chunk(0, &stream[0..400]);
+        verifier.record_stream_bytes(&stream[400..1000]);
+        verifier.check_searched_chunk(400, &stream[390..1000]);
+        verifier.finish();
+
+        assert_eq!(
+            verifier.problems,
+            vec![CoverageProblem::ContentMismatch {
+                claimed_start: 400,
+                len: 610
+            }]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use std::str::FromStr;
+
+const SECTOR_SIZE_BYTES: u64 = 512;
+
+/// Cylinder/head/sector disk geometry (`--chs`), for reporting matches in CHS terms on old images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiskGeometry {
+    pub cylinders: u64,
+    pub heads: u64,
+    pub sectors_per_track: u64,
+}
+
+/// A CHS address. Sectors are 1-based, as is traditional; cylinders and heads are 0-based.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChsAddress {
+    pub cylinder: u64,
+    pub head: u64,
+    pub sector: u64,
+}
+
+impl fmt::Display for ChsAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.cylinder, self.head, self.sector)
+    }
+}
+
+impl DiskGeometry {
+    /// Returns the CHS address of the sector holding `byte_offset`, or None if it's past the end
+    /// of the disk (per the geometry).
+    pub fn chs_address(&self, byte_offset: u64) -> Option<ChsAddress> {
+        let lba = byte_offset / SECTOR_SIZE_BYTES;
+        let cylinder = lba / (self.heads * self.sectors_per_track);
+        if cylinder >= self.cylinders {
+            return None;
+        }
+        Some(ChsAddress {
+            cylinder,
+            head: (lba / self.sectors_per_track) % self.heads,
+            sector: (lba % self.sectors_per_track) + 1,
+        })
+    }
+}
+
+impl FromStr for DiskGeometry {
+    type Err = String;
+
+    /// Parses "cylinders,heads,sectors" (sectors per track), like "1024,16,63".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let vals: Vec<u64> = s
+            .split(',')
+            .map(|val| {
+                val.trim()
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid number '{}'", val))
+            })
+            .collect::<Result<Vec<u64>, String>>()?;
+        match vals[..] {
+            [cylinders, heads, sectors_per_track]
+                if cylinders > 0 && heads > 0 && sectors_per_track > 0 =>
+            {
+                Ok(Self {
+                    cylinders,
+                    heads,
+                    sectors_per_track,
+                })
+            }
+            _ => Err(format!(
+                "Expected 'cylinders,heads,sectors' (all positive), got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chs_address() {
+        let disk_geometry = DiskGeometry::from_str("1024,16,63").unwrap();
+
+        assert_eq!(
+            disk_geometry.chs_address(0),
+            Some(ChsAddress {
+                cylinder: 0,
+                head: 0,
+                sector: 1
+            })
+        );
+        // LBA 63 is the first sector of head 1 (the classic first partition start)
+        assert_eq!(
+            disk_geometry.chs_address(63 * 512).unwrap().to_string(),
+            "0/1/1"
+        );
+        // LBA 100_000, partway into a sector: 100_000 = 99 * (16 * 63) + 3 * 63 + 19
+        assert_eq!(
+            disk_geometry.chs_address(100_000 * 512 + 300),
+            Some(ChsAddress {
+                cylinder: 99,
+                head: 3,
+                sector: 20
+            })
+        );
+        // past the last cylinder
+        assert_eq!(disk_geometry.chs_address(1024 * 16 * 63 * 512), None);
+    }
+
+    #[test]
+    fn test_parse_disk_geometry() {
+        assert!(DiskGeometry::from_str("1024,16").is_err());
+        assert!(DiskGeometry::from_str("1024,0,63").is_err());
+        assert!(DiskGeometry::from_str("1024,16,x").is_err());
+    }
+}
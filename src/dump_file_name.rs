@@ -0,0 +1,163 @@
+use crate::display_hex::display_hex_offset;
+use crate::needle::Needle;
+
+/// The usual dump file name: the match's global offset, and its offset within the dump.
+pub const DEFAULT_DUMP_FILE_NAME_TEMPLATE: &str = "found_g_0x{offset}_startat_0x{start}";
+
+const PLACEHOLDERS: [&str; 5] = ["{needle}", "{offset}", "{start}", "{index}", "{happiness}"];
+
+/// Characters that aren't allowed in file names on at least one common filesystem.
+const UNSAFE_FILE_NAME_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Template for the names of per-match `.bin` dump files (`--dump-filename-template`).
+///
+/// Placeholders: `{needle}` (needle name), `{offset}` (the match's global offset, in hex),
+/// `{start}` (the match's offset within the dump, in hex), `{index}` (the match's number in this
+/// run, from 0), and `{happiness}` (the needle's happiness level). `.bin` is added to the end.
+/// Merged dumps (`--merge-dump-distance`) keep their usual names.
+#[derive(Clone, Debug)]
+pub struct DumpFileNameTemplate {
+    template: String,
+}
+
+impl Default for DumpFileNameTemplate {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_DUMP_FILE_NAME_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl DumpFileNameTemplate {
+    /// Checks that `template` names each match's dump uniquely (it needs `{offset}` or `{index}`),
+    /// and that it renders to a filesystem-safe name for each of `needles`.
+    pub fn new(template: &str, needles: &[Needle]) -> Result<Self, String> {
+        if !template.contains("{offset}") && !template.contains("{index}") {
+            return Err(format!(
+                "Dump filename template '{}' needs {{offset}} or {{index}}, so each match's dump gets its own name",
+                template
+            ));
+        }
+        let unknown_placeholders = PLACEHOLDERS
+            .iter()
+            .fold(template.to_string(), |rest, placeholder| {
+                rest.replace(placeholder, "")
+            });
+        if unknown_placeholders.contains(['{', '}']) {
+            return Err(format!(
+                "Dump filename template '{}' has an unknown placeholder (known ones: {})",
+                template,
+                PLACEHOLDERS.join(", ")
+            ));
+        }
+        let dump_file_name_template = Self {
+            template: template.to_string(),
+        };
+        for needle in needles {
+            let file_name = dump_file_name_template.render(needle, u64::MAX, u64::MAX, usize::MAX);
+            if let Some(unsafe_char) = file_name
+                .chars()
+                .find(|c| UNSAFE_FILE_NAME_CHARS.contains(c) || c.is_control())
+            {
+                return Err(format!(
+                    "Dump filename template '{}' renders an unsafe file name for needle '{}' ({:?} isn't allowed): {}",
+                    template, needle.name, unsafe_char, file_name
+                ));
+            }
+        }
+        Ok(dump_file_name_template)
+    }
+
+    /// Renders the dump file name (with `.bin`) for a match of `needle`.
+    pub fn render(
+        &self,
+        needle: &Needle,
+        match_start_global_offset: u64,
+        offset_within_file: u64,
+        match_index: usize,
+    ) -> String {
+        let file_name = self
+            .template
+            .replace(
+                "{offset}",
+                &display_hex_offset(match_start_global_offset, 20),
+            )
+            .replace("{start}", &display_hex_offset(offset_within_file, 1))
+            .replace("{index}", &match_index.to_string())
+            .replace("{happiness}", &needle.happiness_level.to_string())
+            // last, so placeholders in the needle name aren't replaced
+            .replace("{needle}", &needle.name);
+        format!("{}.bin", file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn make_needle(name: &str) -> Needle {
+        Needle {
+            name: name.to_string(),
+            val: b"Hello".to_vec(),
+            happiness_level: 7,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_template_renders_usual_name() {
+        let file_name = DumpFileNameTemplate::default().render(&make_needle("hello"), 0x1000, 4, 0);
+        assert_eq!(
+            file_name,
+            format!(
+                "found_g_0x{}_startat_0x4.bin",
+                display_hex_offset(0x1000, 20)
+            )
+        );
+    }
+
+    #[test]
+    fn test_custom_template_is_unique_per_match() {
+        let needles = vec![make_needle("hello"), make_needle("world")];
+        let dump_file_name_template =
+            DumpFileNameTemplate::new("h{happiness}-{needle}-{index}-{start}", &needles).unwrap();
+        assert_eq!(
+            dump_file_name_template.render(&needles[0], 0x1000, 0x400, 3),
+            "h7-hello-3-400.bin"
+        );
+
+        // the same needle at the same place in its dump, many times
+        let file_names: HashSet<String> = (0..1000)
+            .map(|match_index| {
+                dump_file_name_template.render(
+                    &needles[match_index % 2],
+                    match_index as u64 * 512,
+                    0x400,
+                    match_index,
+                )
+            })
+            .collect();
+        assert_eq!(file_names.len(), 1000);
+
+        let dump_file_name_template =
+            DumpFileNameTemplate::new("{needle}@{offset}", &needles).unwrap();
+        assert_ne!(
+            dump_file_name_template.render(&needles[0], 0x1000, 0, 0),
+            dump_file_name_template.render(&needles[0], 0x1001, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let needles = vec![make_needle("hello"), make_needle("dir/name")];
+        // not unique per match
+        assert!(DumpFileNameTemplate::new("{needle}-{start}", &needles[..1]).is_err());
+        // unsafe characters, in the template or a needle name
+        assert!(DumpFileNameTemplate::new("{needle}:{offset}", &needles[..1]).is_err());
+        assert!(DumpFileNameTemplate::new("{needle}-{offset}", &needles).is_err());
+        // unknown placeholder
+        assert!(DumpFileNameTemplate::new("{offset}-{sector}", &needles[..1]).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+pub const DEFAULT_DUMP_WRITE_QUEUE_LEN: usize = 64;
+
+struct DumpJob {
+    dump_file_path: PathBuf,
+    bytes: Vec<u8>,
+}
+
+/// Writes haystack chunk dumps (`.bin` files) on a background thread, so the search doesn't stall on slow disks.
+///
+/// Jobs go through a bounded queue: when it's full, `submit` blocks until the writer catches up.
+/// After the first write error, the rest of the queued jobs are dropped, and the error is
+/// available from `take_error` so the search can stop dumping.
+pub struct DumpWriter {
+    job_sender: Option<SyncSender<DumpJob>>,
+    writer_thread: Option<JoinHandle<()>>,
+    first_error: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl DumpWriter {
+    pub fn new(queue_len: usize) -> Self {
+        let (job_sender, job_receiver) = sync_channel::<DumpJob>(queue_len);
+        let first_error: Arc<Mutex<Option<io::Error>>> = Arc::new(Mutex::new(None));
+
+        let thread_first_error = Arc::clone(&first_error);
+        let writer_thread = thread::spawn(move || {
+            let mut has_failed = false;
+            for dump_job in job_receiver {
+                if has_failed {
+                    continue; // drain the queue, so `submit` never blocks forever
+                }
+                if let Err(e) = write_dump_file(&dump_job.dump_file_path, &dump_job.bytes) {
+                    has_failed = true;
+                    *thread_first_error.lock().unwrap() = Some(e);
+                }
+            }
+        });
+
+        Self {
+            job_sender: Some(job_sender),
+            writer_thread: Some(writer_thread),
+            first_error,
+        }
+    }
+
+    /// Queues a dump to be written. Blocks if the queue is full.
+    pub fn submit(&self, dump_file_path: PathBuf, bytes: Vec<u8>) {
+        if let Some(job_sender) = &self.job_sender {
+            // only fails if the writer thread is gone, in which case `finish` reports it
+            let _ = job_sender.send(DumpJob {
+                dump_file_path,
+                bytes,
+            });
+        }
+    }
+
+    /// Returns the first write error, if any (only once).
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.first_error.lock().unwrap().take()
+    }
+
+    /// Waits for all queued dumps to be written.
+    pub fn finish(&mut self) {
+        self.job_sender = None; // closes the queue, so the writer thread exits once it's empty
+        if let Some(writer_thread) = self.writer_thread.take() {
+            if writer_thread.join().is_err() {
+                *self.first_error.lock().unwrap() =
+                    Some(io::Error::other("Dump writer thread panicked"));
+            }
+        }
+    }
+}
+
+impl Drop for DumpWriter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn write_dump_file(dump_file_path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut output_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dump_file_path)?;
+    output_file.write_all(bytes)?;
+    output_file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_dump_writer_writes_all_dumps() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // tiny queue, so the search side hits backpressure
+        let mut dump_writer = DumpWriter::new(1);
+
+        for i in 0..200u32 {
+            dump_writer.submit(
+                tmp_dir.path().join(format!("dump_{}.bin", i)),
+                i.to_le_bytes().repeat(100),
+            );
+        }
+        dump_writer.finish();
+
+        assert!(dump_writer.take_error().is_none());
+        for i in 0..200u32 {
+            let contents = fs::read(tmp_dir.path().join(format!("dump_{}.bin", i))).unwrap();
+            assert_eq!(contents, i.to_le_bytes().repeat(100));
+        }
+    }
+
+    #[test]
+    fn test_dump_writer_reports_first_error() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut dump_writer = DumpWriter::new(4);
+
+        dump_writer.submit(tmp_dir.path().join("missing_dir/a.bin"), vec![1]);
+        dump_writer.submit(tmp_dir.path().join("b.bin"), vec![2]);
+        dump_writer.finish();
+
+        assert!(dump_writer.take_error().is_some());
+        assert!(dump_writer.take_error().is_none()); // only reported once
+        assert!(!tmp_dir.path().join("b.bin").exists()); // dropped after the error
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Max offsets kept per problem kind, as examples to look at.
+const MAX_EXAMPLE_OFFSETS: usize = 10;
+
+/// One kind of problem, with how often it happened.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ErrorSummaryEntry {
+    pub kind: String,
+    pub count: u64,
+    /// Global offsets of the first few occurrences (that have an offset).
+    pub example_offsets: Vec<u64>,
+    /// The message of the first occurrence.
+    pub first_message: String,
+}
+
+/// Collects the warnings and errors of a run (read retries, truncated dumps, throttled logging,
+/// etc.) that would otherwise scroll past in the log, for a quick health check at the end.
+#[derive(Default)]
+pub struct ErrorCollector {
+    entries_by_kind: BTreeMap<String, ErrorSummaryEntry>,
+}
+
+impl ErrorCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one occurrence of the `kind` problem (a short snake_case name).
+    pub fn record(&mut self, kind: &str, global_offset: Option<u64>, message: &str) {
+        self.record_count(kind, 1, global_offset, message);
+    }
+
+    /// Records `count` occurrences of the `kind` problem at once.
+    pub fn record_count(
+        &mut self,
+        kind: &str,
+        count: u64,
+        global_offset: Option<u64>,
+        message: &str,
+    ) {
+        let entry = self
+            .entries_by_kind
+            .entry(kind.to_string())
+            .or_insert_with(|| ErrorSummaryEntry {
+                kind: kind.to_string(),
+                count: 0,
+                example_offsets: Vec::new(),
+                first_message: message.to_string(),
+            });
+        entry.count += count;
+        if let Some(global_offset) = global_offset {
+            if entry.example_offsets.len() < MAX_EXAMPLE_OFFSETS {
+                entry.example_offsets.push(global_offset);
+            }
+        }
+    }
+
+    /// Adds the problems recorded by `other` (like a worker thread's), keeping this collector's
+    /// first messages and example offsets first.
+    pub fn merge(&mut self, other: ErrorCollector) {
+        for (kind, other_entry) in other.entries_by_kind {
+            let entry = self
+                .entries_by_kind
+                .entry(kind)
+                .or_insert_with(|| ErrorSummaryEntry {
+                    kind: other_entry.kind.clone(),
+                    count: 0,
+                    example_offsets: Vec::new(),
+                    first_message: other_entry.first_message.clone(),
+                });
+            entry.count += other_entry.count;
+            let example_room = MAX_EXAMPLE_OFFSETS.saturating_sub(entry.example_offsets.len());
+            entry
+                .example_offsets
+                .extend(other_entry.example_offsets.into_iter().take(example_room));
+        }
+    }
+
+    pub fn total_count(&self) -> u64 {
+        self.entries_by_kind.values().map(|entry| entry.count).sum()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ErrorSummaryEntry> {
+        self.entries_by_kind.values()
+    }
+
+    /// Writes the summary as `{"total_count": ..., "problems": [...]}`, with one entry per kind.
+    pub fn write_to_json_file(&self, json_file_path: &Path) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct ErrorSummary<'a> {
+            total_count: u64,
+            problems: Vec<&'a ErrorSummaryEntry>,
+        }
+
+        let mut writer = BufWriter::new(File::create(json_file_path)?);
+        serde_json::to_writer_pretty(
+            &mut writer,
+            &ErrorSummary {
+                total_count: self.total_count(),
+                problems: self.entries().collect(),
+            },
+        )?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_collector_aggregates_by_kind() {
+        let mut error_collector = ErrorCollector::new();
+        for offset in 0..20 {
+            error_collector.record(
+                "dump_context_truncated",
+                Some(offset * 100),
+                &format!("Truncated at {}", offset * 100),
+            );
+        }
+        error_collector.record_count("read_retried", 3, None, "Read failed");
+
+        assert_eq!(error_collector.total_count(), 23);
+        let entries: Vec<&ErrorSummaryEntry> = error_collector.entries().collect();
+        assert_eq!(entries[0].kind, "dump_context_truncated");
+        assert_eq!(entries[0].count, 20);
+        assert_eq!(entries[0].example_offsets.len(), MAX_EXAMPLE_OFFSETS);
+        assert_eq!(entries[0].first_message, "Truncated at 0");
+        assert_eq!(entries[1].kind, "read_retried");
+        assert_eq!(entries[1].example_offsets, Vec::<u64>::new());
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let json_file_path = tmp_dir.path().join("06_errors.json");
+        error_collector.write_to_json_file(&json_file_path).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json_file_path).unwrap()).unwrap();
+        assert_eq!(json["total_count"], 23);
+        assert_eq!(json["problems"][1]["count"], 3);
+    }
+
+    #[test]
+    fn test_error_collector_merge() {
+        let mut error_collector = ErrorCollector::new();
+        error_collector.record("read_retried", Some(100), "First");
+        let mut other_error_collector = ErrorCollector::new();
+        for offset in [200, 300] {
+            other_error_collector.record("read_retried", Some(offset), "Second");
+        }
+        other_error_collector.record("dump_context_truncated", None, "Truncated");
+
+        error_collector.merge(other_error_collector);
+        assert_eq!(error_collector.total_count(), 4);
+        let entries: Vec<&ErrorSummaryEntry> = error_collector.entries().collect();
+        assert_eq!(entries[0].kind, "dump_context_truncated");
+        assert_eq!(entries[1].count, 3);
+        assert_eq!(entries[1].example_offsets, vec![100, 200, 300]);
+        assert_eq!(entries[1].first_message, "First");
+    }
+}
@@ -0,0 +1,560 @@
+use crate::display_hex::display_hex_offset;
+use crate::found_needle::{jsonl_part_file_paths, NeedleValFound};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Max bytes of a dump file shown in the hexdump pane.
+const MAX_HEXDUMP_BYTES: u64 = 64 * 1024;
+const HEXDUMP_LINE_LEN: usize = 16;
+
+/// Per-needle totals of a result set, shown in the needle pane.
+#[derive(Debug, PartialEq)]
+pub struct NeedleSummary {
+    pub name: String,
+    pub happiness_level: u8,
+    pub description_notes: String,
+    pub match_count: usize,
+}
+
+/// The match records of a results directory (`--explore`).
+pub struct ResultSet {
+    /// Sorted by needle name, then offset.
+    pub matches: Vec<NeedleValFound>,
+    /// Happiest needles first, then by name.
+    pub needle_summaries: Vec<NeedleSummary>,
+}
+
+impl ResultSet {
+    /// Loads the match records from the global JSONL file in `results_dir`, or from the per-needle
+    /// JSONL files if there's no global one (`--no-global-jsonl`).
+    pub fn load(results_dir: &Path) -> Result<Self, String> {
+        let jsonl_file_paths = find_jsonl_file_paths(results_dir)
+            .map_err(|e| format!("Could not read {}: {}", results_dir.display(), e))?;
+        if jsonl_file_paths.is_empty() {
+            return Err(format!(
+                "No JSONL match records found in {}",
+                results_dir.display()
+            ));
+        }
+
+        let mut matches: Vec<NeedleValFound> = Vec::new();
+        for jsonl_file_path in &jsonl_file_paths {
+            let jsonl = fs::read_to_string(jsonl_file_path)
+                .map_err(|e| format!("Could not read {}: {}", jsonl_file_path.display(), e))?;
+            for line in jsonl.lines().filter(|line| !line.trim().is_empty()) {
+                let mut found: NeedleValFound = serde_json::from_str(line).map_err(|e| {
+                    format!(
+                        "Invalid JSONL record in {}: {}",
+                        jsonl_file_path.display(),
+                        e
+                    )
+                })?;
+                // `--deterministic` runs record dump paths relative to the results directory
+                if let Some(dump_file_path) = found.dump_file_path.as_mut() {
+                    if Path::new(dump_file_path).is_relative() {
+                        *dump_file_path = results_dir.join(&dump_file_path).display().to_string();
+                    }
+                }
+                matches.push(found);
+            }
+        }
+        matches.sort_by(|a, b| {
+            (&a.name, a.match_start_global_offset).cmp(&(&b.name, b.match_start_global_offset))
+        });
+
+        let mut needle_summaries: Vec<NeedleSummary> = Vec::new();
+        for found in &matches {
+            match needle_summaries.last_mut() {
+                Some(needle_summary) if needle_summary.name == found.name => {
+                    needle_summary.match_count += 1
+                }
+                _ => needle_summaries.push(NeedleSummary {
+                    name: found.name.clone(),
+                    happiness_level: found.happiness_level,
+                    description_notes: found.description_notes.clone(),
+                    match_count: 1,
+                }),
+            }
+        }
+        needle_summaries.sort_by(|a, b| {
+            b.happiness_level
+                .cmp(&a.happiness_level)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        Ok(Self {
+            matches,
+            needle_summaries,
+        })
+    }
+
+    pub fn matches_of_needle(&self, needle_name: &str) -> Vec<&NeedleValFound> {
+        self.matches
+            .iter()
+            .filter(|found| found.name == needle_name)
+            .collect()
+    }
+}
+
+fn find_jsonl_file_paths(results_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let global_jsonl_file_path = results_dir.join("00_all_output_record.jsonl");
+    if global_jsonl_file_path.is_file() {
+        return Ok(jsonl_part_file_paths(&global_jsonl_file_path));
+    }
+    // per-needle files: `{prefix}_{needle name}/001_{needle name}.jsonl`
+    let mut jsonl_file_paths = Vec::new();
+    for entry in fs::read_dir(results_dir)? {
+        let needle_dir_path = entry?.path();
+        if !needle_dir_path.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&needle_dir_path)? {
+            let file_path = entry?.path();
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+            if file_name.starts_with("001_") && file_name.ends_with(".jsonl") {
+                jsonl_file_paths.push(file_path);
+            }
+        }
+    }
+    jsonl_file_paths.sort();
+    Ok(jsonl_file_paths)
+}
+
+/// Global offset of the first byte of a match's dump file, from the dump file name
+/// (`found_g_0x{match offset}_startat_0x{match offset in file}.bin`, or
+/// `merged_g_0x{dump offset}_len_0x{len}.bin`).
+pub fn dump_start_global_offset(found: &NeedleValFound) -> Option<u64> {
+    let dump_file_path = PathBuf::from(found.dump_file_path.as_ref()?);
+    let file_stem = dump_file_path.file_stem()?.to_str()?;
+    let parse_hex = |hex_str: &str| u64::from_str_radix(&hex_str.replace('_', ""), 16).ok();
+
+    if let Some(rest) = file_stem.strip_prefix("found_g_0x") {
+        let (_, offset_in_file) = rest.split_once("_startat_0x")?;
+        return found
+            .match_start_global_offset
+            .checked_sub(parse_hex(offset_in_file)?);
+    }
+    let rest = file_stem.strip_prefix("merged_g_0x")?;
+    let (dump_start_global_offset, _) = rest.split_once("_len_0x")?;
+    parse_hex(dump_start_global_offset)
+}
+
+/// Formats bytes as hexdump lines (`offset: hex bytes |ascii|`), starting at `start_offset`.
+pub fn format_hexdump_lines(bytes: &[u8], start_offset: u64) -> Vec<String> {
+    bytes
+        .chunks(HEXDUMP_LINE_LEN)
+        .enumerate()
+        .map(|(line_idx, line_bytes)| {
+            let hex: Vec<String> = line_bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = line_bytes
+                .iter()
+                .map(|&b| match b.is_ascii_graphic() || b == b' ' {
+                    true => b as char,
+                    false => '.',
+                })
+                .collect();
+            format!(
+                "0x{}: {:<47} |{}|",
+                display_hex_offset(start_offset + (line_idx * HEXDUMP_LINE_LEN) as u64, 12),
+                hex.join(" "),
+                ascii
+            )
+        })
+        .collect()
+}
+
+/// Reads the start of a match's dump file as hexdump lines, with global offsets where known.
+pub fn read_dump_hexdump_lines(found: &NeedleValFound) -> Result<Vec<String>, String> {
+    let dump_file_path = found
+        .dump_file_path
+        .as_ref()
+        .ok_or("No dump was written for this match (write_to_file: false)")?;
+    let mut bytes = Vec::new();
+    File::open(dump_file_path)
+        .and_then(|file| file.take(MAX_HEXDUMP_BYTES).read_to_end(&mut bytes))
+        .map_err(|e| format!("Could not read {}: {}", dump_file_path, e))?;
+    Ok(format_hexdump_lines(
+        &bytes,
+        dump_start_global_offset(found).unwrap_or(0),
+    ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExplorerFocus {
+    Needles,
+    Matches,
+}
+
+/// The state of the results explorer: the result set, the happiness filter, and the selections.
+pub struct Explorer {
+    pub result_set: ResultSet,
+    /// Only needles with at least this happiness level are listed.
+    pub min_happiness_level: u8,
+    pub focus: ExplorerFocus,
+    pub needle_list_state: ListState,
+    pub match_list_state: ListState,
+    pub hexdump_scroll: u16,
+}
+
+impl Explorer {
+    pub fn new(result_set: ResultSet) -> Self {
+        let mut explorer = Self {
+            result_set,
+            min_happiness_level: 0,
+            focus: ExplorerFocus::Needles,
+            needle_list_state: ListState::default(),
+            match_list_state: ListState::default(),
+            hexdump_scroll: 0,
+        };
+        explorer.reset_selection();
+        explorer
+    }
+
+    pub fn visible_needles(&self) -> Vec<&NeedleSummary> {
+        self.result_set
+            .needle_summaries
+            .iter()
+            .filter(|needle_summary| needle_summary.happiness_level >= self.min_happiness_level)
+            .collect()
+    }
+
+    pub fn selected_needle(&self) -> Option<&NeedleSummary> {
+        self.visible_needles()
+            .get(self.needle_list_state.selected()?)
+            .copied()
+    }
+
+    pub fn selected_needle_matches(&self) -> Vec<&NeedleValFound> {
+        match self.selected_needle() {
+            Some(needle_summary) => self.result_set.matches_of_needle(&needle_summary.name),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn selected_match(&self) -> Option<&NeedleValFound> {
+        self.selected_needle_matches()
+            .get(self.match_list_state.selected()?)
+            .copied()
+    }
+
+    fn reset_selection(&mut self) {
+        self.needle_list_state
+            .select((!self.visible_needles().is_empty()).then_some(0));
+        self.reset_match_selection();
+    }
+
+    fn reset_match_selection(&mut self) {
+        self.match_list_state
+            .select((!self.selected_needle_matches().is_empty()).then_some(0));
+        self.hexdump_scroll = 0;
+    }
+
+    /// Moves the selection in the focused pane by `delta` rows (clamped to the list).
+    pub fn move_selection(&mut self, delta: isize) {
+        let (list_len, list_state) = match self.focus {
+            ExplorerFocus::Needles => (self.visible_needles().len(), &mut self.needle_list_state),
+            ExplorerFocus::Matches => (
+                self.selected_needle_matches().len(),
+                &mut self.match_list_state,
+            ),
+        };
+        if list_len == 0 {
+            return;
+        }
+        let selected = list_state.selected().unwrap_or(0) as isize;
+        list_state.select(Some(
+            (selected + delta).clamp(0, list_len as isize - 1) as usize
+        ));
+        match self.focus {
+            ExplorerFocus::Needles => self.reset_match_selection(),
+            ExplorerFocus::Matches => self.hexdump_scroll = 0,
+        }
+    }
+
+    /// Steps the happiness filter up (0 to 9, then back to 0).
+    pub fn cycle_min_happiness_level(&mut self) {
+        self.min_happiness_level = (self.min_happiness_level + 1) % 10;
+        self.reset_selection();
+    }
+
+    /// Handles a key press. Returns false to quit.
+    pub fn handle_key(&mut self, key_code: KeyCode) -> bool {
+        match key_code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                self.focus = match self.focus {
+                    ExplorerFocus::Needles => ExplorerFocus::Matches,
+                    ExplorerFocus::Matches => ExplorerFocus::Needles,
+                }
+            }
+            KeyCode::Char('h') => self.cycle_min_happiness_level(),
+            KeyCode::PageDown => self.hexdump_scroll = self.hexdump_scroll.saturating_add(16),
+            KeyCode::PageUp => self.hexdump_scroll = self.hexdump_scroll.saturating_sub(16),
+            _ => (),
+        }
+        true
+    }
+}
+
+fn make_pane_block(title: String, is_focused: bool) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL).title(title);
+    match is_focused {
+        true => block.border_style(Style::default().add_modifier(Modifier::BOLD)),
+        false => block,
+    }
+}
+
+/// Draws the explorer: needles on the left, and the selected needle's matches and the selected
+/// match's hexdump on the right.
+pub fn render(frame: &mut Frame, explorer: &mut Explorer) {
+    let [main_area, help_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+    let [needle_area, right_area] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .areas(main_area);
+    let [match_area, hexdump_area] =
+        Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .areas(right_area);
+    let highlight_style = Style::default().add_modifier(Modifier::REVERSED);
+
+    let needle_items: Vec<ListItem> = explorer
+        .visible_needles()
+        .iter()
+        .map(|needle_summary| {
+            ListItem::new(format!(
+                "[{}] {} ({})",
+                needle_summary.happiness_level, needle_summary.name, needle_summary.match_count
+            ))
+        })
+        .collect();
+    let needle_list = List::new(needle_items)
+        .block(make_pane_block(
+            format!("Needles (happiness >= {})", explorer.min_happiness_level),
+            explorer.focus == ExplorerFocus::Needles,
+        ))
+        .highlight_style(highlight_style)
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(needle_list, needle_area, &mut explorer.needle_list_state);
+
+    let match_items: Vec<ListItem> = explorer
+        .selected_needle_matches()
+        .iter()
+        .map(|found| {
+            ListItem::new(format!(
+                "0x{}  {}",
+                display_hex_offset(found.match_start_global_offset, 12),
+                match found.dump_file_path {
+                    Some(_) => "dump",
+                    None => "no dump",
+                }
+            ))
+        })
+        .collect();
+    let match_list_title = match explorer.selected_needle() {
+        Some(needle_summary) => format!(
+            "Matches of '{}': {}",
+            needle_summary.name, needle_summary.description_notes
+        ),
+        None => "Matches".to_string(),
+    };
+    let match_list = List::new(match_items)
+        .block(make_pane_block(
+            match_list_title,
+            explorer.focus == ExplorerFocus::Matches,
+        ))
+        .highlight_style(highlight_style)
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(match_list, match_area, &mut explorer.match_list_state);
+
+    let hexdump_text = match explorer.selected_match() {
+        Some(found) => read_dump_hexdump_lines(found).unwrap_or_else(|e| vec![e]),
+        None => Vec::new(),
+    }
+    .join("\n");
+    frame.render_widget(
+        Paragraph::new(hexdump_text)
+            .block(make_pane_block("Hexdump".to_string(), false))
+            .scroll((explorer.hexdump_scroll, 0)),
+        hexdump_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(
+            "Up/Down: move, Tab: switch pane, h: min happiness, PgUp/PgDn: scroll hexdump, q: quit",
+        ),
+        help_area,
+    );
+}
+
+/// Runs the interactive results explorer (`--explore`) until the user quits.
+pub fn run_explorer(result_set: ResultSet) -> io::Result<()> {
+    let mut explorer = Explorer::new(result_set);
+    let mut terminal = ratatui::init();
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| render(frame, &mut explorer)) {
+            break Err(e);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if !explorer.handle_key(key.code) {
+                    break Ok(());
+                }
+            }
+            Ok(_) => (),
+            Err(e) => break Err(e),
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::found_needle::LineEnding;
+    use crate::needle::Needle;
+
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    /// A results directory with 3 "hello" matches (one with a dump), and 1 happier "wallet" match.
+    fn make_results_dir(tmp_dir: &tempfile::TempDir, global_jsonl: bool) -> PathBuf {
+        let results_dir = tmp_dir.path().to_path_buf();
+        let needles = [
+            Needle {
+                name: "hello".to_string(),
+                val: b"Hello".to_vec(),
+                happiness_level: 1,
+                ..Default::default()
+            },
+            Needle {
+                name: "wallet".to_string(),
+                val: b"wallet.dat".to_vec(),
+                happiness_level: 9,
+                ..Default::default()
+            },
+        ];
+        for (needle_idx, offset) in [(0, 0x3000), (1, 0x2000), (0, 0x1000), (0, 0x5000)] {
+            let needle = &needles[needle_idx];
+            let mut found = NeedleValFound::from_needle_val(needle, offset, Path::new("disk.img"));
+            let needle_dir_path =
+                results_dir.join(format!("{}_{}", needle.happiness_level, needle.name));
+            fs::create_dir_all(&needle_dir_path).unwrap();
+            if offset == 0x1000 {
+                let dump_file_path = needle_dir_path.join(format!(
+                    "found_g_0x{}_startat_0x{}.bin",
+                    display_hex_offset(offset, 20),
+                    display_hex_offset(4, 1)
+                ));
+                fs::write(&dump_file_path, b"....Hello, world").unwrap();
+                found.dump_file_path = Some(dump_file_path.display().to_string());
+            }
+            let jsonl_file_path = match global_jsonl {
+                true => results_dir.join("00_all_output_record.jsonl"),
+                false => needle_dir_path.join(format!("001_{}.jsonl", needle.name)),
+            };
+            found
+                .append_to_jsonl_file(&jsonl_file_path, LineEnding::Lf)
+                .unwrap();
+        }
+        results_dir
+    }
+
+    #[test]
+    fn test_result_set_load() {
+        for global_jsonl in [true, false] {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let result_set = ResultSet::load(&make_results_dir(&tmp_dir, global_jsonl)).unwrap();
+
+            assert_eq!(result_set.matches.len(), 4);
+            let summaries: Vec<(&str, u8, usize)> = result_set
+                .needle_summaries
+                .iter()
+                .map(|summary| {
+                    (
+                        summary.name.as_str(),
+                        summary.happiness_level,
+                        summary.match_count,
+                    )
+                })
+                .collect();
+            assert_eq!(summaries, vec![("wallet", 9, 1), ("hello", 1, 3)]);
+            let hello_offsets: Vec<u64> = result_set
+                .matches_of_needle("hello")
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect();
+            assert_eq!(hello_offsets, vec![0x1000, 0x3000, 0x5000]);
+        }
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        assert!(ResultSet::load(tmp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_explorer_navigation_and_hexdump() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut explorer =
+            Explorer::new(ResultSet::load(&make_results_dir(&tmp_dir, true)).unwrap());
+        assert_eq!(explorer.selected_needle().unwrap().name, "wallet");
+
+        explorer.handle_key(KeyCode::Down);
+        assert_eq!(explorer.selected_needle().unwrap().name, "hello");
+        let found = explorer.selected_match().unwrap();
+        assert_eq!(found.match_start_global_offset, 0x1000);
+        // the dump starts 4 bytes before the match
+        assert_eq!(dump_start_global_offset(found), Some(0xFFC));
+        assert_eq!(
+            read_dump_hexdump_lines(found).unwrap(),
+            vec![
+                "0x0000_0000_0FFC: 2e 2e 2e 2e 48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 |....Hello, world|"
+            ]
+        );
+
+        explorer.handle_key(KeyCode::Tab);
+        explorer.handle_key(KeyCode::Down);
+        let found = explorer.selected_match().unwrap();
+        assert_eq!(found.match_start_global_offset, 0x3000);
+        assert!(read_dump_hexdump_lines(found).is_err());
+
+        // happiness filter: only "wallet" is happy enough
+        explorer.cycle_min_happiness_level();
+        explorer.cycle_min_happiness_level();
+        assert_eq!(explorer.visible_needles().len(), 1);
+        assert_eq!(explorer.selected_needle().unwrap().name, "wallet");
+
+        assert!(!explorer.handle_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_render() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut explorer =
+            Explorer::new(ResultSet::load(&make_results_dir(&tmp_dir, true)).unwrap());
+        explorer.handle_key(KeyCode::Down);
+
+        let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+        terminal.draw(|frame| render(frame, &mut explorer)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("[9] wallet (1)"));
+        assert!(screen.contains("> [1] hello (3)"));
+        assert!(screen.contains("0x0000_0000_3000"));
+        assert!(screen.contains("|....Hello, world|"));
+    }
+}
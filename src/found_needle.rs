@@ -5,6 +5,7 @@ use std::error;
 use std::fs::OpenOptions;
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use chrono::Utc;
 
@@ -12,21 +13,115 @@ use polars::prelude::*;
 
 use log::info;
 
+use crate::display_hex::display_hex_offset;
 use crate::needle::Needle;
 
+/// Line ending between JSONL records (`--jsonl-line-ending`). Some Windows tools want CRLF.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::CrLf),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The needle value in a JSONL record: a hex string (`"val_hex": "48656c6c6f"`) by default, or the
+/// older array of byte values (`"val": [72, 101, ...]`) with `--jsonl-val-array`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JsonlVal {
+    #[serde(rename = "val_hex", with = "hex_string")]
+    Hex(Vec<u8>),
+    #[serde(rename = "val")]
+    Array(Vec<u8>),
+}
+
+impl JsonlVal {
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            JsonlVal::Hex(val) | JsonlVal::Array(val) => val,
+        }
+    }
+
+    /// The same value, in the older array form.
+    pub fn into_array(self) -> Self {
+        match self {
+            JsonlVal::Hex(val) | JsonlVal::Array(val) => JsonlVal::Array(val),
+        }
+    }
+}
+
+/// Serializes bytes as a lowercase hex string, like "48656c6c6f".
+mod hex_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(val: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(val))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex_str = String::deserialize(deserializer)?;
+        hex::decode(&hex_str).map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NeedleValFound {
     pub name: String,
     pub match_start_global_offset: u64,
-    pub val: Vec<u8>,
+    /// The same offset in hex, like "0x1_0000", as in the logs.
+    #[serde(default)]
+    pub match_start_global_offset_hex: String,
+    #[serde(flatten)]
+    pub val: JsonlVal,
     pub val_as_str: String,
     pub description_notes: String,
     pub happiness_level: u8,
-    pub found_timestamp_utc: String,
+    /// None in `--deterministic` runs, so the same search always gives the same records.
+    #[serde(default)]
+    pub found_timestamp_utc: Option<String>,
 
     pub haystack_written_to_file: bool,
     pub haystack_file_path: Option<String>,
     pub haystack_file_name: Option<String>,
+
+    /// The `.bin` dump of the haystack around this match, if written. Nearby matches may share a
+    /// merged dump (`--merge-dump-distance`).
+    #[serde(default)]
+    pub dump_file_path: Option<String>,
+
+    /// Cylinder/head/sector address of the match, like "99/3/20" (only with `--chs`).
+    #[serde(default)]
+    pub chs_address: Option<String>,
+
+    /// Seconds from the start of the search to this match (only with `--record-match-time`), to
+    /// line matches up with a live imaging timeline.
+    #[serde(default)]
+    pub elapsed_since_start_sec: Option<f64>,
+
+    /// Hex+ASCII dump of the written bytes around the match, within the needle's before/after
+    /// context (only with `--jsonl-context-hexdump`), to see the context without opening the dump.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_hexdump: Option<String>,
 }
 
 impl NeedleValFound {
@@ -55,22 +150,46 @@ impl NeedleValFound {
             false => None,
         };
 
-        let needle_val_found = NeedleValFound {
+        NeedleValFound {
+            haystack_written_to_file: needle_val.write_to_file,
+            haystack_file_path,
+            haystack_file_name,
+            ..NeedleValFound::from_needle_val_in_stream(needle_val, match_start_global_offset)
+        }
+    }
+
+    /// A match in a stream that isn't an input file, and isn't dumped (like the library `search`).
+    pub fn from_needle_val_in_stream(
+        needle_val: &Needle,
+        match_start_global_offset: u64,
+    ) -> NeedleValFound {
+        NeedleValFound {
             name: needle_val.name.clone(),
             match_start_global_offset,
-            val: needle_val.val.clone(),
+            match_start_global_offset_hex: format!(
+                "0x{}",
+                display_hex_offset(match_start_global_offset, 1)
+            ),
+            val: JsonlVal::Hex(needle_val.val.clone()),
             val_as_str: needle_val.val_as_string(),
             description_notes: needle_val.description_notes.clone(),
             happiness_level: needle_val.happiness_level,
-            found_timestamp_utc: Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
-            haystack_written_to_file: needle_val.write_to_file,
-            haystack_file_path,
-            haystack_file_name,
-        };
-        needle_val_found
+            found_timestamp_utc: Some(Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string()),
+            haystack_written_to_file: false,
+            haystack_file_path: None,
+            haystack_file_name: None,
+            dump_file_path: None,
+            chs_address: None,
+            elapsed_since_start_sec: None,
+            context_hexdump: None,
+        }
     }
 
-    pub fn append_to_jsonl_file(&self, jsonl_file_path: &PathBuf) -> Result<(), std::io::Error> {
+    pub fn append_to_jsonl_file(
+        &self,
+        jsonl_file_path: &PathBuf,
+        line_ending: LineEnding,
+    ) -> Result<(), std::io::Error> {
         let file = OpenOptions::new()
             .create(true)
             .append(true)
@@ -81,23 +200,71 @@ impl NeedleValFound {
         let json = serde_json::to_string(self)?;
 
         // Write the JSON string followed by a newline to the file
-        writeln!(writer, "{}", json)?;
+        write!(writer, "{}{}", json, line_ending.as_str())?;
         writer.flush() // Ensure all data is written to the file system
     }
 }
 
-pub fn log_polars_summary(
-    jsonl_file_path: &PathBuf,
-) -> std::result::Result<(), Box<dyn error::Error>> {
-    let mut file = std::fs::File::open(jsonl_file_path)?;
-    let df = JsonLineReader::new(&mut file).finish()?;
+/// Path of part `part_number` (from 1) of a JSONL file that's split into parts (`--jsonl-max-size`).
+/// Part 1 is the file itself, and later parts are numbered like `00_all_output_record_part002.jsonl`.
+pub fn jsonl_part_file_path(jsonl_file_path: &Path, part_number: u32) -> PathBuf {
+    match part_number {
+        0 | 1 => jsonl_file_path.to_path_buf(),
+        _ => jsonl_file_path.with_file_name(format!(
+            "{}_part{:03}.jsonl",
+            jsonl_file_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            part_number
+        )),
+    }
+}
 
-    let df = df
-        .lazy()
+/// All the existing parts of a JSONL file that may be split into parts, in order (just the file
+/// itself if it isn't split).
+pub fn jsonl_part_file_paths(jsonl_file_path: &Path) -> Vec<PathBuf> {
+    let mut part_file_paths = vec![jsonl_file_path.to_path_buf()];
+    for part_number in 2.. {
+        let part_file_path = jsonl_part_file_path(jsonl_file_path, part_number);
+        if !part_file_path.exists() {
+            break;
+        }
+        part_file_paths.push(part_file_path);
+    }
+    part_file_paths
+}
+
+/// Summarizes the match records in `jsonl_file_paths` (the global JSONL file, or all the
+/// per-needle ones with `--no-global-jsonl`), one row per needle. Files that don't exist yet are
+/// skipped. None if there are no match records yet.
+fn summarize_matches(
+    jsonl_file_paths: &[PathBuf],
+) -> std::result::Result<Option<DataFrame>, Box<dyn error::Error>> {
+    let mut match_lfs: Vec<LazyFrame> = Vec::new();
+    for jsonl_file_path in jsonl_file_paths.iter().filter(|path| path.exists()) {
+        let mut file = std::fs::File::open(jsonl_file_path)?;
+        let df = JsonLineReader::new(&mut file).finish()?;
+        // only the summarized columns, so optional columns can't differ in type between files
+        match_lfs.push(df.lazy().select([
+            col("name"),
+            col("happiness_level"),
+            col("match_start_global_offset"),
+            col("description_notes"),
+        ]));
+    }
+    if match_lfs.is_empty() {
+        return Ok(None);
+    }
+
+    let df = concat(match_lfs, UnionArgs::default())?
         .group_by(["name"])
         .agg([
             col("happiness_level").first(), // should all be the same
             len().alias("count"),
+            col("match_start_global_offset")
+                .min()
+                .alias("first_global_offset"),
             col("match_start_global_offset")
                 .max()
                 .alias("latest_global_offset"),
@@ -108,6 +275,20 @@ pub fn log_polars_summary(
             SortMultipleOptions::default().with_order_descendings(vec![true, false]),
         )
         .collect()?;
+    Ok(Some(df))
+}
+
+/// Logs a per-needle summary of the match records in `jsonl_file_paths` (see `summarize_matches`).
+pub fn log_polars_summary(
+    jsonl_file_paths: &[PathBuf],
+) -> std::result::Result<(), Box<dyn error::Error>> {
+    let df = match summarize_matches(jsonl_file_paths)? {
+        Some(df) => df,
+        None => {
+            info!("No matches to summarize yet.");
+            return Ok(());
+        }
+    };
 
     // print out the result
     env::set_var("POLARS_FMT_MAX_ROWS", (df.height() + 5).to_string());
@@ -115,3 +296,154 @@ pub fn log_polars_summary(
 
     Ok(())
 }
+
+/// Writes the per-needle summary of the match records in `jsonl_file_paths` (see
+/// `summarize_matches`) to a CSV file, to keep after the run. With no matches, only the header is
+/// written.
+pub fn write_summary_csv_file(
+    jsonl_file_paths: &[PathBuf],
+    csv_file_path: &Path,
+) -> std::result::Result<(), Box<dyn error::Error>> {
+    let mut df = match summarize_matches(jsonl_file_paths)? {
+        Some(df) => df
+            .lazy()
+            .select([
+                col("name"),
+                col("happiness_level"),
+                col("count"),
+                col("first_global_offset").alias("first_offset"),
+                col("latest_global_offset").alias("last_offset"),
+                col("description_notes").alias("description"),
+            ])
+            .collect()?,
+        None => DataFrame::new(vec![
+            Series::new_empty("name", &DataType::String),
+            Series::new_empty("happiness_level", &DataType::Int64),
+            Series::new_empty("count", &DataType::UInt32),
+            Series::new_empty("first_offset", &DataType::Int64),
+            Series::new_empty("last_offset", &DataType::Int64),
+            Series::new_empty("description", &DataType::String),
+        ])?,
+    };
+    let mut file = std::fs::File::create(csv_file_path)?;
+    CsvWriter::new(&mut file).finish(&mut df)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_val_hex_round_trip() {
+        let val: Vec<u8> = (0..=255).collect();
+        let needle = Needle {
+            name: "all_bytes".to_string(),
+            val: val.clone(),
+            ..Default::default()
+        };
+        let needle_val_found = NeedleValFound::from_needle_val(&needle, 0, Path::new("image.bin"));
+
+        let json = serde_json::to_string(&needle_val_found).unwrap();
+        assert!(json.contains(&format!(r#""val_hex":"{}""#, hex::encode(&val))));
+        assert!(!json.contains(r#""val":"#));
+        let round_tripped: NeedleValFound = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_tripped.val, JsonlVal::Hex(val.clone()));
+
+        // the older array form still reads back
+        let json = serde_json::to_string(&NeedleValFound {
+            val: needle_val_found.val.into_array(),
+            ..round_tripped
+        })
+        .unwrap();
+        assert!(json.contains(r#""val":[0,1,2,"#));
+        let round_tripped: NeedleValFound = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_tripped.val.bytes(), &val[..]);
+    }
+
+    #[test]
+    fn test_match_start_global_offset_hex() {
+        let needle = Needle {
+            name: "hello".to_string(),
+            val: b"Hello".to_vec(),
+            ..Default::default()
+        };
+        for (offset, expected_offset_hex) in [
+            (0, "0x0"),
+            (4096, "0x1000"),
+            (0x1_0000, "0x1_0000"),
+            (1_000_000_000_000, "0xE8_D4A5_1000"),
+            (u64::MAX, "0xFFFF_FFFF_FFFF_FFFF"),
+        ] {
+            let needle_val_found =
+                NeedleValFound::from_needle_val(&needle, offset, Path::new("image.bin"));
+            assert_eq!(
+                needle_val_found.match_start_global_offset_hex,
+                expected_offset_hex
+            );
+            let json = serde_json::to_string(&needle_val_found).unwrap();
+            assert!(json.contains(&format!(r#""match_start_global_offset":{},"#, offset)));
+            // the hex and decimal fields agree
+            let round_tripped: NeedleValFound = serde_json::from_str(&json).unwrap();
+            let offset_from_hex = u64::from_str_radix(
+                &round_tripped.match_start_global_offset_hex[2..].replace('_', ""),
+                16,
+            )
+            .unwrap();
+            assert_eq!(offset_from_hex, round_tripped.match_start_global_offset);
+        }
+    }
+
+    #[test]
+    fn test_write_summary_csv_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let jsonl_file_path = tmp_dir.path().join("00_all_output_record.jsonl");
+        let csv_file_path = tmp_dir.path().join("03_summary.csv");
+
+        // no matches yet: just the header
+        write_summary_csv_file(std::slice::from_ref(&jsonl_file_path), &csv_file_path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv_file_path).unwrap(),
+            "name,happiness_level,count,first_offset,last_offset,description\n"
+        );
+
+        let key_needle = Needle {
+            name: "key".to_string(),
+            val: b"KEY".to_vec(),
+            description_notes: "Key header".to_string(),
+            happiness_level: 9,
+            ..Default::default()
+        };
+        let pdf_needle = Needle {
+            name: "pdf".to_string(),
+            val: b"%PDF-".to_vec(),
+            description_notes: "PDF header".to_string(),
+            happiness_level: 2,
+            ..Default::default()
+        };
+        for (needle, offset) in [
+            (&pdf_needle, 5000),
+            (&key_needle, 700),
+            (&pdf_needle, 100),
+            (&key_needle, 300),
+            (&pdf_needle, 9000),
+        ] {
+            NeedleValFound::from_needle_val(needle, offset, Path::new("image.bin"))
+                .append_to_jsonl_file(&jsonl_file_path, LineEnding::Lf)
+                .unwrap();
+        }
+
+        write_summary_csv_file(&[jsonl_file_path], &csv_file_path).unwrap();
+        let csv = std::fs::read_to_string(&csv_file_path).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        // by happiness level, highest first
+        assert_eq!(
+            rows,
+            vec![
+                "name,happiness_level,count,first_offset,last_offset,description",
+                "key,9,2,300,700,Key header",
+                "pdf,2,3,100,9000,PDF header",
+            ]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Hash algorithm for `--hash-algo`. MD5 and SHA-1 are for matching legacy forensic tooling;
+/// BLAKE3 is the fastest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl FromStr for HashAlgo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgo::Sha256),
+            "sha1" => Ok(HashAlgo::Sha1),
+            "md5" => Ok(HashAlgo::Md5),
+            "blake3" => Ok(HashAlgo::Blake3),
+            _ => Err(format!("Invalid hash algorithm: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashAlgo::Sha256 => write!(f, "SHA-256"),
+            HashAlgo::Sha1 => write!(f, "SHA-1"),
+            HashAlgo::Md5 => write!(f, "MD5"),
+            HashAlgo::Blake3 => write!(f, "BLAKE3"),
+        }
+    }
+}
+
+/// A running hash of one of the `HashAlgo` algorithms.
+#[derive(Clone)]
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(md5::Context),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn new(hash_algo: HashAlgo) -> Self {
+        match hash_algo {
+            HashAlgo::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgo::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgo::Md5 => Hasher::Md5(md5::Context::new()),
+            HashAlgo::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.consume(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Returns the lowercase hex digest of all data so far.
+    pub fn hex_digest(&self) -> String {
+        match self.clone() {
+            Hasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            Hasher::Sha1(hasher) => hex::encode(hasher.finalize()),
+            Hasher::Md5(hasher) => format!("{:x}", hasher.compute()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Wraps a reader, and computes a running hash over every byte read through it.
+/// This hashes the input stream as it's searched, without a second pass over the image.
+pub struct HashingReader<R: Read> {
+    inner: R,
+    hasher: Option<Hasher>,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// When `hash_algo` is None, this is a plain pass-through reader.
+    pub fn new(inner: R, hash_algo: Option<HashAlgo>) -> Self {
+        Self {
+            inner,
+            hasher: hash_algo.map(Hasher::new),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the lowercase hex digest of all bytes read so far (or None if hashing is disabled).
+    pub fn hex_digest(&self) -> Option<String> {
+        self.hasher.as_ref().map(|hasher| hasher.hex_digest())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..bytes_read]);
+        }
+        Ok(bytes_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_hashing_reader_matches_independent_digest() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i * 7 % 256) as u8).collect();
+        let mut hashing_reader =
+            HashingReader::new(Cursor::new(data.clone()), Some(HashAlgo::Sha256));
+
+        // read in odd-sized pieces, like the search loop does with its carry-forward
+        let mut buf = vec![0; 4093];
+        while hashing_reader.read(&mut buf).unwrap() > 0 {}
+
+        assert_eq!(
+            hashing_reader.hex_digest().unwrap(),
+            hex::encode(Sha256::digest(&data))
+        );
+    }
+
+    #[test]
+    fn test_hashing_reader_empty_stream() {
+        let hashing_reader = HashingReader::new(Cursor::new(Vec::new()), Some(HashAlgo::Sha256));
+        assert_eq!(
+            hashing_reader.hex_digest().unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_hashing_reader_disabled() {
+        let mut hashing_reader = HashingReader::new(Cursor::new(vec![1, 2, 3]), None);
+        let mut buf = vec![0; 8];
+        assert_eq!(hashing_reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(hashing_reader.hex_digest(), None);
+    }
+
+    #[test]
+    fn test_hasher_known_digests() {
+        // digests of "abc", from the standard test vectors
+        for (hash_algo, expected_hex_digest) in [
+            (
+                HashAlgo::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (HashAlgo::Sha1, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (HashAlgo::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                HashAlgo::Blake3,
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+        ] {
+            let mut hasher = Hasher::new(hash_algo);
+            hasher.update(b"a");
+            hasher.update(b"bc");
+            assert_eq!(hasher.hex_digest(), expected_hex_digest, "{}", hash_algo);
+        }
+    }
+
+    #[test]
+    fn test_parse_hash_algo() {
+        assert_eq!(HashAlgo::from_str("blake3"), Ok(HashAlgo::Blake3));
+        assert!(HashAlgo::from_str("crc32").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::found_needle::LineEnding;
+
+/// A region with a dense cluster of matches (`--hotspot-window`), like a key store, worth a closer
+/// look than its matches one by one.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Hotspot {
+    pub first_match_global_offset: u64,
+    pub last_match_global_offset: u64,
+    pub match_count: u64,
+    /// The distinct needles that matched in the region, sorted.
+    pub needle_names: Vec<String>,
+}
+
+impl Hotspot {
+    pub fn append_to_jsonl_file(
+        &self,
+        jsonl_file_path: &Path,
+        line_ending: LineEnding,
+    ) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(jsonl_file_path)?;
+        let mut writer = BufWriter::new(file);
+        write!(
+            writer,
+            "{}{}",
+            serde_json::to_string(self)?,
+            line_ending.as_str()
+        )?;
+        writer.flush()
+    }
+}
+
+/// Finds hotspots: regions where at least `min_match_count` matches fall within `window_len`
+/// bytes of each other.
+///
+/// Recent matches are kept in a ring buffer. Once it holds `min_match_count` matches within the
+/// window, a hotspot opens, and it grows with each following match within `window_len` of its last
+/// match. It's closed (and returned) by the first match past that, or by `finish`.
+pub struct HotspotDetector {
+    window_len: u64,
+    min_match_count: usize,
+    /// (global offset, needle name) of the recent matches, oldest first.
+    recent_matches: VecDeque<(u64, String)>,
+    open_hotspot: Option<Hotspot>,
+    open_hotspot_needle_names: BTreeSet<String>,
+}
+
+impl HotspotDetector {
+    pub fn new(window_len: u64, min_match_count: usize) -> Self {
+        Self {
+            window_len,
+            min_match_count: min_match_count.max(1),
+            recent_matches: VecDeque::with_capacity(min_match_count),
+            open_hotspot: None,
+            open_hotspot_needle_names: BTreeSet::new(),
+        }
+    }
+
+    /// Records a match. Matches must be recorded in order of offset (within the window, at least).
+    /// Returns the hotspot that this match closed, if any.
+    pub fn record_match(&mut self, global_offset: u64, needle_name: &str) -> Option<Hotspot> {
+        if let Some(open_hotspot) = self.open_hotspot.as_mut() {
+            if global_offset < open_hotspot.last_match_global_offset + self.window_len {
+                open_hotspot.last_match_global_offset =
+                    open_hotspot.last_match_global_offset.max(global_offset);
+                open_hotspot.match_count += 1;
+                self.open_hotspot_needle_names
+                    .insert(needle_name.to_string());
+                return None;
+            }
+        }
+        let closed_hotspot = self.finish();
+
+        while self.recent_matches.len() >= self.min_match_count
+            || self
+                .recent_matches
+                .front()
+                .is_some_and(|(offset, _)| offset + self.window_len <= global_offset)
+        {
+            self.recent_matches.pop_front();
+        }
+        self.recent_matches
+            .push_back((global_offset, needle_name.to_string()));
+
+        if self.recent_matches.len() >= self.min_match_count {
+            let recent_matches: Vec<(u64, String)> = self.recent_matches.drain(..).collect();
+            self.open_hotspot = Some(Hotspot {
+                first_match_global_offset: recent_matches[0].0,
+                last_match_global_offset: recent_matches[recent_matches.len() - 1].0,
+                match_count: recent_matches.len() as u64,
+                needle_names: Vec::new(),
+            });
+            self.open_hotspot_needle_names = recent_matches
+                .into_iter()
+                .map(|(_, needle_name)| needle_name)
+                .collect();
+        }
+        closed_hotspot
+    }
+
+    /// Closes and returns the open hotspot, if any (e.g., at the end of the search).
+    pub fn finish(&mut self) -> Option<Hotspot> {
+        let mut hotspot = self.open_hotspot.take()?;
+        hotspot.needle_names = std::mem::take(&mut self.open_hotspot_needle_names)
+            .into_iter()
+            .collect();
+        Some(hotspot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotspot_detector() {
+        let mut hotspot_detector = HotspotDetector::new(1000, 3);
+        let mut hotspots = Vec::new();
+        for (offset, needle_name) in [
+            // sparse
+            (0, "a"),
+            (900, "a"),
+            (1900, "b"),
+            // dense
+            (10_000, "a"),
+            (10_100, "b"),
+            (10_200, "a"),
+            (10_900, "c"),
+            // sparse again, which closes the hotspot
+            (20_000, "a"),
+            (30_000, "a"),
+            // dense until the end
+            (50_000, "b"),
+            (50_001, "b"),
+            (50_002, "b"),
+        ] {
+            hotspots.extend(hotspot_detector.record_match(offset, needle_name));
+        }
+        hotspots.extend(hotspot_detector.finish());
+
+        assert_eq!(
+            hotspots,
+            vec![
+                Hotspot {
+                    first_match_global_offset: 10_000,
+                    last_match_global_offset: 10_900,
+                    match_count: 4,
+                    needle_names: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                },
+                Hotspot {
+                    first_match_global_offset: 50_000,
+                    last_match_global_offset: 50_002,
+                    match_count: 3,
+                    needle_names: vec!["b".to_string()],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Starting point for a new needle config (`--init-config`), with every field explained.
+pub const NEEDLE_CONFIG_TEMPLATE: &str = r#"# Needle config for drive-image-searcher.
+# Each entry is one "needle" (byte pattern) to search for in the disk image.
+# Run with: drive-image-searcher -i <image> -o <output dir> -n <this file>
+
+# `val_format: hex` - bytes as hex, with or without spaces or 0x prefixes.
+#   `??` (or `*` between spaces) is a wildcard byte that matches any byte, like "48 ?? 6c 6c 6f".
+- name: "hello_hex"
+  val: "48 65 6c 6c 6f"  # "Hello"
+  val_format: hex
+  description_notes: "Hex value of the word 'Hello'"
+  # Significance from 0 (just curious) to 9 (very happy). Shown in the logs, and used to name
+  # the per-needle output directories (see --happiness-dir-prefixes).
+  happiness_level: 1
+  # Skip matches inside a match of one of these longer needles, like "Hello" in a "Hello, world"
+  # needle, so they aren't reported twice (default: none).
+  # suppress_if_covered_by: ["hello_world"]
+
+# `val_format: ascii` - the text itself. Escapes: \n, \t, \r, \0, \xNN (any byte), and \\ (a
+#   backslash). Single-quote values with escapes, like 'key\tvalue', so YAML keeps the backslashes.
+- name: "wallet_dat"
+  val: "wallet.dat"
+  val_format: ascii
+  description_notes: "Filename that's worth a close look"
+  happiness_level: 9
+  # Higher-priority needles are searched and reported first (default: 0). Ties go by name.
+  priority: 10
+  # Write the bytes around each match to a .bin file in the output directory (default: true).
+  write_to_file: true
+  # Bytes of context around each match in that file (default: 1024 each).
+  byte_count_before_match: 65536
+  byte_count_after_match: 65536
+  # Custom log line for each match. Placeholders: {offset}, {name}, {ascii}, {sector}.
+  log_format: "!!! {name} FOUND at {offset} (sector {sector})"
+  # Match letters ignoring case, like "Wallet.DAT" (ascii values only, default: false).
+  case_insensitive: false
+  # Dump the whole aligned block(s) of this size containing the match and its context, instead
+  # of just the context (default: none).
+  capture_block_size: 4096
+  # Also list the printable strings (4+ characters) in each dump, with their offsets, in a
+  # .strings.txt file next to the .bin (default: false).
+  strings: true
+
+# Records at the start of allocated space usually follow a run of padding bytes.
+- name: "record_after_padding"
+  val: "52 45 43 31"  # "REC1"
+  val_format: hex
+  description_notes: "Record header directly after a zero-padding gap"
+  happiness_level: 3
+  # Only match when at least this many identical bytes directly precede the match (default: none).
+  min_preceding_run: 512
+  # Only match at global offsets that are a multiple of this, like sector starts (default: none).
+  alignment: 512
+  # Also search for the value byte-reversed, as `{name}_be` and `{name}_le`, for a number of
+  # unknown byte order (hex values only, default: false).
+  search_both_endians: false
+  # Hex bit mask, one byte per value byte: only the set bits are compared (default: none).
+  # Ignoring the low nibble of the last byte matches any record version, REC0 to REC?.
+  mask: "ff ff ff f0"
+
+# `val_format: regex` - a regular expression over bytes, for variable content. The match can be
+#   any length (up to the 1024-byte carry-forward across chunk boundaries), and its context is
+#   dumped. Single-quote it, so YAML keeps the backslashes.
+- name: "user_id"
+  val: 'user_id=[0-9]{4,10}'
+  val_format: regex
+  description_notes: "A user id in a config or log file"
+  happiness_level: 4
+  # Bytes of context around each match (default: 1024 each).
+  byte_count_before_match: 256
+  byte_count_after_match: 256
+
+# `val_format: file` - the path of a file whose raw bytes are the value, like a whole binary
+#   header kept as its own file. Relative paths are relative to this config's directory.
+# - name: "png_header"
+#   val: "signatures/png_header.bin"
+#   val_format: file
+#   description_notes: "PNG file signature"
+#   happiness_level: 2
+
+# Common needles can match thousands of times. Record them in the JSONL output only.
+- name: "pdf_header"
+  val: "0x25 0x50 0x44 0x46 0x2D"  # "%PDF-"
+  val_format: hex
+  description_notes: "Start of a PDF file"
+  happiness_level: 2
+  write_to_file: false
+  # Keep matches inside embedded JPEG/ZIP files, even with --exclude-carved-files (default: false).
+  allow_inside_carved_files: true
+  # Stop recording matches after this many. Later ones are only counted (default: none).
+  max_matches: 100000
+"#;
+
+/// Writes the needle config template to `file_path`. Won't overwrite an existing file.
+pub fn write_needle_config_template(file_path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(file_path)?;
+    file.write_all(NEEDLE_CONFIG_TEMPLATE.as_bytes())?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::needle::load_needles_from_file;
+
+    #[test]
+    fn test_needle_config_template_loads() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_file_path = tmp_dir.path().join("needle_config.yaml");
+        write_needle_config_template(&config_file_path).unwrap();
+
+        let needles = load_needles_from_file(config_file_path.to_str().unwrap()).unwrap();
+        assert_eq!(needles.len(), 5);
+        // sorted by priority, then name
+        assert_eq!(needles[0].val, b"wallet.dat");
+        assert_eq!(needles[1].val, b"Hello");
+        assert_eq!(needles[2].val, b"%PDF-");
+        assert!(needles[0].log_format.is_some());
+        assert!(needles[2].allow_inside_carved_files);
+        assert_eq!(needles[2].max_matches, Some(100000));
+        assert_eq!(needles[3].min_preceding_run, Some(512));
+        assert_eq!(needles[3].alignment, Some(512));
+        assert!(needles[4].regex.is_some());
+        assert_eq!(needles[0].capture_block_size, Some(4096));
+        assert!(needles[0].strings);
+        assert!(NEEDLE_CONFIG_TEMPLATE.contains("val_format: file"));
+
+        // doesn't overwrite
+        assert!(write_needle_config_template(&config_file_path).is_err());
+    }
+}
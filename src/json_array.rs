@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Assembles the match records from JSONL files into one JSON array file (`--output-format json-array`),
+/// for tools that want a single JSON document. Records are streamed one line at a time, so the
+/// whole output never has to fit in memory. Missing JSONL files (needles with no matches) are skipped.
+/// Returns the number of records written.
+pub fn write_json_array_file(
+    jsonl_file_paths: &[PathBuf],
+    json_file_path: &Path,
+) -> io::Result<u64> {
+    let mut writer = BufWriter::new(File::create(json_file_path)?);
+    let mut record_count: u64 = 0;
+
+    write!(writer, "[")?;
+    for jsonl_file_path in jsonl_file_paths.iter().filter(|path| path.exists()) {
+        for line in BufReader::new(File::open(jsonl_file_path)?).lines() {
+            let line = line?;
+            let record = line.trim();
+            if record.is_empty() {
+                continue;
+            }
+            // a truncated line (e.g., from a killed run) would make the whole array invalid
+            serde_json::from_str::<serde_json::Value>(record).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid JSONL record in {}: {}",
+                        jsonl_file_path.display(),
+                        e
+                    ),
+                )
+            })?;
+
+            if record_count > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "\n{}", record)?;
+            record_count += 1;
+        }
+    }
+    if record_count > 0 {
+        writeln!(writer)?;
+    }
+    writeln!(writer, "]")?;
+    writer.flush()?;
+
+    Ok(record_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::found_needle::{LineEnding, NeedleValFound};
+    use crate::needle::Needle;
+
+    use std::fs;
+
+    #[test]
+    fn test_write_json_array_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let jsonl_file_paths = vec![
+            tmp_dir.path().join("first.jsonl"),
+            tmp_dir.path().join("missing.jsonl"),
+            tmp_dir.path().join("second.jsonl"),
+        ];
+        let needle = Needle {
+            name: "hello".to_string(),
+            val: b"Hello".to_vec(),
+            ..Default::default()
+        };
+        for (jsonl_file_path, offset) in [
+            (&jsonl_file_paths[0], 100),
+            (&jsonl_file_paths[0], 200),
+            (&jsonl_file_paths[2], 300),
+        ] {
+            NeedleValFound::from_needle_val(&needle, offset, Path::new("image.bin"))
+                .append_to_jsonl_file(jsonl_file_path, LineEnding::CrLf)
+                .unwrap();
+        }
+
+        let json_file_path = tmp_dir.path().join("all.json");
+        assert_eq!(
+            write_json_array_file(&jsonl_file_paths, &json_file_path).unwrap(),
+            3
+        );
+
+        let records: Vec<NeedleValFound> =
+            serde_json::from_str(&fs::read_to_string(&json_file_path).unwrap()).unwrap();
+        let offsets: Vec<u64> = records
+            .iter()
+            .map(|record| record.match_start_global_offset)
+            .collect();
+        assert_eq!(offsets, vec![100, 200, 300]);
+    }
+
+    #[test]
+    fn test_write_json_array_file_no_records() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let json_file_path = tmp_dir.path().join("all.json");
+        assert_eq!(
+            write_json_array_file(&[tmp_dir.path().join("missing.jsonl")], &json_file_path)
+                .unwrap(),
+            0
+        );
+        assert_eq!(fs::read_to_string(&json_file_path).unwrap(), "[]\n");
+    }
+}
@@ -0,0 +1,48 @@
+//! Searches disk images (or any byte stream) for needles (byte patterns), chunk by chunk.
+//!
+//! The `drive-image-searcher` binary is a thin command-line wrapper around this crate. To search
+//! from your own tool, load needles (`needle::load_needles_from_file`), and iterate over the
+//! matches of `search`, which doesn't write anything to disk.
+
+pub mod benchmark;
+pub mod carved_files;
+pub mod checkpoint;
+pub mod compare_runs;
+pub mod counting_reader;
+pub mod coverage_verifier;
+pub mod disk_geometry;
+pub mod display_hex;
+pub mod dump_file_name;
+pub mod dump_writer;
+pub mod error_summary;
+pub mod explore;
+pub mod found_needle;
+pub mod hashing_reader;
+pub mod hotspot;
+pub mod init_config;
+pub mod json_array;
+pub mod log_level;
+pub mod memory_estimate;
+pub mod multi_file_reader;
+pub mod multi_frame_lz4;
+pub mod needle;
+pub mod needle_matcher;
+pub mod nested_archive;
+pub mod offsets_list;
+mod parse_hex_string;
+pub mod partition_table;
+pub mod process_data;
+pub mod progress_bar;
+pub mod progress_stats;
+pub mod retrying_reader;
+pub mod run_stats;
+pub mod search;
+pub mod sidecar;
+pub mod sqlite_output;
+pub mod strings;
+pub mod text_regions;
+pub mod truncated_stream;
+pub mod uncompressed_size;
+
+pub use crate::needle::Needle;
+pub use crate::search::{search, Search, SearchOptions};
@@ -0,0 +1,36 @@
+use log::LevelFilter;
+
+/// The log levels from quietest to most verbose. Info is the default.
+const LOG_LEVELS: [LevelFilter; 5] = [
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// The log level for `-v` given `verbose_count` times and `-q` given `quiet_count` times: each
+/// `-v` is one level more verbose than Info (Debug, then Trace), and each `-q` one level quieter
+/// (Warn, then Error).
+pub fn log_level_filter(verbose_count: u64, quiet_count: u64) -> LevelFilter {
+    let default_level_idx = 2;
+    let level_idx = (default_level_idx + verbose_count as i64 - quiet_count as i64)
+        .clamp(0, LOG_LEVELS.len() as i64 - 1);
+    LOG_LEVELS[level_idx as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_level_filter() {
+        assert_eq!(log_level_filter(0, 0), LevelFilter::Info);
+        assert_eq!(log_level_filter(1, 0), LevelFilter::Debug);
+        assert_eq!(log_level_filter(2, 0), LevelFilter::Trace);
+        assert_eq!(log_level_filter(5, 0), LevelFilter::Trace);
+        assert_eq!(log_level_filter(0, 1), LevelFilter::Warn);
+        assert_eq!(log_level_filter(0, 2), LevelFilter::Error);
+        assert_eq!(log_level_filter(0, 5), LevelFilter::Error);
+    }
+}
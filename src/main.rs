@@ -1,36 +1,86 @@
-mod parse_hex_string;
-
-mod needle;
-use crate::needle::{load_needles_from_file, Needle};
-
-mod found_needle;
-
-mod process_data;
-use crate::process_data::{ProcessDataState, SearchAssignment};
-
-mod display_hex;
+use drive_image_searcher::benchmark::{
+    make_synthetic_needles, run_benchmark, BENCHMARK_CHUNK_SIZE_BYTES,
+};
+use drive_image_searcher::checkpoint::{config_hash, Checkpoint, CHECKPOINT_INTERVAL_SEC};
+use drive_image_searcher::compare_runs::{compare_runs, resolve_results_jsonl_path};
+use drive_image_searcher::counting_reader::CountingReader;
+use drive_image_searcher::coverage_verifier::CoverageVerifier;
+use drive_image_searcher::disk_geometry::DiskGeometry;
+use drive_image_searcher::display_hex::display_hex_offset;
+use drive_image_searcher::dump_file_name::{DumpFileNameTemplate, DEFAULT_DUMP_FILE_NAME_TEMPLATE};
+use drive_image_searcher::dump_writer::DEFAULT_DUMP_WRITE_QUEUE_LEN;
+use drive_image_searcher::explore::{run_explorer, ResultSet};
+use drive_image_searcher::hashing_reader::{HashAlgo, HashingReader};
+use drive_image_searcher::hotspot::HotspotDetector;
+use drive_image_searcher::init_config::write_needle_config_template;
+use drive_image_searcher::json_array::write_json_array_file;
+use drive_image_searcher::log_level::log_level_filter;
+use drive_image_searcher::memory_estimate::{parse_byte_size, MemoryEstimate};
+use drive_image_searcher::multi_file_reader::MultiFileReader;
+use drive_image_searcher::multi_frame_lz4::MultiFrameLz4Decoder;
+use drive_image_searcher::needle::{
+    check_needle_config_str, check_needle_lens, filter_needles_by_name_regex, filter_text_needles,
+    load_needles_from_str_in_dir, needle_config_copy_file_name, needle_config_dir,
+    override_write_to_file, read_needle_config_text, Needle, ValDisplayFormat,
+};
+use drive_image_searcher::needle_matcher::NeedleMatcher;
+use drive_image_searcher::nested_archive::{
+    is_tar_file, open_nested_image, NestedCompression, NestedReader,
+};
+use drive_image_searcher::offsets_list::write_offsets_files;
+use drive_image_searcher::partition_table::{read_partitions, Partition};
+use drive_image_searcher::process_data::{
+    self, haystack_carry_forward_len_bytes_for, max_needle_len_bytes_for_chunk_size,
+    parse_happiness_dir_prefixes, MappedHaystack, ProcessDataState, SearchAssignment,
+    DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES, DEFAULT_HAYSTACK_CHUNK_BUFFER_SIZE_BYTES,
+};
+use drive_image_searcher::progress_bar::{make_progress_bar, ProgressBarReader};
+use drive_image_searcher::progress_stats::ProgressStats;
+use drive_image_searcher::retrying_reader::RetryingReader;
+use drive_image_searcher::run_stats::RunStats;
+use drive_image_searcher::sidecar::{write_sidecar_file, SidecarFormat};
+use drive_image_searcher::sqlite_output::SqliteWriter;
+use drive_image_searcher::truncated_stream::TruncationTolerantReader;
+use drive_image_searcher::uncompressed_size::read_declared_uncompressed_size;
 
 use num_format::{Locale, ToFormattedString as _};
 
 use clap::{crate_version, App, Arg};
-use found_needle::log_polars_summary;
+use drive_image_searcher::found_needle::{log_polars_summary, write_summary_csv_file, LineEnding};
 
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{self, Read, Seek as _};
-use std::path::PathBuf;
-use std::time::Instant;
+use std::io::{self, BufReader, IsTerminal as _, Read, Seek as _, SeekFrom, StdinLock};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::SystemTime;
 
 use chrono::Utc;
 
 // use: lz4_flex
+use flate2::read::MultiGzDecoder;
 use xz2::read::XzDecoder;
 
 use fern::Dispatch;
-use log::{debug, error, info, warn};
-use num_traits::AsPrimitive;
+use indicatif::ProgressBar;
+use log::{error, info, warn};
+use memmap2::Mmap;
 
-fn setup_logger(log_file: &PathBuf) -> Result<(), fern::InitError> {
+fn setup_logger(
+    log_file: &PathBuf,
+    progress_bar: Option<&ProgressBar>,
+    level: log::LevelFilter,
+) -> Result<(), fern::InitError> {
+    let stdout_output: fern::Output = match progress_bar {
+        // print each line above the progress bar, instead of through it
+        Some(progress_bar) => {
+            let progress_bar = progress_bar.clone();
+            fern::Output::call(move |record| progress_bar.suspend(|| println!("{}", record.args())))
+        }
+        None => std::io::stdout().into(),
+    };
     Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
@@ -41,8 +91,8 @@ fn setup_logger(log_file: &PathBuf) -> Result<(), fern::InitError> {
                 message
             ))
         })
-        .level(log::LevelFilter::Info)
-        .chain(std::io::stdout())
+        .level(level)
+        .chain(stdout_output)
         .chain(fern::log_file(log_file)?)
         .apply()?;
     Ok(())
@@ -58,77 +108,890 @@ fn main() -> io::Result<()> {
         .about("Search for byte patterns in large disk images, and explore the results.")
         .arg(
             Arg::with_name("input_file_path")
-                .help("Path to the input image file (can be compressed)")
+                .help("Path to the input image file (can be compressed), or - for stdin (uncompressed). Give it more than once for the parts of a split image (like -i disk.001 -i disk.002), which are searched in order as one image")
                 .short('i')
                 .long("input-file-path")
-                .required(true)
-                .takes_value(true),
+                .required_unless_present_any(["compare_runs", "init_config", "benchmark", "explore", "needle_config_check_only"])
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .help("Log more: -v for debug lines, -vv for trace (the default is info)")
+                .short('v')
+                .long("verbose")
+                .multiple_occurrences(true)
+                .conflicts_with("quiet"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .help("Log less: -q for warnings and errors only (without the per-match lines), -qq for errors only")
+                .short('q')
+                .long("quiet")
+                .multiple_occurrences(true),
         )
         .arg(
             Arg::with_name("output_dir")
                 .help("Path to output directory")
                 .short('o')
                 .long("output-dir")
-                .required(true)
+                .required_unless_present_any(["compare_runs", "init_config", "benchmark", "explore", "needle_config_check_only", "resume"])
                 .takes_value(true),
         )
         // TODO: automatically detect the compression format
         .arg(
             Arg::with_name("compression_format")
-                .help("Compression format of input file (none, xz, or lz4)")
+                .help("Compression format of input file (none, xz, lz4, or gzip/gz)")
                 .short('c')
                 .long("compression-format")
-                .possible_values(vec!["none", "xz", "lz4"])
+                .possible_values(vec!["none", "xz", "lz4", "gzip", "gz"])
                 .default_value("none"),
         )
+        .arg(
+            Arg::with_name("recursive")
+                .help("If the input is a tar archive, search the first file in it (e.g., a compressed image), decompressing it if it's xz or lz4. Only one level of nesting is opened.")
+                .long("recursive")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("needle_config_yaml_path")
-                .help("Path to needle config file")
+                .help("Path to needle config file, or an http(s):// URL to fetch it from")
                 .short('n')
                 .long("needle-config-file-path")
-                .required(true)
+                .required_unless_present_any(["compare_runs", "init_config", "benchmark", "explore"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("needle_name_regex")
+                .help("Only search for needles whose names match this regex (e.g., '^crypto_')")
+                .long("needle-name-regex")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("happiness_dir_prefixes")
+                .help("Per-needle output directory prefix for each happiness level, like '9=critical,0=info' (default: the level number)")
+                .long("happiness-dir-prefixes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sidecar_format")
+                .help("Also write a sidecar file of all matches, for use in other forensic tools (none, bodyfile, or csv)")
+                .long("sidecar-format")
+                .possible_values(vec!["none", "bodyfile", "csv"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("output_format")
+                .help("Match record output: jsonl (one record per line, written as matches are found), or json-array (also assembles them into one JSON array file at the end)")
+                .long("output-format")
+                .possible_values(vec!["jsonl", "json-array"])
+                .default_value("jsonl"),
+        )
+        .arg(
+            Arg::with_name("json_array")
+                .help("Same as --output-format json-array: also assemble the match records into one JSON array file (00_all_output_record.json) at the end")
+                .long("json-array")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("jsonl_max_size")
+                .help("Split 00_all_output_record.jsonl into numbered parts (like 00_all_output_record_part002.jsonl), starting a new part once one reaches this size, like 100M or 1G")
+                .long("jsonl-max-size")
+                .takes_value(true)
+                .conflicts_with("no_global_jsonl")
+                .value_name("SIZE"),
+        )
+        .arg(
+            Arg::with_name("jsonl_max_records")
+                .help("Split 00_all_output_record.jsonl into numbered parts, starting a new part once one has this many match records")
+                .long("jsonl-max-records")
+                .takes_value(true)
+                .conflicts_with("no_global_jsonl"),
+        )
+        .arg(
+            Arg::with_name("jsonl_line_ending")
+                .help("Line ending between JSONL records: lf, or crlf (for Windows tools that want it)")
+                .long("jsonl-line-ending")
+                .possible_values(vec!["lf", "crlf"])
+                .default_value("lf"),
+        )
+        .arg(
+            Arg::with_name("jsonl_val_array")
+                .help("Write the needle value in JSONL records as an array of byte values (\"val\": [72, 101, ...]), like older versions, instead of a hex string (\"val_hex\": \"4865...\")")
+                .long("jsonl-val-array")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("jsonl_context_hexdump")
+                .help("Add a hex+ASCII dump of each match's written context (within the needle's before/after bytes) to its JSONL record (context_hexdump)")
+                .long("jsonl-context-hexdump")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("val_display_format")
+                .help("How needle values are shown in logs and JSONL: debug ([72, 101]) or hex (48 65)")
+                .long("val-display-format")
+                .possible_values(vec!["debug", "hex"])
+                .default_value("debug"),
+        )
+        .arg(
+            Arg::with_name("match_log_throttle_threshold")
+                .help("Max matches per needle per chunk to log individually, before switching to a per-chunk summary")
+                .long("match-log-throttle-threshold")
+                .takes_value(true)
+                .default_value("100"),
+        )
+        .arg(
+            Arg::with_name("chunk_search_threads")
+                .help("Number of threads to split each chunk's search across (useful with large chunks)")
+                .long("chunk-search-threads")
+                .takes_value(true)
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .help("Search an uncompressed image file on this many threads, each reading and searching its own part of the file (for fast drives, like NVMe). Compressed input is always searched on one thread.")
+                .long("threads")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("1")
+                .conflicts_with_all(&["resume", "partition", "hash_stream", "hotspot_window", "single_pass_verify", "sample_every_nth_chunk", "jsonl_max_size", "jsonl_max_records", "progress_bar"]),
+        )
+        .arg(
+            Arg::with_name("mmap")
+                .help("Memory-map an uncompressed image file and search it in place, instead of copying it into a buffer chunk by chunk. Compressed input and stdin are always streamed.")
+                .long("mmap")
+                .conflicts_with_all(&["threads", "resume", "hash_stream", "progress_bar"]),
+        )
+        .arg(
+            Arg::with_name("chunk_size_mib")
+                .help("Size of each haystack chunk read and searched, in MiB (e.g., 32 or 0.5). Bigger chunks mean fewer reads, and more memory. Default: 8 for uncompressed images, 4 for compressed ones")
+                .long("chunk-size-mib")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sample_every_nth_chunk")
+                .help("Quick triage: only search every Nth chunk, skipping the rest. Results are incomplete!")
+                .long("sample-every-nth-chunk")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dump_write_queue_len")
+                .help("Max haystack chunk dumps waiting to be written to disk, before the search waits for the disk")
+                .long("dump-write-queue-len")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_memory")
+                .help("Refuse to start if the estimated memory use (chunk buffer, dump write queue, needles, and found matches) is over this size, like 512M or 4G")
+                .long("max-memory")
+                .takes_value(true)
+                .value_name("SIZE"),
+        )
+        .arg(
+            Arg::with_name("only_printable_needles")
+                .help("Only search for the printable (text) needles in the config, skipping binary ones, for a quick keyword sweep")
+                .long("only-printable-needles")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("max_needles")
+                .help("Refuse to start if the needle config has more than this many needles (after --needle-name-regex)")
+                .long("max-needles")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_matches_per_chunk")
+                .help("Only report this many matches per haystack chunk (the highest-priority ones), so a pathological chunk can't stall the search")
+                .long("max-matches-per-chunk")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("hash_stream")
+                .help("Compute a hash (see --hash-algo) of the whole (uncompressed) input stream while searching, and record it in the run stats")
+                .long("hash-stream")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("hash_algo")
+                .help("Hash algorithm for --hash-stream (md5 and sha1 match legacy forensic tools; blake3 is fastest)")
+                .long("hash-algo")
+                .possible_values(vec!["sha256", "sha1", "md5", "blake3"])
+                .default_value("sha256"),
+        )
+        .arg(
+            Arg::with_name("read_retries")
+                .help("Times to retry a read that fails with a transient error (e.g., on a network filesystem), before giving up")
+                .long("read-retries")
+                .takes_value(true)
+                .default_value("3"),
+        )
+        .arg(
+            Arg::with_name("skip_read_errors")
+                .help("If the compressed image is corrupt partway through, stop searching there with a warning (keeping the results so far), instead of with an error. A truncated image is always treated this way.")
+                .long("skip-read-errors")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("read_retry_delay")
+                .help("Delay before the first read retry (e.g., 500ms, 2s). Doubles for each retry after that")
+                .long("read-retry-delay")
+                .takes_value(true)
+                .default_value("1s"),
+        )
+        .arg(
+            Arg::with_name("explore")
+                .help("Instead of searching, browse a results directory interactively: per-needle summary, happiness filter, and hexdumps of the dumps")
+                .long("explore")
+                .takes_value(true)
+                .value_name("RESULTS_DIR"),
+        )
+        .arg(
+            Arg::with_name("compare_runs")
+                .help("Instead of searching, compare two results directories, and report matches only in one of them")
+                .long("compare-runs")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["RESULTS_DIR_A", "RESULTS_DIR_B"]),
+        )
+        .arg(
+            Arg::with_name("deterministic")
+                .help("Make the output the same for every run of the same search, for golden-file tests: no timestamp in the results directory name or match records, and dump paths relative to the results directory")
+                .long("deterministic")
+                .takes_value(false)
+                .conflicts_with_all(&["record_match_time", "resume_from_offset"]),
+        )
+        .arg(
+            Arg::with_name("record_match_time")
+                .help("Record each match's time in high resolution, and the seconds since the start of the search (elapsed_since_start_sec), to line matches up with a live imaging timeline")
+                .long("record-match-time")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("stats_precision")
+                .help("Decimal places for the throughput (MiB/s) and percentages in the progress stats, so slow or just-started scans show meaningful values")
+                .long("stats-precision")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("progress_bar")
+                .help("Show a live progress bar (throughput, percent complete, and ETA) under the log output. Only on a terminal: otherwise, progress is only logged every 30 seconds.")
+                .long("progress-bar")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("progress_json_to")
+                .help("Append each progress tick as a JSON line to this file (e.g., progress.ndjson), to plot throughput after the run")
+                .long("progress-json-to")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude_carved_files")
+                .help("Drop matches inside embedded JPEG/ZIP files (noise), unless the needle sets allow_inside_carved_files")
+                .long("exclude-carved-files")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sqlite")
+                .help("Also write the match records to a SQLite database at this path (table: needle_finds, indexed by name and offset), to query them with SQL")
+                .long("sqlite")
+                .takes_value(true)
+                .value_name("DB_PATH"),
+        )
+        .arg(
+            Arg::with_name("no_emoji")
+                .help("Show needle happiness levels as plain text, like \"(level 4)\", instead of with emoji, for terminals and CI logs that don't show them")
+                .long("no-emoji")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("no_global_jsonl")
+                .help("Only write the per-needle JSONL files, not the combined 00_all_output_record.jsonl (which can get unwieldy on huge scans)")
+                .long("no-global-jsonl")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("chs")
+                .help("Disk geometry as 'cylinders,heads,sectors' (e.g., 1024,16,63), to also record each match's CHS address")
+                .long("chs")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("offsets_only")
+                .help("Don't dump haystack chunks. Instead, write a plain list of hex match offsets per needle ({needle}.offsets), e.g., for hex editor bookmarks")
+                .long("offsets-only")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("no_dumps")
+                .help("Don't dump haystack chunks for any needle (only write the JSONL records), overriding write_to_file in the needle config")
+                .long("no-dumps")
+                .takes_value(false)
+                .conflicts_with("force_dumps"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .help("Search the whole image and record the matches in the combined JSONL file and the summary, but don't create the per-needle directories or dump any haystack chunks. To see how many matches each needle gets before a full run")
+                .long("dry-run")
+                .takes_value(false)
+                .conflicts_with_all(&["no_global_jsonl", "force_dumps", "offsets_only"]),
+        )
+        .arg(
+            Arg::with_name("dedup")
+                .help("Record each match (needle name and offset) only once in the run, even if its region is searched again. When resuming, the matches already in the results directory are skipped too. Keeps every recorded match's name and offset in memory")
+                .long("dedup")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("force_dumps")
+                .help("Dump haystack chunks for every needle, overriding write_to_file in the needle config")
+                .long("force-dumps")
+                .takes_value(false)
+                .conflicts_with("offsets_only"),
+        )
+        .arg(
+            Arg::with_name("text_regions_only")
+                .help("Only match text needles (all printable ASCII) inside runs of at least this many printable bytes, to skip coincidences in binary data")
+                .long("text-regions-only")
+                .takes_value(true)
+                .value_name("MIN_RUN_LEN"),
+        )
+        .arg(
+            Arg::with_name("scan_window_context")
+                .help("Dump the full before/after context of matches near a chunk boundary, instead of cutting it off at the chunk (uses a little more memory)")
+                .long("scan-window-context")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("hotspot_window")
+                .help("Record a hotspot (in 07_hotspots.jsonl) for each region where at least --hotspot-min-matches matches are within this many bytes of each other, like 64K")
+                .long("hotspot-window")
+                .takes_value(true)
+                .value_name("SIZE"),
+        )
+        .arg(
+            Arg::with_name("hotspot_min_matches")
+                .help("Number of matches within --hotspot-window that makes a hotspot")
+                .long("hotspot-min-matches")
+                .takes_value(true)
+                .default_value("10")
+                .requires("hotspot_window"),
+        )
+        .arg(
+            Arg::with_name("dump_filename_template")
+                .help("Name of each match's dump file (.bin is added). Placeholders: {needle}, {offset} (hex global offset), {start} (hex offset of the match in the dump), {index} (match number in this run), {happiness}. Needs {offset} or {index}.")
+                .long("dump-filename-template")
+                .takes_value(true)
+                .default_value(DEFAULT_DUMP_FILE_NAME_TEMPLATE),
+        )
+        .arg(
+            Arg::with_name("merge_dump_distance")
+                .help("Write one shared dump for matches whose context windows are within this many bytes of each other, instead of one per match")
+                .long("merge-dump-distance")
+                .takes_value(true)
+                .value_name("BYTES"),
+        )
+        .arg(
+            Arg::with_name("resume_from_offset")
+                .help("Start searching at this uncompressed offset (decimal, or hex with 0x), appending to the existing results directory given as -o. Reported offsets are still absolute. Compressed input is decompressed and discarded up to the offset.")
+                .long("resume-from-offset")
+                .takes_value(true)
+                .value_name("OFFSET"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .help("Continue an interrupted search from the checkpoint in its results directory, appending to it. Needs the same input file, needle config, and needle filters. Compressed input is decompressed and discarded up to the checkpoint.")
+                .long("resume")
+                .takes_value(true)
+                .value_name("RESULTS_DIR")
+                .conflicts_with_all(&["output_dir", "resume_from_offset", "start_offset", "partition", "deterministic", "single_pass_verify"]),
+        )
+        .arg(
+            Arg::with_name("start_offset")
+                .help("Only search from this uncompressed offset on (decimal, or hex with 0x). Reported offsets are still absolute. Compressed input is decompressed and discarded up to the offset.")
+                .long("start-offset")
+                .takes_value(true)
+                .value_name("OFFSET")
+                .conflicts_with_all(&["resume_from_offset", "partition"]),
+        )
+        .arg(
+            Arg::with_name("end_offset")
+                .help("Stop searching at this uncompressed offset (exclusive; decimal, or hex with 0x)")
+                .long("end-offset")
+                .takes_value(true)
+                .value_name("OFFSET")
+                .conflicts_with("partition"),
+        )
+        .arg(
+            Arg::with_name("partition")
+                .help("Only search this partition of a whole-disk image (numbered like sda1: 1-4 for MBR primary partitions, 5+ for logical ones, or the GPT entry number). Needs an uncompressed image.")
+                .long("partition")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("resume_from_offset"),
+        )
+        .arg(
+            Arg::with_name("partition_relative_offsets")
+                .help("With --partition, report offsets from the start of the partition, instead of the start of the disk")
+                .long("partition-relative-offsets")
+                .takes_value(false)
+                .requires("partition"),
+        )
+        .arg(
+            Arg::with_name("benchmark")
+                .help("Instead of searching an image, measure search throughput on this many MiB of synthetic data (with the -n needles, or random ones)")
+                .long("benchmark")
+                .takes_value(true)
+                .value_name("MIB"),
+        )
+        .arg(
+            Arg::with_name("needle_config_check_only")
+                .help("Instead of searching, check that every needle in the needle config decodes, and report all the errors at once")
+                .long("needle-config-check-only")
+                .requires("needle_config_yaml_path")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("init_config")
+                .help("Instead of searching, write a commented sample needle config file to this path, to start from")
+                .long("init-config")
+                .takes_value(true)
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::with_name("single_pass_verify")
+                .help("Diagnostic: verify that every byte of the stream is searched exactly once (plus carry-forward), at its true offset")
+                .long("single-pass-verify")
+                .takes_value(false),
+        )
         .get_matches();
 
-    let input_file_path_str = cli_arg_matches
-        .value_of("input_file_path")
-        .expect("No valid input file provided");
+    if let Some(config_file_path) = cli_arg_matches.value_of("init_config") {
+        match write_needle_config_template(&PathBuf::from(config_file_path)) {
+            Ok(()) => println!("Wrote sample needle config: {}", config_file_path),
+            Err(e) => panic!("Could not write sample needle config: {}", e),
+        }
+        return Ok(());
+    }
+
+    if cli_arg_matches.is_present("needle_config_check_only") {
+        let needle_config_path = cli_arg_matches
+            .value_of("needle_config_yaml_path")
+            .expect("No valid needle config file provided");
+        let needle_config_text = read_needle_config_text(needle_config_path)
+            .unwrap_or_else(|e| panic!("Could not load needle config: {}", e));
+        match check_needle_config_str(&needle_config_text, &needle_config_dir(needle_config_path)) {
+            Ok(needle_count) => println!(
+                "Needle config is valid: {} needles in {}",
+                needle_count, needle_config_path
+            ),
+            Err(errors) => panic!(
+                "Needle config {} has {} error(s):\n{}",
+                needle_config_path,
+                errors.len(),
+                errors.join("\n")
+            ),
+        }
+        return Ok(());
+    }
+
+    if let Some(benchmark_size_mib) = cli_arg_matches.value_of("benchmark") {
+        let benchmark_size_mib: usize = benchmark_size_mib
+            .parse()
+            .expect("Benchmark size must be a positive integer (MiB)");
+        let needles: Vec<Needle> = match cli_arg_matches.value_of("needle_config_yaml_path") {
+            Some(needle_config_path) => read_needle_config_text(needle_config_path)
+                .and_then(|text| {
+                    load_needles_from_str_in_dir(&text, &needle_config_dir(needle_config_path))
+                })
+                .unwrap_or_else(|e| panic!("Could not load needle config: {}", e)),
+            None => make_synthetic_needles(0),
+        };
+        let chunk_search_threads: usize = cli_arg_matches
+            .value_of("chunk_search_threads")
+            .expect("No valid chunk search thread count provided")
+            .parse()
+            .expect("Chunk search thread count must be a positive integer");
+
+        println!(
+            "Benchmarking search of {} MiB of synthetic data for {} needles, with {} thread(s)...",
+            benchmark_size_mib,
+            needles.len(),
+            chunk_search_threads
+        );
+        let benchmark_result = run_benchmark(
+            &needles,
+            benchmark_size_mib * 1024 * 1024,
+            BENCHMARK_CHUNK_SIZE_BYTES,
+            chunk_search_threads,
+        );
+        println!(
+            "Searched {} MiB in {:.2} s: {:.1} MiB/s ({} matches)",
+            benchmark_size_mib,
+            benchmark_result.search_duration.as_secs_f64(),
+            benchmark_result.mib_per_sec(),
+            benchmark_result.match_count
+        );
+        return Ok(());
+    }
+
+    if let Some(results_dir) = cli_arg_matches.value_of("explore") {
+        let result_set = ResultSet::load(&PathBuf::from(results_dir))
+            .unwrap_or_else(|e| panic!("Could not load results: {}", e));
+        run_explorer(result_set).expect("Could not run the results explorer");
+        return Ok(());
+    }
+
+    if let Some(mut results_paths) = cli_arg_matches.values_of("compare_runs") {
+        let jsonl_file_path_a =
+            resolve_results_jsonl_path(&PathBuf::from(results_paths.next().unwrap()));
+        let jsonl_file_path_b =
+            resolve_results_jsonl_path(&PathBuf::from(results_paths.next().unwrap()));
+        println!(
+            "Comparing run A ({}) to run B ({})",
+            jsonl_file_path_a.display(),
+            jsonl_file_path_b.display()
+        );
+        match compare_runs(&jsonl_file_path_a, &jsonl_file_path_b) {
+            Ok(run_comparison) => println!("{}", run_comparison.make_summary_message()),
+            Err(e) => panic!("Could not compare runs: {}", e),
+        }
+        return Ok(());
+    }
+
+    // the parts of a split image, in order (usually just the one input file)
+    let input_file_path_strs: Vec<&str> = cli_arg_matches
+        .values_of("input_file_path")
+        .expect("No valid input file provided")
+        .collect();
+    let input_file_path_str = input_file_path_strs[0];
     let compression_format = cli_arg_matches
         .value_of("compression_format")
         .expect("No valid compression format provided");
+    // with --resume, the output dir is the interrupted run's results directory
     let cli_output_dir_str = cli_arg_matches
         .value_of("output_dir")
+        .or_else(|| cli_arg_matches.value_of("resume"))
         .expect("No valid output directory provided");
     let needle_config_yaml_path = cli_arg_matches
         .value_of("needle_config_yaml_path")
         .expect("No valid needle config file provided");
+    let jsonl_line_ending =
+        LineEnding::from_str(cli_arg_matches.value_of("jsonl_line_ending").unwrap())
+            .expect("Invalid JSONL line ending");
+    let write_json_array = cli_arg_matches.value_of("output_format") == Some("json-array")
+        || cli_arg_matches.is_present("json_array");
+    let sidecar_format: Option<SidecarFormat> = cli_arg_matches
+        .value_of("sidecar_format")
+        .and_then(|val| SidecarFormat::from_str(val).ok());
+    let single_pass_verify = cli_arg_matches.is_present("single_pass_verify");
+    let stats_precision: usize = cli_arg_matches
+        .value_of("stats_precision")
+        .expect("No valid stats precision provided")
+        .parse()
+        .expect("Stats precision must be a non-negative integer");
+    let progress_json_file_path: Option<PathBuf> = cli_arg_matches
+        .value_of("progress_json_to")
+        .map(PathBuf::from);
+    let happiness_dir_prefixes = match cli_arg_matches.value_of("happiness_dir_prefixes") {
+        Some(mapping_str) => parse_happiness_dir_prefixes(mapping_str)
+            .unwrap_or_else(|e| panic!("Invalid happiness dir prefixes: {}", e)),
+        None => HashMap::new(),
+    };
+    let stream_hash_algo: Option<HashAlgo> = match cli_arg_matches.is_present("hash_stream") {
+        true => Some(
+            HashAlgo::from_str(cli_arg_matches.value_of("hash_algo").unwrap())
+                .expect("Invalid hash algorithm"),
+        ),
+        false => None,
+    };
+    let read_retries: u32 = cli_arg_matches
+        .value_of("read_retries")
+        .expect("No valid read retry count provided")
+        .parse()
+        .expect("Read retry count must be a non-negative integer");
+    let read_retry_delay = humantime::parse_duration(
+        cli_arg_matches
+            .value_of("read_retry_delay")
+            .expect("No valid read retry delay provided"),
+    )
+    .expect("Read retry delay must be a duration, like 500ms or 2s");
+    let exclude_carved_files = cli_arg_matches.is_present("exclude_carved_files");
+    let no_global_jsonl = cli_arg_matches.is_present("no_global_jsonl");
+    let disk_geometry: Option<DiskGeometry> = cli_arg_matches.value_of("chs").map(|val| {
+        DiskGeometry::from_str(val).unwrap_or_else(|e| panic!("Invalid disk geometry: {}", e))
+    });
+    let val_display_format = cli_arg_matches
+        .value_of("val_display_format")
+        .and_then(|val| ValDisplayFormat::from_str(val).ok())
+        .unwrap_or_default();
+    let merge_dump_distance_bytes: Option<usize> =
+        cli_arg_matches.value_of("merge_dump_distance").map(|val| {
+            val.parse()
+                .expect("Merge dump distance must be a non-negative integer")
+        });
+    let resume_from_offset: Option<u64> =
+        cli_arg_matches.value_of("resume_from_offset").map(|val| {
+            parse_stream_offset(val)
+            .expect("Resume-from offset must be a non-negative integer (decimal, or hex with a 0x prefix)")
+        });
+    let start_offset: Option<u64> = cli_arg_matches.value_of("start_offset").map(|val| {
+        parse_stream_offset(val).expect(
+            "Start offset must be a non-negative integer (decimal, or hex with a 0x prefix)",
+        )
+    });
+    let end_offset: Option<u64> = cli_arg_matches.value_of("end_offset").map(|val| {
+        parse_stream_offset(val)
+            .expect("End offset must be a non-negative integer (decimal, or hex with a 0x prefix)")
+    });
+    let resume_checkpoint: Option<Checkpoint> =
+        cli_arg_matches.value_of("resume").map(|results_dir| {
+            Checkpoint::read_from_json_file(&Checkpoint::file_path(Path::new(results_dir)))
+                .unwrap_or_else(|e| panic!("Could not resume: {}", e))
+        });
+    if let Some(end_offset) = end_offset {
+        let range_start = start_offset
+            .or(resume_from_offset)
+            .or(resume_checkpoint
+                .as_ref()
+                .map(|checkpoint| checkpoint.searched_end_global_offset))
+            .unwrap_or(0);
+        if end_offset <= range_start {
+            panic!(
+                "End offset ({}) must be after the start offset ({})",
+                end_offset, range_start
+            );
+        }
+    }
+    let text_regions_min_run_len: Option<usize> = cli_arg_matches
+        .value_of("text_regions_only")
+        .map(|val| match val.parse::<usize>() {
+            Ok(min_run_len) if min_run_len > 0 => min_run_len,
+            _ => panic!("Text regions min run length must be a positive integer"),
+        });
+    let max_memory_bytes: Option<u64> = cli_arg_matches
+        .value_of("max_memory")
+        .map(|val| parse_byte_size(val).unwrap_or_else(|e| panic!("Invalid --max-memory: {}", e)));
+    let max_needle_count: Option<usize> = cli_arg_matches.value_of("max_needles").map(|val| {
+        val.parse()
+            .expect("Max needles must be a non-negative integer")
+    });
+    let chunk_size_bytes: Option<usize> =
+        cli_arg_matches
+            .value_of("chunk_size_mib")
+            .map(|val| match val.parse::<f64>() {
+                Ok(mib) if mib > 0.0 => (mib * 1024.0 * 1024.0).round() as usize,
+                _ => panic!("Chunk size must be a positive number of MiB"),
+            });
+    let max_matches_per_chunk: Option<usize> = cli_arg_matches
+        .value_of("max_matches_per_chunk")
+        .map(|val| match val.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => panic!("Max matches per chunk must be a positive integer"),
+        });
+    let jsonl_max_part_size_bytes: Option<u64> =
+        cli_arg_matches.value_of("jsonl_max_size").map(|val| {
+            parse_byte_size(val).unwrap_or_else(|e| panic!("Invalid --jsonl-max-size: {}", e))
+        });
+    let jsonl_max_part_records: Option<u64> =
+        cli_arg_matches
+            .value_of("jsonl_max_records")
+            .map(|val| match val.parse::<u64>() {
+                Ok(n) if n > 0 => n,
+                _ => panic!("JSONL max records must be a positive integer"),
+            });
+    let hotspot_window_len_bytes: Option<u64> =
+        cli_arg_matches.value_of("hotspot_window").map(|val| {
+            parse_byte_size(val).unwrap_or_else(|e| panic!("Invalid --hotspot-window: {}", e))
+        });
+    let hotspot_min_match_count: usize = match cli_arg_matches
+        .value_of("hotspot_min_matches")
+        .unwrap()
+        .parse::<usize>()
+    {
+        Ok(n) if n > 1 => n,
+        _ => panic!("Hotspot min matches must be an integer over 1"),
+    };
+    let dump_write_queue_len: usize = match cli_arg_matches.value_of("dump_write_queue_len") {
+        Some(val) => val
+            .parse()
+            .expect("Dump write queue length must be a non-negative integer"),
+        None => DEFAULT_DUMP_WRITE_QUEUE_LEN,
+    };
+    let sample_every_nth_chunk: Option<u64> = cli_arg_matches
+        .value_of("sample_every_nth_chunk")
+        .map(|val| match val.parse::<u64>() {
+            Ok(n) if n > 0 => n,
+            _ => panic!("Sample every Nth chunk must be a positive integer"),
+        });
+    if let Some(n) = sample_every_nth_chunk {
+        warn!(
+            "SAMPLED SCAN: only every {} chunk(s) will be searched. Results will be incomplete.",
+            n
+        );
+    }
+    let chunk_search_threads: usize = cli_arg_matches
+        .value_of("chunk_search_threads")
+        .expect("No valid chunk search thread count provided")
+        .parse()
+        .expect("Chunk search thread count must be a positive integer");
+    let thread_count: usize = cli_arg_matches
+        .value_of("threads")
+        .expect("No valid thread count provided")
+        .parse()
+        .ok()
+        .filter(|&thread_count| thread_count > 0)
+        .expect("Thread count must be a positive integer");
+    let match_log_throttle_threshold: u64 = cli_arg_matches
+        .value_of("match_log_throttle_threshold")
+        .expect("No valid match log throttle threshold provided")
+        .parse()
+        .expect("Match log throttle threshold must be a non-negative integer");
 
+    // `-i -` reads the image from stdin, like from `dd` or `ssh`, so it has no known size and can't seek.
+    let is_stdin_input = input_file_path_str == "-";
+    if is_stdin_input {
+        if compression_format != "none" {
+            panic!("Input from stdin must be uncompressed (-c none). Decompress it in the pipe instead, like `xz -dc image.xz | drive-image-searcher -c none -i - ...`");
+        }
+        if cli_arg_matches.is_present("recursive") || cli_arg_matches.is_present("partition") {
+            panic!("--recursive and --partition need an input file, not stdin");
+        }
+    }
+    let is_split_input = input_file_path_strs.len() > 1;
+    if is_split_input {
+        if input_file_path_strs.contains(&"-") {
+            panic!("Stdin (-i -) can't be one of the parts of a split image");
+        }
+        if cli_arg_matches.is_present("recursive") || cli_arg_matches.is_present("partition") {
+            panic!("--recursive and --partition need a single input file, not the parts of a split image");
+        }
+        info!(
+            "Searching the {} parts of a split image, in order, as one image: {}",
+            input_file_path_strs.len(),
+            input_file_path_strs.join(", ")
+        );
+    }
     let input_file_path = PathBuf::from(input_file_path_str);
-    let input_file_name = input_file_path
-        .file_name()
-        .expect("Could not get input file name")
-        .to_str()
-        .expect("Could not convert input file name to str");
-
-    let output_dir_path = PathBuf::from(cli_output_dir_str).join(format!(
-        "results__{}__{}",
-        input_file_name,
-        Utc::now().format("%Y-%m-%dT%H_%M_%S")
-    ));
+    let input_file_name = match is_stdin_input {
+        true => "stdin",
+        false => input_file_path
+            .file_name()
+            .expect("Could not get input file name")
+            .to_str()
+            .expect("Could not convert input file name to str"),
+    };
 
-    let input_file_size_bytes = fs::metadata(input_file_path_str)
-        .expect("Could not get input file size")
-        .len();
-    info!(
-        "Total compressed image size: {} bytes = {} MiB",
-        input_file_size_bytes.to_formatted_string(&Locale::en),
-        ((input_file_size_bytes as f32 / 1024.0 / 1024.0).round() as u64)
-            .to_formatted_string(&Locale::en),
-    );
-    let input_file = File::open(input_file_path_str).expect("Could not open input file");
+    let deterministic = cli_arg_matches.is_present("deterministic");
+    // When resuming, -o is the previous run's results directory, which is appended to.
+    let output_dir_path = match resume_from_offset {
+        Some(_) => {
+            let output_dir_path = PathBuf::from(cli_output_dir_str);
+            if !output_dir_path.is_dir() {
+                panic!(
+                    "With --resume-from-offset, the output dir must be an existing results directory: {}",
+                    output_dir_path.display()
+                );
+            }
+            output_dir_path
+        }
+        None if resume_checkpoint.is_some() => PathBuf::from(cli_output_dir_str),
+        None if deterministic => {
+            let output_dir_path =
+                PathBuf::from(cli_output_dir_str).join(format!("results__{}", input_file_name));
+            if output_dir_path.exists() {
+                panic!(
+                    "With --deterministic, the results directory name has no timestamp, so it must not exist yet: {}",
+                    output_dir_path.display()
+                );
+            }
+            output_dir_path
+        }
+        None => PathBuf::from(cli_output_dir_str).join(format!(
+            "results__{}__{}",
+            input_file_name,
+            Utc::now().format("%Y-%m-%dT%H_%M_%S")
+        )),
+    };
 
-    let needles: Vec<Needle> = match load_needles_from_file(needle_config_yaml_path) {
+    let input_file_size_bytes: Option<u64> = match is_stdin_input {
+        true => None,
+        false => Some(
+            input_file_path_strs
+                .iter()
+                .map(|input_file_path_str| {
+                    fs::metadata(input_file_path_str)
+                        .expect("Could not get input file size")
+                        .len()
+                })
+                .sum(),
+        ),
+    };
+    match input_file_size_bytes {
+        Some(input_file_size_bytes) => info!(
+            "Total compressed image size: {} bytes = {} MiB",
+            input_file_size_bytes.to_formatted_string(&Locale::en),
+            ((input_file_size_bytes as f32 / 1024.0 / 1024.0).round() as u64)
+                .to_formatted_string(&Locale::en),
+        ),
+        None => info!("Reading the image from stdin, so its total size is unknown"),
+    }
+    let mut input_files: Vec<File> = match is_stdin_input {
+        true => Vec::new(),
+        false => input_file_path_strs
+            .iter()
+            .map(|input_file_path_str| {
+                File::open(input_file_path_str).expect("Could not open input file")
+            })
+            .collect(),
+    };
+
+    // The progress bar is drawn to stderr, so only when that's a terminal.
+    let wants_progress_bar = cli_arg_matches.is_present("progress_bar");
+    let progress_bar: Option<ProgressBar> = match wants_progress_bar && io::stderr().is_terminal() {
+        true => Some(make_progress_bar(input_file_size_bytes)),
+        false => None,
+    };
+
+    // With --partition, only that partition's byte range of the disk image is searched.
+    let partition: Option<Partition> = cli_arg_matches.value_of("partition").map(|val| {
+        let partition_number: u32 = val
+            .parse()
+            .expect("Partition number must be a positive integer");
+        if compression_format != "none" || cli_arg_matches.is_present("recursive") {
+            panic!("--partition needs an uncompressed disk image");
+        }
+        let partitions = read_partitions(
+            input_files
+                .first_mut()
+                .expect("--partition needs an input file"),
+        )
+        .expect("Could not read the partition table");
+        let partition = partitions
+            .iter()
+            .find(|partition| partition.number == partition_number)
+            .cloned()
+            .unwrap_or_else(|| {
+                panic!(
+                    "Partition {} not found. Partitions in the image: {:?}",
+                    partition_number,
+                    partitions
+                        .iter()
+                        .map(|partition| partition.number)
+                        .collect::<Vec<u32>>()
+                )
+            });
+        partition
+    });
+    let partition_relative_offsets = cli_arg_matches.is_present("partition_relative_offsets");
+
+    let needle_config_text = read_needle_config_text(needle_config_yaml_path)
+        .unwrap_or_else(|e| panic!("Could not load needle config: {}", e));
+    let needles: Vec<Needle> = match load_needles_from_str_in_dir(
+        &needle_config_text,
+        &needle_config_dir(needle_config_yaml_path),
+    ) {
         Ok(vals) => {
             info!(
                 "Loaded {} needle values from {}",
@@ -137,8 +1000,117 @@ fn main() -> io::Result<()> {
             );
             vals
         }
-        Err(e) => panic!("Could not load needle values: {:?}", e),
+        Err(e) => panic!("Could not load needle values: {}", e),
+    };
+    // A needle that doesn't fit the chunk buffer (with its carry-forward) can't be found. A
+    // compressed input's default buffer grows to fit the needles, so this is the strictest case.
+    if let Err(e) = check_needle_lens(
+        &needles,
+        max_needle_len_bytes_for_chunk_size(
+            chunk_size_bytes.unwrap_or(DEFAULT_HAYSTACK_CHUNK_BUFFER_SIZE_BYTES),
+        ),
+    ) {
+        panic!("Could not load needle values: {}", e);
+    }
+    let needles: Vec<Needle> = match cli_arg_matches.value_of("needle_name_regex") {
+        Some(needle_name_regex) => match filter_needles_by_name_regex(needles, needle_name_regex) {
+            Ok(vals) => {
+                info!(
+                    "Selected {} needles matching name regex '{}'",
+                    vals.len(),
+                    needle_name_regex
+                );
+                vals
+            }
+            Err(e) => panic!("Could not filter needles: {}", e),
+        },
+        None => needles,
+    };
+    let needles: Vec<Needle> = match cli_arg_matches.is_present("only_printable_needles") {
+        true => {
+            let needle_count = needles.len();
+            match filter_text_needles(needles) {
+                Ok(vals) => {
+                    info!(
+                        "Keyword-only mode: selected {} printable needles, skipped {} binary needles",
+                        vals.len(),
+                        needle_count - vals.len()
+                    );
+                    vals
+                }
+                Err(e) => panic!("Could not filter needles: {}", e),
+            }
+        }
+        false => needles,
+    };
+
+    // In offsets-only mode and dry runs, matches are only recorded (JSONL and offsets files), not
+    // dumped.
+    let offsets_only = cli_arg_matches.is_present("offsets_only");
+    let write_to_file_override = match (
+        cli_arg_matches.is_present("no_dumps")
+            || offsets_only
+            || cli_arg_matches.is_present("dry_run"),
+        cli_arg_matches.is_present("force_dumps"),
+    ) {
+        (true, _) => Some(false),
+        (false, true) => Some(true),
+        (false, false) => None,
     };
+    let needles: Vec<Needle> = override_write_to_file(needles, write_to_file_override)
+        .into_iter()
+        .map(|needle| Needle {
+            val_display_format,
+            emoji: !cli_arg_matches.is_present("no_emoji"),
+            ..needle
+        })
+        .collect();
+
+    let dump_file_name_template = DumpFileNameTemplate::new(
+        cli_arg_matches.value_of("dump_filename_template").unwrap(),
+        &needles,
+    )
+    .unwrap_or_else(|e| panic!("Invalid --dump-filename-template: {}", e));
+
+    for needle in &needles {
+        let uniform_byte_vals = needle.uniform_byte_vals();
+        if !uniform_byte_vals.is_empty() {
+            let uniform_byte_vals_str = uniform_byte_vals
+                .iter()
+                .map(|uniform_byte_val| format!("0x{:02X}", uniform_byte_val))
+                .collect::<Vec<String>>()
+                .join(", ");
+            warn!(
+                "Needle '{}' matches a run of only {} bytes. Chunks of only those bytes (normally skipped) will be searched too, which is slower and may find a lot of matches.",
+                needle.name, uniform_byte_vals_str
+            );
+        }
+    }
+
+    if let Some(max_needle_count) = max_needle_count {
+        if needles.len() > max_needle_count {
+            panic!(
+                "Needle config has {} needles, which is over the --max-needles limit of {}",
+                needles.len(),
+                max_needle_count
+            );
+        }
+    }
+
+    let config_hash = config_hash(
+        input_file_name,
+        compression_format,
+        &needle_config_text,
+        &needles
+            .iter()
+            .map(|needle| needle.name.as_str())
+            .collect::<Vec<&str>>(),
+    );
+    if let Some(resume_checkpoint) = &resume_checkpoint {
+        resume_checkpoint
+            .check_resumable(&config_hash)
+            .unwrap_or_else(|e| panic!("Could not resume: {}", e));
+    }
 
     // checked all pre-conditions; probably should not fail anymore based on invalid args, so we can start making dirs
 
@@ -154,7 +1126,15 @@ fn main() -> io::Result<()> {
 
     // bind the logs to the output directory
     let log_file_path = output_dir_path.join("01_general_log.log");
-    setup_logger(&log_file_path).expect("Could not set up logger");
+    let log_level = log_level_filter(
+        cli_arg_matches.occurrences_of("verbose"),
+        cli_arg_matches.occurrences_of("quiet"),
+    );
+    setup_logger(&log_file_path, progress_bar.as_ref(), log_level)
+        .expect("Could not set up logger");
+    if wants_progress_bar && progress_bar.is_none() {
+        warn!("Not showing a progress bar, because stderr isn't a terminal. Logging progress every 30 seconds instead.");
+    }
 
     // re-log a few things so they show in the file
     info!("Logging to: {}", log_file_path.display());
@@ -165,10 +1145,26 @@ fn main() -> io::Result<()> {
     info!("Using args: {:?}", cli_arg_matches);
     info!("Using args: input_file_path: {}, compression_format: {}, output_dir: {}, needle_config_yaml_path: {}",
         input_file_path_str, compression_format, cli_output_dir_str, needle_config_yaml_path);
+    if let Some(partition) = &partition {
+        info!(
+            "Searching partition {} ({}): offset 0x{} to 0x{} ({} bytes), with {} offsets",
+            partition.number,
+            partition.partition_type,
+            display_hex_offset(partition.start_offset, 1),
+            display_hex_offset(partition.end_offset(), 1),
+            partition.len_bytes.to_formatted_string(&Locale::en),
+            match partition_relative_offsets {
+                true => "partition-relative",
+                false => "absolute",
+            }
+        );
+    }
 
     // copy the needle config file to the output directory
-    let needle_config_file_dest_path = output_dir_path.join("02_needle_config.yaml");
-    fs::copy(needle_config_yaml_path, &needle_config_file_dest_path)
+    let needle_config_file_dest_path =
+        output_dir_path.join(needle_config_copy_file_name(needle_config_yaml_path));
+    // (written from what was loaded, so configs from a URL are kept too)
+    fs::write(&needle_config_file_dest_path, &needle_config_text)
         .expect("Could not copy needle config file to output directory");
     info!(
         "Copied needle config file to: {}",
@@ -178,202 +1174,847 @@ fn main() -> io::Result<()> {
     let jsonl_output_log_file_path = output_dir_path.clone().join("00_all_output_record.jsonl");
 
     // pack into a struct for easy passage as an arg
+    let haystack_carry_forward_len_bytes = haystack_carry_forward_len_bytes_for(&needles);
+    for needle in &needles {
+        let match_len = needle.match_len().unwrap_or(0);
+        if match_len > DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES {
+            warn!(
+                "Needle '{}' is {} bytes, longer than the default carry-forward of {} bytes. Carrying forward {} bytes between chunks, so its matches across chunk boundaries are found.",
+                needle.name,
+                match_len,
+                DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES,
+                haystack_carry_forward_len_bytes
+            );
+        }
+    }
+
     let search_assignment = SearchAssignment {
         input_file_path: input_file_path.clone(),
         output_dir_path: output_dir_path.clone(),
-        jsonl_output_log_file_path: jsonl_output_log_file_path.clone(),
+        jsonl_output_log_file_path: match no_global_jsonl {
+            true => None,
+            false => Some(jsonl_output_log_file_path),
+        },
+        needle_matcher: NeedleMatcher::new(&needles).unwrap_or_else(|e| panic!("{}", e)),
         needles: needles.clone(),
+        chunk_search_threads,
+        happiness_dir_prefixes,
+        haystack_carry_forward_len_bytes,
+        sample_every_nth_chunk,
+        exclude_carved_files,
+        merge_dump_distance_bytes,
+        disk_geometry,
+        text_regions_min_run_len,
+        jsonl_line_ending,
+        jsonl_val_array: cli_arg_matches.is_present("jsonl_val_array"),
+        jsonl_context_hexdump: cli_arg_matches.is_present("jsonl_context_hexdump"),
+        scan_window_context: cli_arg_matches.is_present("scan_window_context"),
+        max_matches_per_chunk,
+        jsonl_max_part_size_bytes,
+        jsonl_max_part_records,
+        dump_file_name_template,
+        deterministic,
+        checkpoint_interval_sec: Some(CHECKPOINT_INTERVAL_SEC),
+        config_hash,
+        defer_jsonl_records: false,
+        dry_run: cli_arg_matches.is_present("dry_run"),
+        dedup: cli_arg_matches.is_present("dedup"),
+        stop_requested: Arc::new(AtomicBool::new(false)),
+        record_match_time: cli_arg_matches.is_present("record_match_time"),
+        end_global_offset: partition
+            .as_ref()
+            .map(|partition| match partition_relative_offsets {
+                true => partition.len_bytes,
+                false => partition.end_offset(),
+            })
+            .or(end_offset),
+        is_searched_past_end: false,
     };
 
-    let mut input_reader: InputReader = match compression_format {
-        "none" => InputReader::File(input_file),
-        "lz4" => InputReader::Lz4(lz4_flex::frame::FrameDecoder::new(input_file)),
-        "xz" => InputReader::Xz(XzDecoder::new(input_file)),
-        other_compression_format => panic!(
-            "Invalid compression format provided: {}",
-            other_compression_format
-        ),
+    // the size in a split image's first part is only that part's (if it's declared at all)
+    let declared_uncompressed_size: Option<u64> = match is_split_input {
+        true => None,
+        false => input_files
+            .first_mut()
+            .and_then(|input_file| read_declared_uncompressed_size(input_file, compression_format)),
     };
+    if let Some(declared_uncompressed_size) = declared_uncompressed_size {
+        info!(
+            "Uncompressed image size (declared in the compressed stream): {} bytes = {} MiB",
+            declared_uncompressed_size.to_formatted_string(&Locale::en),
+            ((declared_uncompressed_size as f32 / 1024.0 / 1024.0).round() as u64)
+                .to_formatted_string(&Locale::en),
+        );
+    }
 
-    // Amount from the end of the previous read to carry forward
-    let haystack_carry_forward_len_bytes = 1024;
+    // With --recursive, a tar archive is opened, and its first file is searched (decompressed if needed)
+    let is_nested_image = cli_arg_matches.is_present("recursive")
+        && input_files
+            .first_mut()
+            .is_some_and(|input_file| is_tar_file(input_file).expect("Could not read input file"));
+    let mut input_reader: InputReader = match is_stdin_input {
+        true => InputReader::Stdin {
+            stdin: io::stdin().lock(),
+            bytes_read: 0,
+        },
+        false => match compression_format {
+            _ if is_nested_image => {
+                let (tar_member, nested_reader) = open_nested_image(input_files.remove(0))
+                    .expect("Could not open the image in the tar archive");
+                info!(
+                "Input is a tar archive. Searching its first file: '{}' ({} bytes, compression: {:?})",
+                tar_member.name,
+                tar_member.size.to_formatted_string(&Locale::en),
+                nested_reader.compression()
+            );
+                InputReader::Nested(nested_reader)
+            }
+            "none" if !is_split_input => InputReader::File(input_files.remove(0)),
+            "none" => InputReader::Files(
+                MultiFileReader::new(input_files).expect("Could not read the input files"),
+            ),
+            // both read all the concatenated frames/streams (like `cat a.xz b.xz`), not just the
+            // first, and the parts of a split image are concatenated before they're decompressed
+            "lz4" => {
+                let counting_reader = CountingReader::new(
+                    MultiFileReader::new(input_files).expect("Could not read the input files"),
+                );
+                InputReader::Lz4 {
+                    compressed_bytes_read: counting_reader.bytes_read_counter(),
+                    lz4_decoder: MultiFrameLz4Decoder::new(BufReader::new(counting_reader)),
+                    bytes_read: 0,
+                }
+            }
+            "xz" => InputReader::Xz(XzDecoder::new_multi_decoder(
+                MultiFileReader::new(input_files).expect("Could not read the input files"),
+            )),
+            // gzip members (like `cat a.gz b.gz`) too
+            "gzip" | "gz" => {
+                let counting_reader = CountingReader::new(
+                    MultiFileReader::new(input_files).expect("Could not read the input files"),
+                );
+                InputReader::Gzip {
+                    compressed_bytes_read: counting_reader.bytes_read_counter(),
+                    gz_decoder: MultiGzDecoder::new(counting_reader),
+                    bytes_read: 0,
+                }
+            }
+            other_compression_format => panic!(
+                "Invalid compression format provided: {}",
+                other_compression_format
+            ),
+        },
+    };
 
-    // These sizes are important, as they determine how much memory to allocate for the haystack buffer.
-    let haystack_chunk_buffer_size_bytes: usize = match input_reader {
-        InputReader::File(_) => 8*1024*1024, // 8 MiB
-        InputReader::Lz4(_) => 4194304 + haystack_carry_forward_len_bytes,
-        InputReader::Xz(_) => unimplemented!("XzReader not implemented yet, because the returned buffer is a variable length. A refactor is required to work like that.") // 4096 + haystack_carry_forward_len_bytes,
+    // With --threads, an uncompressed image file is split into parts that are searched at once.
+    let parallel_thread_count: Option<usize> = match (thread_count > 1, &input_reader) {
+        (true, InputReader::File(_)) => {
+            if cli_arg_matches
+                .value_of("dump_filename_template")
+                .is_some_and(|template| template.contains("{index}"))
+            {
+                panic!("--threads can't be used with {{index}} in --dump-filename-template, as each thread numbers its own matches");
+            }
+            if search_assignment
+                .needles
+                .iter()
+                .any(|needle| needle.max_matches.is_some())
+            {
+                panic!("--threads can't be used with needles that set max_matches, as each thread counts its own matches");
+            }
+            info!("Searching on {} threads.", thread_count);
+            Some(thread_count)
+        }
+        (true, _) => {
+            warn!("--threads only applies to a single uncompressed image file. Searching on one thread.");
+            None
+        }
+        (false, _) => None,
     };
-    info!(
-        "Haystack (uncompressed) chunk buffer size: {} bytes = {} MiB",
-        haystack_chunk_buffer_size_bytes,
-        ((haystack_chunk_buffer_size_bytes as f32 / 1024.0 / 1024.0).round() as u64)
-            .to_formatted_string(&Locale::en)
-    );
 
-    let mut process_data_state = ProcessDataState::new(haystack_chunk_buffer_size_bytes);
+    // With --mmap, an uncompressed image file is searched in place, instead of read chunk by chunk.
+    let mapped_input_file: Option<Mmap> = match (cli_arg_matches.is_present("mmap"), &input_reader)
+    {
+        (true, InputReader::File(file)) => {
+            info!("Searching the memory-mapped input file.");
+            // The image shouldn't change while it's searched (a changed file gives wrong results).
+            Some(unsafe { Mmap::map(file) }.expect("Could not memory-map the input file"))
+        }
+        (true, _) => {
+            warn!(
+                "--mmap only applies to a single uncompressed image file. Reading the input as a stream."
+            );
+            None
+        }
+        (false, _) => None,
+    };
+    let is_input_file_mapped = mapped_input_file.is_some();
+    if let Some(resume_from_offset) = resume_from_offset {
+        info!(
+            "Resuming from offset 0x{} ({} bytes)...",
+            display_hex_offset(resume_from_offset, 1),
+            resume_from_offset.to_formatted_string(&Locale::en)
+        );
+        skip_input_to_offset(&mut input_reader, resume_from_offset)
+            .expect("Could not skip to the resume-from offset");
+        if stream_hash_algo.is_some() {
+            warn!("Resuming partway through: the input stream hash only covers the bytes from the resume offset on.");
+        }
+    }
+    // The carry-forward before the checkpoint is read again, so matches across it are found.
+    let resume_carried_len: usize = resume_checkpoint.as_ref().map_or(0, |checkpoint| {
+        checkpoint
+            .searched_end_global_offset
+            .min(haystack_carry_forward_len_bytes as u64) as usize
+    });
+    if let Some(resume_checkpoint) = &resume_checkpoint {
+        info!(
+            "Resuming from the checkpoint at offset 0x{} ({} bytes)...",
+            display_hex_offset(resume_checkpoint.searched_end_global_offset, 1),
+            resume_checkpoint
+                .searched_end_global_offset
+                .to_formatted_string(&Locale::en)
+        );
+        skip_input_to_offset(
+            &mut input_reader,
+            resume_checkpoint.searched_end_global_offset - resume_carried_len as u64,
+        )
+        .expect("Could not skip to the checkpoint");
+        if stream_hash_algo.is_some() {
+            warn!("Resuming partway through: the input stream hash only covers the bytes from the checkpoint on.");
+        }
+    }
+    if let Some(start_offset) = start_offset {
+        info!(
+            "Starting at offset 0x{} ({} bytes)...",
+            display_hex_offset(start_offset, 1),
+            start_offset.to_formatted_string(&Locale::en)
+        );
+        skip_input_to_offset(&mut input_reader, start_offset)
+            .expect("Could not skip to the start offset");
+        if stream_hash_algo.is_some() {
+            info!("The input stream hash only covers the bytes from the start offset on.");
+        }
+    }
+    if let Some(end_offset) = end_offset {
+        info!(
+            "Stopping at offset 0x{} ({} bytes)",
+            display_hex_offset(end_offset, 1),
+            end_offset.to_formatted_string(&Locale::en)
+        );
+    }
+    if let Some(partition) = &partition {
+        skip_input_to_offset(&mut input_reader, partition.start_offset)
+            .expect("Could not skip to the start of the partition");
+        if stream_hash_algo.is_some() {
+            info!("The input stream hash only covers the searched partition.");
+        }
+    }
+    // A truncated compressed image ends the stream early (with a warning), instead of failing the run
+    let input_reader = TruncationTolerantReader::new(
+        input_reader,
+        InputReader::is_input_exhausted,
+        cli_arg_matches.is_present("skip_read_errors"),
+    );
+    let input_reader = RetryingReader::new(input_reader, read_retries, read_retry_delay);
+    // Hash the stream as it's read, so the carry-forward bytes aren't hashed twice
+    let input_reader = HashingReader::new(input_reader, stream_hash_algo);
+    let mut input_reader =
+        ProgressBarReader::new(input_reader, progress_bar.clone(), |input_reader| {
+            input_reader.inner().inner().inner().total_in()
+        });
 
-    // Read chunks of the file
-    info!("Starting search...");
+    // These sizes are important, as they determine how much memory to allocate for the haystack buffer.
+    let default_haystack_chunk_buffer_size_bytes: usize =
+        match input_reader.inner().inner().inner().inner() {
+            InputReader::File(_) | InputReader::Files(_) | InputReader::Stdin { .. } => {
+                DEFAULT_HAYSTACK_CHUNK_BUFFER_SIZE_BYTES
+            }
+            InputReader::Lz4 { .. } => 4194304 + haystack_carry_forward_len_bytes,
+            InputReader::Xz(_) => 4194304 + haystack_carry_forward_len_bytes,
+            InputReader::Gzip { .. } => 4194304 + haystack_carry_forward_len_bytes,
+            InputReader::Nested(nested_reader) => match nested_reader.compression() {
+                NestedCompression::None => DEFAULT_HAYSTACK_CHUNK_BUFFER_SIZE_BYTES,
+                NestedCompression::Lz4 => 4194304 + haystack_carry_forward_len_bytes,
+                NestedCompression::Xz => 4194304 + haystack_carry_forward_len_bytes,
+                NestedCompression::Gzip => 4194304 + haystack_carry_forward_len_bytes,
+            },
+        };
+    let haystack_chunk_buffer_size_bytes =
+        chunk_size_bytes.unwrap_or(default_haystack_chunk_buffer_size_bytes);
+    if let Err(e) =
+        search_assignment.check_haystack_chunk_buffer_size(haystack_chunk_buffer_size_bytes)
+    {
+        panic!("Invalid --chunk-size-mib: {}", e);
+    }
+    info!(
+        "Haystack (uncompressed) chunk buffer size: {} bytes = {:.2} MiB{}",
+        haystack_chunk_buffer_size_bytes.to_formatted_string(&Locale::en),
+        haystack_chunk_buffer_size_bytes as f64 / 1024.0 / 1024.0,
+        match chunk_size_bytes {
+            Some(_) => " (from --chunk-size-mib)",
+            None => "",
+        }
+    );
 
-    loop {
-        if process_data_state.total_haystack_bytes_read > 0 {
-            // move the last `haystack_carry_forward_len_bytes` bytes to the beginning of the buffer
-            process_data_state.haystack_chunk_buffer.copy_within(
-                (haystack_carry_forward_len_bytes)..(haystack_chunk_buffer_size_bytes as usize), // to the end
-                0,
-            );
+    // each --threads worker has its own chunk buffer and dump queue
+    let worker_count = parallel_thread_count.unwrap_or(1);
+    let memory_estimate = MemoryEstimate::new(
+        &search_assignment.needles,
+        haystack_chunk_buffer_size_bytes * worker_count,
+        dump_write_queue_len * worker_count,
+    );
+    info!(
+        "Estimated memory use: {} MiB",
+        (memory_estimate.total_bytes() / 1024 / 1024).to_formatted_string(&Locale::en)
+    );
+    if let Some(max_memory_bytes) = max_memory_bytes {
+        if let Err(e) = memory_estimate.check_max_memory(max_memory_bytes) {
+            panic!("{}", e);
         }
+    }
 
-        // TODO: refactor this read step to probably be in the process_data_state/process_data places
-        // FIXME: fix the bug where the offsets are incorrect as a result of the carry forward not shifting the haystack
-        // TODO: before writing out a Needle Find, check that it's not already found (by offset and pattern), because if it's in the 1024 byte carry forward, it gets duplicated right now
-        match input_reader
-            .read(&mut process_data_state.haystack_chunk_buffer[haystack_carry_forward_len_bytes..])
-        {
-            Ok(bytes_read_this_chunk) => {
-                debug!("Read {} bytes", bytes_read_this_chunk);
+    let mut process_data_state = ProcessDataState::new(
+        match is_input_file_mapped {
+            true => 0, // the chunks are windows of the mapped file
+            false => haystack_chunk_buffer_size_bytes,
+        },
+        match_log_throttle_threshold,
+        dump_write_queue_len,
+    );
+    if let Some(mmap) = mapped_input_file {
+        process_data_state.mapped_haystack = Some(MappedHaystack {
+            mmap,
+            global_start_file_offset: match (&partition, partition_relative_offsets) {
+                (Some(partition), true) => partition.start_offset,
+                _ => 0,
+            },
+            window_len: haystack_chunk_buffer_size_bytes,
+        });
+    }
 
-                if (process_data_state.sec_since_last_progress_log() >= 30.0)
-                    || (bytes_read_this_chunk == 0)
-                {
-                    info!(
-                        "Progress stats: {}",
-                        make_progress_stats_message(
-                            &input_reader,
-                            input_file_size_bytes,
-                            &process_data_state
-                        )
-                    );
+    if single_pass_verify {
+        info!("Single-pass verification enabled. Search coverage will be checked against the raw stream.");
+        process_data_state.coverage_verifier =
+            Some(CoverageVerifier::new(haystack_carry_forward_len_bytes));
+    }
 
-                    match log_polars_summary(&jsonl_output_log_file_path) {
-                        Ok(()) => (),
-                        Err(e) => error!("Failed to log polars summary: {}", e),
-                    }
+    if let Some(sqlite_db_path) = cli_arg_matches.value_of("sqlite") {
+        info!(
+            "Writing match records to SQLite database: {}",
+            sqlite_db_path
+        );
+        process_data_state.sqlite_writer = Some(
+            SqliteWriter::open(Path::new(sqlite_db_path))
+                .expect("Could not open the SQLite database"),
+        );
+    }
 
-                    process_data_state.last_progress_log_time = Instant::now();
-                }
+    if let Some(hotspot_window_len_bytes) = hotspot_window_len_bytes {
+        process_data_state.hotspot_detector = Some(HotspotDetector::new(
+            hotspot_window_len_bytes,
+            hotspot_min_match_count,
+        ));
+    }
 
-                if bytes_read_this_chunk == 0 {
+    if search_assignment.dedup {
+        info!(
+            "--dedup keeps the needle name and offset of every recorded match in memory: {} to {} bytes per match, plus its needle name.",
+            process_data::RECORDED_MATCH_KEY_BYTES_RANGE.0,
+            process_data::RECORDED_MATCH_KEY_BYTES_RANGE.1
+        );
+        // When resuming, the matches already in the results directory count as recorded.
+        if resume_from_offset.is_some() || resume_checkpoint.is_some() {
+            match ResultSet::load(&output_dir_path) {
+                Ok(result_set) => {
                     info!(
-                        "Finished searching. No more bytes to read. Total haystack bytes read: {}",
-                        process_data_state
-                            .total_haystack_bytes_read
-                            .to_formatted_string(&Locale::en)
+                        "--dedup: skipping the {} match(es) already recorded in {}.",
+                        result_set.matches.len().to_formatted_string(&Locale::en),
+                        output_dir_path.display()
+                    );
+                    process_data_state.recorded_match_keys.extend(
+                        result_set
+                            .matches
+                            .into_iter()
+                            .map(|found| (found.name, found.match_start_global_offset)),
                     );
-                    break;
-                } else if bytes_read_this_chunk
-                    < (haystack_chunk_buffer_size_bytes - haystack_carry_forward_len_bytes)
-                {
-                    // null out the rest of the buffer to the end
-                    let end_of_data_idx = haystack_carry_forward_len_bytes + bytes_read_this_chunk;
-                    process_data_state.haystack_chunk_buffer[(end_of_data_idx + 1)..].fill(0);
-                    info!("Finishing search. This should be the last haystack chunk. Only read {}/{} bytes",
-                        end_of_data_idx.to_formatted_string(&Locale::en),
-                        (haystack_chunk_buffer_size_bytes - haystack_carry_forward_len_bytes)
-                            .to_formatted_string(&Locale::en));
-
-                    if process_data_state.partial_chunk_read_count > 0 {
-                        warn!("Partial chunk read count: {} (>0) already. This should only happen once.",
-                            process_data_state.partial_chunk_read_count);
-                    }
-                    process_data_state.partial_chunk_read_count += 1;
                 }
+                Err(e) => info!("--dedup: no earlier matches to skip ({}).", e),
             }
-            Err(e) => panic!("Could not read: {}", e),
         }
+    }
 
-        // If all the bytes in the chunk are the same value, then we can skip searching this chunk.
-        // This happens a lot for null/0 bytes in practice.
-        let first_val = process_data_state.haystack_chunk_buffer[0];
-        if process_data_state
-            .haystack_chunk_buffer
+    // When resuming, the matches already in the results directory count towards max_matches.
+    if (resume_from_offset.is_some() || resume_checkpoint.is_some())
+        && search_assignment
+            .needles
             .iter()
-            .all(|&val| val == first_val)
-        {
-            // This log message happens a lot:
-            // debug!(
-            //     "Skipping search for chunk {} because all bytes are the same: {}",
-            //     process_data_state.chunk_count, first_val
-            // );
-        } else {
-            // don't need to skip, so search
-            process_data::do_search(&mut process_data_state, &search_assignment);
+            .any(|needle| needle.max_matches.is_some())
+    {
+        match ResultSet::load(&output_dir_path) {
+            Ok(result_set) => process_data_state
+                .count_earlier_matches(&result_set.matches, &search_assignment.needles),
+            Err(e) => info!("max_matches: no earlier matches to count ({}).", e),
         }
+    }
 
-        // update stats
-        process_data_state.total_haystack_bytes_read +=
-            process_data_state.haystack_chunk_buffer.len() as u64;
-        process_data_state.chunk_count += 1;
+    // When resuming, the new match records go after the earlier ones, in the JSONL file's last part.
+    if resume_from_offset.is_some() || resume_checkpoint.is_some() {
+        process_data_state
+            .continue_jsonl_parts(&search_assignment)
+            .expect("Could not read the existing JSONL file parts");
+    }
+
+    if let Some(resume_from_offset) = resume_from_offset {
+        process_data_state.resume_from_offset(resume_from_offset);
+    }
+    if let Some(start_offset) = start_offset {
+        process_data_state.resume_from_offset(start_offset);
+    }
+    if let Some(resume_checkpoint) = &resume_checkpoint {
+        process_data_state
+            .resume_from_checkpoint(
+                &mut input_reader,
+                resume_checkpoint.searched_end_global_offset,
+                resume_carried_len,
+            )
+            .expect("Could not read the carry-forward before the checkpoint");
     }
+    if let (Some(partition), false) = (&partition, partition_relative_offsets) {
+        process_data_state.resume_from_offset(partition.start_offset);
+    }
+
+    // On Ctrl-C, finish the current chunk, then write the output and summary as usual. A second
+    // Ctrl-C quits right away.
+    let stop_requested = search_assignment.stop_requested.clone();
+    ctrlc::set_handler(move || {
+        if stop_requested.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+        warn!("Ctrl-C: stopping after the current chunk, then writing the summary. Press Ctrl-C again to quit right away.");
+    })
+    .expect("Could not set the Ctrl-C handler");
+
+    // Read chunks of the file
+    info!("Starting search...");
+
+    let mut log_progress = |input_reader: &ProgressBarReader<
+        HashingReader<RetryingReader<TruncationTolerantReader<InputReader>>>,
+    >,
+                            process_data_state: &ProcessDataState| {
+        // a parallel or memory-mapped search reads the (uncompressed) file directly
+        let (total_in, total_out) = match parallel_thread_count.is_some() || is_input_file_mapped {
+            true => (
+                process_data_state.total_haystack_bytes_read,
+                process_data_state.total_haystack_bytes_read,
+            ),
+            false => (
+                input_reader.inner().inner().inner().inner().total_in(),
+                input_reader.inner().inner().inner().inner().total_out(),
+            ),
+        };
+        let progress_stats = ProgressStats::new(
+            total_in,
+            total_out,
+            input_file_size_bytes,
+            declared_uncompressed_size,
+            process_data_state,
+        );
+        info!(
+            "Progress stats: {}",
+            progress_stats.to_message(stats_precision)
+        );
+        if let Some(progress_json_file_path) = &progress_json_file_path {
+            if let Err(e) = progress_stats.append_to_ndjson_file(progress_json_file_path) {
+                error!("Failed to append to progress log: {}", e);
+            }
+        }
+
+        match log_polars_summary(&search_assignment.all_jsonl_file_paths()) {
+            Ok(()) => (),
+            Err(e) => error!("Failed to log polars summary: {}", e),
+        }
+    };
+    match parallel_thread_count {
+        Some(parallel_thread_count) => {
+            let searched_range = process_data_state.total_haystack_bytes_read
+                ..search_assignment
+                    .end_global_offset
+                    .or(input_file_size_bytes)
+                    .expect("Could not get input file size");
+            process_data::search_file_in_parallel(
+                &input_file_path,
+                searched_range,
+                parallel_thread_count,
+                || {
+                    ProcessDataState::new(
+                        haystack_chunk_buffer_size_bytes,
+                        match_log_throttle_threshold,
+                        dump_write_queue_len,
+                    )
+                },
+                &mut process_data_state,
+                &search_assignment,
+            )
+            .expect("Could not search the input file on several threads");
+        }
+        None if is_input_file_mapped => {
+            process_data::search_mapped_file(
+                &mut process_data_state,
+                &search_assignment,
+                |process_data_state| log_progress(&input_reader, process_data_state),
+            );
+        }
+        None => process_data::search_stream(
+            &mut input_reader,
+            &mut process_data_state,
+            &search_assignment,
+            &mut log_progress,
+        ),
+    }
+    if let Some(progress_bar) = &progress_bar {
+        progress_bar.finish_and_clear();
+    }
+
+    // Flush everything before the final summary, so it covers every record. The JSONL records are
+    // flushed as they're appended, but the last haystack chunk dumps may still be queued.
+    process_data_state.finish_dump_writes();
+    process_data_state.finish_sqlite_writes();
+    match (process_data_state.interrupted, parallel_thread_count) {
+        (false, _) => process_data_state.write_checkpoint(&search_assignment, true),
+        // resumable from where it stopped
+        (true, None) => process_data_state.write_checkpoint(&search_assignment, false),
+        // the `--threads` workers each stopped partway through their own part of the file
+        (true, Some(_)) => {
+            warn!("An interrupted --threads search can't be resumed, so no checkpoint was written.")
+        }
+    }
+    log_progress(&input_reader, &process_data_state);
 
     info!(
         "Finished searching. Found {} matches.",
         process_data_state.needle_vals_found.len()
     );
 
-    Ok(())
-}
+    if process_data_state.interrupted {
+        warn!("INTERRUPTED SCAN (Ctrl-C): the search stopped early. Results are incomplete.");
+    }
 
-fn format_duration<T: AsPrimitive<u64>>(seconds: T) -> String {
-    let secs = seconds.as_();
-    let hours = secs / 3600;
-    let minutes = (secs % 3600) / 60;
-    let seconds = secs % 60;
+    if let Some(n) = sample_every_nth_chunk {
+        warn!(
+            "SAMPLED SCAN: only searched every {} chunk(s). Results are incomplete.",
+            n
+        );
+    }
 
-    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
-}
+    if exclude_carved_files {
+        info!(
+            "Excluded {} match(es) inside carved JPEG/ZIP files.",
+            process_data_state
+                .carved_file_excluded_match_count
+                .to_formatted_string(&Locale::en)
+        );
+    }
 
-fn make_progress_stats_message(
-    input_reader: &InputReader,
-    input_source_file_size: u64,
-    process_data_state: &ProcessDataState,
-) -> String {
-    let compression_ratio = input_reader.total_in() as f32 / input_reader.total_out() as f32;
-    let total_uncompressed_image_size = (input_source_file_size as f32) / compression_ratio;
-    let elapsed_time_sec = process_data_state.start_time.elapsed().as_secs_f32();
-    let expected_time_remaining_sec =
-        elapsed_time_sec * (input_source_file_size as f32) / input_reader.total_in() as f32;
-
-    let message = format!("{} elapsed, {}MiB / {}MiB decompressed ({}% complete), {} MiB/{} MiB searched ({}% complete), {} remaining, {} MiB/s out, ratio: {}%, {} chunks",
-        format_duration(elapsed_time_sec.round()),
-
-        // compressed (input-side) stats
-        ((input_reader.total_in() as f32 / 1024.0 / 1024.0).round() as u64).to_formatted_string(&Locale::en),
-        ((input_source_file_size as f32 / 1024.0 / 1024.0).round() as u64).to_formatted_string(&Locale::en),
-        (input_reader.total_in() as f32 / input_source_file_size as f32 * 100.0).round(),
-
-        // uncompressed (output-side) stats
-        ((process_data_state.total_haystack_bytes_read as f32 / 1024.0 / 1024.0).round() as u64).to_formatted_string(&Locale::en),
-        ((total_uncompressed_image_size / 1024.0 / 1024.0).round() as u64).to_formatted_string(&Locale::en),
-        (process_data_state.total_haystack_bytes_read as f32 / total_uncompressed_image_size * 100.0).round(),
-
-        // other
-        format_duration(expected_time_remaining_sec.round()),
-        (process_data_state.total_haystack_bytes_read as f32 / elapsed_time_sec / 1024.0 / 1024.0).round(),
-        (compression_ratio * 100.0).round(),
-        process_data_state.chunk_count
-    );
+    if search_assignment.dedup {
+        info!(
+            "--dedup skipped {} match(es) that were already recorded. Its {} recorded match keys used about {} MiB of memory.",
+            process_data_state
+                .dedup_skipped_match_count
+                .to_formatted_string(&Locale::en),
+            process_data_state
+                .recorded_match_keys
+                .len()
+                .to_formatted_string(&Locale::en),
+            (process_data_state.recorded_match_keys_memory_bytes() as f64 / 1024.0 / 1024.0)
+                .ceil() as u64
+        );
+    }
+
+    for (needle_name, capped_match_count) in &process_data_state.capped_match_counts {
+        info!(
+            "Needle '{}' capped: {} more match(es) past its max_matches were counted, but not recorded.",
+            needle_name,
+            capped_match_count.to_formatted_string(&Locale::en)
+        );
+    }
+
+    if process_data_state.dump_writing_disabled {
+        warn!("Haystack chunk dumps stopped partway through the run due to a write error. Some matches have no .bin file.");
+    }
+
+    let run_stats = RunStats {
+        drive_image_searcher_version: env!("CARGO_PKG_VERSION").to_string(),
+        input_file_path: input_file_path_strs.join(", "),
+        total_haystack_bytes_read: process_data_state.total_haystack_bytes_read,
+        chunk_count: process_data_state.chunk_count,
+        match_count: process_data_state.needle_vals_found.len() as u64,
+        elapsed_sec: process_data_state.start_time.elapsed().as_secs_f32(),
+        dump_writing_disabled: process_data_state.dump_writing_disabled,
+        sample_every_nth_chunk,
+        input_stream_hash_algo: stream_hash_algo,
+        input_stream_hash: input_reader.inner().hex_digest(),
+        capped_match_counts: process_data_state.capped_match_counts.clone(),
+        interrupted: process_data_state.interrupted,
+    };
+    if let (Some(stream_hash_algo), Some(input_stream_hash)) =
+        (stream_hash_algo, &run_stats.input_stream_hash)
+    {
+        info!("Input stream {}: {}", stream_hash_algo, input_stream_hash);
+    }
+    let summary_csv_file_path = output_dir_path.join("03_summary.csv");
+    match write_summary_csv_file(
+        &search_assignment.all_jsonl_file_paths(),
+        &summary_csv_file_path,
+    ) {
+        Ok(()) => info!("Wrote match summary: {}", summary_csv_file_path.display()),
+        Err(e) => error!("Failed to write match summary: {}", e),
+    }
+
+    let run_stats_file_path = output_dir_path.join("04_run_stats.json");
+    match run_stats.write_to_json_file(&run_stats_file_path) {
+        Ok(()) => info!("Wrote run stats: {}", run_stats_file_path.display()),
+        Err(e) => error!("Failed to write run stats: {}", e),
+    }
+
+    if let Some(mut coverage_verifier) = process_data_state.coverage_verifier.take() {
+        coverage_verifier.finish();
+        for problem in &coverage_verifier.problems {
+            error!("Single-pass verification: {}", problem);
+            process_data_state.error_collector.record(
+                "coverage_problem",
+                Some(problem.start_global_offset()),
+                &problem.to_string(),
+            );
+        }
+        match coverage_verifier.problems.len() {
+            0 => info!("Single-pass verification passed. No coverage gaps or overlaps found."),
+            problem_count => error!(
+                "Single-pass verification FAILED with {} problem(s). Reported offsets may be wrong.",
+                problem_count
+            ),
+        }
+    }
+
+    if let Some(sidecar_format) = sidecar_format {
+        let sidecar_file_path = output_dir_path.join(format!(
+            "03_matches_sidecar.{}",
+            sidecar_format.file_extension()
+        ));
+        match write_sidecar_file(
+            &process_data_state.needle_vals_found,
+            sidecar_format,
+            &sidecar_file_path,
+        ) {
+            Ok(()) => info!("Wrote match sidecar file: {}", sidecar_file_path.display()),
+            Err(e) => error!("Failed to write match sidecar file: {}", e),
+        }
+    }
+
+    if write_json_array {
+        let json_array_file_path = output_dir_path.join("00_all_output_record.json");
+        match write_json_array_file(
+            &search_assignment.all_jsonl_file_paths(),
+            &json_array_file_path,
+        ) {
+            Ok(record_count) => info!(
+                "Wrote {} match record(s) to JSON array file: {}",
+                record_count,
+                json_array_file_path.display()
+            ),
+            Err(e) => error!("Failed to write JSON array file: {}", e),
+        }
+    }
+
+    if offsets_only {
+        match write_offsets_files(&process_data_state.needle_vals_found, &output_dir_path) {
+            Ok(offsets_file_paths) => info!(
+                "Wrote {} offsets file(s) to: {}",
+                offsets_file_paths.len(),
+                output_dir_path.display()
+            ),
+            Err(e) => error!("Failed to write offsets files: {}", e),
+        }
+    }
+
+    let retrying_reader = input_reader.inner().inner();
+    if let Some(stream_end_error) = &retrying_reader.inner().stream_end_error {
+        warn!(
+            "The search ended early, at offset 0x{}, because of a decompression error: {}",
+            display_hex_offset(process_data_state.total_haystack_bytes_read, 1),
+            stream_end_error.message
+        );
+        process_data_state.error_collector.record(
+            stream_end_error.kind.as_str(),
+            Some(process_data_state.total_haystack_bytes_read),
+            &stream_end_error.message,
+        );
+    }
+    if retrying_reader.retried_read_count > 0 {
+        process_data_state.error_collector.record_count(
+            "read_retried",
+            retrying_reader.retried_read_count,
+            None,
+            retrying_reader
+                .first_retried_read_error
+                .as_deref()
+                .unwrap_or_default(),
+        );
+    }
+    let error_summary_file_path = output_dir_path.join("06_errors.json");
+    match process_data_state
+        .error_collector
+        .write_to_json_file(&error_summary_file_path)
+    {
+        Ok(()) => match process_data_state.error_collector.total_count() {
+            0 => info!(
+                "No problems during the run. Wrote error summary: {}",
+                error_summary_file_path.display()
+            ),
+            problem_count => warn!(
+                "{} problem(s) during the run. See the error summary: {}",
+                problem_count,
+                error_summary_file_path.display()
+            ),
+        },
+        Err(e) => error!("Failed to write error summary: {}", e),
+    }
 
-    message
+    Ok(())
 }
 
 enum InputReader {
     File(File),
-    Xz(XzDecoder<File>),
-    Lz4(lz4_flex::frame::FrameDecoder<File>),
+    /// The parts of a split image, read in order as one image (uncompressed)
+    Files(MultiFileReader),
+    Xz(XzDecoder<MultiFileReader>),
+    /// The image inside a tar archive (`--recursive`)
+    Nested(NestedReader),
+    /// Counts the compressed bytes consumed by the decoder, and the decompressed bytes read.
+    Lz4 {
+        lz4_decoder: MultiFrameLz4Decoder<BufReader<CountingReader<MultiFileReader>>>,
+        compressed_bytes_read: Arc<AtomicU64>,
+        bytes_read: u64,
+    },
+    /// Counts the compressed bytes consumed by the decoder (it doesn't tell its input position),
+    /// and the decompressed bytes read.
+    Gzip {
+        gz_decoder: MultiGzDecoder<CountingReader<MultiFileReader>>,
+        compressed_bytes_read: Arc<AtomicU64>,
+        bytes_read: u64,
+    },
+    /// Uncompressed image piped in (`-i -`). Counts the bytes read, as stdin can't tell its position.
+    Stdin {
+        stdin: StdinLock<'static>,
+        bytes_read: u64,
+    },
 }
 
 impl Read for InputReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
             InputReader::File(file) => file.read(buf),
+            InputReader::Files(input_files) => input_files.read(buf),
             InputReader::Xz(xz_decoder) => xz_decoder.read(buf),
-            InputReader::Lz4(lz4_decoder) => lz4_decoder.read(buf),
+            InputReader::Lz4 {
+                lz4_decoder,
+                bytes_read,
+                ..
+            } => {
+                let len = lz4_decoder.read(buf)?;
+                *bytes_read += len as u64;
+                Ok(len)
+            }
+            InputReader::Gzip {
+                gz_decoder,
+                bytes_read,
+                ..
+            } => {
+                let len = gz_decoder.read(buf)?;
+                *bytes_read += len as u64;
+                Ok(len)
+            }
+            InputReader::Nested(nested_reader) => nested_reader.read(buf),
+            InputReader::Stdin { stdin, bytes_read } => {
+                let len = stdin.read(buf)?;
+                *bytes_read += len as u64;
+                Ok(len)
+            }
         }
     }
 }
 
+impl InputReader {
+    /// True if all of the (compressed) input file has been read, so a decompression error now
+    /// means the image is truncated, rather than corrupt partway through.
+    fn is_input_exhausted(&self) -> bool {
+        let file = match self {
+            InputReader::File(file) => file,
+            InputReader::Files(input_files) => return input_files.is_exhausted(),
+            InputReader::Xz(xz_decoder) => return xz_decoder.get_ref().is_exhausted(),
+            InputReader::Lz4 { lz4_decoder, .. } => {
+                return lz4_decoder.get_ref().get_ref().inner().is_exhausted()
+            }
+            InputReader::Gzip { gz_decoder, .. } => {
+                return gz_decoder.get_ref().inner().is_exhausted()
+            }
+            InputReader::Nested(nested_reader) => return nested_reader.is_input_exhausted(),
+            // the end of a pipe can't be known ahead of time
+            InputReader::Stdin { .. } => return false,
+        };
+        // the clone is used because getting the position otherwise needs a mutable reference
+        match (file.try_clone(), file.metadata()) {
+            (Ok(mut cloned_file), Ok(metadata)) => cloned_file
+                .stream_position()
+                .is_ok_and(|position| position >= metadata.len()),
+            _ => false,
+        }
+    }
+}
+
+/// Parses a stream offset given on the command line: decimal, or hex with a 0x prefix.
+fn parse_stream_offset(val: &str) -> Result<u64, std::num::ParseIntError> {
+    match val.strip_prefix("0x") {
+        Some(hex_val) => u64::from_str_radix(hex_val, 16),
+        None => val.parse(),
+    }
+}
+
+/// Positions the input at the uncompressed `offset` (`--resume-from-offset`).
+fn skip_input_to_offset(input_reader: &mut InputReader, offset: u64) -> io::Result<()> {
+    match input_reader {
+        InputReader::File(file) => {
+            if offset > file.metadata()?.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "offset is past the end of the input file",
+                ));
+            }
+            file.seek(SeekFrom::Start(offset))?;
+        }
+        InputReader::Files(input_files) => {
+            if offset > input_files.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "offset is past the end of the input files",
+                ));
+            }
+            input_files.seek(SeekFrom::Start(offset))?;
+        }
+        // compressed streams and stdin can't seek, so read and discard up to the offset
+        _ => {
+            let skipped_len = io::copy(&mut input_reader.by_ref().take(offset), &mut io::sink())?;
+            if skipped_len < offset {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "offset is past the end of the uncompressed stream",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 trait TotalInOut {
     fn total_in(&self) -> u64;
     fn total_out(&self) -> u64;
@@ -392,11 +2033,18 @@ impl TotalInOut for InputReader {
                     Err(_e) => 1, // arbitrary non-zero value
                 }
             }
+            InputReader::Files(input_files) => input_files.position(),
             InputReader::Xz(xz_reader) => xz_reader.total_in(),
-            InputReader::Lz4(_lz4_reader) => {
-                // FIXME: use lz4_reader.total_in(), if it's ever added
-                1 // hack to return a non-zero value, because lz4 doesn't support total_in()
-            }
+            InputReader::Lz4 {
+                compressed_bytes_read,
+                ..
+            } => compressed_bytes_read.load(Ordering::Relaxed),
+            InputReader::Gzip {
+                compressed_bytes_read,
+                ..
+            } => compressed_bytes_read.load(Ordering::Relaxed),
+            InputReader::Nested(nested_reader) => nested_reader.total_in(),
+            InputReader::Stdin { bytes_read, .. } => *bytes_read,
         }
     }
 
@@ -412,10 +2060,12 @@ impl TotalInOut for InputReader {
                     Err(_e) => 1, // arbitrary non-zero value
                 }
             }
+            InputReader::Files(input_files) => input_files.position(),
             InputReader::Xz(xz_reader) => xz_reader.total_out(),
-            InputReader::Lz4(_lz4_reader) => {
-                1 // hack to return a non-zero value, because lz4 doesn't support total_out()
-            }
+            InputReader::Lz4 { bytes_read, .. } => *bytes_read,
+            InputReader::Gzip { bytes_read, .. } => *bytes_read,
+            InputReader::Nested(nested_reader) => nested_reader.total_out(),
+            InputReader::Stdin { bytes_read, .. } => *bytes_read,
         }
     }
 }
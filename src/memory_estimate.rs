@@ -0,0 +1,175 @@
+use crate::found_needle::NeedleValFound;
+use crate::needle::Needle;
+
+use std::mem::size_of;
+
+/// Rough guess of how many matches each needle will have, for sizing the found match records.
+const ESTIMATED_MATCH_COUNT_PER_NEEDLE: u64 = 100;
+
+/// Rough size of the strings in each found match record (timestamp, file paths, notes), beyond
+/// the needle's own value, name, and description.
+const ESTIMATED_FOUND_MATCH_STRING_BYTES: u64 = 512;
+
+/// Up-front estimate of the memory a search needs (`--max-memory`), so huge needle configs fail
+/// at startup instead of with a surprise OOM partway through a long scan.
+#[derive(Debug, PartialEq)]
+pub struct MemoryEstimate {
+    pub haystack_chunk_bytes: u64,
+    /// Haystack chunk dumps waiting in the write queue, if it's full of the largest dumps.
+    pub dump_queue_bytes: u64,
+    /// The needles themselves, and their per-needle bookkeeping.
+    pub needle_bytes: u64,
+    /// Found match records, assuming `ESTIMATED_MATCH_COUNT_PER_NEEDLE` matches per needle.
+    pub found_match_bytes: u64,
+}
+
+impl MemoryEstimate {
+    pub fn new(
+        needles: &[Needle],
+        haystack_chunk_buffer_size_bytes: usize,
+        dump_write_queue_len: usize,
+    ) -> Self {
+        let largest_dump_bytes = needles
+            .iter()
+            .filter(|needle| needle.write_to_file)
+            .map(|needle| {
+                let context_len = needle.byte_count_before_match
+                    + needle.val.len() as u64
+                    + needle.byte_count_after_match;
+                match needle.capture_block_size {
+                    // whole blocks, and one more if the context isn't aligned
+                    Some(block_size) if block_size > 0 => {
+                        (context_len.div_ceil(block_size) + 1) * block_size
+                    }
+                    _ => context_len,
+                }
+            })
+            .max()
+            .unwrap_or(0)
+            .min(haystack_chunk_buffer_size_bytes as u64);
+
+        let needle_bytes = needles
+            .iter()
+            .map(|needle| {
+                size_of::<Needle>() as u64
+                    + needle.val.len() as u64
+                    // name, in the needle and in the match log throttle's counts
+                    + 2 * needle.name.len() as u64
+                    + needle.description_notes.len() as u64
+            })
+            .sum();
+
+        let found_match_bytes = needles
+            .iter()
+            .map(|needle| {
+                ESTIMATED_MATCH_COUNT_PER_NEEDLE
+                    * (size_of::<NeedleValFound>() as u64
+                        // `val`, and `val_as_str` (which is at least as long)
+                        + 2 * needle.val.len() as u64
+                        + needle.name.len() as u64
+                        + needle.description_notes.len() as u64
+                        + ESTIMATED_FOUND_MATCH_STRING_BYTES)
+            })
+            .sum();
+
+        Self {
+            haystack_chunk_bytes: haystack_chunk_buffer_size_bytes as u64,
+            dump_queue_bytes: dump_write_queue_len as u64 * largest_dump_bytes,
+            needle_bytes,
+            found_match_bytes,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.haystack_chunk_bytes
+            + self.dump_queue_bytes
+            + self.needle_bytes
+            + self.found_match_bytes
+    }
+
+    /// Errors if the estimate is over `max_memory_bytes`, explaining where the memory goes.
+    pub fn check_max_memory(&self, max_memory_bytes: u64) -> Result<(), String> {
+        match self.total_bytes() > max_memory_bytes {
+            true => Err(format!(
+                "Estimated memory use of {} bytes is over the --max-memory limit of {} bytes (haystack chunk: {}, dump write queue: {}, needles: {}, found matches: {}). Use fewer needles, or a smaller --dump-write-queue-len.",
+                self.total_bytes(),
+                max_memory_bytes,
+                self.haystack_chunk_bytes,
+                self.dump_queue_bytes,
+                self.needle_bytes,
+                self.found_match_bytes
+            )),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Parses a byte size like "512M", "4GiB", "2g", or "1048576" (suffixes are powers of 1024).
+pub fn parse_byte_size(size_str: &str) -> Result<u64, String> {
+    let size_str = size_str.trim();
+    let digit_count = size_str
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size_str.len());
+    let (number, suffix) = size_str.split_at(digit_count);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid byte size '{}'", size_str))?;
+    let multiplier: u64 = match suffix
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("Invalid byte size suffix in '{}'", size_str)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Byte size '{}' is too large", size_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_max_memory_with_many_needles() {
+        let needles: Vec<Needle> = (0..10_000)
+            .map(|needle_idx| Needle {
+                name: format!("needle_{}", needle_idx),
+                val: vec![0xAB; 16],
+                ..Default::default()
+            })
+            .collect();
+
+        let memory_estimate = MemoryEstimate::new(&needles, 4096, 4);
+        assert!(memory_estimate.found_match_bytes > memory_estimate.needle_bytes);
+        assert!(memory_estimate
+            .check_max_memory(1024 * 1024)
+            .unwrap_err()
+            .contains("--max-memory"));
+        assert_eq!(
+            memory_estimate.check_max_memory(memory_estimate.total_bytes()),
+            Ok(())
+        );
+
+        // a few needles fit easily
+        assert_eq!(
+            MemoryEstimate::new(&needles[..3], 4096, 4).check_max_memory(1024 * 1024),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1048576"), Ok(1024 * 1024));
+        assert_eq!(parse_byte_size("512M"), Ok(512 * 1024 * 1024));
+        assert_eq!(parse_byte_size("4GiB"), Ok(4 * 1024 * 1024 * 1024));
+        assert_eq!(parse_byte_size("2kb"), Ok(2048));
+        assert!(parse_byte_size("4X").is_err());
+        assert!(parse_byte_size("G").is_err());
+    }
+}
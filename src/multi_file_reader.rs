@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Reads the parts of a split disk image (like `disk.001`, `disk.002`, ...) one after the other,
+/// as one stream, like `cat disk.0*`. Offsets (and seeks) are over the combined parts, so a match
+/// in a later part has the same offset as in the whole image.
+pub struct MultiFileReader {
+    files: Vec<File>,
+    /// Combined offset of the start of each file, then the end of the last one.
+    file_start_offsets: Vec<u64>,
+    /// Index of the file being read.
+    file_idx: usize,
+    /// Combined offset of the next byte to read.
+    position: u64,
+}
+
+impl MultiFileReader {
+    /// Reads `files` in order, from their start.
+    pub fn new(mut files: Vec<File>) -> io::Result<Self> {
+        let mut file_start_offsets = vec![0];
+        for file in files.iter_mut() {
+            file.seek(SeekFrom::Start(0))?;
+            let file_len = file.metadata()?.len();
+            file_start_offsets.push(file_start_offsets.last().unwrap() + file_len);
+        }
+        Ok(Self {
+            files,
+            file_start_offsets,
+            file_idx: 0,
+            position: 0,
+        })
+    }
+
+    /// The combined length of the files.
+    pub fn len(&self) -> u64 {
+        *self.file_start_offsets.last().unwrap()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Combined offset of the next byte to read.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// True if all of the files have been read.
+    pub fn is_exhausted(&self) -> bool {
+        self.position >= self.len()
+    }
+}
+
+impl Read for MultiFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.file_idx < self.files.len() {
+            let len = self.files[self.file_idx].read(buf)?;
+            if len > 0 || buf.is_empty() {
+                self.position += len as u64;
+                return Ok(len);
+            }
+            // the end of this file: continue with the start of the next one
+            self.file_idx += 1;
+            if let Some(next_file) = self.files.get_mut(self.file_idx) {
+                next_file.seek(SeekFrom::Start(0))?;
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl Seek for MultiFileReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.len().checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing position",
+            )
+        })?;
+
+        // the last file that starts at or before the position (a position past the end is in the
+        // last file, like seeking past the end of one file)
+        let file_idx = self.file_start_offsets[1..self.files.len().max(1)]
+            .partition_point(|&file_start_offset| file_start_offset <= position);
+        if let Some(file) = self.files.get_mut(file_idx) {
+            file.seek(SeekFrom::Start(
+                position - self.file_start_offsets[file_idx],
+            ))?;
+        }
+        self.file_idx = file_idx;
+        self.position = position;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::needle::Needle;
+    use crate::search::{search, SearchOptions};
+
+    use std::io::Write;
+
+    fn make_part_files(tmp_dir: &tempfile::TempDir, parts: &[&[u8]]) -> Vec<File> {
+        parts
+            .iter()
+            .enumerate()
+            .map(|(part_idx, part)| {
+                let part_file_path = tmp_dir.path().join(format!("disk.{:03}", part_idx + 1));
+                File::create(&part_file_path)
+                    .unwrap()
+                    .write_all(part)
+                    .unwrap();
+                File::open(&part_file_path).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_multi_file_reader_reads_and_seeks_across_files() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut reader =
+            MultiFileReader::new(make_part_files(&tmp_dir, &[b"abc", b"", b"defg", b"h"])).unwrap();
+        assert_eq!(reader.len(), 8);
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"abcdefgh");
+        assert!(reader.is_exhausted());
+
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"cdefgh");
+
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(reader.position(), 6);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"gh");
+        assert!(reader.seek(SeekFrom::Current(-9)).is_err());
+    }
+
+    #[test]
+    fn test_search_needle_split_across_files() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // "Hello" starts 2 bytes before the end of the first part
+        let mut first_part = vec![0u8; 5000];
+        first_part[4998..].copy_from_slice(b"He");
+        let mut second_part = vec![0u8; 3000];
+        second_part[..3].copy_from_slice(b"llo");
+        second_part[1000..1005].copy_from_slice(b"Hello");
+        let reader =
+            MultiFileReader::new(make_part_files(&tmp_dir, &[&first_part, &second_part])).unwrap();
+
+        let needles = vec![Needle {
+            name: "hello".to_string(),
+            val: b"Hello".to_vec(),
+            ..Default::default()
+        }];
+        let found_offsets: Vec<u64> = search(
+            reader,
+            &needles,
+            SearchOptions {
+                chunk_size_bytes: 4096,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .map(|found| found.unwrap().match_start_global_offset)
+        .collect();
+        assert_eq!(found_offsets, vec![4998, 6000]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{self, BufRead, Read};
+
+use lz4_flex::frame::FrameDecoder;
+
+/// Reads every frame of an lz4 file made of several concatenated frames (like `cat a.lz4 b.lz4`),
+/// so the whole image is searched, not just the first part.
+///
+/// `FrameDecoder` moves on to the next frame, but returns 0 at the end of each frame, which looks
+/// like the end of the stream. So the stream only ends when there's no compressed input left.
+/// Reads also fill `buf` across frame boundaries, so the short last block of a frame doesn't look
+/// like the last (partial) chunk of the image.
+pub struct MultiFrameLz4Decoder<R: BufRead> {
+    decoder: FrameDecoder<R>,
+    /// An error after part of `buf` was filled, returned by the next read (so the data before it,
+    /// like that of a truncated image, is still searched).
+    pending_error: Option<io::Error>,
+}
+
+impl<R: BufRead> MultiFrameLz4Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            decoder: FrameDecoder::new(reader),
+            pending_error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.decoder.get_ref()
+    }
+}
+
+impl<R: BufRead> Read for MultiFrameLz4Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+        let mut total_bytes_read = 0;
+        while total_bytes_read < buf.len() {
+            let bytes_read = match self.decoder.read(&mut buf[total_bytes_read..]) {
+                Ok(bytes_read) => bytes_read,
+                Err(e) if total_bytes_read > 0 => {
+                    self.pending_error = Some(e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            total_bytes_read += bytes_read;
+            // 0 is the end of a frame: the end of the stream if no frame follows
+            if bytes_read == 0 && self.decoder.get_mut().fill_buf()?.is_empty() {
+                break;
+            }
+        }
+        Ok(total_bytes_read)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use lz4_flex::frame::FrameEncoder;
+    use std::io::{Cursor, Write};
+
+    pub fn lz4_compress(data: &[u8]) -> Vec<u8> {
+        let mut lz4_encoder = FrameEncoder::new(Vec::new());
+        lz4_encoder.write_all(data).unwrap();
+        lz4_encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_reads_all_concatenated_frames() {
+        let mut compressed = lz4_compress(b"first frame. ");
+        compressed.extend(lz4_compress(b""));
+        compressed.extend(lz4_compress(b"third frame."));
+
+        let mut data = String::new();
+        MultiFrameLz4Decoder::new(Cursor::new(compressed))
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "first frame. third frame.");
+    }
+
+    #[test]
+    fn test_truncated_frame_returns_data_before_error() {
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let mut compressed = lz4_compress(&data[..100_000]);
+        let mut second_frame = lz4_compress(&data[100_000..]);
+        second_frame.truncate(second_frame.len() / 2);
+        compressed.extend(second_frame);
+
+        let mut decoder = MultiFrameLz4Decoder::new(Cursor::new(compressed));
+        let mut buf = vec![0u8; data.len()];
+        let bytes_read = decoder.read(&mut buf).unwrap();
+        assert!(bytes_read >= 100_000);
+        assert_eq!(buf[..bytes_read], data[..bytes_read]);
+        assert!(decoder.read(&mut buf).is_err());
+    }
+}
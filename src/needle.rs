@@ -1,74 +1,341 @@
-use crate::parse_hex_string::parse_hex_string;
+use crate::display_hex::display_hex_offset;
+use crate::parse_hex_string::{parse_hex_pattern, parse_hex_string};
+use crate::text_regions::is_text_byte;
 
+use regex::bytes::{Regex as BytesRegex, RegexBuilder as BytesRegexBuilder};
+use regex::Regex;
 use serde::{self, Deserialize, Deserializer, Serialize};
 
-use std::fs::File;
-use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use std::str::FromStr;
 
 const DEFAULT_BYTE_COUNT_BEFORE_MATCH: u64 = 1024;
 const DEFAULT_BYTE_COUNT_AFTER_MATCH: u64 = 1024;
 
-#[derive(Clone)]
+/// Limit on the compiled size of a regex needle, so a pathological pattern (like a huge repeat
+/// count) fails at load, instead of eating memory or stalling the search.
+const REGEX_NEEDLE_SIZE_LIMIT_BYTES: usize = 10 * 1024 * 1024;
+
+/// How needle values are shown in logs and in the JSONL `val_as_str` field.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ValDisplayFormat {
+    /// Rust debug byte array, like `[72, 101, 108]`
+    #[default]
+    Debug,
+    /// Spaced lowercase hex, like `48 65 6c` (the same as the `hex` config format)
+    Hex,
+}
+
+impl FromStr for ValDisplayFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "debug" => Ok(ValDisplayFormat::Debug),
+            "hex" => Ok(ValDisplayFormat::Hex),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Formats a needle value for display. Printable values also show the text itself, like `48 65 6c ('Hel')`.
+/// Wildcard bytes (0x00 in `mask`) are shown as `??` (and `?` in the text).
+pub fn format_needle_val(
+    val: &[u8],
+    mask: Option<&[u8]>,
+    val_display_format: ValDisplayFormat,
+) -> String {
+    let is_wildcard = |idx: usize| mask.is_some_and(|mask| mask[idx] == 0x00);
+    let byte_strs: Vec<String> = val
+        .iter()
+        .enumerate()
+        .map(|(idx, b)| match (is_wildcard(idx), val_display_format) {
+            (true, _) => "??".to_string(),
+            (false, ValDisplayFormat::Debug) => b.to_string(),
+            (false, ValDisplayFormat::Hex) => format!("{:02x}", b),
+        })
+        .collect();
+    let bytes_str = match val_display_format {
+        ValDisplayFormat::Debug => format!("[{}]", byte_strs.join(", ")),
+        ValDisplayFormat::Hex => byte_strs.join(" "),
+    };
+    let is_printable = val
+        .iter()
+        .enumerate()
+        .all(|(idx, b)| is_wildcard(idx) || b.is_ascii_graphic());
+    match is_printable {
+        true => format!("{} ('{}')", bytes_str, format_needle_text(val, mask)),
+        false => bytes_str,
+    }
+}
+
+/// The needle value as (lossy) text, with `?` for wildcard bytes.
+fn format_needle_text(val: &[u8], mask: Option<&[u8]>) -> String {
+    match mask {
+        Some(mask) => val
+            .iter()
+            .zip(mask)
+            .map(|(&b, &mask_byte)| match mask_byte {
+                0x00 => b'?',
+                _ => b,
+            })
+            .map(char::from)
+            .collect(),
+        None => String::from_utf8_lossy(val).to_string(),
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct Needle {
     pub name: String,
     pub val: Vec<u8>,
+    /// Bit mask, the same length as `val`: only the set bits of each byte are compared. Wildcard
+    /// (`??`) bytes are 0x00. None compares every bit.
+    pub mask: Option<Vec<u8>>,
+    /// Compare letters ignoring ASCII case (ASCII values only), like "admin" matching "ADMIN".
+    pub case_insensitive: bool,
+    /// For `val_format: regex` needles, the compiled pattern (`val` is the pattern text). Each
+    /// match can have a different length. A match that crosses a chunk boundary is only found
+    /// whole if it fits in the carry-forward (see `defer_regex_matches_at_chunk_end`).
+    pub regex: Option<BytesRegex>,
     pub description_notes: String,
     /// significance level from 0-9, where 9 is "very happy"
     pub happiness_level: u8,
     pub write_to_file: bool,
     pub byte_count_before_match: u64,
     pub byte_count_after_match: u64,
-    // TODO: add more options to search both endians, etc.
+    /// Custom log line for matches of this needle, with `{offset}`, `{name}`, `{ascii}`, and `{sector}` placeholders.
+    pub log_format: Option<String>,
+    /// Keep matches that fall inside carved JPEG/ZIP files, when `--exclude-carved-files` is used.
+    pub allow_inside_carved_files: bool,
+    /// Higher-priority needles are searched and reported first (default 0).
+    pub priority: i32,
+    /// Only match when at least this many identical bytes (e.g., zero padding) directly precede
+    /// the match, like a record at the start of allocated space.
+    pub min_preceding_run: Option<u64>,
+    /// Dump the whole aligned block(s) of this size that contain the match and its before/after
+    /// context, instead of just the context (e.g., 4096 for the filesystem block of a match).
+    pub capture_block_size: Option<u64>,
+    /// Stop recording matches of this needle (dumps and JSONL records) after this many. Later
+    /// matches are only counted, as capped.
+    pub max_matches: Option<u64>,
+    /// Only match at global offsets that are a multiple of this, like 512 for a sector-aligned
+    /// structure.
+    pub alignment: Option<u64>,
+    /// Skip a match that's inside a match of one of these (longer) needles, like a short needle
+    /// that's a substring of a more valuable one.
+    pub suppress_if_covered_by: Vec<String>,
+    /// Also write the printable strings in each dump to a `.strings.txt` file next to it.
+    pub strings: bool,
+    /// How `val` is shown in logs and JSONL (set for all needles from `--val-display-format`).
+    pub val_display_format: ValDisplayFormat,
+    /// Show the happiness level with emoji in logs (cleared for all needles by `--no-emoji`).
+    pub emoji: bool,
     // TODO: add option for 'shortest substring to match' to search for chunks within each needle
-    // TODO: add "ignore if other one is found" option to ignore substrings of other searches
 }
 
 impl Needle {
     pub fn from_needle_val_config(config_needle_val: &ConfigNeedle) -> Self {
-        let val = match config_needle_val.val_format {
-            ConfigNeedleValFormat::Hex => {
-                // The string is like "48656c6c6f", or "72 65 6c 6c 6f", or "0x72 0x65 0x6c 0x6c 0x6f".
-                // We must parse it from these values.
-
-                match parse_hex_string(config_needle_val.val.as_str()) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        panic!("Failed to parse hex string: {}", config_needle_val.val);
-                    }
-                }
-            }
-            ConfigNeedleValFormat::Ascii => {
-                // convert the string to bytes as you'd do normally
-                config_needle_val.val.as_bytes().to_vec()
-            }
+        let (val, mask, regex) = match config_needle_val.decode_val().and_then(|val| {
+            Ok((
+                val,
+                config_needle_val.decode_mask()?,
+                config_needle_val.compile_regex()?,
+            ))
+        }) {
+            Ok(val_mask_and_regex) => val_mask_and_regex,
+            Err(e) => panic!("{}", e),
         };
         Self {
             name: config_needle_val.name.clone(),
             val,
+            mask,
+            case_insensitive: config_needle_val.case_insensitive,
+            regex,
             description_notes: config_needle_val.description_notes.clone(),
             happiness_level: config_needle_val.happiness_level,
             write_to_file: config_needle_val.write_to_file,
-            byte_count_before_match: DEFAULT_BYTE_COUNT_BEFORE_MATCH,
-            byte_count_after_match: DEFAULT_BYTE_COUNT_AFTER_MATCH,
+            byte_count_before_match: config_needle_val.byte_count_before_match,
+            byte_count_after_match: config_needle_val.byte_count_after_match,
+            log_format: config_needle_val.log_format.clone(),
+            allow_inside_carved_files: config_needle_val.allow_inside_carved_files,
+            priority: config_needle_val.priority,
+            min_preceding_run: config_needle_val.min_preceding_run,
+            capture_block_size: config_needle_val.capture_block_size,
+            max_matches: config_needle_val.max_matches,
+            alignment: config_needle_val.alignment,
+            suppress_if_covered_by: config_needle_val.suppress_if_covered_by.clone(),
+            strings: config_needle_val.strings,
+            val_display_format: ValDisplayFormat::default(),
+            emoji: true,
         }
     }
 
-    pub fn is_val_printable(&self) -> bool {
-        self.val.iter().all(|b| b.is_ascii_graphic())
+    /// The needles of a config entry: just the one, or with `search_both_endians`, the value as
+    /// written (`{name}_be`) and byte-reversed (`{name}_le`). A value that reads the same reversed
+    /// is only searched once, under its own name.
+    pub fn all_from_needle_val_config(config_needle_val: &ConfigNeedle) -> Vec<Self> {
+        let needle = Self::from_needle_val_config(config_needle_val);
+        let reversed_val: Vec<u8> = needle.val.iter().rev().copied().collect();
+        let reversed_mask: Option<Vec<u8>> = needle
+            .mask
+            .as_ref()
+            .map(|mask| mask.iter().rev().copied().collect());
+        if !config_needle_val.search_both_endians
+            || (reversed_val == needle.val && reversed_mask == needle.mask)
+        {
+            return vec![needle];
+        }
+        vec![
+            Self {
+                name: format!("{}_le", needle.name),
+                val: reversed_val,
+                mask: reversed_mask,
+                ..needle.clone()
+            },
+            Self {
+                name: format!("{}_be", needle.name),
+                ..needle
+            },
+        ]
     }
 
     pub fn val_as_string(&self) -> String {
-        match self.is_val_printable() {
-            true => {
-                format!("{:?} ('{}')", self.val, String::from_utf8_lossy(&self.val))
+        format_needle_val(&self.val, self.mask.as_deref(), self.val_display_format)
+    }
+
+    /// True if `window` (the same length as `val`) matches the value, under `mask` if set.
+    pub fn matches_at(&self, window: &[u8]) -> bool {
+        match (&self.mask, self.case_insensitive) {
+            (Some(mask), false) => window
+                .iter()
+                .zip(&self.val)
+                .zip(mask)
+                .all(|((&b, &val), &mask)| (b & mask) == (val & mask)),
+            (Some(mask), true) => {
+                window
+                    .iter()
+                    .zip(&self.val)
+                    .zip(mask)
+                    .all(|((&b, &val), &mask)| {
+                        (b.to_ascii_lowercase() & mask) == (val.to_ascii_lowercase() & mask)
+                    })
             }
-            false => format!("{:?}", self.val),
+            (None, false) => window == self.val,
+            (None, true) => window.eq_ignore_ascii_case(&self.val),
         }
     }
 
+    /// The bytes of the value that aren't wildcards.
+    fn fixed_val_bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.val
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.mask.as_ref().is_none_or(|mask| mask[*idx] != 0x00))
+            .map(|(_, &b)| b)
+    }
+
+    /// The length of this needle's matches, or None for a regex needle, whose matches vary (its
+    /// `val` is the pattern). A regex match is only found whole across a chunk boundary if it
+    /// fits in the carry-forward.
+    pub fn match_len(&self) -> Option<usize> {
+        match self.regex {
+            Some(_) => None,
+            None => Some(self.val.len()),
+        }
+    }
+
+    /// True if the value is all text bytes (printable ASCII or whitespace), like a keyword.
+    /// Wildcard bytes don't count either way. Never for a regex needle, whose value is the
+    /// pattern, not the bytes it matches.
+    pub fn is_text(&self) -> bool {
+        self.regex.is_none()
+            && self.fixed_val_bytes().next().is_some()
+            && self.fixed_val_bytes().all(is_text_byte)
+    }
+
+    /// The bytes that a run of only that byte matches (like 0x00 for an all-zero needle, or
+    /// several bytes with a `mask`). Such needles can be found in uniform chunks of those bytes,
+    /// which are normally skipped.
+    pub fn uniform_byte_vals(&self) -> Vec<u8> {
+        (0..=255u8)
+            .filter(|&uniform_byte_val| match &self.regex {
+                // a regex match can be any length, so check a run much longer than most matches
+                Some(regex) => regex.is_match(&vec![uniform_byte_val; 64 * 1024]),
+                None => self.matches_at(&vec![uniform_byte_val; self.val.len()]),
+            })
+            .collect()
+    }
+
+    /// True if the match at `pos` in the haystack meets `min_preceding_run`: the bytes directly
+    /// before it are a run of at least that many identical bytes. The carry-forward is sized so
+    /// the run is in the haystack (see `haystack_carry_forward_len_bytes_for`), so only a match
+    /// near the start of the stream doesn't have enough bytes before it.
+    pub fn has_min_preceding_run(&self, haystack: &[u8], pos: usize) -> bool {
+        let min_preceding_run = match self.min_preceding_run {
+            Some(min_preceding_run) => min_preceding_run as usize,
+            None => return true,
+        };
+        if min_preceding_run == 0 {
+            return true;
+        }
+        if pos < min_preceding_run {
+            return false;
+        }
+        let preceding_bytes = &haystack[(pos - min_preceding_run)..pos];
+        preceding_bytes.iter().all(|&b| b == preceding_bytes[0])
+    }
+
+    /// True if a match at `match_start_global_offset` meets `alignment` (an alignment of 0 is
+    /// the same as none).
+    pub fn is_aligned(&self, match_start_global_offset: u64) -> bool {
+        match self.alignment {
+            Some(alignment) if alignment > 0 => match_start_global_offset.is_multiple_of(alignment),
+            _ => true,
+        }
+    }
+
+    /// The global range to dump for a match of `match_len` bytes at `match_start_global_offset`:
+    /// the before/after context, widened to whole `capture_block_size` blocks if set.
+    pub fn dump_global_range(
+        &self,
+        match_start_global_offset: u64,
+        match_len: usize,
+    ) -> Range<u64> {
+        let start = match_start_global_offset.saturating_sub(self.byte_count_before_match);
+        let end = match_start_global_offset + match_len as u64 + self.byte_count_after_match;
+        match self.capture_block_size {
+            Some(block_size) if block_size > 0 => {
+                (start / block_size * block_size)..end.div_ceil(block_size) * block_size
+            }
+            _ => start..end,
+        }
+    }
+
+    /// Renders this needle's `log_format` template for a match at `match_start_global_offset`.
+    /// Placeholders: `{offset}` (hex), `{name}`, `{ascii}` (the needle value, lossy), and `{sector}` (512-byte LBA).
+    pub fn render_log_format(&self, log_format: &str, match_start_global_offset: u64) -> String {
+        log_format
+            .replace(
+                "{offset}",
+                &format!("0x{}", display_hex_offset(match_start_global_offset, 20)),
+            )
+            .replace("{name}", &self.name)
+            .replace(
+                "{ascii}",
+                &format_needle_text(&self.val, self.mask.as_deref()),
+            )
+            .replace("{sector}", &(match_start_global_offset / 512).to_string())
+    }
+
+    /// Like "😊😊 (4)", or "(level 4)" without `emoji`, for terminals and CI logs that don't show
+    /// emoji.
     pub fn happiness_level_as_string(&self) -> String {
+        if !self.emoji {
+            return format!("(level {})", self.happiness_level);
+        }
         let emojis = "😶😐🙂🙃😊😁😄😃😆😂";
         let emoji = emojis.chars().nth(self.happiness_level as usize).unwrap();
         format!("{}{} ({})", emoji, emoji, self.happiness_level)
@@ -85,16 +352,286 @@ pub struct ConfigNeedle {
 
     #[serde(default = "default_write_to_file")]
     pub write_to_file: bool,
+
+    /// Bytes of context to dump before each match (default 1024).
+    #[serde(default = "default_byte_count_before_match")]
+    pub byte_count_before_match: u64,
+
+    /// Bytes of context to dump after each match (default 1024).
+    #[serde(default = "default_byte_count_after_match")]
+    pub byte_count_after_match: u64,
+
+    #[serde(default)]
+    pub log_format: Option<String>,
+
+    #[serde(default)]
+    pub allow_inside_carved_files: bool,
+
+    #[serde(default)]
+    pub priority: i32,
+
+    #[serde(default)]
+    pub min_preceding_run: Option<u64>,
+
+    #[serde(default)]
+    pub capture_block_size: Option<u64>,
+
+    /// Hex bit mask, one byte per byte of `val`: only the set bits are compared (e.g., "ff f0"
+    /// ignores the low nibble of the second byte).
+    #[serde(default)]
+    pub mask: Option<String>,
+
+    /// Also search for the value byte-reversed, for a number of unknown byte order (hex values
+    /// only). The needles are named `{name}_be` (as written) and `{name}_le` (reversed).
+    #[serde(default)]
+    pub search_both_endians: bool,
+
+    /// Match letters of the value ignoring ASCII case, like "password" matching "Password"
+    /// (ASCII values only).
+    #[serde(default)]
+    pub case_insensitive: bool,
+
+    /// Stop recording matches after this many, for a broad needle (like a 2-byte magic) that
+    /// would otherwise fill the output disk. Later matches are only counted.
+    #[serde(default)]
+    pub max_matches: Option<u64>,
+
+    /// Only match at global offsets that are a multiple of this (e.g., 512 for sector-aligned
+    /// structures).
+    #[serde(default)]
+    pub alignment: Option<u64>,
+
+    /// Names of other needles: a match of this needle inside one of their matches isn't recorded,
+    /// so a substring of a longer needle doesn't repeat each of its matches.
+    #[serde(default)]
+    pub suppress_if_covered_by: Vec<String>,
+
+    /// Also list the printable (ASCII/UTF-8) strings of at least 4 characters in each dump, with
+    /// their global offsets, in a `.strings.txt` file next to the `.bin`, for triage.
+    #[serde(default)]
+    pub strings: bool,
+}
+
+impl ConfigNeedle {
+    /// The happiness level must be 0-9, one of the emojis of `Needle::happiness_level_as_string`.
+    fn check_happiness_level(&self) -> Result<(), String> {
+        match self.happiness_level {
+            0..=9 => Ok(()),
+            happiness_level => Err(format!(
+                "happiness_level must be 0-9, not {}",
+                happiness_level
+            )),
+        }
+    }
+
+    /// Decodes `val` under its `val_format`, with None for wildcard bytes.
+    fn decode_pattern(&self) -> Result<Vec<Option<u8>>, String> {
+        if self.search_both_endians && !matches!(self.val_format, ConfigNeedleValFormat::Hex) {
+            return Err("search_both_endians only applies to hex (numeric) values".to_string());
+        }
+        if self.case_insensitive
+            && matches!(
+                self.val_format,
+                ConfigNeedleValFormat::Hex | ConfigNeedleValFormat::File
+            )
+        {
+            return Err(
+                "case_insensitive only applies to ascii (text) and regex values".to_string(),
+            );
+        }
+        let pattern = match self.val_format {
+            ConfigNeedleValFormat::Hex => {
+                // The string is like "48656c6c6f", or "72 65 6c 6c 6f", or "0x72 0x65 0x6c 0x6c 0x6f",
+                // maybe with `??` wildcards, like "48 ?? 6c 6c 6f".
+                // We must parse it from these values.
+
+                match parse_hex_pattern(self.val.as_str()) {
+                    Ok(pattern) => pattern,
+                    Err(_) => return Err(format!("Failed to parse hex string: {}", self.val)),
+                }
+            }
+            ConfigNeedleValFormat::Ascii => {
+                // the text's bytes, with escapes like `\n` and `\x00` decoded
+                unescape_ascii_val(&self.val)?
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+            ConfigNeedleValFormat::Regex => {
+                // the pattern text; the matches are found with the compiled regex
+                self.compile_regex()?;
+                self.val.bytes().map(Some).collect()
+            }
+            ConfigNeedleValFormat::File => {
+                // the file's raw bytes (`val` is resolved by `resolve_val_file_path` at load)
+                std::fs::read(&self.val)
+                    .map_err(|e| format!("Could not read needle value file {}: {}", self.val, e))?
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+        };
+        if pattern.is_empty() {
+            return Err("Empty needle value".to_string());
+        }
+        if pattern.iter().all(|b| b.is_none()) {
+            return Err(format!(
+                "Needle value is all wildcards, so it matches everywhere: {}",
+                self.val
+            ));
+        }
+        Ok(pattern)
+    }
+
+    /// For `val_format: file`, makes a relative `val` path relative to the needle config's
+    /// directory, so the config works from any working directory.
+    fn resolve_val_file_path(&mut self, needle_config_dir: &Path) {
+        if matches!(self.val_format, ConfigNeedleValFormat::File) {
+            self.val = needle_config_dir
+                .join(&self.val)
+                .to_string_lossy()
+                .into_owned();
+        }
+    }
+
+    /// Compiles `val` for `val_format: regex` (None for other formats). The compiled size is
+    /// limited, so a catastrophic pattern is an error here, not a stalled search.
+    fn compile_regex(&self) -> Result<Option<BytesRegex>, String> {
+        match self.val_format {
+            ConfigNeedleValFormat::Regex => BytesRegexBuilder::new(&self.val)
+                .case_insensitive(self.case_insensitive)
+                .size_limit(REGEX_NEEDLE_SIZE_LIMIT_BYTES)
+                .dfa_size_limit(REGEX_NEEDLE_SIZE_LIMIT_BYTES)
+                .build()
+                .map(Some)
+                .map_err(|e| format!("Invalid regex: {}: {}", self.val, e)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Decodes `val` under its `val_format`. Wildcard bytes are 0x00 (see `decode_mask`).
+    pub fn decode_val(&self) -> Result<Vec<u8>, String> {
+        Ok(self
+            .decode_pattern()?
+            .into_iter()
+            .map(|b| b.unwrap_or(0x00))
+            .collect())
+    }
+
+    /// The bit mask of `val`: `mask` if set, with 0x00 for wildcard bytes. None if there's no
+    /// `mask` and no wildcards.
+    pub fn decode_mask(&self) -> Result<Option<Vec<u8>>, String> {
+        let pattern = self.decode_pattern()?;
+        if self.mask.is_some() && matches!(self.val_format, ConfigNeedleValFormat::Regex) {
+            return Err("mask doesn't apply to regex values".to_string());
+        }
+        let wildcard_mask: Vec<u8> = pattern
+            .iter()
+            .map(|b| match b {
+                Some(_) => 0xFF,
+                None => 0x00,
+            })
+            .collect();
+        let mask = match &self.mask {
+            Some(mask_str) => {
+                let mask = parse_hex_string(mask_str)
+                    .map_err(|_| format!("Failed to parse mask hex string: {}", mask_str))?;
+                if mask.len() != pattern.len() {
+                    return Err(format!(
+                        "Mask has {} bytes, but the needle value has {}: {}",
+                        mask.len(),
+                        pattern.len(),
+                        mask_str
+                    ));
+                }
+                mask.iter()
+                    .zip(wildcard_mask)
+                    .map(|(mask_byte, wildcard_mask_byte)| mask_byte & wildcard_mask_byte)
+                    .collect()
+            }
+            None if pattern.iter().any(|b| b.is_none()) => wildcard_mask,
+            None => return Ok(None),
+        };
+        match mask.iter().all(|&mask_byte| mask_byte == 0x00) {
+            true => Err(format!(
+                "Mask ignores every bit, so the needle matches everywhere: {}",
+                self.mask.as_deref().unwrap_or_default()
+            )),
+            false => Ok(Some(mask)),
+        }
+    }
+}
+
+/// Decodes the escapes in an ASCII needle value: `\n`, `\t`, `\r`, `\0`, `\xNN` (any byte), and
+/// `\\` (a backslash). Text without backslashes is unchanged.
+fn unescape_ascii_val(val: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(val.len());
+    let mut val_bytes = val.bytes();
+    while let Some(b) = val_bytes.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let unescaped_byte = match val_bytes.next() {
+            Some(b'n') => b'\n',
+            Some(b't') => b'\t',
+            Some(b'r') => b'\r',
+            Some(b'0') => b'\0',
+            Some(b'\\') => b'\\',
+            Some(b'x') => {
+                let hex_digits: Vec<u8> = val_bytes.by_ref().take(2).collect();
+                match hex_digits.len() == 2 && hex_digits.iter().all(u8::is_ascii_hexdigit) {
+                    true => {
+                        u8::from_str_radix(std::str::from_utf8(&hex_digits).unwrap(), 16).unwrap()
+                    }
+                    false => {
+                        return Err(format!(
+                            "Invalid \\x escape (needs 2 hex digits) in: {}",
+                            val
+                        ))
+                    }
+                }
+            }
+            Some(other) => {
+                return Err(format!(
+                    "Unknown escape '\\{}' in: {} (use \\\\ for a backslash)",
+                    char::from(other),
+                    val
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "Trailing backslash in: {} (use \\\\ for a backslash)",
+                    val
+                ))
+            }
+        };
+        bytes.push(unescaped_byte);
+    }
+    Ok(bytes)
 }
 
 fn default_write_to_file() -> bool {
     true
 }
 
+fn default_byte_count_before_match() -> u64 {
+    DEFAULT_BYTE_COUNT_BEFORE_MATCH
+}
+
+fn default_byte_count_after_match() -> u64 {
+    DEFAULT_BYTE_COUNT_AFTER_MATCH
+}
+
 #[derive(Serialize, Debug)]
 pub enum ConfigNeedleValFormat {
     Hex,
     Ascii,
+    /// A regular expression over bytes (`regex::bytes` syntax), like `user_id=[0-9]+`.
+    Regex,
+    /// The path of a file whose raw bytes are the value, like a whole binary header. Relative to
+    /// the needle config's directory.
+    File,
 }
 
 impl FromStr for ConfigNeedleValFormat {
@@ -104,6 +641,8 @@ impl FromStr for ConfigNeedleValFormat {
         match s.to_lowercase().as_ref() {
             "hex" => Ok(ConfigNeedleValFormat::Hex),
             "ascii" => Ok(ConfigNeedleValFormat::Ascii),
+            "regex" => Ok(ConfigNeedleValFormat::Regex),
+            "file" => Ok(ConfigNeedleValFormat::File),
             _ => Err(()),
         }
     }
@@ -122,23 +661,227 @@ impl<'de> Deserialize<'de> for ConfigNeedleValFormat {
     }
 }
 
-fn load_config_needles_from_file(file_path: &str) -> Result<Vec<ConfigNeedle>, serde_yaml::Error> {
-    let mut file = File::open(file_path).expect("unable to open file");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .expect("unable to read file");
-    serde_yaml::from_str(&contents)
+pub fn is_needle_config_url(needle_config_path: &str) -> bool {
+    needle_config_path.starts_with("http://") || needle_config_path.starts_with("https://")
 }
 
-pub fn load_needles_from_file(file_path: &str) -> Result<Vec<Needle>, serde_yaml::Error> {
-    let config_needle_vals = load_config_needles_from_file(file_path)?;
-    let needle_vals = config_needle_vals
+/// Reads the needle config from a local file, or fetches it from an `http(s)://` URL (for
+/// centrally-managed needle sets).
+pub fn read_needle_config_text(needle_config_path: &str) -> Result<String, String> {
+    match is_needle_config_url(needle_config_path) {
+        true => reqwest::blocking::get(needle_config_path)
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|e| {
+                format!(
+                    "Could not fetch needle config from {}: {}",
+                    needle_config_path, e
+                )
+            }),
+        false => std::fs::read_to_string(needle_config_path).map_err(|e| {
+            format!(
+                "Could not read needle config file {}: {}",
+                needle_config_path, e
+            )
+        }),
+    }
+}
+
+/// The directory that `val_format: file` paths are relative to: the needle config file's
+/// directory, or the working directory for a config fetched from a URL.
+pub fn needle_config_dir(needle_config_path: &str) -> PathBuf {
+    match is_needle_config_url(needle_config_path) {
+        true => PathBuf::new(),
+        false => Path::new(needle_config_path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    }
+}
+
+/// Parses needles from the text of a needle config (see `read_needle_config_text`), with
+/// `val_format: file` paths relative to the working directory.
+pub fn load_needles_from_str(needle_config_text: &str) -> Result<Vec<Needle>, String> {
+    load_needles_from_str_in_dir(needle_config_text, Path::new(""))
+}
+
+/// Parses needles from the text of a needle config, with `val_format: file` paths relative to
+/// `needle_config_dir` (see `needle_config_dir`). Errors on a needle with an out-of-range
+/// `happiness_level`, or a value that doesn't decode (like an empty one, which can't be searched
+/// for, or an unreadable value file), naming it.
+pub fn load_needles_from_str_in_dir(
+    needle_config_text: &str,
+    needle_config_dir: &Path,
+) -> Result<Vec<Needle>, String> {
+    let mut config_needle_vals: Vec<ConfigNeedle> =
+        serde_yaml::from_str(needle_config_text).map_err(|e| e.to_string())?;
+    for config_needle in &mut config_needle_vals {
+        config_needle
+            .check_happiness_level()
+            .map_err(|e| format!("Needle '{}': {}", config_needle.name, e))?;
+        config_needle.resolve_val_file_path(needle_config_dir);
+        config_needle
+            .decode_val()
+            .and_then(|_| config_needle.decode_mask())
+            .map_err(|e| format!("Needle '{}': {}", config_needle.name, e))?;
+    }
+    let mut needle_vals: Vec<Needle> = config_needle_vals
         .iter()
-        .map(Needle::from_needle_val_config)
+        .flat_map(Needle::all_from_needle_val_config)
         .collect();
+    sort_needles_by_priority(&mut needle_vals);
     Ok(needle_vals)
 }
 
+/// Strictly checks a needle config (`--needle-config-check-only`): every needle must deserialize,
+/// have a value that decodes under its `val_format`, and a happiness level from 0-9. Returns the
+/// needle count, or all the errors found (not just the first), so a whole config can be fixed in
+/// one pass. `val_format: file` paths are relative to `needle_config_dir`.
+pub fn check_needle_config_str(
+    needle_config_text: &str,
+    needle_config_dir: &Path,
+) -> Result<usize, Vec<String>> {
+    let config_entries: Vec<serde_yaml::Value> =
+        serde_yaml::from_str(needle_config_text).map_err(|e| vec![e.to_string()])?;
+
+    let needle_count = config_entries.len();
+    let mut errors: Vec<String> = Vec::new();
+    for (needle_idx, config_entry) in config_entries.into_iter().enumerate() {
+        let entry_name = match config_entry.get("name").and_then(|name| name.as_str()) {
+            Some(name) => format!("needle #{} ('{}')", needle_idx + 1, name),
+            None => format!("needle #{}", needle_idx + 1),
+        };
+        let mut config_needle: ConfigNeedle = match serde_yaml::from_value(config_entry) {
+            Ok(config_needle) => config_needle,
+            Err(e) => {
+                errors.push(format!("{}: {}", entry_name, e));
+                continue;
+            }
+        };
+        config_needle.resolve_val_file_path(needle_config_dir);
+        if let Err(e) = config_needle
+            .decode_val()
+            .and_then(|_| config_needle.decode_mask())
+        {
+            errors.push(format!("{}: {}", entry_name, e));
+        }
+        if let Err(e) = config_needle.check_happiness_level() {
+            errors.push(format!("{}: {}", entry_name, e));
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(needle_count),
+        false => Err(errors),
+    }
+}
+
+/// Checks that no needle is longer than `max_needle_len_bytes` (like the most that fits the chunk
+/// buffer), naming the first one that is. Regex needles have no fixed length, so they're skipped.
+pub fn check_needle_lens(needles: &[Needle], max_needle_len_bytes: usize) -> Result<(), String> {
+    match needles.iter().find(|needle| {
+        needle
+            .match_len()
+            .is_some_and(|match_len| match_len > max_needle_len_bytes)
+    }) {
+        Some(needle) => Err(format!(
+            "Needle '{}' is {} bytes, longer than the {} bytes that fit the chunk buffer",
+            needle.name,
+            needle.val.len(),
+            max_needle_len_bytes
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Sorts needles into search and reporting order: highest `priority` first, then by name, so the
+/// order is deterministic.
+pub fn sort_needles_by_priority(needles: &mut [Needle]) {
+    needles.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Keeps only the needles whose names match `name_regex` (e.g., `^crypto_`).
+/// Errors if the regex is invalid, or if it matches no needles.
+pub fn filter_needles_by_name_regex(
+    needles: Vec<Needle>,
+    name_regex: &str,
+) -> Result<Vec<Needle>, String> {
+    let name_regex =
+        Regex::new(name_regex).map_err(|e| format!("Invalid needle name regex: {}", e))?;
+    let filtered_needles: Vec<Needle> = needles
+        .into_iter()
+        .filter(|needle| name_regex.is_match(&needle.name))
+        .collect();
+    match filtered_needles.is_empty() {
+        true => Err(format!(
+            "Needle name regex '{}' does not match any needle names",
+            name_regex
+        )),
+        false => Ok(filtered_needles),
+    }
+}
+
+/// Keeps only the text needles (`Needle::is_text`), for a quick keyword-only sweep with a mixed
+/// config (`--only-printable-needles`). Errors if there are none.
+pub fn filter_text_needles(needles: Vec<Needle>) -> Result<Vec<Needle>, String> {
+    let text_needles: Vec<Needle> = needles
+        .into_iter()
+        .filter(|needle| needle.is_text())
+        .collect();
+    match text_needles.is_empty() {
+        true => Err("The needle config has no printable (text) needles".to_string()),
+        false => Ok(text_needles),
+    }
+}
+
+/// Sets `write_to_file` on all needles, ignoring the config (`--no-dumps`/`--force-dumps`).
+/// With `None`, each needle keeps its configured value.
+pub fn override_write_to_file(needles: Vec<Needle>, write_to_file: Option<bool>) -> Vec<Needle> {
+    match write_to_file {
+        Some(write_to_file) => needles
+            .into_iter()
+            .map(|needle| Needle {
+                write_to_file,
+                ..needle
+            })
+            .collect(),
+        None => needles,
+    }
+}
+
+/// Name for the copy of the needle config in the output directory, keeping the original extension
+/// (e.g., a `.json` config is copied to `02_needle_config.json`, not a misleading `.yaml` name).
+pub fn needle_config_copy_file_name(needle_config_file_path: &str) -> String {
+    // for URLs, only look at the path (not the host, query string, or fragment)
+    let needle_config_file_path = match is_needle_config_url(needle_config_file_path) {
+        true => needle_config_file_path
+            .split(['?', '#'])
+            .next()
+            .and_then(|url| url.split_once("://"))
+            .and_then(|(_, host_and_path)| host_and_path.split_once('/'))
+            .map(|(_, path)| path)
+            .unwrap_or_default(),
+        false => needle_config_file_path,
+    };
+    let extension = Path::new(needle_config_file_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_else(|| "yaml".to_string());
+    format!("02_needle_config.{}", extension)
+}
+
+/// Reads and parses a needle config from a local path or URL (see `read_needle_config_text`).
+pub fn load_needles_from_file(file_path: &str) -> Result<Vec<Needle>, String> {
+    load_needles_from_str_in_dir(
+        &read_needle_config_text(file_path)?,
+        &needle_config_dir(file_path),
+    )
+}
+
 // test: load needles from file in <repo root>/needle_config.sample.yaml
 #[cfg(test)]
 mod tests {
@@ -152,4 +895,657 @@ mod tests {
         assert!(needles[1].name == "Example Needle 2");
         assert!(needles[2].name == "Example Needle 3");
     }
+
+    #[test]
+    fn test_load_needles_from_val_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let png_header = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        std::fs::create_dir(tmp_dir.path().join("signatures")).unwrap();
+        std::fs::write(tmp_dir.path().join("signatures/png.bin"), png_header).unwrap();
+        let config_file_path = tmp_dir.path().join("needle_config.yaml");
+        let needle_config_text = "- name: png_header\n  val: signatures/png.bin\n  val_format: file\n  description_notes: \"\"\n  happiness_level: 2\n";
+        std::fs::write(&config_file_path, needle_config_text).unwrap();
+
+        // relative to the config's directory, not the working directory
+        let needles = load_needles_from_file(config_file_path.to_str().unwrap()).unwrap();
+        assert_eq!(needles.len(), 1);
+        assert_eq!(needles[0].val, png_header);
+        assert_eq!(
+            check_needle_config_str(needle_config_text, tmp_dir.path()),
+            Ok(1)
+        );
+
+        std::fs::remove_file(tmp_dir.path().join("signatures/png.bin")).unwrap();
+        let e = load_needles_from_file(config_file_path.to_str().unwrap())
+            .err()
+            .unwrap();
+        assert!(
+            e.starts_with("Needle 'png_header': Could not read needle value file "),
+            "{}",
+            e
+        );
+        assert!(e.contains("png.bin"), "{}", e);
+        assert_eq!(
+            check_needle_config_str(needle_config_text, tmp_dir.path())
+                .unwrap_err()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_load_needles_rejects_empty_val() {
+        for (val, val_format) in [("\"\"", "ascii"), ("\"\"", "hex"), ("\"\"", "regex")] {
+            let needle_config_text = format!(
+                "- name: fine\n  val: fine\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: 1\n- name: empty\n  val: {}\n  val_format: {}\n  description_notes: \"\"\n  happiness_level: 1\n",
+                val, val_format
+            );
+            assert_eq!(
+                load_needles_from_str(&needle_config_text).err(),
+                Some("Needle 'empty': Empty needle value".to_string()),
+                "{}",
+                val_format
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_needle_lens() {
+        let make_needle = |name: &str, len: usize| Needle {
+            name: name.to_string(),
+            val: vec![0xAB; len],
+            ..load_needles_from_file("needle_config.sample.yaml").unwrap()[0].clone()
+        };
+        let needles = vec![make_needle("short", 4), make_needle("header", 5000)];
+        assert!(check_needle_lens(&needles, 5000).is_ok());
+        assert_eq!(
+            check_needle_lens(&needles, 4096),
+            Err(
+                "Needle 'header' is 5000 bytes, longer than the 4096 bytes that fit the chunk buffer"
+                    .to_string()
+            )
+        );
+
+        // a long regex pattern can still match short runs
+        let regex_needle = Needle {
+            regex: Some(BytesRegex::new("[0-9]+").unwrap()),
+            ..make_needle("digits", 5000)
+        };
+        assert_eq!(regex_needle.match_len(), None);
+        assert!(check_needle_lens(&[regex_needle], 4096).is_ok());
+    }
+
+    #[test]
+    fn test_render_log_format() {
+        let needle = Needle {
+            name: "mbr".to_string(),
+            val: vec![0x55, 0xAA],
+            log_format: Some("CRITICAL {name} at {offset} (sector {sector}): {ascii}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            needle.render_log_format(needle.log_format.as_ref().unwrap(), 0x1FE + 512 * 3),
+            "CRITICAL mbr at 0x0000_0000_0000_0000_07FE (sector 3): U\u{FFFD}"
+        );
+
+        let needle = Needle {
+            name: "hello".to_string(),
+            val: b"Hello".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(
+            needle.render_log_format("HIT|{offset}|{name}|{ascii}", 16),
+            "HIT|0x0000_0000_0000_0000_0010|hello|Hello"
+        );
+    }
+
+    #[test]
+    fn test_filter_needles_by_name_regex() {
+        let needles = load_needles_from_file("needle_config.sample.yaml").unwrap();
+        let filtered_needles = filter_needles_by_name_regex(needles, "Needle [13]$").unwrap();
+        assert_eq!(filtered_needles.len(), 2);
+        assert_eq!(filtered_needles[0].name, "Example Needle 1");
+        assert_eq!(filtered_needles[1].name, "Example Needle 3");
+
+        let needles = load_needles_from_file("needle_config.sample.yaml").unwrap();
+        assert!(filter_needles_by_name_regex(needles, "^crypto_").is_err());
+
+        let needles = load_needles_from_file("needle_config.sample.yaml").unwrap();
+        assert!(filter_needles_by_name_regex(needles, "(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_check_needle_config_str() {
+        let needle_config_text = std::fs::read_to_string("needle_config.sample.yaml").unwrap();
+        let needle_count = check_needle_config_str(&needle_config_text, Path::new("")).unwrap();
+        assert_eq!(
+            needle_count,
+            load_needles_from_file("needle_config.sample.yaml")
+                .unwrap()
+                .len()
+        );
+
+        let needle_config_text = r#"
+- name: good
+  val: "48 65 6c 6c 6f"
+  val_format: hex
+  description_notes: ok
+  happiness_level: 1
+- name: bad_hex
+  val: "48 zz"
+  val_format: hex
+  description_notes: not hex
+  happiness_level: 1
+- name: odd_hex
+  val: "48656"
+  val_format: hex
+  description_notes: odd digit count
+  happiness_level: 1
+- name: empty_ascii
+  val: ""
+  val_format: ascii
+  description_notes: empty
+  happiness_level: 1
+- name: bad_format
+  val: "Hello"
+  val_format: utf16
+  description_notes: unknown format
+  happiness_level: 1
+- name: too_happy
+  val: "Hello"
+  val_format: ascii
+  description_notes: happiness out of range
+  happiness_level: 12
+- val: "Hello"
+  val_format: ascii
+"#;
+        let errors = check_needle_config_str(needle_config_text, Path::new("")).unwrap_err();
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+        assert!(errors[0].starts_with("needle #2 ('bad_hex'): Failed to parse hex string"));
+        assert!(errors[1].starts_with("needle #3 ('odd_hex'): Failed to parse hex string"));
+        assert_eq!(errors[2], "needle #4 ('empty_ascii'): Empty needle value");
+        assert!(errors[3].starts_with("needle #5 ('bad_format'): "));
+        assert!(errors[3].contains("unknown format"));
+        assert_eq!(
+            errors[4],
+            "needle #6 ('too_happy'): happiness_level must be 0-9, not 12"
+        );
+        assert!(errors[5].starts_with("needle #7: missing field `name`"));
+
+        assert_eq!(
+            check_needle_config_str("not: [a list", Path::new(""))
+                .unwrap_err()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_filter_text_needles() {
+        let needles: Vec<Needle> = [
+            ("keyword", b"wallet.dat".to_vec()),
+            ("binary", vec![0x25, 0x50, 0x00, 0xFF]),
+            ("phrase", b"seed phrase\n".to_vec()),
+            ("magic", vec![0x89, b'P', b'N', b'G']),
+        ]
+        .into_iter()
+        .map(|(name, val)| Needle {
+            name: name.to_string(),
+            val,
+            ..Default::default()
+        })
+        .collect();
+
+        let text_needles = filter_text_needles(needles.clone()).unwrap();
+        let names: Vec<&str> = text_needles
+            .iter()
+            .map(|needle| needle.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["keyword", "phrase"]);
+
+        let binary_needles: Vec<Needle> = needles
+            .into_iter()
+            .filter(|needle| !needle.is_text())
+            .collect();
+        assert!(filter_text_needles(binary_needles).is_err());
+    }
+
+    #[test]
+    fn test_override_write_to_file() {
+        let needles = load_needles_from_file("needle_config.sample.yaml").unwrap();
+        assert!(needles.iter().any(|needle| needle.write_to_file));
+        assert!(needles.iter().any(|needle| !needle.write_to_file));
+
+        let needles = override_write_to_file(needles, None);
+        assert!(needles.iter().any(|needle| !needle.write_to_file));
+        let needles = override_write_to_file(needles, Some(false));
+        assert!(needles.iter().all(|needle| !needle.write_to_file));
+        let needles = override_write_to_file(needles, Some(true));
+        assert!(needles.iter().all(|needle| needle.write_to_file));
+    }
+
+    #[test]
+    fn test_has_min_preceding_run() {
+        let needle = Needle {
+            val: b"REC1".to_vec(),
+            min_preceding_run: Some(8),
+            ..Default::default()
+        };
+        let haystack = b"abc\x00\x00\x00\x00\x00\x00\x00\x00REC1xyz\x00\x00\x00REC1";
+        assert!(needle.has_min_preceding_run(haystack, 11));
+        assert!(!needle.has_min_preceding_run(haystack, 21)); // only 3 zeros
+        assert!(!needle.has_min_preceding_run(haystack, 4)); // not enough bytes before it
+
+        let needle = Needle {
+            min_preceding_run: None,
+            ..needle
+        };
+        assert!(needle.has_min_preceding_run(haystack, 21));
+    }
+
+    #[test]
+    fn test_needle_config_copy_file_name() {
+        assert_eq!(
+            needle_config_copy_file_name("needle_config.sample.yaml"),
+            "02_needle_config.yaml"
+        );
+        assert_eq!(
+            needle_config_copy_file_name("/configs/needles.json"),
+            "02_needle_config.json"
+        );
+        assert_eq!(
+            needle_config_copy_file_name("./configs/needles.YML"),
+            "02_needle_config.yml"
+        );
+        assert_eq!(
+            needle_config_copy_file_name("needles_without_ext"),
+            "02_needle_config.yaml"
+        );
+        assert_eq!(
+            needle_config_copy_file_name("https://example.com/needles/set.yml?token=abc"),
+            "02_needle_config.yml"
+        );
+        assert_eq!(
+            needle_config_copy_file_name("https://example.com"),
+            "02_needle_config.yaml"
+        );
+    }
+
+    /// Serves one HTTP response on localhost, and returns the URL to fetch it from.
+    fn serve_one_http_response(status_line: &'static str, body: String) -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let bytes_read = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..bytes_read]);
+            }
+            write!(
+                stream,
+                "{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status_line,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        format!("http://127.0.0.1:{}/needle_config.yaml", port)
+    }
+
+    #[test]
+    fn test_load_needles_from_url() {
+        let url = serve_one_http_response(
+            "HTTP/1.1 200 OK",
+            std::fs::read_to_string("needle_config.sample.yaml").unwrap(),
+        );
+        let needles = load_needles_from_str(&read_needle_config_text(&url).unwrap()).unwrap();
+        assert_eq!(needles[0].name, "Example Needle 1");
+
+        let url = serve_one_http_response("HTTP/1.1 404 Not Found", String::new());
+        let e = read_needle_config_text(&url).unwrap_err();
+        assert!(e.starts_with("Could not fetch needle config from http://127.0.0.1:"));
+        assert!(e.contains("404"));
+    }
+
+    #[test]
+    fn test_load_needles_sorted_by_priority() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_file_path = tmp_dir.path().join("needle_config.yaml");
+        let mut config_yaml = String::new();
+        for (name, priority_line) in [
+            ("low", "  priority: -1\n"),
+            ("b_default", ""),
+            ("high", "  priority: 5\n"),
+            ("a_default", ""),
+        ] {
+            config_yaml.push_str(&format!(
+                "- name: {}\n  val: {}\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: 1\n{}",
+                name, name, priority_line
+            ));
+        }
+        std::fs::write(&config_file_path, config_yaml).unwrap();
+
+        let needles = load_needles_from_file(config_file_path.to_str().unwrap()).unwrap();
+        let needle_names: Vec<&str> = needles.iter().map(|needle| needle.name.as_str()).collect();
+        assert_eq!(needle_names, vec!["high", "a_default", "b_default", "low"]);
+    }
+
+    #[test]
+    fn test_format_needle_val() {
+        // printable
+        assert_eq!(
+            format_needle_val(b"Hel", None, ValDisplayFormat::Hex),
+            "48 65 6c ('Hel')"
+        );
+        assert_eq!(
+            format_needle_val(b"Hel", None, ValDisplayFormat::Debug),
+            "[72, 101, 108] ('Hel')"
+        );
+        // mixed
+        assert_eq!(
+            format_needle_val(b"Hi\x00\xFF", None, ValDisplayFormat::Hex),
+            "48 69 00 ff"
+        );
+        // binary
+        assert_eq!(
+            format_needle_val(&[0x55, 0xAA], None, ValDisplayFormat::Hex),
+            "55 aa"
+        );
+        assert_eq!(
+            format_needle_val(&[0x55, 0xAA], None, ValDisplayFormat::Debug),
+            "[85, 170]"
+        );
+        // wildcards
+        assert_eq!(
+            format_needle_val(
+                b"H\x00l",
+                Some(&[0xFF, 0x00, 0xFF]),
+                ValDisplayFormat::Debug
+            ),
+            "[72, ??, 108] ('H?l')"
+        );
+    }
+
+    #[test]
+    fn test_wildcard_needle() {
+        let config_needle: ConfigNeedle = serde_yaml::from_str(
+            "name: wild\nval: \"48 ?? 6c ?? 6f\"\nval_format: hex\ndescription_notes: \"\"\nhappiness_level: 1",
+        )
+        .unwrap();
+        let needle = Needle::from_needle_val_config(&config_needle);
+        assert_eq!(needle.val, vec![0x48, 0x00, 0x6c, 0x00, 0x6f]);
+        assert_eq!(needle.mask, Some(vec![0xFF, 0x00, 0xFF, 0x00, 0xFF]));
+
+        assert!(needle.matches_at(b"Hello"));
+        assert!(needle.matches_at(b"H\x00l\xFFo"));
+        assert!(needle.matches_at(b"HalLo"));
+        assert!(!needle.matches_at(b"Jello"));
+        assert!(!needle.matches_at(b"Hellx"));
+
+        assert_eq!(needle.val_as_string(), "[72, ??, 108, ??, 111] ('H?l?o')");
+        assert!(needle.is_text());
+        assert!(needle.uniform_byte_vals().is_empty());
+
+        let config_needle = ConfigNeedle {
+            val: "?? ??".to_string(),
+            ..config_needle
+        };
+        assert!(config_needle.decode_val().is_err());
+    }
+
+    #[test]
+    fn test_load_needles_checks_happiness_level() {
+        let make_needle_config_text = |happiness_level: u8| {
+            format!(
+                "- name: fine\n  val: fine\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: 1\n- name: ecstatic\n  val: Hello\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: {}\n",
+                happiness_level
+            )
+        };
+        assert_eq!(
+            load_needles_from_str(&make_needle_config_text(10)).err(),
+            Some("Needle 'ecstatic': happiness_level must be 0-9, not 10".to_string())
+        );
+
+        let needles = load_needles_from_str(&make_needle_config_text(9)).unwrap();
+        let needle = needles
+            .iter()
+            .find(|needle| needle.name == "ecstatic")
+            .unwrap();
+        assert_eq!(needle.happiness_level, 9);
+        assert_eq!(needle.happiness_level_as_string(), "😂😂 (9)");
+        let needle = Needle {
+            emoji: false,
+            ..needle.clone()
+        };
+        assert_eq!(needle.happiness_level_as_string(), "(level 9)");
+    }
+
+    #[test]
+    fn test_unescape_ascii_val() {
+        assert_eq!(
+            unescape_ascii_val("line\\nfeed"),
+            Ok(b"line\nfeed".to_vec())
+        );
+        assert_eq!(unescape_ascii_val("\\x00\\xFF"), Ok(vec![0x00, 0xFF]));
+        assert_eq!(unescape_ascii_val("C:\\\\Users"), Ok(b"C:\\Users".to_vec()));
+        assert_eq!(
+            unescape_ascii_val("a\\tb\\rc\\0d"),
+            Ok(b"a\tb\rc\0d".to_vec())
+        );
+        assert_eq!(unescape_ascii_val("wallet.dat"), Ok(b"wallet.dat".to_vec()));
+
+        assert!(unescape_ascii_val("\\xZZ").is_err());
+        assert!(unescape_ascii_val("\\x4").is_err());
+        assert!(unescape_ascii_val("\\q").is_err());
+        assert!(unescape_ascii_val("trailing\\").is_err());
+
+        // from a YAML config, where single quotes keep the backslashes for the needle
+        let needles = load_needles_from_str(
+            "- name: tabbed\n  val: 'key\\tvalue\\x00'\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: 1\n",
+        )
+        .unwrap();
+        assert_eq!(needles[0].val, b"key\tvalue\0".to_vec());
+    }
+
+    #[test]
+    fn test_case_insensitive_config() {
+        let needles = load_needles_from_str(
+            "- name: password\n  val: password\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: 1\n  case_insensitive: true\n",
+        )
+        .unwrap();
+        assert!(needles[0].case_insensitive);
+        assert!(needles[0].matches_at(b"PassWord"));
+
+        // hex values are bytes, not text
+        let config_needle: ConfigNeedle = serde_yaml::from_str(
+            "name: hex\nval: \"41 42\"\nval_format: hex\ndescription_notes: \"\"\nhappiness_level: 1\ncase_insensitive: true",
+        )
+        .unwrap();
+        assert!(config_needle.decode_val().is_err());
+    }
+
+    #[test]
+    fn test_regex_config() {
+        let config_needle: ConfigNeedle = serde_yaml::from_str(
+            "name: user_id\nval: 'user_id=[0-9]+'\nval_format: regex\ndescription_notes: \"\"\nhappiness_level: 1\nbyte_count_before_match: 16\nbyte_count_after_match: 16",
+        )
+        .unwrap();
+        let needle = Needle::from_needle_val_config(&config_needle);
+        let regex = needle.regex.as_ref().unwrap();
+        assert_eq!(
+            regex.find(b"..user_id=4217;").map(|m| m.range()),
+            Some(2..14)
+        );
+        assert!(needle.uniform_byte_vals().is_empty());
+        // the dump window is around the matched bytes, not the pattern text
+        assert_eq!(needle.dump_global_range(100, 12), 84..128);
+
+        // with case_insensitive
+        let config_needle = ConfigNeedle {
+            case_insensitive: true,
+            ..config_needle
+        };
+        let needle = Needle::from_needle_val_config(&config_needle);
+        assert!(needle.regex.unwrap().is_match(b"USER_ID=1"));
+
+        let config_needle = ConfigNeedle {
+            val: "user_id=[0-9".to_string(),
+            ..config_needle
+        };
+        assert!(config_needle.decode_val().is_err());
+
+        // a pattern that compiles to a huge program is rejected, instead of eating memory
+        let config_needle = ConfigNeedle {
+            val: "[a-z]{1000}{1000}".to_string(),
+            ..config_needle
+        };
+        assert!(config_needle.decode_val().is_err());
+    }
+
+    #[test]
+    fn test_per_needle_context_sizes() {
+        let needle_config_text = r#"
+- name: rare
+  val: "52 41 52 45"
+  val_format: hex
+  description_notes: ""
+  happiness_level: 9
+  byte_count_before_match: 1048576
+  byte_count_after_match: 1048576
+- name: noisy
+  val: "noise"
+  val_format: ascii
+  description_notes: ""
+  happiness_level: 1
+  byte_count_before_match: 16
+- name: plain
+  val: "plain"
+  val_format: ascii
+  description_notes: ""
+  happiness_level: 1
+"#;
+        let needles = load_needles_from_str(needle_config_text).unwrap();
+        let context_sizes = |name: &str| {
+            let needle = needles.iter().find(|needle| needle.name == name).unwrap();
+            (
+                needle.byte_count_before_match,
+                needle.byte_count_after_match,
+            )
+        };
+        assert_eq!(context_sizes("rare"), (1048576, 1048576));
+        assert_eq!(context_sizes("noisy"), (16, DEFAULT_BYTE_COUNT_AFTER_MATCH));
+        assert_eq!(
+            context_sizes("plain"),
+            (
+                DEFAULT_BYTE_COUNT_BEFORE_MATCH,
+                DEFAULT_BYTE_COUNT_AFTER_MATCH
+            )
+        );
+    }
+
+    #[test]
+    fn test_masked_needle() {
+        let config_needle: ConfigNeedle = serde_yaml::from_str(
+            "name: flags\nval: \"46 4c 47 a0\"\nval_format: hex\nmask: \"ff ff ff f0\"\ndescription_notes: \"\"\nhappiness_level: 1",
+        )
+        .unwrap();
+        let needle = Needle::from_needle_val_config(&config_needle);
+        assert_eq!(needle.mask, Some(vec![0xFF, 0xFF, 0xFF, 0xF0]));
+        // the low nibble of the flags byte is ignored
+        for flags_byte in 0xA0..=0xAF {
+            assert!(needle.matches_at(&[0x46, 0x4c, 0x47, flags_byte]));
+        }
+        assert!(!needle.matches_at(&[0x46, 0x4c, 0x47, 0xB0]));
+        assert!(!needle.matches_at(&[0x46, 0x4c, 0x48, 0xA0]));
+
+        // combined with a wildcard
+        let config_needle = ConfigNeedle {
+            val: "46 ?? 47 a0".to_string(),
+            ..config_needle
+        };
+        assert_eq!(
+            config_needle.decode_mask(),
+            Ok(Some(vec![0xFF, 0x00, 0xFF, 0xF0]))
+        );
+
+        // a uniform run of any 0x0? byte matches
+        let config_needle = ConfigNeedle {
+            val: "00 00".to_string(),
+            mask: Some("f0 f0".to_string()),
+            ..config_needle
+        };
+        let needle = Needle::from_needle_val_config(&config_needle);
+        assert_eq!(
+            needle.uniform_byte_vals(),
+            (0x00..=0x0F).collect::<Vec<u8>>()
+        );
+    }
+
+    #[test]
+    fn test_mask_length_mismatch() {
+        let needle_config_text = r#"
+- name: short_mask
+  val: "46 4c 47 a0"
+  val_format: hex
+  mask: "ff f0"
+  description_notes: ""
+  happiness_level: 1
+- name: zero_mask
+  val: "46 4c"
+  val_format: hex
+  mask: "00 00"
+  description_notes: ""
+  happiness_level: 1
+"#;
+        let errors = check_needle_config_str(needle_config_text, Path::new("")).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "needle #1 ('short_mask'): Mask has 2 bytes, but the needle value has 4: ff f0",
+                "needle #2 ('zero_mask'): Mask ignores every bit, so the needle matches everywhere: 00 00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_both_endians() {
+        let needles = load_needles_from_str(
+            r#"
+- name: magic
+  val: "12 34 56 78"
+  val_format: hex
+  description_notes: ""
+  happiness_level: 1
+  search_both_endians: true
+- name: palindrome
+  val: "a1 b2 b2 a1"
+  val_format: hex
+  description_notes: ""
+  happiness_level: 1
+  search_both_endians: true
+"#,
+        )
+        .unwrap();
+        let needle_names: Vec<&str> = needles.iter().map(|needle| needle.name.as_str()).collect();
+        assert_eq!(needle_names, vec!["magic_be", "magic_le", "palindrome"]);
+        assert!(needles[0].matches_at(&0x12345678u32.to_be_bytes()));
+        assert!(!needles[0].matches_at(&0x12345678u32.to_le_bytes()));
+        assert!(needles[1].matches_at(&0x12345678u32.to_le_bytes()));
+        assert!(!needles[1].matches_at(&0x12345678u32.to_be_bytes()));
+
+        // not for text
+        let errors = check_needle_config_str(
+            "- name: text\n  val: abc\n  val_format: ascii\n  description_notes: \"\"\n  happiness_level: 1\n  search_both_endians: true",
+            Path::new(""),
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["needle #1 ('text'): search_both_endians only applies to hex (numeric) values"]
+        );
+    }
 }
@@ -0,0 +1,350 @@
+use aho_corasick::{AhoCorasick, MatchKind};
+
+use crate::needle::Needle;
+use crate::process_data::ChunkMatch;
+
+/// Finds the matches of all the needles in one pass over the haystack.
+///
+/// Needles without a `mask` are literals, which are all searched at once with an Aho-Corasick
+/// automaton, built once for the search. Masked needles (wildcards, bit masks) and
+/// case-insensitive needles are each checked with a linear scan. Regex needles are each searched
+/// with their own regex.
+#[derive(Clone)]
+pub struct NeedleMatcher {
+    /// The automaton of the literal needles, if there are any.
+    literal_matcher: Option<AhoCorasick>,
+    /// Index of each automaton pattern's needle (in the `needles` the matcher was built from).
+    literal_needle_idxs: Vec<usize>,
+    /// Length of the longest literal needle.
+    max_literal_len: usize,
+    /// Indexes of the needles checked with a linear scan (masked or case-insensitive).
+    scanned_needle_idxs: Vec<usize>,
+    /// Indexes of the regex needles.
+    regex_needle_idxs: Vec<usize>,
+}
+
+impl NeedleMatcher {
+    pub fn new(needles: &[Needle]) -> Result<Self, String> {
+        let (regex_needle_idxs, other_needle_idxs): (Vec<usize>, Vec<usize>) =
+            (0..needles.len()).partition(|&needle_idx| needles[needle_idx].regex.is_some());
+        let (scanned_needle_idxs, literal_needle_idxs): (Vec<usize>, Vec<usize>) =
+            other_needle_idxs.into_iter().partition(|&needle_idx| {
+                needles[needle_idx].mask.is_some() || needles[needle_idx].case_insensitive
+            });
+        let literal_matcher = match literal_needle_idxs.is_empty() {
+            true => None,
+            false => Some(
+                AhoCorasick::builder()
+                    // every match, including overlapping ones
+                    .match_kind(MatchKind::Standard)
+                    .build(
+                        literal_needle_idxs
+                            .iter()
+                            .map(|&needle_idx| &needles[needle_idx].val),
+                    )
+                    .map_err(|e| format!("Could not build the needle matcher: {}", e))?,
+            ),
+        };
+        Ok(Self {
+            literal_matcher,
+            max_literal_len: literal_needle_idxs
+                .iter()
+                .map(|&needle_idx| needles[needle_idx].val.len())
+                .max()
+                .unwrap_or(0),
+            literal_needle_idxs,
+            scanned_needle_idxs,
+            regex_needle_idxs,
+        })
+    }
+
+    /// Finds every match of each needle that starts within
+    /// `haystack[sub_range_start..sub_range_end]`, skipping matches that end within the first
+    /// `carried_len` bytes (see `find_matches_in_chunk`). `needles` must be the ones the matcher
+    /// was built from. Matches may extend past `sub_range_end`. Positions are relative to the
+    /// start of `haystack`. Matches aren't in any particular order.
+    ///
+    /// A regex match can't be split at a sub-range boundary (a search starting partway through a
+    /// match would find its tail), so regex needles are searched over the whole haystack, by the
+    /// sub-range that starts at 0.
+    pub fn find_matches_in_sub_range(
+        &self,
+        haystack: &[u8],
+        needles: &[Needle],
+        sub_range_start: usize,
+        sub_range_end: usize,
+        carried_len: usize,
+    ) -> Vec<ChunkMatch> {
+        // the first position each needle can match at, to skip matches within the carry-forward
+        let needle_search_start =
+            |needle: &Needle| sub_range_start.max(carried_len.saturating_sub(needle.val.len() - 1));
+        let mut chunk_matches = Vec::new();
+
+        if let Some(literal_matcher) = &self.literal_matcher {
+            let search_start =
+                sub_range_start.max(carried_len.saturating_sub(self.max_literal_len - 1));
+            let search_end = (sub_range_end + self.max_literal_len - 1).min(haystack.len());
+            if search_start < sub_range_end {
+                chunk_matches.extend(
+                    literal_matcher
+                        .find_overlapping_iter(&haystack[search_start..search_end])
+                        .filter_map(|literal_match| {
+                            let needle_idx =
+                                self.literal_needle_idxs[literal_match.pattern().as_usize()];
+                            let needle = &needles[needle_idx];
+                            let pos_in_chunk = search_start + literal_match.start();
+                            match pos_in_chunk >= needle_search_start(needle)
+                                && pos_in_chunk < sub_range_end
+                                && needle.has_min_preceding_run(haystack, pos_in_chunk)
+                            {
+                                true => Some(ChunkMatch {
+                                    needle_idx,
+                                    pos_in_chunk,
+                                    match_len: needle.val.len(),
+                                }),
+                                false => None,
+                            }
+                        }),
+                );
+            }
+        }
+
+        for &needle_idx in &self.scanned_needle_idxs {
+            let needle = &needles[needle_idx];
+            let search_start = needle_search_start(needle);
+            let search_end = (sub_range_end + needle.val.len() - 1).min(haystack.len());
+            if search_start >= sub_range_end {
+                continue;
+            }
+            chunk_matches.extend(
+                haystack[search_start..search_end]
+                    .windows(needle.val.len())
+                    .enumerate()
+                    .filter(|(pos_in_search_range, window)| {
+                        needle.matches_at(window)
+                            && needle
+                                .has_min_preceding_run(haystack, search_start + pos_in_search_range)
+                    })
+                    .map(|(pos_in_search_range, _)| ChunkMatch {
+                        needle_idx,
+                        pos_in_chunk: search_start + pos_in_search_range,
+                        match_len: needle.val.len(),
+                    }),
+            );
+        }
+
+        if sub_range_start == 0 {
+            for &needle_idx in &self.regex_needle_idxs {
+                let needle = &needles[needle_idx];
+                let regex = needle.regex.as_ref().expect("Regex needle without a regex");
+                chunk_matches.extend(
+                    regex
+                        .find_iter(haystack)
+                        .filter(|regex_match| {
+                            // An empty match (like `a*` between bytes) isn't a find. A match
+                            // ending right at the end of the carry-forward was left for this
+                            // chunk (`defer_regex_matches_at_chunk_end`), so it's kept.
+                            !regex_match.is_empty()
+                                && regex_match.end() >= carried_len
+                                && needle.has_min_preceding_run(haystack, regex_match.start())
+                        })
+                        .map(|regex_match| ChunkMatch {
+                            needle_idx,
+                            pos_in_chunk: regex_match.start(),
+                            match_len: regex_match.len(),
+                        }),
+                );
+            }
+        }
+        chunk_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::process_data::find_matches_in_chunk;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, RngCore, SeedableRng};
+
+    /// The per-needle linear scan that the automaton replaced, as the reference.
+    fn find_matches_by_linear_scan(
+        haystack: &[u8],
+        needles: &[Needle],
+        carried_len: usize,
+    ) -> Vec<ChunkMatch> {
+        let mut chunk_matches = Vec::new();
+        for (needle_idx, needle) in needles.iter().enumerate() {
+            for (pos_in_chunk, window) in haystack.windows(needle.val.len()).enumerate() {
+                if pos_in_chunk + needle.val.len() > carried_len
+                    && needle.matches_at(window)
+                    && needle.has_min_preceding_run(haystack, pos_in_chunk)
+                {
+                    chunk_matches.push(ChunkMatch {
+                        needle_idx,
+                        pos_in_chunk,
+                        match_len: needle.val.len(),
+                    });
+                }
+            }
+        }
+        chunk_matches
+    }
+
+    #[test]
+    fn test_matches_linear_scan_on_random_haystack() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // few byte values, so short needles match often, and overlap
+        let haystack: Vec<u8> = (0..200_000).map(|_| rng.gen_range(0..4u8)).collect();
+        let mut needles: Vec<Needle> = (0..30)
+            .map(|needle_idx| {
+                let mut val = vec![0u8; rng.gen_range(1..=12)];
+                rng.fill_bytes(&mut val);
+                val.iter_mut().for_each(|b| *b %= 4);
+                Needle {
+                    name: format!("needle_{}", needle_idx),
+                    val,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        // a duplicate value, a prefix of another needle, a masked needle, a case-insensitive
+        // needle, and a preceding run
+        needles.push(Needle {
+            name: "duplicate".to_string(),
+            ..needles[0].clone()
+        });
+        needles.push(Needle {
+            name: "prefix".to_string(),
+            val: needles[1].val[..1].to_vec(),
+            ..Default::default()
+        });
+        needles.push(Needle {
+            name: "masked".to_string(),
+            val: vec![1, 0, 2],
+            mask: Some(vec![0xFF, 0x00, 0xFF]),
+            ..Default::default()
+        });
+        needles.push(Needle {
+            name: "case_insensitive".to_string(),
+            val: vec![b'a', 2, b'B'],
+            case_insensitive: true,
+            ..Default::default()
+        });
+        needles.push(Needle {
+            name: "after_run".to_string(),
+            val: vec![3, 3],
+            min_preceding_run: Some(3),
+            ..Default::default()
+        });
+        let needle_matcher = NeedleMatcher::new(&needles).unwrap();
+
+        for carried_len in [0, 1024] {
+            let mut expected_matches =
+                find_matches_by_linear_scan(&haystack, &needles, carried_len);
+            expected_matches
+                .sort_by_key(|chunk_match| (chunk_match.needle_idx, chunk_match.pos_in_chunk));
+            assert!(expected_matches.len() > 10_000);
+            for sub_range_count in [1, 3, 8] {
+                assert!(
+                    find_matches_in_chunk(
+                        &haystack,
+                        &needles,
+                        &needle_matcher,
+                        sub_range_count,
+                        carried_len
+                    ) == expected_matches,
+                    "carried_len: {}, sub_range_count: {}",
+                    carried_len,
+                    sub_range_count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_needle() {
+        let haystack = b"user Admin, user admin, user ADMIN, user AdMiNistrator, user adm1n";
+        let needles = vec![
+            Needle {
+                name: "admin".to_string(),
+                val: b"admin".to_vec(),
+                case_insensitive: true,
+                ..Default::default()
+            },
+            // the same value, case-sensitive
+            Needle {
+                name: "admin_exact".to_string(),
+                val: b"admin".to_vec(),
+                ..Default::default()
+            },
+        ];
+        let needle_matcher = NeedleMatcher::new(&needles).unwrap();
+        let chunk_matches = find_matches_in_chunk(haystack, &needles, &needle_matcher, 1, 0);
+        let match_positions = |needle_idx: usize| {
+            chunk_matches
+                .iter()
+                .filter(|chunk_match| chunk_match.needle_idx == needle_idx)
+                .map(|chunk_match| chunk_match.pos_in_chunk)
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(match_positions(0), vec![5, 17, 29, 41]);
+        assert_eq!(match_positions(1), vec![17]);
+    }
+
+    #[test]
+    fn test_regex_needle() {
+        let needles = crate::needle::load_needles_from_str(
+            "- name: id\n  val: 'ID=[0-9]{4}'\n  val_format: regex\n  description_notes: \"\"\n  happiness_level: 1\n",
+        )
+        .unwrap();
+        let mut haystack = vec![b'.'; 1000];
+        for (pos, id) in [(10, b"ID=1234"), (248, b"ID=5678"), (600, b"ID=12x4")] {
+            haystack[pos..(pos + id.len())].copy_from_slice(id);
+        }
+        let needle_matcher = NeedleMatcher::new(&needles).unwrap();
+
+        // the same matches, wherever the sub-range boundaries fall
+        for sub_range_count in [1, 4] {
+            let chunk_matches =
+                find_matches_in_chunk(&haystack, &needles, &needle_matcher, sub_range_count, 0);
+            assert_eq!(
+                chunk_matches,
+                vec![
+                    ChunkMatch {
+                        needle_idx: 0,
+                        pos_in_chunk: 10,
+                        match_len: 7,
+                    },
+                    ChunkMatch {
+                        needle_idx: 0,
+                        pos_in_chunk: 248,
+                        match_len: 7,
+                    },
+                ]
+            );
+        }
+
+        // a match that ends within the carry-forward was found in the previous chunk
+        let chunk_matches = find_matches_in_chunk(&haystack, &needles, &needle_matcher, 1, 18);
+        assert_eq!(
+            chunk_matches
+                .iter()
+                .map(|chunk_match| chunk_match.pos_in_chunk)
+                .collect::<Vec<usize>>(),
+            vec![248]
+        );
+
+        // but one that ends right at the end of the carry-forward was left for this chunk
+        let chunk_matches = find_matches_in_chunk(&haystack, &needles, &needle_matcher, 1, 17);
+        assert_eq!(
+            chunk_matches
+                .iter()
+                .map(|chunk_match| chunk_match.pos_in_chunk)
+                .collect::<Vec<usize>>(),
+            vec![10, 248]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Take};
+
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+use crate::multi_frame_lz4::MultiFrameLz4Decoder;
+
+const TAR_BLOCK_LEN: u64 = 512;
+
+/// Only one level of nesting (a tar around an image, which may be compressed) is opened, so a
+/// crafted archive can't send the search through layer after layer of archives.
+pub const MAX_NESTING_DEPTH: usize = 1;
+
+const XZ_MAGIC: &[u8] = &[0xFD, b'7', b'z', b'X', b'Z', 0x00];
+const LZ4_FRAME_MAGIC: &[u8] = &[0x04, 0x22, 0x4D, 0x18];
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+
+/// True if `block` starts with a ustar (POSIX or GNU) tar header.
+pub fn is_tar_header(block: &[u8]) -> bool {
+    block.len() >= TAR_BLOCK_LEN as usize && &block[257..262] == b"ustar"
+}
+
+/// True if the file is a tar archive. Leaves the file at the start.
+pub fn is_tar_file(file: &mut File) -> io::Result<bool> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut first_block = Vec::new();
+    file.by_ref()
+        .take(TAR_BLOCK_LEN)
+        .read_to_end(&mut first_block)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(is_tar_header(&first_block))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TarMember {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Compression of the nested image, detected from its magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NestedCompression {
+    None,
+    Xz,
+    Lz4,
+    Gzip,
+}
+
+enum NestedDecoder {
+    Plain(Take<File>),
+    Xz(XzDecoder<Take<File>>),
+    Lz4(MultiFrameLz4Decoder<BufReader<Take<File>>>),
+    Gzip(MultiGzDecoder<Take<File>>),
+}
+
+/// Reads the (decompressed) image inside a tar archive (`--recursive`).
+pub struct NestedReader {
+    decoder: NestedDecoder,
+    total_out: u64,
+}
+
+impl NestedReader {
+    pub fn compression(&self) -> NestedCompression {
+        match self.decoder {
+            NestedDecoder::Plain(_) => NestedCompression::None,
+            NestedDecoder::Xz(_) => NestedCompression::Xz,
+            NestedDecoder::Lz4(_) => NestedCompression::Lz4,
+            NestedDecoder::Gzip(_) => NestedCompression::Gzip,
+        }
+    }
+
+    /// Bytes read from the outer archive file so far (including the tar headers).
+    pub fn total_in(&self) -> u64 {
+        let outer_file = match &self.decoder {
+            NestedDecoder::Plain(take) => take.get_ref(),
+            NestedDecoder::Xz(xz_decoder) => xz_decoder.get_ref().get_ref(),
+            NestedDecoder::Lz4(lz4_decoder) => lz4_decoder.get_ref().get_ref().get_ref(),
+            NestedDecoder::Gzip(gz_decoder) => gz_decoder.get_ref().get_ref(),
+        };
+        // the clone is used because getting the position otherwise needs a mutable reference
+        match outer_file.try_clone() {
+            Ok(mut cloned_outer_file) => cloned_outer_file.stream_position().unwrap_or(1),
+            Err(_e) => 1, // arbitrary non-zero value
+        }
+    }
+
+    /// True if all of the nested image's (compressed) bytes in the archive have been read.
+    pub fn is_input_exhausted(&self) -> bool {
+        let member_reader = match &self.decoder {
+            NestedDecoder::Plain(take) => take,
+            NestedDecoder::Xz(xz_decoder) => xz_decoder.get_ref(),
+            NestedDecoder::Lz4(lz4_decoder) => lz4_decoder.get_ref().get_ref(),
+            NestedDecoder::Gzip(gz_decoder) => gz_decoder.get_ref(),
+        };
+        member_reader.limit() == 0
+    }
+
+    /// Bytes of the nested image read so far.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+}
+
+impl Read for NestedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = match &mut self.decoder {
+            NestedDecoder::Plain(take) => take.read(buf)?,
+            NestedDecoder::Xz(xz_decoder) => xz_decoder.read(buf)?,
+            NestedDecoder::Lz4(lz4_decoder) => lz4_decoder.read(buf)?,
+            NestedDecoder::Gzip(gz_decoder) => gz_decoder.read(buf)?,
+        };
+        self.total_out += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+/// Opens the first regular file in the tar archive, and decompresses it if it's xz, lz4, or gzip.
+/// Errors if that file is itself a tar archive (deeper than `MAX_NESTING_DEPTH`).
+pub fn open_nested_image(mut file: File) -> io::Result<(TarMember, NestedReader)> {
+    file.seek(SeekFrom::Start(0))?;
+    let tar_member = read_to_first_tar_file_member(&mut file)?;
+
+    // peek at the start of the member, to tell what it is
+    let member_start = file.stream_position()?;
+    let mut member_head = Vec::new();
+    file.by_ref()
+        .take(TAR_BLOCK_LEN.min(tar_member.size))
+        .read_to_end(&mut member_head)?;
+    file.seek(SeekFrom::Start(member_start))?;
+
+    if is_tar_header(&member_head) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "'{}' is itself a tar archive. Only {} level of nesting is supported.",
+                tar_member.name, MAX_NESTING_DEPTH
+            ),
+        ));
+    }
+
+    let member_reader = file.take(tar_member.size);
+    let decoder = if member_head.starts_with(XZ_MAGIC) {
+        NestedDecoder::Xz(XzDecoder::new_multi_decoder(member_reader))
+    } else if member_head.starts_with(LZ4_FRAME_MAGIC) {
+        NestedDecoder::Lz4(MultiFrameLz4Decoder::new(BufReader::new(member_reader)))
+    } else if member_head.starts_with(GZIP_MAGIC) {
+        NestedDecoder::Gzip(MultiGzDecoder::new(member_reader))
+    } else {
+        NestedDecoder::Plain(member_reader)
+    };
+    Ok((
+        tar_member,
+        NestedReader {
+            decoder,
+            total_out: 0,
+        },
+    ))
+}
+
+/// Reads tar headers up to the first regular file, and leaves the reader at the start of its data.
+/// Directories, links, etc. before it are skipped. Supports GNU long names, pax headers, and
+/// base-256 sizes (for members over 8 GiB).
+fn read_to_first_tar_file_member<R: Read>(reader: &mut R) -> io::Result<TarMember> {
+    let mut long_name: Option<String> = None;
+    let mut pax_size: Option<u64> = None;
+
+    loop {
+        let mut header = [0u8; TAR_BLOCK_LEN as usize];
+        reader.read_exact(&mut header)?;
+        if header.iter().all(|&b| b == 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The tar archive has no regular file in it",
+            ));
+        }
+        if !is_tar_header(&header) || !is_tar_header_checksum_valid(&header) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid tar header",
+            ));
+        }
+
+        let size = parse_tar_size(&header[124..136])?;
+        match header[156] {
+            // regular file
+            b'0' | b'\0' | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    String::from_utf8_lossy(trim_nul(&header[0..100])).to_string()
+                });
+                return Ok(TarMember {
+                    name,
+                    size: pax_size.take().unwrap_or(size),
+                });
+            }
+            // GNU long name of the next entry
+            b'L' => {
+                let data = read_tar_member_data(reader, size)?;
+                long_name = Some(String::from_utf8_lossy(trim_nul(&data)).to_string());
+            }
+            // pax extended header of the next entry
+            b'x' => {
+                let data = read_tar_member_data(reader, size)?;
+                for (key, val) in parse_pax_records(&data) {
+                    match key.as_str() {
+                        "size" => pax_size = val.parse().ok(),
+                        "path" => long_name = Some(val),
+                        _ => (),
+                    }
+                }
+            }
+            // anything else (directories, links, global pax headers, etc.)
+            _ => {
+                read_tar_member_data(reader, size)?;
+                long_name = None;
+                pax_size = None;
+            }
+        }
+    }
+}
+
+/// Reads a member's data, and the padding up to the next block.
+fn read_tar_member_data<R: Read>(reader: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(size).read_to_end(&mut data)?;
+    if (data.len() as u64) < size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let padding_len = (TAR_BLOCK_LEN - size % TAR_BLOCK_LEN) % TAR_BLOCK_LEN;
+    io::copy(&mut reader.take(padding_len), &mut io::sink())?;
+    Ok(data)
+}
+
+fn trim_nul(field: &[u8]) -> &[u8] {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..end]
+}
+
+/// Parses a tar size field: octal text, or GNU base-256 (high bit set) for big sizes.
+fn parse_tar_size(field: &[u8]) -> io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7F), |size, &b| {
+                (size << 8) | u64::from(b)
+            }));
+    }
+    let octal_str = String::from_utf8_lossy(trim_nul(field));
+    let octal_str = octal_str.trim();
+    match octal_str.is_empty() {
+        true => Ok(0),
+        false => u64::from_str_radix(octal_str, 8).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid tar size field: '{}'", octal_str),
+            )
+        }),
+    }
+}
+
+/// The checksum is the sum of the header bytes, with the checksum field itself taken as spaces.
+fn is_tar_header_checksum_valid(header: &[u8]) -> bool {
+    let checksum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(pos, &b)| match pos {
+            148..=155 => u64::from(b' '),
+            _ => u64::from(b),
+        })
+        .sum();
+    parse_tar_size(&header[148..156]).is_ok_and(|stored_checksum| stored_checksum == checksum)
+}
+
+/// Parses pax records, like "30 path=some/long/file/name.img\n".
+fn parse_pax_records(data: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|record| {
+            let (_len, key_val) = record.split_once(' ')?;
+            let (key, val) = key_val.split_once('=')?;
+            Some((key.to_string(), val.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::needle::Needle;
+    use crate::needle_matcher::NeedleMatcher;
+    use crate::process_data::find_matches_in_chunk;
+
+    use std::io::Write;
+
+    fn make_tar_header(name: &str, size: u64, type_flag: u8) -> Vec<u8> {
+        let mut header = vec![0u8; TAR_BLOCK_LEN as usize];
+        header[0..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = type_flag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u64 = header.iter().map(|&b| u64::from(b)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        header
+    }
+
+    /// A tar archive with a directory, then `members` (name, contents), then the end blocks.
+    pub fn make_tar(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = make_tar_header("images/", 0, b'5');
+        for (name, contents) in members {
+            tar.extend(make_tar_header(name, contents.len() as u64, b'0'));
+            tar.extend_from_slice(contents);
+            let padding_len =
+                (TAR_BLOCK_LEN - contents.len() as u64 % TAR_BLOCK_LEN) % TAR_BLOCK_LEN;
+            tar.extend(vec![0u8; padding_len as usize]);
+        }
+        tar.extend(vec![0u8; 2 * TAR_BLOCK_LEN as usize]);
+        tar
+    }
+
+    fn write_temp_file(tmp_dir: &tempfile::TempDir, bytes: &[u8]) -> File {
+        let file_path = tmp_dir.path().join("image.tar");
+        std::fs::write(&file_path, bytes).unwrap();
+        File::open(&file_path).unwrap()
+    }
+
+    #[test]
+    fn test_scan_lz4_image_in_tar() {
+        let mut image: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        image[77_777..77_782].copy_from_slice(b"Hello");
+        let mut lz4_encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        lz4_encoder.write_all(&image).unwrap();
+        let compressed_image = lz4_encoder.finish().unwrap();
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut tar_file = write_temp_file(
+            &tmp_dir,
+            &make_tar(&[
+                ("images/disk.img.lz4", &compressed_image),
+                ("images/notes.txt", b"not searched"),
+            ]),
+        );
+        assert!(is_tar_file(&mut tar_file).unwrap());
+
+        let (tar_member, mut nested_reader) = open_nested_image(tar_file).unwrap();
+        assert_eq!(tar_member.name, "images/disk.img.lz4");
+        assert_eq!(nested_reader.compression(), NestedCompression::Lz4);
+
+        let mut nested_image = Vec::new();
+        nested_reader.read_to_end(&mut nested_image).unwrap();
+        assert_eq!(nested_image.len(), image.len());
+        assert_eq!(nested_reader.total_out(), image.len() as u64);
+
+        let needles = vec![Needle {
+            name: "hello".to_string(),
+            val: b"Hello".to_vec(),
+            ..Default::default()
+        }];
+        let chunk_matches = find_matches_in_chunk(
+            &nested_image,
+            &needles,
+            &NeedleMatcher::new(&needles).unwrap(),
+            1,
+            0,
+        );
+        assert_eq!(chunk_matches.len(), 1);
+        assert_eq!(chunk_matches[0].pos_in_chunk, 77_777);
+    }
+
+    #[test]
+    fn test_gzip_image_in_tar() {
+        let image: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let mut gz_encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz_encoder.write_all(&image).unwrap();
+        let compressed_image = gz_encoder.finish().unwrap();
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let tar_file = write_temp_file(
+            &tmp_dir,
+            &make_tar(&[("images/disk.img.gz", &compressed_image)]),
+        );
+        let (_, mut nested_reader) = open_nested_image(tar_file).unwrap();
+        assert_eq!(nested_reader.compression(), NestedCompression::Gzip);
+
+        let mut nested_image = Vec::new();
+        nested_reader.read_to_end(&mut nested_image).unwrap();
+        assert_eq!(nested_image, image);
+        assert!(nested_reader.is_input_exhausted());
+    }
+
+    #[test]
+    fn test_nested_tar_is_too_deep() {
+        let inner_tar = make_tar(&[("disk.img", b"Hello")]);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let tar_file = write_temp_file(&tmp_dir, &make_tar(&[("inner.tar", &inner_tar)]));
+
+        let e = open_nested_image(tar_file).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("level of nesting"));
+    }
+
+    #[test]
+    fn test_parse_tar_size() {
+        assert_eq!(parse_tar_size(b"00000001750\0").unwrap(), 1000);
+        // base-256, for a 10 GiB member
+        let mut field = [0u8; 12];
+        field[0] = 0x80;
+        field[7..12].copy_from_slice(&[0x02, 0x80, 0x00, 0x00, 0x00]);
+        assert_eq!(parse_tar_size(&field).unwrap(), 10 * 1024 * 1024 * 1024);
+    }
+}
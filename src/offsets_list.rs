@@ -0,0 +1,77 @@
+use crate::found_needle::NeedleValFound;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes a `{needle name}.offsets` file per needle that matched, to `output_dir_path`. Each is a
+/// plain newline-delimited list of hex match offsets (like `0x1BE`), in ascending order, for hex
+/// editors that can import a bookmark list. Returns the paths of the written files.
+pub fn write_offsets_files(
+    needle_vals_found: &[NeedleValFound],
+    output_dir_path: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let mut offsets_by_needle_name: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+    for needle_val_found in needle_vals_found {
+        offsets_by_needle_name
+            .entry(&needle_val_found.name)
+            .or_default()
+            .push(needle_val_found.match_start_global_offset);
+    }
+
+    let mut offsets_file_paths = Vec::new();
+    for (needle_name, mut offsets) in offsets_by_needle_name {
+        offsets.sort_unstable();
+        let offsets_file_path = output_dir_path.join(format!("{}.offsets", needle_name));
+        let mut writer = BufWriter::new(File::create(&offsets_file_path)?);
+        for offset in offsets {
+            writeln!(writer, "0x{:X}", offset)?;
+        }
+        writer.flush()?;
+        offsets_file_paths.push(offsets_file_path);
+    }
+    Ok(offsets_file_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::needle::Needle;
+    use std::fs;
+
+    #[test]
+    fn test_write_offsets_files() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let needle_vals_found: Vec<NeedleValFound> =
+            [("mbr", 0x7FE), ("wallet", 0x2000), ("mbr", 0x1FE)]
+                .into_iter()
+                .map(|(name, offset)| {
+                    let needle = Needle {
+                        name: name.to_string(),
+                        val: vec![0x55, 0xAA],
+                        ..Default::default()
+                    };
+                    NeedleValFound::from_needle_val(&needle, offset, Path::new("/images/disk.img"))
+                })
+                .collect();
+
+        let offsets_file_paths = write_offsets_files(&needle_vals_found, tmp_dir.path()).unwrap();
+
+        assert_eq!(
+            offsets_file_paths,
+            vec![
+                tmp_dir.path().join("mbr.offsets"),
+                tmp_dir.path().join("wallet.offsets")
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&offsets_file_paths[0]).unwrap(),
+            "0x1FE\n0x7FE\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&offsets_file_paths[1]).unwrap(),
+            "0x2000\n"
+        );
+    }
+}
@@ -26,6 +26,41 @@ pub fn parse_hex_string(hex_str: &str) -> Result<Vec<u8>, ()> {
     }
 }
 
+/// Like `parse_hex_string`, but also accepts wildcard bytes that match any byte (None): `??` or
+/// `*` between spaces (like "48 ?? 6c"), or `??` in a concatenated string (like "48??6c").
+pub fn parse_hex_pattern(hex_str: &str) -> Result<Vec<Option<u8>>, ()> {
+    if !hex_str.contains(['?', '*']) {
+        return parse_hex_string(hex_str).map(|bytes| bytes.into_iter().map(Some).collect());
+    }
+    let is_wildcard = |s: &str| s == "??" || s == "*";
+    if hex_str.contains(' ') {
+        hex_str
+            .split_whitespace()
+            .map(|s| s.trim_start_matches("0x"))
+            .filter(|s| !s.is_empty())
+            .map(|s| match is_wildcard(s) {
+                true => Ok(None),
+                false => u8::from_str_radix(s, 16).map(Some).map_err(|_| ()),
+            })
+            .collect()
+    } else {
+        let hex_str = hex_str.trim_start_matches("0x");
+        if !hex_str.is_ascii() || !hex_str.len().is_multiple_of(2) {
+            return Err(());
+        }
+        (0..hex_str.len())
+            .step_by(2)
+            .map(|pos| {
+                let s = &hex_str[pos..(pos + 2)];
+                match is_wildcard(s) {
+                    true => Ok(None),
+                    false => u8::from_str_radix(s, 16).map(Some).map_err(|_| ()),
+                }
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,4 +130,21 @@ mod tests {
         let hex_str = "72 65 6z 6c 6f"; // Contains an invalid 'z'
         assert!(parse_hex_string(hex_str).is_err());
     }
+
+    #[test]
+    fn test_pattern_with_wildcards() {
+        let expected = Ok(vec![Some(0x48), None, Some(0x6c), None, Some(0x6f)]);
+        assert_eq!(parse_hex_pattern("48 ?? 6c ?? 6f"), expected);
+        assert_eq!(parse_hex_pattern("0x48 * 0x6c * 0x6f"), expected);
+        assert_eq!(parse_hex_pattern("48??6c??6f"), expected);
+        // no wildcards
+        assert_eq!(parse_hex_pattern("4865"), Ok(vec![Some(0x48), Some(0x65)]));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(parse_hex_pattern("48 ? 6c").is_err()); // half a wildcard
+        assert!(parse_hex_pattern("48?6c").is_err());
+        assert!(parse_hex_pattern("48 ?g").is_err());
+    }
 }
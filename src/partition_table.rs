@@ -0,0 +1,355 @@
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+const SECTOR_SIZE_BYTES: u64 = 512;
+
+/// GPT headers are in the second logical block, which is 4096 bytes on 4K-native disks.
+const GPT_LOGICAL_BLOCK_SIZES: [u64; 2] = [512, 4096];
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const MBR_GPT_PROTECTIVE_TYPE: u8 = 0xEE;
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+
+/// Guards against a corrupt (e.g., looping) chain of logical partitions.
+const MAX_LOGICAL_PARTITION_COUNT: u32 = 128;
+/// Guards against a corrupt GPT header asking for a huge partition entry array.
+const MAX_GPT_ENTRY_ARRAY_BYTES: u64 = 1024 * 1024;
+
+/// The partition type, as stored in the partition table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PartitionType {
+    /// MBR partition type byte, like 0x83 (Linux) or 0x07 (NTFS/exFAT).
+    Mbr(u8),
+    /// GPT partition type GUID, like "0FC63DAF-8483-4772-8E79-3D69D8477DE4" (Linux filesystem).
+    Gpt(String),
+}
+
+impl fmt::Display for PartitionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartitionType::Mbr(type_byte) => write!(f, "MBR type 0x{:02X}", type_byte),
+            PartitionType::Gpt(type_guid) => write!(f, "GPT type {}", type_guid),
+        }
+    }
+}
+
+/// A partition of a whole-disk image (`--partition`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    /// Partition number, like in Linux device names (sda1): 1-4 for MBR primary partitions, 5 and
+    /// up for MBR logical partitions, and the entry index + 1 for GPT.
+    pub number: u32,
+    pub start_offset: u64,
+    pub len_bytes: u64,
+    pub partition_type: PartitionType,
+}
+
+impl Partition {
+    pub fn end_offset(&self) -> u64 {
+        self.start_offset + self.len_bytes
+    }
+}
+
+/// Reads the partitions of a whole-disk image, from its GPT (if the MBR is a protective MBR) or
+/// its MBR (including the logical partitions in an extended partition). Returns no partitions if
+/// there's no partition table.
+pub fn read_partitions<R: Read + Seek>(disk: &mut R) -> io::Result<Vec<Partition>> {
+    let mbr = match read_sector(disk, 0)? {
+        Some(mbr) => mbr,
+        None => return Ok(Vec::new()),
+    };
+    if mbr[510..512] != [0x55, 0xAA] {
+        return Ok(Vec::new());
+    }
+    let mbr_entries = parse_mbr_entries(&mbr);
+    if mbr_entries
+        .iter()
+        .any(|entry| entry.type_byte == MBR_GPT_PROTECTIVE_TYPE)
+    {
+        return read_gpt_partitions(disk);
+    }
+
+    let mut partitions = Vec::new();
+    for (entry_idx, entry) in mbr_entries.iter().enumerate() {
+        if entry.type_byte == 0 || entry.sector_count == 0 {
+            continue;
+        }
+        partitions.push(entry.to_partition(entry_idx as u32 + 1, 0));
+    }
+    if let Some(extended_entry) = mbr_entries
+        .iter()
+        .find(|entry| MBR_EXTENDED_TYPES.contains(&entry.type_byte))
+    {
+        partitions.extend(read_logical_partitions(disk, extended_entry.start_lba)?);
+    }
+    Ok(partitions)
+}
+
+/// One of the four entries in an MBR (or EBR) partition table.
+struct MbrEntry {
+    type_byte: u8,
+    start_lba: u64,
+    sector_count: u64,
+}
+
+impl MbrEntry {
+    fn to_partition(&self, number: u32, base_lba: u64) -> Partition {
+        Partition {
+            number,
+            start_offset: (base_lba + self.start_lba) * SECTOR_SIZE_BYTES,
+            len_bytes: self.sector_count * SECTOR_SIZE_BYTES,
+            partition_type: PartitionType::Mbr(self.type_byte),
+        }
+    }
+}
+
+fn parse_mbr_entries(sector: &[u8]) -> Vec<MbrEntry> {
+    (0..4)
+        .map(|entry_idx| {
+            let entry = &sector[(446 + entry_idx * 16)..(446 + (entry_idx + 1) * 16)];
+            MbrEntry {
+                type_byte: entry[4],
+                start_lba: u64::from(read_u32_le(&entry[8..12])),
+                sector_count: u64::from(read_u32_le(&entry[12..16])),
+            }
+        })
+        .collect()
+}
+
+/// Follows the chain of extended boot records (EBRs). Each EBR describes one logical partition
+/// (relative to the EBR), and links to the next EBR (relative to the start of the extended partition).
+fn read_logical_partitions<R: Read + Seek>(
+    disk: &mut R,
+    extended_start_lba: u64,
+) -> io::Result<Vec<Partition>> {
+    let mut partitions = Vec::new();
+    let mut ebr_lba = extended_start_lba;
+    for number in 5..(5 + MAX_LOGICAL_PARTITION_COUNT) {
+        let ebr = match read_sector(disk, ebr_lba)? {
+            Some(ebr) if ebr[510..512] == [0x55, 0xAA] => ebr,
+            _ => break,
+        };
+        let ebr_entries = parse_mbr_entries(&ebr);
+        if ebr_entries[0].type_byte != 0 && ebr_entries[0].sector_count != 0 {
+            partitions.push(ebr_entries[0].to_partition(number, ebr_lba));
+        }
+        match ebr_entries[1].start_lba {
+            0 => break,
+            next_ebr_relative_lba => ebr_lba = extended_start_lba + next_ebr_relative_lba,
+        }
+    }
+    Ok(partitions)
+}
+
+fn read_gpt_partitions<R: Read + Seek>(disk: &mut R) -> io::Result<Vec<Partition>> {
+    for logical_block_size in GPT_LOGICAL_BLOCK_SIZES {
+        let mut header = [0u8; 92];
+        disk.seek(SeekFrom::Start(logical_block_size))?;
+        if read_full(disk, &mut header)? < header.len() || &header[0..8] != GPT_SIGNATURE {
+            continue;
+        }
+
+        let entry_array_lba = read_u64_le(&header[72..80]);
+        let entry_count = u64::from(read_u32_le(&header[80..84]));
+        let entry_len = u64::from(read_u32_le(&header[84..88]));
+        if entry_len < 128 || entry_count * entry_len > MAX_GPT_ENTRY_ARRAY_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid GPT partition entry array size",
+            ));
+        }
+
+        let mut entry_array = vec![0u8; (entry_count * entry_len) as usize];
+        disk.seek(SeekFrom::Start(entry_array_lba * logical_block_size))?;
+        let entry_array_len = read_full(disk, &mut entry_array)?;
+
+        let mut partitions = Vec::new();
+        for (entry_idx, entry) in entry_array[..entry_array_len]
+            .chunks_exact(entry_len as usize)
+            .enumerate()
+        {
+            // an all-zero type GUID marks an unused entry
+            if entry[0..16].iter().all(|&b| b == 0) {
+                continue;
+            }
+            let first_lba = read_u64_le(&entry[32..40]);
+            let last_lba = read_u64_le(&entry[40..48]); // inclusive
+            if last_lba < first_lba {
+                continue;
+            }
+            partitions.push(Partition {
+                number: entry_idx as u32 + 1,
+                start_offset: first_lba * logical_block_size,
+                len_bytes: (last_lba - first_lba + 1) * logical_block_size,
+                partition_type: PartitionType::Gpt(format_guid(&entry[0..16])),
+            });
+        }
+        return Ok(partitions);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Protective MBR found, but no GPT header",
+    ))
+}
+
+/// Formats a GUID in its usual mixed-endian text form.
+fn format_guid(guid: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{}-{}",
+        read_u32_le(&guid[0..4]),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        hex::encode_upper(&guid[8..10]),
+        hex::encode_upper(&guid[10..16])
+    )
+}
+
+/// Reads the 512-byte sector at `lba`, or None if the disk ends before it.
+fn read_sector<R: Read + Seek>(disk: &mut R, lba: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut sector = vec![0u8; SECTOR_SIZE_BYTES as usize];
+    disk.seek(SeekFrom::Start(lba * SECTOR_SIZE_BYTES))?;
+    match read_full(disk, &mut sector)? == sector.len() {
+        true => Ok(Some(sector)),
+        false => Ok(None),
+    }
+}
+
+/// Like `read_exact`, but returns the length read if the reader ends first. Readers like
+/// `XzDecoder` return fewer bytes than asked for well before the end, so this keeps reading.
+pub fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut total_len = 0;
+    while total_len < buf.len() {
+        match reader.read(&mut buf[total_len..])? {
+            0 => break,
+            len => total_len += len,
+        }
+    }
+    Ok(total_len)
+}
+
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+fn read_u64_le(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes.try_into().unwrap())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn write_mbr_entry(
+        sector: &mut [u8],
+        entry_idx: usize,
+        type_byte: u8,
+        start_lba: u32,
+        sector_count: u32,
+    ) {
+        let entry = &mut sector[(446 + entry_idx * 16)..(446 + (entry_idx + 1) * 16)];
+        entry[4] = type_byte;
+        entry[8..12].copy_from_slice(&start_lba.to_le_bytes());
+        entry[12..16].copy_from_slice(&sector_count.to_le_bytes());
+    }
+
+    /// A whole-disk image of `disk_sector_count` sectors, with an MBR holding `partitions`
+    /// (type byte, start LBA, sector count).
+    pub fn make_mbr_disk(disk_sector_count: usize, partitions: &[(u8, u32, u32)]) -> Vec<u8> {
+        let mut disk = vec![0u8; disk_sector_count * SECTOR_SIZE_BYTES as usize];
+        for (entry_idx, &(type_byte, start_lba, sector_count)) in partitions.iter().enumerate() {
+            write_mbr_entry(&mut disk, entry_idx, type_byte, start_lba, sector_count);
+        }
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+        disk
+    }
+
+    #[test]
+    fn test_read_mbr_partitions() {
+        let mut disk = make_mbr_disk(
+            200,
+            &[
+                (0x83, 2, 40),
+                (0x00, 0, 0),
+                (0x05, 100, 100),
+                (0x07, 50, 10),
+            ],
+        );
+        // two logical partitions in the extended partition
+        let ebr_offset = 100 * SECTOR_SIZE_BYTES as usize;
+        let ebr = &mut disk[ebr_offset..(ebr_offset + 512)];
+        write_mbr_entry(ebr, 0, 0x83, 1, 20);
+        write_mbr_entry(ebr, 1, 0x05, 50, 30);
+        ebr[510..512].copy_from_slice(&[0x55, 0xAA]);
+        let ebr_offset = 150 * SECTOR_SIZE_BYTES as usize;
+        let ebr = &mut disk[ebr_offset..(ebr_offset + 512)];
+        write_mbr_entry(ebr, 0, 0x0B, 2, 10);
+        ebr[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        let partitions = read_partitions(&mut Cursor::new(&disk)).unwrap();
+        let summary: Vec<(u32, u64, u64)> = partitions
+            .iter()
+            .map(|partition| {
+                (
+                    partition.number,
+                    partition.start_offset / 512,
+                    partition.len_bytes / 512,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 2, 40),
+                (3, 100, 100),
+                (4, 50, 10),
+                (5, 101, 20),
+                (6, 152, 10)
+            ]
+        );
+        assert_eq!(partitions[0].partition_type.to_string(), "MBR type 0x83");
+    }
+
+    #[test]
+    fn test_read_gpt_partitions() {
+        let mut disk = make_mbr_disk(64, &[(MBR_GPT_PROTECTIVE_TYPE, 1, 63)]);
+        let header = &mut disk[512..1024];
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&4u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        // entry 2 is used (entry 1 is unused, so partition numbers have a gap)
+        let entry = &mut disk[(1024 + 128)..(1024 + 256)];
+        entry[0..16].copy_from_slice(&[
+            0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47,
+            0x7D, 0xE4,
+        ]);
+        entry[32..40].copy_from_slice(&34u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&63u64.to_le_bytes());
+
+        let partitions = read_partitions(&mut Cursor::new(&disk)).unwrap();
+        assert_eq!(
+            partitions,
+            vec![Partition {
+                number: 2,
+                start_offset: 34 * 512,
+                len_bytes: 30 * 512,
+                partition_type: PartitionType::Gpt(
+                    "0FC63DAF-8483-4772-8E79-3D69D8477DE4".to_string()
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_read_partitions_without_partition_table() {
+        assert_eq!(
+            read_partitions(&mut Cursor::new(vec![0u8; 4096])).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            read_partitions(&mut Cursor::new(vec![0u8; 100])).unwrap(),
+            vec![]
+        );
+    }
+}
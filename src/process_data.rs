@@ -1,168 +1,4588 @@
+use crate::carved_files::{find_carved_file_containing, find_carved_files};
+use crate::checkpoint::Checkpoint;
+use crate::coverage_verifier::CoverageVerifier;
+use crate::disk_geometry::DiskGeometry;
 use crate::display_hex::display_hex_offset;
-use crate::found_needle::NeedleValFound;
+use crate::dump_file_name::DumpFileNameTemplate;
+use crate::dump_writer::DumpWriter;
+use crate::error_summary::ErrorCollector;
+use crate::explore::format_hexdump_lines;
+use crate::found_needle::{
+    jsonl_part_file_path, jsonl_part_file_paths, LineEnding, NeedleValFound,
+};
+use crate::hotspot::{Hotspot, HotspotDetector};
 use crate::needle::Needle;
+use crate::needle_matcher::NeedleMatcher;
+use crate::partition_table::read_full;
+use crate::sqlite_output::SqliteWriter;
+use crate::strings::{format_printable_strings, strings_file_path};
+use crate::text_regions::mask_non_text_regions;
 
+use chrono::Utc;
 use num_format::{Locale, ToFormattedString as _};
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{self, BufRead as _, Read, Seek as _, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use memmap2::Mmap;
+use rayon::prelude::*;
 
+/// Amount from the end of the previous read to carry forward, unless a needle is longer.
+pub const DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES: usize = 1024;
+
+/// The carry-forward length for `needles`: the default, or enough for the longest needle to be
+/// found across a chunk boundary (its length - 1), plus its `min_preceding_run`, so the run before
+/// a match is always in the haystack, whichever chunk the match is found in. A regex needle's
+/// matches vary in length, so they're only found whole across a chunk boundary if they fit in it.
+pub fn haystack_carry_forward_len_bytes_for(needles: &[Needle]) -> usize {
+    needles
+        .iter()
+        .map(|needle| {
+            needle.match_len().unwrap_or(0).saturating_sub(1)
+                + needle.min_preceding_run.unwrap_or(0) as usize
+        })
+        .max()
+        .unwrap_or(0)
+        .max(DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES)
+}
+
+/// Haystack chunk buffer size for uncompressed input, without `--chunk-size-mib`.
+pub const DEFAULT_HAYSTACK_CHUNK_BUFFER_SIZE_BYTES: usize = 8 * 1024 * 1024; // 8 MiB
+
+/// The longest needle that a haystack chunk buffer of `chunk_size_bytes` fits (see
+/// `check_haystack_chunk_buffer_size`), with the carry-forward it needs.
+pub fn max_needle_len_bytes_for_chunk_size(chunk_size_bytes: usize) -> usize {
+    // past the default carry-forward, the carry-forward grows with the needle (its length - 1)
+    match chunk_size_bytes / 2 > DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES {
+        true => chunk_size_bytes / 2,
+        false => chunk_size_bytes.saturating_sub(DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES + 1),
+    }
+}
+
+/// Checks that a haystack chunk buffer of `chunk_size_bytes` has room for fresh data after a
+/// carry-forward of `haystack_carry_forward_len_bytes`, and fits the longest of `needles`.
+pub fn check_haystack_chunk_buffer_size(
+    chunk_size_bytes: usize,
+    haystack_carry_forward_len_bytes: usize,
+    needles: &[Needle],
+) -> Result<(), String> {
+    let longest_needle_len = needles
+        .iter()
+        .filter_map(|needle| needle.match_len())
+        .max()
+        .unwrap_or(0);
+    let min_chunk_size_bytes = haystack_carry_forward_len_bytes + longest_needle_len;
+    match chunk_size_bytes > min_chunk_size_bytes {
+        true => Ok(()),
+        false => Err(format!(
+            "Chunk size of {} bytes is too small. It must be over {} bytes: the carry-forward ({} bytes) plus the longest needle ({} bytes).",
+            chunk_size_bytes,
+            min_chunk_size_bytes,
+            haystack_carry_forward_len_bytes,
+            longest_needle_len
+        )),
+    }
+}
+
+#[derive(Clone)]
 pub struct SearchAssignment {
     pub input_file_path: PathBuf,
     pub output_dir_path: PathBuf,
-    pub jsonl_output_log_file_path: PathBuf,
+    /// The global JSONL file with every match record (None with `--no-global-jsonl`, in which case
+    /// records are only written to the per-needle JSONL files).
+    pub jsonl_output_log_file_path: Option<PathBuf>,
     pub needles: Vec<Needle>,
+    /// Finds the matches of `needles` in each chunk, built once from them.
+    pub needle_matcher: NeedleMatcher,
+
+    /// Number of threads to split each chunk's search across (1 = search the chunk on the main thread).
+    pub chunk_search_threads: usize,
+
+    /// Per-needle output directories are named `{prefix}_{needle name}`. The prefix for each
+    /// happiness level comes from here, and defaults to the happiness level number itself.
+    pub happiness_dir_prefixes: HashMap<u8, String>,
+
+    /// Amount from the end of the previous read to carry forward into the next chunk.
+    pub haystack_carry_forward_len_bytes: usize,
+
+    /// For quick sampled scans, only search every Nth chunk (None = search every chunk).
+    pub sample_every_nth_chunk: Option<u64>,
+
+    /// Drop matches inside embedded JPEG/ZIP files found in the chunk (unless the needle opts in).
+    pub exclude_carved_files: bool,
+
+    /// Merge the dumps of matches whose context windows are within this many bytes of each other
+    /// into one shared dump (None = one dump per match).
+    pub merge_dump_distance_bytes: Option<usize>,
+
+    /// Disk geometry, to report each match's CHS address (`--chs`).
+    pub disk_geometry: Option<DiskGeometry>,
+
+    /// Only match text needles inside runs of at least this many text bytes (`--text-regions-only`).
+    pub text_regions_min_run_len: Option<usize>,
+
+    /// Line ending between JSONL records (`--jsonl-line-ending`).
+    pub jsonl_line_ending: LineEnding,
+
+    /// Write the needle value in JSONL records as the older array of byte values (`--jsonl-val-array`),
+    /// instead of a hex string.
+    pub jsonl_val_array: bool,
+
+    /// Add a hex+ASCII dump of each match's written context to its JSONL record
+    /// (`--jsonl-context-hexdump`).
+    pub jsonl_context_hexdump: bool,
+
+    /// Dump the full before/after context of matches near a chunk boundary (`--scan-window-context`),
+    /// instead of clamping it to the chunk.
+    pub scan_window_context: bool,
+
+    /// Record each match's time in high resolution, and the time since the start of the search
+    /// (`--record-match-time`).
+    pub record_match_time: bool,
+
+    /// Stop searching at this global offset (exclusive), like the end of the partition being
+    /// searched (`--partition`). None = search to the end of the stream.
+    pub end_global_offset: Option<u64>,
+    /// The stream past `end_global_offset` is searched too, by another `--threads` worker, so a
+    /// regex match running to the end is left for it (see `defer_regex_matches_at_chunk_end`).
+    pub is_searched_past_end: bool,
+
+    /// Only report this many matches per chunk (None = no limit), so one pathological chunk (e.g.,
+    /// all 0x00 with a 0x00 needle) can't stall the search with a flood of matches.
+    pub max_matches_per_chunk: Option<usize>,
+
+    /// Start a new numbered part of the global JSONL file once the current part reaches this size
+    /// (`--jsonl-max-size`), or this many records (`--jsonl-max-records`). None = no limit.
+    pub jsonl_max_part_size_bytes: Option<u64>,
+    pub jsonl_max_part_records: Option<u64>,
+
+    /// Names of the per-match dump files (`--dump-filename-template`).
+    pub dump_file_name_template: DumpFileNameTemplate,
+
+    /// Make the JSONL records the same for every run of the same search (`--deterministic`): no
+    /// match timestamps, and dump file paths relative to the output directory.
+    pub deterministic: bool,
+
+    /// Write a checkpoint (`08_checkpoint.json`) at least this many seconds apart, so an
+    /// interrupted search can be continued with `--resume`. None = no checkpoints.
+    pub checkpoint_interval_sec: Option<f32>,
+    /// Hash of the run's config, recorded in each checkpoint (see `checkpoint::config_hash`).
+    pub config_hash: String,
+
+    /// Don't write the JSONL records as the matches are found, only keep them in
+    /// `needle_vals_found`, for the caller to write in order (like the `--threads` workers).
+    pub defer_jsonl_records: bool,
+
+    /// Only count and record the matches in the global JSONL file (`--dry-run`): no per-needle
+    /// directories, so no dumps or per-needle JSONL files.
+    pub dry_run: bool,
+
+    /// Skip a match whose needle name and global offset were already recorded in this run
+    /// (`--dedup`), like when a region is searched again.
+    pub dedup: bool,
+
+    /// Set (by the Ctrl-C handler) to stop the search after the current chunk, so the run still
+    /// finishes its output. Shared with the `--threads` workers.
+    pub stop_requested: Arc<AtomicBool>,
+}
+
+impl SearchAssignment {
+    pub fn needle_dir_path(&self, needle: &Needle) -> PathBuf {
+        let prefix = match self.happiness_dir_prefixes.get(&needle.happiness_level) {
+            Some(prefix) => prefix.clone(),
+            None => needle.happiness_level.to_string(),
+        };
+        self.output_dir_path
+            .join(format!("{}_{}", prefix, needle.name))
+    }
+
+    pub fn needle_jsonl_file_path(&self, needle: &Needle) -> PathBuf {
+        self.needle_dir_path(needle)
+            .join(format!("001_{}.jsonl", needle.name))
+    }
+
+    /// The JSONL files that together hold every match record (including all the parts of the
+    /// global JSONL file, if it was split).
+    pub fn all_jsonl_file_paths(&self) -> Vec<PathBuf> {
+        match &self.jsonl_output_log_file_path {
+            Some(jsonl_output_log_file_path) => jsonl_part_file_paths(jsonl_output_log_file_path),
+            None => self
+                .needles
+                .iter()
+                .map(|needle| self.needle_jsonl_file_path(needle))
+                .collect(),
+        }
+    }
+
+    /// True if the global JSONL part at `part_file_path`, with `part_record_count` records, has
+    /// reached `--jsonl-max-size` or `--jsonl-max-records`.
+    fn is_jsonl_part_full(&self, part_file_path: &Path, part_record_count: u64) -> bool {
+        let is_over_max_records = self
+            .jsonl_max_part_records
+            .is_some_and(|max_part_records| part_record_count >= max_part_records);
+        let is_over_max_size = self.jsonl_max_part_size_bytes.is_some_and(|max_part_size| {
+            fs::metadata(part_file_path).is_ok_and(|metadata| metadata.len() >= max_part_size)
+        });
+        is_over_max_records || is_over_max_size
+    }
+
+    /// Checks that a haystack chunk buffer of `chunk_size_bytes` has room for fresh data after the
+    /// carry-forward, and fits the longest needle.
+    pub fn check_haystack_chunk_buffer_size(&self, chunk_size_bytes: usize) -> Result<(), String> {
+        check_haystack_chunk_buffer_size(
+            chunk_size_bytes,
+            self.haystack_carry_forward_len_bytes,
+            &self.needles,
+        )
+    }
+
+    /// Merged dumps are shared by several needles, so they get their own directory.
+    pub fn merged_dump_dir_path(&self) -> PathBuf {
+        self.output_dir_path.join("05_merged_dumps")
+    }
+
+    /// Dense clusters of matches (`--hotspot-window`), one JSONL record per region.
+    pub fn hotspots_jsonl_file_path(&self) -> PathBuf {
+        self.output_dir_path.join("07_hotspots.jsonl")
+    }
+
+    pub fn checkpoint_file_path(&self) -> PathBuf {
+        Checkpoint::file_path(&self.output_dir_path)
+    }
+}
+
+/// Parses a happiness level to directory prefix mapping, like "9=critical,5=interesting,0=info".
+pub fn parse_happiness_dir_prefixes(mapping_str: &str) -> Result<HashMap<u8, String>, String> {
+    let mut happiness_dir_prefixes = HashMap::new();
+    for entry in mapping_str
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+    {
+        let (happiness_level, prefix) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Expected 'level=prefix', got '{}'", entry))?;
+        let happiness_level: u8 = happiness_level
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid happiness level '{}'", happiness_level))?;
+        let prefix = prefix.trim();
+        if prefix.is_empty() || prefix.contains(['/', '\\']) {
+            return Err(format!(
+                "Invalid directory prefix '{}' for happiness level {}",
+                prefix, happiness_level
+            ));
+        }
+        happiness_dir_prefixes.insert(happiness_level, prefix.to_string());
+    }
+    Ok(happiness_dir_prefixes)
+}
+
+/// A needle match within the haystack chunk buffer.
+#[derive(Debug, PartialEq)]
+pub struct ChunkMatch {
+    /// Index of the needle in `SearchAssignment.needles`
+    pub needle_idx: usize,
+    pub pos_in_chunk: usize,
+    /// Length of the matched bytes: the needle's length, except for regex needles.
+    pub match_len: usize,
+}
+
+impl ChunkMatch {
+    pub fn range_in_chunk(&self) -> Range<usize> {
+        self.pos_in_chunk..(self.pos_in_chunk + self.match_len)
+    }
+}
+
+/// Drops the regex needles' matches that run to the end of the haystack chunk, for a chunk that
+/// isn't the last one of the stream. A greedy match (like `[0-9]+`) there may continue in the next
+/// chunk, which finds it whole from the carry-forward, so it's reported once, with its full length.
+/// A match longer than the carry-forward can't be found whole across a chunk boundary: the next
+/// chunk reports the part of it that's in the carry-forward and after.
+pub fn defer_regex_matches_at_chunk_end(
+    chunk_matches: &mut Vec<ChunkMatch>,
+    needles: &[Needle],
+    haystack_len: usize,
+) {
+    chunk_matches.retain(|chunk_match| {
+        needles[chunk_match.needle_idx].regex.is_none()
+            || chunk_match.range_in_chunk().end < haystack_len
+    });
+}
+
+/// The needles' own match filters, shared by `do_search` and the library `Search`, so both report
+/// the same matches: drops the matches at offsets that don't meet their needle's `alignment`, and
+/// the ones inside a match of one of their `suppress_if_covered_by` needles.
+/// Alignment is to the global offset, so it's the same whichever chunk a match is found in.
+pub fn filter_needle_matches(
+    chunk_matches: &mut Vec<ChunkMatch>,
+    needles: &[Needle],
+    chunk_start_global_offset: u64,
+) {
+    chunk_matches.retain(|chunk_match| {
+        needles[chunk_match.needle_idx]
+            .is_aligned(chunk_start_global_offset + chunk_match.pos_in_chunk as u64)
+    });
+
+    let is_covered: Vec<bool> = chunk_matches
+        .iter()
+        .map(|chunk_match| is_covered_match(chunk_match, chunk_matches, needles))
+        .collect();
+    let mut is_covered = is_covered.into_iter();
+    chunk_matches.retain(|_| !is_covered.next().unwrap());
+}
+
+/// Once a needle reaches its `max_matches` (counting its kept matches in `needle_match_counts`),
+/// drops its later matches, and counts them in `capped_match_counts`. Shared by `do_search` and
+/// the library `Search`, after `filter_needle_matches`. It's separate from that, so `do_search`'s
+/// own filters (like `--exclude-carved-files`) run in between, and only recorded matches count.
+/// Returns the needle index and global offset of the first capped match of each needle that
+/// reaches its `max_matches` here, to report.
+pub fn cap_needle_matches(
+    chunk_matches: &mut Vec<ChunkMatch>,
+    needles: &[Needle],
+    chunk_start_global_offset: u64,
+    needle_match_counts: &mut HashMap<String, u64>,
+    capped_match_counts: &mut BTreeMap<String, u64>,
+) -> Vec<(usize, u64)> {
+    let mut newly_capped: Vec<(usize, u64)> = Vec::new();
+    chunk_matches.retain(|chunk_match| {
+        let needle = &needles[chunk_match.needle_idx];
+        let max_matches = match needle.max_matches {
+            Some(max_matches) => max_matches,
+            None => return true,
+        };
+        let match_count = needle_match_counts.entry(needle.name.clone()).or_insert(0);
+        if *match_count < max_matches {
+            *match_count += 1;
+            return true;
+        }
+        let capped_match_count = capped_match_counts.entry(needle.name.clone()).or_insert(0);
+        *capped_match_count += 1;
+        if *capped_match_count == 1 {
+            newly_capped.push((
+                chunk_match.needle_idx,
+                chunk_start_global_offset + chunk_match.pos_in_chunk as u64,
+            ));
+        }
+        false
+    });
+    newly_capped
+}
+
+/// True if `chunk_match` is inside a longer match (in `chunk_matches`) of one of its needle's
+/// `suppress_if_covered_by` needles.
+fn is_covered_match(
+    chunk_match: &ChunkMatch,
+    chunk_matches: &[ChunkMatch],
+    needles: &[Needle],
+) -> bool {
+    let suppress_if_covered_by = &needles[chunk_match.needle_idx].suppress_if_covered_by;
+    if suppress_if_covered_by.is_empty() {
+        return false;
+    }
+    let match_range = chunk_match.range_in_chunk();
+    chunk_matches.iter().any(|covering_match| {
+        let covering_range = covering_match.range_in_chunk();
+        covering_match.match_len > chunk_match.match_len
+            && covering_range.start <= match_range.start
+            && covering_range.end >= match_range.end
+            && suppress_if_covered_by.contains(&needles[covering_match.needle_idx].name)
+    })
+}
+
+/// Finds every match of each needle within the haystack, in needle order, then by position.
+/// Overlapping matches (like "aa" twice in "aaa") are each reported.
+///
+/// Matches that lie entirely within the first `carried_len` bytes (the carry-forward from the previous
+/// chunk) are skipped, because the previous chunk already found them. A match that starts in the
+/// carry-forward and ends in the fresh data is found here (and only here), because it didn't fit in
+/// the previous chunk. This way, each match in the stream is reported exactly once.
+///
+/// When `sub_range_count > 1`, the haystack is split into that many sub-ranges, which are searched
+/// on the rayon thread pool. Each sub-range is extended by `longest needle - 1` bytes, so needles
+/// crossing a sub-range boundary are found, but a match is only kept by the sub-range it starts in,
+/// so each match is found exactly once.
+pub fn find_matches_in_chunk(
+    haystack: &[u8],
+    needles: &[Needle],
+    needle_matcher: &NeedleMatcher,
+    sub_range_count: usize,
+    carried_len: usize,
+) -> Vec<ChunkMatch> {
+    let sub_range_len = haystack.len().div_ceil(sub_range_count.max(1)).max(1);
+
+    let sub_range_matches: Vec<Vec<ChunkMatch>> = match sub_range_count > 1 {
+        true => (0..haystack.len())
+            .step_by(sub_range_len)
+            .collect::<Vec<usize>>()
+            .into_par_iter()
+            .map(|sub_range_start| {
+                needle_matcher.find_matches_in_sub_range(
+                    haystack,
+                    needles,
+                    sub_range_start,
+                    (sub_range_start + sub_range_len).min(haystack.len()),
+                    carried_len,
+                )
+            })
+            .collect(),
+        false => vec![needle_matcher.find_matches_in_sub_range(
+            haystack,
+            needles,
+            0,
+            haystack.len(),
+            carried_len,
+        )],
+    };
+
+    // merge: each match starts in exactly one sub-range, so there are no duplicates
+    let mut chunk_matches: Vec<ChunkMatch> = sub_range_matches.into_iter().flatten().collect();
+    chunk_matches.sort_by_key(|chunk_match| (chunk_match.needle_idx, chunk_match.pos_in_chunk));
+    chunk_matches
+}
+
+/// Like `find_matches_in_chunk`, but text needles (`Needle::is_text`) only match inside runs of at
+/// least `min_run_len` text bytes (`--text-regions-only`), to skip coincidences in binary data.
+/// Other needles match anywhere.
+fn find_matches_in_text_regions(
+    haystack: &[u8],
+    needles: &[Needle],
+    needle_matcher: &NeedleMatcher,
+    sub_range_count: usize,
+    carried_len: usize,
+    min_run_len: usize,
+) -> Vec<ChunkMatch> {
+    let mut chunk_matches: Vec<ChunkMatch> = Vec::new();
+    if needles.iter().any(|needle| needle.is_text()) {
+        let masked_haystack = mask_non_text_regions(haystack, min_run_len);
+        chunk_matches.extend(
+            find_matches_in_chunk(
+                &masked_haystack,
+                needles,
+                needle_matcher,
+                sub_range_count,
+                carried_len,
+            )
+            .into_iter()
+            .filter(|chunk_match| needles[chunk_match.needle_idx].is_text()),
+        );
+    }
+    if needles.iter().any(|needle| !needle.is_text()) {
+        chunk_matches.extend(
+            find_matches_in_chunk(
+                haystack,
+                needles,
+                needle_matcher,
+                sub_range_count,
+                carried_len,
+            )
+            .into_iter()
+            .filter(|chunk_match| !needles[chunk_match.needle_idx].is_text()),
+        );
+    }
+    chunk_matches.sort_by_key(|chunk_match| chunk_match.needle_idx);
+    chunk_matches
+}
+
+/// The region of the haystack chunk to dump for a match.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DumpWindow {
+    pub range_in_chunk: Range<usize>,
+    /// True if this window is shared with nearby matches (`--merge-dump-distance`).
+    pub is_merged: bool,
+}
+
+/// Plans the dump window for each match (None = not dumped), from each needle's context byte counts
+/// (and `capture_block_size`, aligned to global offsets, as the window starts at
+/// `window_start_global_offset`).
+///
+/// With `merge_distance_bytes`, windows that overlap or are within that distance of each other are
+/// merged into one shared window, so dense regions are dumped once, instead of as several
+/// nearly-identical files.
+pub fn plan_dump_windows(
+    chunk_matches: &[ChunkMatch],
+    needles: &[Needle],
+    haystack_chunk_len: usize,
+    window_start_global_offset: u64,
+    merge_distance_bytes: Option<usize>,
+) -> Vec<Option<DumpWindow>> {
+    let mut dump_windows: Vec<Option<DumpWindow>> = chunk_matches
+        .iter()
+        .map(|chunk_match| {
+            let needle = &needles[chunk_match.needle_idx];
+            let dump_global_range = needle.dump_global_range(
+                window_start_global_offset + chunk_match.pos_in_chunk as u64,
+                chunk_match.match_len,
+            );
+            let write_start_pos_in_chunk = dump_global_range
+                .start
+                .saturating_sub(window_start_global_offset)
+                as usize;
+            let write_end_pos_in_chunk = ((dump_global_range.end - window_start_global_offset)
+                as usize)
+                .min(haystack_chunk_len);
+            match needle.write_to_file {
+                true => Some(DumpWindow {
+                    range_in_chunk: write_start_pos_in_chunk..write_end_pos_in_chunk,
+                    is_merged: false,
+                }),
+                false => None,
+            }
+        })
+        .collect();
+
+    let merge_distance_bytes = match merge_distance_bytes {
+        Some(merge_distance_bytes) => merge_distance_bytes,
+        None => return dump_windows,
+    };
+
+    // sweep the windows in order of start, grouping each with the previous one if it's close enough
+    let mut windows_by_start: Vec<(usize, Range<usize>)> = dump_windows
+        .iter()
+        .enumerate()
+        .filter_map(|(match_idx, dump_window)| {
+            dump_window
+                .as_ref()
+                .map(|dump_window| (match_idx, dump_window.range_in_chunk.clone()))
+        })
+        .collect();
+    windows_by_start.sort_by_key(|(_, range)| range.start);
+
+    let mut groups: Vec<(Range<usize>, Vec<usize>)> = Vec::new();
+    for (match_idx, range) in windows_by_start {
+        match groups.last_mut() {
+            Some((group_range, group_match_idxs))
+                if range.start <= group_range.end + merge_distance_bytes =>
+            {
+                group_range.end = group_range.end.max(range.end);
+                group_match_idxs.push(match_idx);
+            }
+            _ => groups.push((range, vec![match_idx])),
+        }
+    }
+
+    for (group_range, group_match_idxs) in groups {
+        if group_match_idxs.len() > 1 {
+            for match_idx in group_match_idxs {
+                dump_windows[match_idx] = Some(DumpWindow {
+                    range_in_chunk: group_range.clone(),
+                    is_merged: true,
+                });
+            }
+        }
+    }
+    dump_windows
+}
+
+/// A dump whose window ends past the chunk it was found in (`--scan-window-context`). It's finished
+/// with the bytes of the following chunks, and submitted once complete (or at the end of the stream).
+pub struct PendingDump {
+    dump_file_path: PathBuf,
+    bytes: Vec<u8>,
+    start_global_offset: u64,
+    end_global_offset: u64,
+    /// Also write the dump's printable strings (the needle's `strings`).
+    write_strings: bool,
+}
+
+impl PendingDump {
+    fn next_global_offset(&self) -> u64 {
+        self.start_global_offset + self.bytes.len() as u64
+    }
+}
+
+/// Throttles per-match logging for needles that match very often. Once a needle matches more than
+/// `threshold` times in a chunk, the rest of its matches in that chunk are only logged as a summary.
+/// Matches are still recorded to the JSONL either way.
+pub struct MatchLogThrottle {
+    threshold: u64,
+    match_counts_this_chunk: HashMap<String, u64>,
+}
+
+impl MatchLogThrottle {
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold,
+            match_counts_this_chunk: HashMap::new(),
+        }
+    }
+
+    /// Records a match, and returns whether it should be logged individually.
+    pub fn record_match(&mut self, needle_name: &str) -> bool {
+        let match_count = self
+            .match_counts_this_chunk
+            .entry(needle_name.to_string())
+            .or_insert(0);
+        *match_count += 1;
+        *match_count <= self.threshold
+    }
+
+    /// Call at the end of each chunk. Returns `(needle name, match count)` for each needle that
+    /// was throttled in this chunk, and resets the counts.
+    pub fn finish_chunk(&mut self) -> Vec<(String, u64)> {
+        let mut throttled_needles: Vec<(String, u64)> = self
+            .match_counts_this_chunk
+            .drain()
+            .filter(|(_, match_count)| *match_count > self.threshold)
+            .collect();
+        throttled_needles.sort();
+        throttled_needles
+    }
+}
+
+/// A memory-mapped uncompressed input file (`--mmap`), searched in place.
+pub struct MappedHaystack {
+    pub mmap: Mmap,
+    /// The file offset of global offset 0 (the partition start, with partition-relative offsets).
+    pub global_start_file_offset: u64,
+    /// The length of each searched window, including the overlap with the previous one.
+    pub window_len: usize,
+}
+
+impl MappedHaystack {
+    /// The mapped bytes at `global_range`.
+    fn global_slice(&self, global_range: Range<u64>) -> &[u8] {
+        &self.mmap[((global_range.start + self.global_start_file_offset) as usize)
+            ..((global_range.end + self.global_start_file_offset) as usize)]
+    }
+
+    /// The global offset of the end of the file.
+    fn end_global_offset(&self) -> u64 {
+        (self.mmap.len() as u64).saturating_sub(self.global_start_file_offset)
+    }
+}
+
+/// Memory for the table of a `recorded_match_keys` set with `capacity`, not counting the needle
+/// names: a key and a control byte per bucket. The table has a power-of-two number of buckets, at
+/// most 7/8 full (or all but one, when small), and it doubles when full, so each recorded match
+/// takes `RECORDED_MATCH_KEY_BYTES_RANGE` bytes.
+pub fn recorded_match_keys_table_bytes(capacity: usize) -> usize {
+    let bucket_count = match capacity {
+        0 => 0,
+        1..=7 => capacity + 1,
+        _ => capacity / 7 * 8,
+    };
+    bucket_count * (std::mem::size_of::<(String, u64)>() + 1)
 }
 
+/// Bytes per recorded match in a `recorded_match_keys` table (not counting its needle name): from
+/// a 7/8 full table to one that just doubled.
+pub const RECORDED_MATCH_KEY_BYTES_RANGE: (usize, usize) = (
+    (std::mem::size_of::<(String, u64)>() + 1) * 8 / 7,
+    (std::mem::size_of::<(String, u64)>() + 1) * 16 / 7,
+);
+
 pub struct ProcessDataState {
     // variables to keep track of progress, etc.
     pub haystack_chunk_buffer: Vec<u8>,
+    /// Number of valid bytes at the start of `haystack_chunk_buffer` (the rest is stale).
+    pub haystack_chunk_len: usize,
+    /// Number of bytes at the start of the chunk that were carried forward from the previous chunk.
+    pub haystack_carried_len: usize,
+    /// Global offset (in the uncompressed stream) of the first byte of the chunk, including the carry-forward.
+    pub haystack_chunk_start_global_offset: u64,
+    /// True if the chunk runs to the end of the input stream (or of the searched range), so a
+    /// regex match at its end can't continue in a next chunk (see
+    /// `defer_regex_matches_at_chunk_end`).
+    pub haystack_is_last_chunk: bool,
+    /// Total bytes read from the input stream. Carried-forward bytes are only counted once.
+    /// When resuming partway through, this starts at the resume offset, so it's also the global
+    /// offset of the next byte to read.
     pub total_haystack_bytes_read: u64,
     pub last_progress_log_time: Instant,
+    pub last_checkpoint_time: Instant,
     pub start_time: Instant,
 
     pub needle_vals_found: Vec<NeedleValFound>,
     pub chunk_count: u64,
     pub partial_chunk_read_count: u32,
+
+    pub match_log_throttle: MatchLogThrottle,
+
+    /// Matches dropped because they were inside a carved file (`--exclude-carved-files`).
+    pub carved_file_excluded_match_count: u64,
+
+    /// Needle name and global offset of each recorded match, with `dedup`.
+    pub recorded_match_keys: HashSet<(String, u64)>,
+    /// Matches skipped because they were already recorded (`--dedup`).
+    pub dedup_skipped_match_count: u64,
+
+    /// Recorded matches of each needle with a `max_matches` cap.
+    pub needle_match_counts: HashMap<String, u64>,
+    /// Matches past each capped needle's `max_matches`, which were only counted.
+    pub capped_match_counts: BTreeMap<String, u64>,
+
+    /// Set after a write error (e.g., disk full). The search continues, recording JSONL only.
+    pub dump_writing_disabled: bool,
+
+    /// Set if the search stopped early, at `stop_requested` (Ctrl-C), so only part of the input
+    /// was searched.
+    pub interrupted: bool,
+
+    /// Only set for `--single-pass-verify` runs.
+    pub coverage_verifier: Option<CoverageVerifier>,
+
+    /// Only set with `--hotspot-window`. Holds the ring buffer of recent match offsets.
+    pub hotspot_detector: Option<HotspotDetector>,
+
+    /// The stream bytes directly before the chunk (not including the carry-forward), for the
+    /// before-match context of dumps (`--scan-window-context`). Empty otherwise.
+    pub lookbehind_bytes: Vec<u8>,
+    /// Dumps waiting for the after-match context from the following chunks (`--scan-window-context`).
+    pub pending_dumps: Vec<PendingDump>,
+
+    /// Only set with `--mmap`. The chunks are then windows of the mapped file, instead of copies
+    /// in `haystack_chunk_buffer`.
+    pub mapped_haystack: Option<MappedHaystack>,
+
+    /// Warnings and errors during the run, for the error summary file.
+    pub error_collector: ErrorCollector,
+
+    pub dump_writer: DumpWriter,
+
+    /// Only set with `--sqlite`. Gets each match record, along with the JSONL files.
+    pub sqlite_writer: Option<SqliteWriter>,
+
+    /// The global JSONL part being written (from 1), and its record count (`--jsonl-max-size`).
+    pub jsonl_part_number: u32,
+    pub jsonl_part_record_count: u64,
 }
 
 impl ProcessDataState {
-    pub fn new(haystack_chunk_buffer_size_bytes: usize) -> Self {
+    pub fn new(
+        haystack_chunk_buffer_size_bytes: usize,
+        match_log_throttle_threshold: u64,
+        dump_write_queue_len: usize,
+    ) -> Self {
         Self {
             haystack_chunk_buffer: vec![0; haystack_chunk_buffer_size_bytes],
+            haystack_chunk_len: 0,
+            haystack_carried_len: 0,
+            haystack_chunk_start_global_offset: 0,
+            haystack_is_last_chunk: false,
             total_haystack_bytes_read: 0,
             last_progress_log_time: Instant::now(),
+            last_checkpoint_time: Instant::now(),
             start_time: Instant::now(),
             needle_vals_found: Vec::new(),
             chunk_count: 0,
             partial_chunk_read_count: 0,
+            match_log_throttle: MatchLogThrottle::new(match_log_throttle_threshold),
+            carved_file_excluded_match_count: 0,
+            recorded_match_keys: HashSet::new(),
+            dedup_skipped_match_count: 0,
+            needle_match_counts: HashMap::new(),
+            capped_match_counts: BTreeMap::new(),
+            dump_writing_disabled: false,
+            interrupted: false,
+            coverage_verifier: None,
+            hotspot_detector: None,
+            lookbehind_bytes: Vec::new(),
+            pending_dumps: Vec::new(),
+            mapped_haystack: None,
+            error_collector: ErrorCollector::new(),
+            dump_writer: DumpWriter::new(dump_write_queue_len),
+            sqlite_writer: None,
+            jsonl_part_number: 1,
+            jsonl_part_record_count: 0,
         }
     }
 
-    pub fn sec_since_last_progress_log(&self) -> f32 {
-        self.last_progress_log_time.elapsed().as_secs_f32()
+    /// Counts the matches recorded before a resumed search towards their needles' `max_matches`,
+    /// so the cap holds across the resume.
+    pub fn count_earlier_matches(
+        &mut self,
+        earlier_matches: &[NeedleValFound],
+        needles: &[Needle],
+    ) {
+        let capped_needle_names: HashSet<&str> = needles
+            .iter()
+            .filter(|needle| needle.max_matches.is_some())
+            .map(|needle| needle.name.as_str())
+            .collect();
+        for earlier_match in earlier_matches {
+            if capped_needle_names.contains(earlier_match.name.as_str()) {
+                *self
+                    .needle_match_counts
+                    .entry(earlier_match.name.clone())
+                    .or_insert(0) += 1;
+            }
+        }
     }
-}
 
-pub fn do_search(process_data_state: &mut ProcessDataState, search_assignment: &SearchAssignment) {
-    let haystack_chunk_start_global_offset = process_data_state.total_haystack_bytes_read;
-    let _haystack_chunk_end_global_offset = process_data_state.total_haystack_bytes_read
-        + (process_data_state.haystack_chunk_buffer.len() as u64);
+    /// Continues the global JSONL file's parts when resuming a search, so the new match records go
+    /// after the earlier ones: in its last existing part, or in the next one if that's full.
+    pub fn continue_jsonl_parts(&mut self, search_assignment: &SearchAssignment) -> io::Result<()> {
+        let Some(jsonl_output_log_file_path) = &search_assignment.jsonl_output_log_file_path else {
+            return Ok(());
+        };
+        let part_file_paths = jsonl_part_file_paths(jsonl_output_log_file_path);
+        let last_part_file_path = &part_file_paths[part_file_paths.len() - 1];
+        self.jsonl_part_number = part_file_paths.len() as u32;
+        self.jsonl_part_record_count = match last_part_file_path.exists() {
+            true => io::BufReader::new(fs::File::open(last_part_file_path)?)
+                .lines()
+                .count() as u64,
+            false => 0,
+        };
+        if search_assignment.is_jsonl_part_full(last_part_file_path, self.jsonl_part_record_count) {
+            self.jsonl_part_number += 1;
+            self.jsonl_part_record_count = 0;
+        }
+        Ok(())
+    }
 
-    for needle in search_assignment.needles.as_slice() {
-        let needle_val_sequence = &needle.val;
-        if let Some(pos_in_chunk) = process_data_state
-            .haystack_chunk_buffer
-            .windows(needle_val_sequence.len())
-            .position(|window| window == needle_val_sequence)
-        {
-            // Found a match!
-            // Window = Match now
-            let match_start_global_offset: u64 =
-                haystack_chunk_start_global_offset + pos_in_chunk as u64;
-            let needle_val_as_string = needle.val_as_string();
-
-            // just a debug, not the main log
-            debug!(
-                "{} Found '{}' {} at position {} in the chunk",
-                needle.happiness_level_as_string(),
-                needle.name,
-                needle_val_as_string,
-                pos_in_chunk
+    /// Starts the search at `global_offset` instead of 0, for an input stream that's already
+    /// positioned there (`--resume-from-offset`). Call before searching, so offsets are absolute.
+    pub fn resume_from_offset(&mut self, global_offset: u64) {
+        self.total_haystack_bytes_read = global_offset;
+        if let Some(coverage_verifier) = self.coverage_verifier.as_mut() {
+            coverage_verifier.start_at(global_offset);
+        }
+    }
+
+    /// Continues a search from a checkpoint (`--resume`), which recorded every match ending at or
+    /// before `searched_end_global_offset`. The input stream must be positioned `carried_len`
+    /// bytes before that (the carry-forward length, or less at the very start of the stream).
+    /// Those bytes are read back in as the carry-forward, so matches across the checkpoint are
+    /// found, and the ones before it aren't recorded again.
+    pub fn resume_from_checkpoint<R: Read>(
+        &mut self,
+        input_reader: &mut R,
+        searched_end_global_offset: u64,
+        carried_len: usize,
+    ) -> io::Result<()> {
+        let bytes_read = read_full(input_reader, &mut self.haystack_chunk_buffer[..carried_len])?;
+        if bytes_read < carried_len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The input stream ended before the checkpoint",
+            ));
+        }
+        self.haystack_chunk_len = carried_len;
+        self.total_haystack_bytes_read = searched_end_global_offset;
+        Ok(())
+    }
+
+    /// Approximate memory used by `recorded_match_keys`: the set's table (a key and a control byte
+    /// per bucket, full or not), and the heap allocation of each key's needle name.
+    pub fn recorded_match_keys_memory_bytes(&self) -> usize {
+        recorded_match_keys_table_bytes(self.recorded_match_keys.capacity())
+            + self
+                .recorded_match_keys
+                .iter()
+                .map(|(needle_name, _)| needle_name.capacity())
+                .sum::<usize>()
+    }
+
+    /// Writes a checkpoint of how far the search got. Unless `is_complete`, call only between
+    /// chunks, with no pending dumps, so every match up to `total_haystack_bytes_read` is recorded.
+    /// Dumps still queued in the dump writer may be lost if the run is interrupted right after.
+    pub fn write_checkpoint(&mut self, search_assignment: &SearchAssignment, is_complete: bool) {
+        let checkpoint = Checkpoint {
+            drive_image_searcher_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash: search_assignment.config_hash.clone(),
+            searched_end_global_offset: self.total_haystack_bytes_read,
+            is_complete,
+        };
+        if let Err(e) = checkpoint.write_to_json_file(&search_assignment.checkpoint_file_path()) {
+            error!("Could not write checkpoint: {}", e);
+            self.error_collector.record(
+                "checkpoint_write_failed",
+                Some(self.total_haystack_bytes_read),
+                &e.to_string(),
+            );
+        }
+        self.last_checkpoint_time = Instant::now();
+    }
+
+    /// Logs a hotspot, and appends it to the hotspots JSONL file.
+    fn record_hotspot(&mut self, hotspot: &Hotspot, search_assignment: &SearchAssignment) {
+        info!(
+            "Hotspot: {} matches of {} needle(s) ({}) between 0x{} and 0x{}",
+            hotspot.match_count.to_formatted_string(&Locale::en),
+            hotspot.needle_names.len(),
+            hotspot.needle_names.join(", "),
+            display_hex_offset(hotspot.first_match_global_offset, 20),
+            display_hex_offset(hotspot.last_match_global_offset, 20),
+        );
+        if let Err(e) = hotspot.append_to_jsonl_file(
+            &search_assignment.hotspots_jsonl_file_path(),
+            search_assignment.jsonl_line_ending,
+        ) {
+            error!("Could not write hotspot to JSONL file: {}", e);
+            self.error_collector.record(
+                "jsonl_write_failed",
+                Some(hotspot.first_match_global_offset),
+                &e.to_string(),
             );
+        }
+    }
 
-            // Create the NeedleValFound object
-            let needle_val_found = NeedleValFound::from_needle_val(
-                needle,
-                match_start_global_offset + pos_in_chunk as u64,
-                &search_assignment.input_file_path,
+    /// The bytes of `window_range` (relative to the start of the lookbehind bytes) that are
+    /// available so far: the lookbehind bytes, then the chunk.
+    fn window_bytes(&self, window_range: Range<usize>) -> Vec<u8> {
+        let lookbehind_len = self.lookbehind_bytes.len();
+        let available_end = lookbehind_len + self.haystack_chunk_len;
+
+        let mut bytes = Vec::with_capacity(window_range.len());
+        if window_range.start < lookbehind_len {
+            bytes.extend_from_slice(
+                &self.lookbehind_bytes[window_range.start..window_range.end.min(lookbehind_len)],
             );
+        }
+        bytes.extend_from_slice(
+            &self.haystack_chunk()[(window_range.start.max(lookbehind_len) - lookbehind_len)
+                ..(window_range.end.min(available_end) - lookbehind_len)],
+        );
+        bytes
+    }
 
-            // Write the haystack chunk to disk
-            let needle_dir_path = search_assignment.output_dir_path.clone().join(format!(
-                "{}_{}",
-                needle.happiness_level,
-                needle.name.clone()
-            ));
-            if !needle_dir_path.exists() {
-                fs::create_dir(&needle_dir_path)
-                    .expect("Could not create per-needle output directory");
-                info!(
-                    "{}. First time for '{}' needle. Created new needle directory: {}",
-                    needle.happiness_level_as_string(),
-                    needle.name,
-                    needle_dir_path.display()
+    /// Queues a complete dump to be written, and its `.strings.txt` file if `write_strings`.
+    fn submit_complete_dump(
+        &mut self,
+        dump_file_path: PathBuf,
+        bytes: Vec<u8>,
+        start_global_offset: u64,
+        write_strings: bool,
+    ) {
+        if write_strings {
+            self.dump_writer.submit(
+                strings_file_path(&dump_file_path),
+                format_printable_strings(&bytes, start_global_offset).into_bytes(),
+            );
+        }
+        self.dump_writer.submit(dump_file_path, bytes);
+    }
+
+    /// Submits the dump of `dump_range`, which is relative to the start of `lookbehind_bytes` (which
+    /// directly precede the chunk). If the range ends past the chunk, the dump is finished by
+    /// `extend_pending_dumps` as the following chunks are read.
+    fn submit_dump(
+        &mut self,
+        dump_file_path: PathBuf,
+        dump_range: Range<usize>,
+        write_strings: bool,
+    ) {
+        let lookbehind_len = self.lookbehind_bytes.len();
+        let available_end = lookbehind_len + self.haystack_chunk_len;
+        let bytes = self.window_bytes(dump_range.clone());
+        let start_global_offset = self.haystack_chunk_start_global_offset - lookbehind_len as u64
+            + dump_range.start as u64;
+
+        match dump_range.end > available_end {
+            true => self.pending_dumps.push(PendingDump {
+                dump_file_path,
+                bytes,
+                start_global_offset,
+                end_global_offset: start_global_offset + dump_range.len() as u64,
+                write_strings,
+            }),
+            false => {
+                self.submit_complete_dump(dump_file_path, bytes, start_global_offset, write_strings)
+            }
+        }
+    }
+
+    /// Adds the chunk's bytes to the pending dumps, and submits the ones that are now complete.
+    pub fn extend_pending_dumps(&mut self) {
+        if self.pending_dumps.is_empty() {
+            return;
+        }
+        let chunk_start_global_offset = self.haystack_chunk_start_global_offset;
+        let chunk_end_global_offset = chunk_start_global_offset + self.haystack_chunk_len as u64;
+
+        let mut pending_dumps = std::mem::take(&mut self.pending_dumps);
+        for pending_dump in pending_dumps.iter_mut() {
+            // the pending bytes always continue within this chunk, because of the carry-forward
+            let next_global_offset = pending_dump.next_global_offset();
+            let end_global_offset = pending_dump.end_global_offset.min(chunk_end_global_offset);
+            if next_global_offset < end_global_offset {
+                pending_dump.bytes.extend_from_slice(
+                    &self.haystack_chunk()[((next_global_offset - chunk_start_global_offset)
+                        as usize)
+                        ..((end_global_offset - chunk_start_global_offset) as usize)],
+                );
+            }
+        }
+
+        let (complete_dumps, pending_dumps): (Vec<PendingDump>, Vec<PendingDump>) =
+            pending_dumps.into_iter().partition(|pending_dump| {
+                pending_dump.next_global_offset() >= pending_dump.end_global_offset
+            });
+        self.pending_dumps = pending_dumps;
+        for complete_dump in complete_dumps {
+            if !self.dump_writing_disabled {
+                self.submit_complete_dump(
+                    complete_dump.dump_file_path,
+                    complete_dump.bytes,
+                    complete_dump.start_global_offset,
+                    complete_dump.write_strings,
                 );
             }
+        }
+    }
 
-            if needle.write_to_file {
-                let write_start_pos_in_chunk =
-                    (pos_in_chunk as i64 - needle.byte_count_before_match as i64).max(0) as usize;
-                let write_end_pos_in_chunk = (pos_in_chunk
-                    + needle_val_sequence.len()
-                    + needle.byte_count_after_match as usize)
-                    .min(process_data_state.haystack_chunk_buffer.len());
+    /// Keeps the last `lookbehind_capacity` bytes of the stream before the next chunk, which starts
+    /// with the last `next_carried_len` bytes of this chunk. Call before carrying forward.
+    fn update_lookbehind_bytes(&mut self, lookbehind_capacity: usize, next_carried_len: usize) {
+        if lookbehind_capacity == 0 {
+            return;
+        }
+        let mut lookbehind_bytes = std::mem::take(&mut self.lookbehind_bytes);
+        let chunk_bytes_before_next_chunk =
+            &self.haystack_chunk()[..(self.haystack_chunk_len - next_carried_len)];
+        if chunk_bytes_before_next_chunk.len() >= lookbehind_capacity {
+            lookbehind_bytes.clear();
+            lookbehind_bytes.extend_from_slice(
+                &chunk_bytes_before_next_chunk
+                    [(chunk_bytes_before_next_chunk.len() - lookbehind_capacity)..],
+            );
+        } else {
+            lookbehind_bytes.extend_from_slice(chunk_bytes_before_next_chunk);
+            let excess_len = lookbehind_bytes.len().saturating_sub(lookbehind_capacity);
+            lookbehind_bytes.drain(..excess_len);
+        }
+        self.lookbehind_bytes = lookbehind_bytes;
+    }
 
-                // `chunk_file_name` format: <this match's global offset>_<file_start_offset>_<file_end_offset>
-                let chunk_file_name = format!(
-                    "found_g_0x{}_startat_0x{}.bin",
-                    display_hex_offset(match_start_global_offset, 20),
-                    // offset_within_file:
-                    display_hex_offset(
-                        pos_in_chunk - write_start_pos_in_chunk,
-                        1 // minimum width is fine
-                    ),
+    /// Finishes the pending dumps by reading their remaining bytes (up to `end_global_offset`)
+    /// directly from `input_file`, for a search that stops before the end of what is dumped.
+    fn read_pending_dumps_from_file(
+        &mut self,
+        input_file: &mut fs::File,
+        end_global_offset: u64,
+    ) -> io::Result<()> {
+        for pending_dump in self.pending_dumps.iter_mut() {
+            let next_global_offset = pending_dump.next_global_offset();
+            let read_end_global_offset = pending_dump.end_global_offset.min(end_global_offset);
+            if next_global_offset >= read_end_global_offset {
+                continue;
+            }
+            input_file.seek(SeekFrom::Start(next_global_offset))?;
+            input_file
+                .by_ref()
+                .take(read_end_global_offset - next_global_offset)
+                .read_to_end(&mut pending_dump.bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for all queued haystack chunk dumps to be written to disk.
+    /// Pending dumps are written as they are (cut short by the end of the stream).
+    pub fn finish_dump_writes(&mut self) {
+        for pending_dump in std::mem::take(&mut self.pending_dumps) {
+            if !self.dump_writing_disabled {
+                self.submit_complete_dump(
+                    pending_dump.dump_file_path,
+                    pending_dump.bytes,
+                    pending_dump.start_global_offset,
+                    pending_dump.write_strings,
                 );
+            }
+        }
+        self.dump_writer.finish();
+        if let Some(e) = self.dump_writer.take_error() {
+            self.disable_dump_writing(&e);
+        }
+    }
+
+    /// Commits the last batch of match records to the SQLite database (`--sqlite`), if any.
+    pub fn finish_sqlite_writes(&mut self) {
+        if let Some(Err(e)) = self.sqlite_writer.as_mut().map(SqliteWriter::finish) {
+            error!("Could not commit match records to SQLite database: {}", e);
+            self.error_collector
+                .record("sqlite_write_failed", None, &e.to_string());
+        }
+    }
+
+    /// Stops writing haystack chunk dumps for the rest of the run, after a write error.
+    pub fn disable_dump_writing(&mut self, e: &io::Error) {
+        if self.dump_writing_disabled {
+            return;
+        }
+        self.dump_writing_disabled = true;
+        self.error_collector.record(
+            "dump_writing_disabled",
+            Some(self.haystack_chunk_start_global_offset),
+            &e.to_string(),
+        );
+        match e.kind() {
+            io::ErrorKind::StorageFull => warn!(
+                "Output disk is full ({}). No more haystack chunks will be written to disk. Still searching, and recording matches to JSONL where possible.",
+                e
+            ),
+            _ => warn!(
+                "Could not write haystack chunk to disk ({}). No more haystack chunks will be written to disk. Still searching, and recording matches to JSONL where possible.",
+                e
+            ),
+        }
+    }
+
+    /// The valid part of the haystack chunk buffer (or window of the mapped file, with `--mmap`).
+    pub fn haystack_chunk(&self) -> &[u8] {
+        match &self.mapped_haystack {
+            Some(mapped_haystack) => mapped_haystack.global_slice(
+                self.haystack_chunk_start_global_offset
+                    ..(self.haystack_chunk_start_global_offset + self.haystack_chunk_len as u64),
+            ),
+            None => &self.haystack_chunk_buffer[..self.haystack_chunk_len],
+        }
+    }
+
+    /// The most bytes a chunk can have. Only the last chunk of the stream has fewer.
+    pub fn haystack_chunk_capacity(&self) -> usize {
+        match &self.mapped_haystack {
+            Some(mapped_haystack) => mapped_haystack.window_len,
+            None => self.haystack_chunk_buffer.len(),
+        }
+    }
+
+    pub fn sec_since_last_progress_log(&self) -> f32 {
+        self.last_progress_log_time.elapsed().as_secs_f32()
+    }
+}
 
-                let chunk_output_file_path = PathBuf::from(&needle_dir_path).join(chunk_file_name);
-                let mut output_file = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(chunk_output_file_path.clone())
-                    .expect("Could not open chunk output file");
-
-                match output_file.write_all(
-                    &process_data_state.haystack_chunk_buffer
-                        [write_start_pos_in_chunk..write_end_pos_in_chunk],
+/// Writes a match record to the JSONL files: the global one (if any), and the needle's own.
+/// These are small, so keep trying even if dump writing has failed.
+fn write_jsonl_record(
+    process_data_state: &mut ProcessDataState,
+    search_assignment: &SearchAssignment,
+    needle_val_found: &NeedleValFound,
+    needle: &Needle,
+) {
+    if let Some(jsonl_output_log_file_path) = &search_assignment.jsonl_output_log_file_path {
+        let jsonl_part_file_path = jsonl_part_file_path(
+            jsonl_output_log_file_path,
+            process_data_state.jsonl_part_number,
+        );
+        match needle_val_found
+            .append_to_jsonl_file(&jsonl_part_file_path, search_assignment.jsonl_line_ending)
+        {
+            Ok(()) => {
+                process_data_state.jsonl_part_record_count += 1;
+                if search_assignment.is_jsonl_part_full(
+                    &jsonl_part_file_path,
+                    process_data_state.jsonl_part_record_count,
                 ) {
-                    Ok(_) => {}
-                    Err(e) => error!("Could not write haystack chunk to disk: {}", e),
+                    process_data_state.jsonl_part_number += 1;
+                    process_data_state.jsonl_part_record_count = 0;
+                    info!(
+                        "Overall JSONL file part is full. Continuing in part {}.",
+                        process_data_state.jsonl_part_number
+                    );
                 }
-
-                info!(
-                    "Offset 0x{}. Needle '{}'. {}. Wrote to disk ({} bytes).",
-                    display_hex_offset(match_start_global_offset, 20),
-                    needle.name,
-                    needle.happiness_level_as_string(),
-                    (write_end_pos_in_chunk - write_start_pos_in_chunk)
-                        .to_formatted_string(&Locale::en),
-                );
-            } else {
-                info!(
-                    "Offset 0x{}. Needle '{}'. Happiness level {}. Skipping writing to disk.",
-                    display_hex_offset(match_start_global_offset, 20),
-                    needle.name,
-                    needle.happiness_level,
+            }
+            Err(e) => {
+                error!("Could not write needle val to overall JSONL file: {}", e);
+                process_data_state.error_collector.record(
+                    "jsonl_write_failed",
+                    Some(needle_val_found.match_start_global_offset),
+                    &e.to_string(),
                 );
             }
+        }
+    }
+    if let Some(sqlite_writer) = process_data_state.sqlite_writer.as_mut() {
+        if let Err(e) = sqlite_writer.insert(needle_val_found) {
+            error!("Could not write needle val to SQLite database: {}", e);
+            process_data_state.error_collector.record(
+                "sqlite_write_failed",
+                Some(needle_val_found.match_start_global_offset),
+                &e.to_string(),
+            );
+        }
+    }
+    // a dry run has no per-needle directories
+    if search_assignment.dry_run {
+        return;
+    }
+    if let Err(e) = needle_val_found.append_to_jsonl_file(
+        &search_assignment.needle_jsonl_file_path(needle),
+        search_assignment.jsonl_line_ending,
+    ) {
+        error!("Could not write needle val to per-needle JSONL file: {}", e);
+        process_data_state.error_collector.record(
+            "jsonl_write_failed",
+            Some(needle_val_found.match_start_global_offset),
+            &e.to_string(),
+        );
+    }
+}
 
-            // Write the needle val to disk as JSONL (in both the general file, and the needle-specific file)
-            needle_val_found
-                .append_to_jsonl_file(&search_assignment.jsonl_output_log_file_path)
-                .expect("Could not write needle val to overall JSONL file");
-            needle_val_found
-                .append_to_jsonl_file(
-                    &PathBuf::from(&needle_dir_path).join(format!("001_{}.jsonl", needle.name)),
-                )
-                .expect("Could not write needle val to per-needle JSONL file");
+/// With `scan_window_context`, enough of the stream is kept before each chunk for the longest
+/// before-match context (up to the start of its block, with `capture_block_size`).
+fn lookbehind_capacity(search_assignment: &SearchAssignment) -> usize {
+    match search_assignment.scan_window_context {
+        true => search_assignment
+            .needles
+            .iter()
+            .filter(|needle| needle.write_to_file)
+            .map(|needle| {
+                (needle.byte_count_before_match
+                    + needle.capture_block_size.unwrap_or(1).saturating_sub(1))
+                    as usize
+            })
+            .max()
+            .unwrap_or(0),
+        false => 0,
+    }
+}
 
-            process_data_state.needle_vals_found.push(needle_val_found);
+/// Reads the input stream chunk by chunk (with carry-forward between chunks), and searches each chunk.
+/// `on_progress` is called every 30 seconds. At the end of the stream, the caller should finish
+/// the dump writes (`finish_dump_writes`) before reporting the final progress and summary.
+pub fn search_stream<R: Read>(
+    input_reader: &mut R,
+    process_data_state: &mut ProcessDataState,
+    search_assignment: &SearchAssignment,
+    mut on_progress: impl FnMut(&R, &ProcessDataState),
+) {
+    let haystack_carry_forward_len_bytes = search_assignment.haystack_carry_forward_len_bytes;
+    let haystack_chunk_buffer_size_bytes = process_data_state.haystack_chunk_buffer.len();
+
+    // Uniform chunks of these byte values can't be skipped, because a needle is made of only that byte.
+    let unskippable_uniform_vals: HashSet<u8> = search_assignment
+        .needles
+        .iter()
+        .flat_map(|needle| needle.uniform_byte_vals())
+        .collect();
+
+    let lookbehind_capacity = lookbehind_capacity(search_assignment);
+
+    loop {
+        if stop_if_requested(process_data_state, search_assignment) {
+            break;
         }
+
+        // Carry forward the end of the previous chunk, so needles crossing the chunk boundary are found.
+        let carried_len = process_data_state
+            .haystack_chunk_len
+            .min(haystack_carry_forward_len_bytes);
+        process_data_state.update_lookbehind_bytes(lookbehind_capacity, carried_len);
+        process_data_state.haystack_chunk_buffer.copy_within(
+            (process_data_state.haystack_chunk_len - carried_len)
+                ..process_data_state.haystack_chunk_len,
+            0,
+        );
+        process_data_state.haystack_carried_len = carried_len;
+        process_data_state.haystack_chunk_start_global_offset =
+            process_data_state.total_haystack_bytes_read - carried_len as u64;
+
+        // Don't read past the end of the searched range (a 0-length read ends the search).
+        let read_len = match search_assignment.end_global_offset {
+            Some(end_global_offset) => ((haystack_chunk_buffer_size_bytes - carried_len) as u64)
+                .min(end_global_offset.saturating_sub(process_data_state.total_haystack_bytes_read))
+                as usize,
+            None => haystack_chunk_buffer_size_bytes - carried_len,
+        };
+        // Fill the chunk, even from readers that return short reads (like xz), so only the last
+        // chunk of the stream is partial.
+        match read_full(
+            input_reader,
+            &mut process_data_state.haystack_chunk_buffer[carried_len..(carried_len + read_len)],
+        ) {
+            Ok(bytes_read_this_chunk) => {
+                debug!("Read {} bytes", bytes_read_this_chunk);
+                process_data_state.haystack_chunk_len = carried_len + bytes_read_this_chunk;
+                process_data_state.total_haystack_bytes_read += bytes_read_this_chunk as u64;
+                let was_last_chunk = process_data_state.haystack_is_last_chunk;
+                process_data_state.haystack_is_last_chunk = bytes_read_this_chunk < read_len
+                    || (search_assignment.end_global_offset
+                        == Some(process_data_state.total_haystack_bytes_read)
+                        && !search_assignment.is_searched_past_end);
+                process_data_state.extend_pending_dumps();
+
+                if let Some(coverage_verifier) = process_data_state.coverage_verifier.as_mut() {
+                    coverage_verifier.record_stream_bytes(
+                        &process_data_state.haystack_chunk_buffer
+                            [carried_len..(carried_len + bytes_read_this_chunk)],
+                    );
+                }
+
+                if (process_data_state.sec_since_last_progress_log() >= 30.0)
+                    && (bytes_read_this_chunk > 0)
+                {
+                    on_progress(input_reader, process_data_state);
+                    process_data_state.last_progress_log_time = Instant::now();
+                }
+
+                if bytes_read_this_chunk == 0 {
+                    info!(
+                        "Finished searching. No more bytes to read. Total haystack bytes read: {}",
+                        process_data_state
+                            .total_haystack_bytes_read
+                            .to_formatted_string(&Locale::en)
+                    );
+                    // The stream ended right after a full chunk: search its carry-forward again as
+                    // the last chunk, for the regex matches it left for the next one.
+                    if process_data_state.haystack_is_last_chunk
+                        && !was_last_chunk
+                        && carried_len > 0
+                        && search_assignment
+                            .needles
+                            .iter()
+                            .any(|needle| needle.regex.is_some())
+                    {
+                        do_search(process_data_state, search_assignment);
+                    }
+                    break;
+                } else if bytes_read_this_chunk < (haystack_chunk_buffer_size_bytes - carried_len) {
+                    // only the valid part of the buffer (`haystack_chunk_len`) gets searched
+                    info!("Finishing search. This should be the last haystack chunk. Only read {}/{} bytes",
+                        bytes_read_this_chunk.to_formatted_string(&Locale::en),
+                        (haystack_chunk_buffer_size_bytes - carried_len)
+                            .to_formatted_string(&Locale::en));
+
+                    if process_data_state.partial_chunk_read_count > 0 {
+                        warn!("Partial chunk read count: {} (>0) already. This should only happen once.",
+                            process_data_state.partial_chunk_read_count);
+                        process_data_state.error_collector.record(
+                            "repeated_partial_chunk_read",
+                            Some(process_data_state.haystack_chunk_start_global_offset),
+                            "More than one partial chunk read. The input may be a slow or unusual stream.",
+                        );
+                    }
+                    process_data_state.partial_chunk_read_count += 1;
+                }
+            }
+            Err(e) => panic!("Could not read: {}", e),
+        }
+
+        search_chunk(
+            process_data_state,
+            search_assignment,
+            &unskippable_uniform_vals,
+        );
+    }
+
+    finish_search(process_data_state, search_assignment);
+}
+
+/// Between chunks: true (and marks the search `interrupted`) if the search should stop early, at
+/// `stop_requested`.
+fn stop_if_requested(
+    process_data_state: &mut ProcessDataState,
+    search_assignment: &SearchAssignment,
+) -> bool {
+    if !search_assignment.stop_requested.load(Ordering::Relaxed) {
+        return false;
+    }
+    info!(
+        "Stopping the search as requested, after {} chunks. Total haystack bytes read: {}",
+        process_data_state
+            .chunk_count
+            .to_formatted_string(&Locale::en),
+        process_data_state
+            .total_haystack_bytes_read
+            .to_formatted_string(&Locale::en)
+    );
+    process_data_state.interrupted = true;
+    true
+}
+
+/// Searches the current (non-empty) chunk, unless it's skipped, and counts it.
+fn search_chunk(
+    process_data_state: &mut ProcessDataState,
+    search_assignment: &SearchAssignment,
+    unskippable_uniform_vals: &HashSet<u8>,
+) {
+    let is_sampled_chunk = match search_assignment.sample_every_nth_chunk {
+        Some(n) => process_data_state.chunk_count.is_multiple_of(n),
+        None => true,
+    };
+
+    if is_sampled_chunk {
+        if let Some(mut coverage_verifier) = process_data_state.coverage_verifier.take() {
+            // Skipped (uniform) chunks still count as searched.
+            coverage_verifier.check_searched_chunk(
+                process_data_state.haystack_chunk_start_global_offset,
+                process_data_state.haystack_chunk(),
+            );
+            process_data_state.coverage_verifier = Some(coverage_verifier);
+        }
+
+        // If all the bytes in the chunk are the same value, then we can skip searching this chunk.
+        // This happens a lot for null/0 bytes in practice.
+        let first_val = process_data_state.haystack_chunk()[0];
+        if !unskippable_uniform_vals.contains(&first_val)
+            && is_uniform_chunk(process_data_state.haystack_chunk())
+        {
+            // This log message happens a lot:
+            // debug!(
+            //     "Skipping search for chunk {} because all bytes are the same: {}",
+            //     process_data_state.chunk_count, first_val
+            // );
+        } else {
+            // don't need to skip, so search
+            do_search(process_data_state, search_assignment);
+        }
+    }
+
+    process_data_state.chunk_count += 1;
+
+    // (a pending dump's match is recorded, but its dump isn't written yet)
+    if let Some(checkpoint_interval_sec) = search_assignment.checkpoint_interval_sec {
+        if process_data_state
+            .last_checkpoint_time
+            .elapsed()
+            .as_secs_f32()
+            >= checkpoint_interval_sec
+            && process_data_state.pending_dumps.is_empty()
+        {
+            process_data_state.write_checkpoint(search_assignment, false);
+        }
+    }
+}
+
+/// Block size for `is_uniform_chunk`: big enough for vectorized comparisons, small enough to stop
+/// soon after the first differing byte.
+const UNIFORM_CHECK_BLOCK_LEN_BYTES: usize = 64;
+
+/// True if every byte of `chunk` is the same value (or it's empty). Compares whole blocks at a
+/// time, and stops at the first block with a differing byte.
+fn is_uniform_chunk(chunk: &[u8]) -> bool {
+    let Some(&first_val) = chunk.first() else {
+        return true;
+    };
+    let uniform_block = [first_val; UNIFORM_CHECK_BLOCK_LEN_BYTES];
+    let blocks = chunk.chunks_exact(UNIFORM_CHECK_BLOCK_LEN_BYTES);
+    let remainder = blocks.remainder();
+    blocks.into_iter().all(|block| block == uniform_block)
+        && remainder.iter().all(|&val| val == first_val)
+}
+
+/// Records what's left at the end of the stream.
+fn finish_search(process_data_state: &mut ProcessDataState, search_assignment: &SearchAssignment) {
+    // a hotspot that runs to the end of the stream
+    if let Some(hotspot) = process_data_state
+        .hotspot_detector
+        .as_mut()
+        .and_then(|hotspot_detector| hotspot_detector.finish())
+    {
+        process_data_state.record_hotspot(&hotspot, search_assignment);
+    }
+}
+
+/// Searches the memory-mapped input file (`--mmap`, see `ProcessDataState::mapped_haystack`) from
+/// `total_haystack_bytes_read` to `search_assignment.end_global_offset` (or the end of the file),
+/// without copying it. Each window of the file is searched like a chunk of `search_stream`, so the
+/// results are the same: the windows overlap by the carry-forward length, instead of copying it.
+pub fn search_mapped_file(
+    process_data_state: &mut ProcessDataState,
+    search_assignment: &SearchAssignment,
+    mut on_progress: impl FnMut(&ProcessDataState),
+) {
+    let (window_len, file_end_global_offset) = match &process_data_state.mapped_haystack {
+        Some(mapped_haystack) => (
+            mapped_haystack.window_len,
+            mapped_haystack.end_global_offset(),
+        ),
+        None => panic!("search_mapped_file needs a mapped input file"),
+    };
+    let end_global_offset = search_assignment
+        .end_global_offset
+        .map_or(file_end_global_offset, |end_global_offset| {
+            end_global_offset.min(file_end_global_offset)
+        });
+    let unskippable_uniform_vals: HashSet<u8> = search_assignment
+        .needles
+        .iter()
+        .flat_map(|needle| needle.uniform_byte_vals())
+        .collect();
+    let lookbehind_capacity = lookbehind_capacity(search_assignment);
+
+    loop {
+        if stop_if_requested(process_data_state, search_assignment) {
+            break;
+        }
+
+        let carried_len = process_data_state
+            .haystack_chunk_len
+            .min(search_assignment.haystack_carry_forward_len_bytes);
+        process_data_state.update_lookbehind_bytes(lookbehind_capacity, carried_len);
+        process_data_state.haystack_carried_len = carried_len;
+        process_data_state.haystack_chunk_start_global_offset =
+            process_data_state.total_haystack_bytes_read - carried_len as u64;
+
+        let read_len = ((window_len - carried_len) as u64)
+            .min(end_global_offset.saturating_sub(process_data_state.total_haystack_bytes_read))
+            as usize;
+        process_data_state.haystack_chunk_len = carried_len + read_len;
+        process_data_state.total_haystack_bytes_read += read_len as u64;
+        process_data_state.haystack_is_last_chunk =
+            process_data_state.total_haystack_bytes_read >= end_global_offset;
+        process_data_state.extend_pending_dumps();
+
+        if let Some(mut coverage_verifier) = process_data_state.coverage_verifier.take() {
+            coverage_verifier
+                .record_stream_bytes(&process_data_state.haystack_chunk()[carried_len..]);
+            process_data_state.coverage_verifier = Some(coverage_verifier);
+        }
+
+        if read_len == 0 {
+            info!(
+                "Finished searching. Reached the end of the mapped file. Total haystack bytes read: {}",
+                process_data_state
+                    .total_haystack_bytes_read
+                    .to_formatted_string(&Locale::en)
+            );
+            break;
+        }
+        if process_data_state.sec_since_last_progress_log() >= 30.0 {
+            on_progress(process_data_state);
+            process_data_state.last_progress_log_time = Instant::now();
+        }
+
+        search_chunk(
+            process_data_state,
+            search_assignment,
+            &unskippable_uniform_vals,
+        );
+    }
+
+    finish_search(process_data_state, search_assignment);
+}
+
+pub fn do_search(process_data_state: &mut ProcessDataState, search_assignment: &SearchAssignment) {
+    let haystack_chunk_start_global_offset = process_data_state.haystack_chunk_start_global_offset;
+
+    let chunk_matches = match search_assignment.text_regions_min_run_len {
+        None => find_matches_in_chunk(
+            process_data_state.haystack_chunk(),
+            &search_assignment.needles,
+            &search_assignment.needle_matcher,
+            search_assignment.chunk_search_threads,
+            process_data_state.haystack_carried_len,
+        ),
+        Some(min_run_len) => find_matches_in_text_regions(
+            process_data_state.haystack_chunk(),
+            &search_assignment.needles,
+            &search_assignment.needle_matcher,
+            search_assignment.chunk_search_threads,
+            process_data_state.haystack_carried_len,
+            min_run_len,
+        ),
+    };
+
+    let mut chunk_matches = chunk_matches;
+    if !process_data_state.haystack_is_last_chunk {
+        defer_regex_matches_at_chunk_end(
+            &mut chunk_matches,
+            &search_assignment.needles,
+            process_data_state.haystack_chunk_len,
+        );
+    }
+    filter_needle_matches(
+        &mut chunk_matches,
+        &search_assignment.needles,
+        haystack_chunk_start_global_offset,
+    );
+
+    // With `dedup`, a match that was already recorded (like in a region searched again) is skipped.
+    // It's recorded below, once it's past the other filters, so a dropped match can't hide a later
+    // one.
+    if search_assignment.dedup {
+        let match_count_before_dedup = chunk_matches.len();
+        chunk_matches.retain(|chunk_match| {
+            !process_data_state.recorded_match_keys.contains(&(
+                search_assignment.needles[chunk_match.needle_idx]
+                    .name
+                    .clone(),
+                haystack_chunk_start_global_offset + chunk_match.pos_in_chunk as u64,
+            ))
+        });
+        process_data_state.dedup_skipped_match_count +=
+            (match_count_before_dedup - chunk_matches.len()) as u64;
+    }
+
+    // Carve-and-exclude pre-pass: only needed if there's a match to exclude.
+    let carved_files = match search_assignment.exclude_carved_files && !chunk_matches.is_empty() {
+        true => find_carved_files(process_data_state.haystack_chunk()),
+        false => Vec::new(),
+    };
+
+    let match_count_before_exclusion = chunk_matches.len();
+    chunk_matches.retain(|chunk_match| {
+        let needle = &search_assignment.needles[chunk_match.needle_idx];
+        let match_range =
+            chunk_match.pos_in_chunk..(chunk_match.pos_in_chunk + chunk_match.match_len);
+        match (
+            needle.allow_inside_carved_files,
+            find_carved_file_containing(&carved_files, &match_range),
+        ) {
+            (false, Some(carved_file)) => {
+                debug!(
+                    "Excluded '{}' match at offset 0x{}, inside a carved {} file",
+                    needle.name,
+                    display_hex_offset(
+                        haystack_chunk_start_global_offset + chunk_match.pos_in_chunk as u64,
+                        20
+                    ),
+                    carved_file.file_type
+                );
+                false
+            }
+            _ => true,
+        }
+    });
+    process_data_state.carved_file_excluded_match_count +=
+        (match_count_before_exclusion - chunk_matches.len()) as u64;
+
+    // Matches are in needle (priority) order, so the highest-priority ones are kept.
+    if let Some(max_matches_per_chunk) = search_assignment.max_matches_per_chunk {
+        if chunk_matches.len() > max_matches_per_chunk {
+            let dropped_match_count = chunk_matches.len() - max_matches_per_chunk;
+            let message = format!(
+                "Chunk at 0x{} has {} matches, over the --max-matches-per-chunk limit of {}. Dropped the other {} match(es) in this chunk.",
+                display_hex_offset(haystack_chunk_start_global_offset, 20),
+                chunk_matches.len(),
+                max_matches_per_chunk,
+                dropped_match_count
+            );
+            warn!("{}", message);
+            process_data_state.error_collector.record_count(
+                "max_matches_per_chunk_exceeded",
+                dropped_match_count as u64,
+                Some(haystack_chunk_start_global_offset),
+                &message,
+            );
+            chunk_matches.truncate(max_matches_per_chunk);
+        }
+    }
+
+    // Once a needle reaches its `max_matches`, its matches are only counted (as capped).
+    for (needle_idx, match_start_global_offset) in cap_needle_matches(
+        &mut chunk_matches,
+        &search_assignment.needles,
+        haystack_chunk_start_global_offset,
+        &mut process_data_state.needle_match_counts,
+        &mut process_data_state.capped_match_counts,
+    ) {
+        let needle = &search_assignment.needles[needle_idx];
+        let message = format!(
+            "Needle '{}' reached its max_matches of {} at 0x{}. Its later matches are only counted.",
+            needle.name,
+            needle.max_matches.unwrap_or_default(),
+            display_hex_offset(match_start_global_offset, 20)
+        );
+        warn!("{}", message);
+        process_data_state.error_collector.record(
+            "needle_max_matches_reached",
+            Some(match_start_global_offset),
+            &message,
+        );
+    }
+
+    if search_assignment.dedup {
+        process_data_state
+            .recorded_match_keys
+            .extend(chunk_matches.iter().map(|chunk_match| {
+                (
+                    search_assignment.needles[chunk_match.needle_idx]
+                        .name
+                        .clone(),
+                    haystack_chunk_start_global_offset + chunk_match.pos_in_chunk as u64,
+                )
+            }));
+    }
+
+    // Dump windows are relative to the start of the lookbehind bytes (which directly precede the
+    // chunk), so with `scan_window_context` they can extend before and after the chunk.
+    let lookbehind_len = process_data_state.lookbehind_bytes.len();
+    let dump_window_start_global_offset =
+        haystack_chunk_start_global_offset - lookbehind_len as u64;
+    let dump_window_matches: Vec<ChunkMatch> = chunk_matches
+        .iter()
+        .map(|chunk_match| ChunkMatch {
+            needle_idx: chunk_match.needle_idx,
+            pos_in_chunk: lookbehind_len + chunk_match.pos_in_chunk,
+            match_len: chunk_match.match_len,
+        })
+        .collect();
+    let dump_windows = plan_dump_windows(
+        &dump_window_matches,
+        &search_assignment.needles,
+        match search_assignment.scan_window_context {
+            true => usize::MAX,
+            false => process_data_state.haystack_chunk_len,
+        },
+        dump_window_start_global_offset,
+        search_assignment.merge_dump_distance_bytes,
+    );
+    let mut submitted_merged_dump_starts: HashSet<usize> = HashSet::new();
+
+    for (chunk_match, dump_window) in chunk_matches.iter().zip(dump_windows) {
+        let needle = &search_assignment.needles[chunk_match.needle_idx];
+        let pos_in_chunk = chunk_match.pos_in_chunk;
+
+        // Found a match!
+        // Window = Match now
+        let match_start_global_offset: u64 =
+            haystack_chunk_start_global_offset + pos_in_chunk as u64;
+        let needle_val_as_string = needle.val_as_string();
+        let log_this_match = process_data_state
+            .match_log_throttle
+            .record_match(&needle.name);
+
+        // just a debug, not the main log
+        debug!(
+            "{} Found '{}' {} at position {} in the chunk",
+            needle.happiness_level_as_string(),
+            needle.name,
+            needle_val_as_string,
+            pos_in_chunk
+        );
+
+        // Create the NeedleValFound object
+        let mut needle_val_found = NeedleValFound::from_needle_val(
+            needle,
+            match_start_global_offset,
+            &search_assignment.input_file_path,
+        );
+        needle_val_found.chs_address = search_assignment
+            .disk_geometry
+            .and_then(|disk_geometry| disk_geometry.chs_address(match_start_global_offset))
+            .map(|chs_address| chs_address.to_string());
+        if search_assignment.deterministic {
+            needle_val_found.found_timestamp_utc = None;
+        }
+        if search_assignment.record_match_time {
+            needle_val_found.found_timestamp_utc =
+                Some(Utc::now().format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string());
+            needle_val_found.elapsed_since_start_sec =
+                Some(process_data_state.start_time.elapsed().as_secs_f64());
+        }
+        if search_assignment.jsonl_val_array {
+            needle_val_found.val = needle_val_found.val.into_array();
+        }
+
+        // Write the haystack chunk to disk (on the dump writer thread)
+        if let Some(e) = process_data_state.dump_writer.take_error() {
+            process_data_state.disable_dump_writing(&e);
+        }
+        // a dry run only records the match
+        let dump_window = dump_window.filter(|_| !search_assignment.dry_run);
+        let needle_dir_path = search_assignment.needle_dir_path(needle);
+        if !search_assignment.dry_run && !needle_dir_path.exists() {
+            match fs::create_dir(&needle_dir_path) {
+                Ok(()) => info!(
+                    "{}. First time for '{}' needle. Created new needle directory: {}",
+                    needle.happiness_level_as_string(),
+                    needle.name,
+                    needle_dir_path.display()
+                ),
+                // created by another `--threads` worker in the meantime
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(e) => process_data_state.disable_dump_writing(&e),
+            }
+        }
+        let merged_dump_dir_path = search_assignment.merged_dump_dir_path();
+        if dump_window
+            .as_ref()
+            .is_some_and(|dump_window| dump_window.is_merged)
+            && !merged_dump_dir_path.exists()
+        {
+            match fs::create_dir(&merged_dump_dir_path) {
+                Ok(()) => info!(
+                    "Created merged dump directory: {}",
+                    merged_dump_dir_path.display()
+                ),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(e) => process_data_state.disable_dump_writing(&e),
+            }
+        }
+
+        if let (Some(dump_window), false) = (dump_window, process_data_state.dump_writing_disabled)
+        {
+            let write_start_pos = dump_window.range_in_chunk.start;
+            let write_end_pos = dump_window.range_in_chunk.end;
+
+            let chunk_output_file_path = match dump_window.is_merged {
+                // by default: found_g_0x<this match's global offset>_startat_0x<offset within file>
+                false => needle_dir_path.join(search_assignment.dump_file_name_template.render(
+                    needle,
+                    match_start_global_offset,
+                    (lookbehind_len + pos_in_chunk - write_start_pos) as u64,
+                    process_data_state.needle_vals_found.len(),
+                )),
+                // shared by several matches, so named by the dump's own global offset and length
+                true => merged_dump_dir_path.join(format!(
+                    "merged_g_0x{}_len_0x{}.bin",
+                    display_hex_offset(
+                        dump_window_start_global_offset + write_start_pos as u64,
+                        20
+                    ),
+                    display_hex_offset((write_end_pos - write_start_pos) as u64, 1),
+                )),
+            };
+
+            // a merged dump is only written once, by the first of its matches
+            if !dump_window.is_merged || submitted_merged_dump_starts.insert(write_start_pos) {
+                process_data_state.submit_dump(
+                    chunk_output_file_path.clone(),
+                    write_start_pos..write_end_pos,
+                    needle.strings,
+                );
+            }
+            let recorded_dump_file_path = match search_assignment.deterministic {
+                true => chunk_output_file_path
+                    .strip_prefix(&search_assignment.output_dir_path)
+                    .unwrap_or(&chunk_output_file_path),
+                false => &chunk_output_file_path,
+            };
+            needle_val_found.dump_file_path = Some(recorded_dump_file_path.display().to_string());
+
+            if search_assignment.jsonl_context_hexdump {
+                // the written window, clamped to this match's own before/after context
+                let match_pos = lookbehind_len + pos_in_chunk;
+                let context_start = (match_pos as u64)
+                    .saturating_sub(needle.byte_count_before_match)
+                    .max(write_start_pos as u64) as usize;
+                let context_end = (match_pos as u64
+                    + chunk_match.match_len as u64
+                    + needle.byte_count_after_match)
+                    .min(write_end_pos as u64) as usize;
+                let context_bytes = process_data_state.window_bytes(context_start..context_end);
+                needle_val_found.context_hexdump = Some(
+                    format_hexdump_lines(
+                        &context_bytes,
+                        dump_window_start_global_offset + context_start as u64,
+                    )
+                    .join("\n"),
+                );
+            }
+
+            // Context cut off by the chunk boundary (not by the start or end of the stream).
+            // Only the last chunk isn't full, so a full chunk can't end at the end of the stream.
+            let dump_start_global_offset = dump_window_start_global_offset + write_start_pos as u64;
+            let dump_end_global_offset = dump_window_start_global_offset + write_end_pos as u64;
+            let is_chunk_full = process_data_state.haystack_chunk_len
+                == process_data_state.haystack_chunk_capacity();
+            let dump_global_range =
+                needle.dump_global_range(match_start_global_offset, chunk_match.match_len);
+            let is_dump_truncated = !dump_window.is_merged
+                && (dump_start_global_offset > dump_global_range.start
+                    || (is_chunk_full && dump_end_global_offset < dump_global_range.end));
+            if is_dump_truncated {
+                process_data_state.error_collector.record(
+                    "dump_context_truncated",
+                    Some(match_start_global_offset),
+                    &format!(
+                        "Dump for '{}' match at 0x{} was cut off at the chunk boundary (use --scan-window-context for the full context)",
+                        needle.name,
+                        display_hex_offset(match_start_global_offset, 20)
+                    ),
+                );
+            }
+
+            if log_this_match && needle.log_format.is_none() {
+                info!(
+                    "Offset 0x{}. Needle '{}'. {}. Wrote to disk ({} bytes{}).",
+                    display_hex_offset(match_start_global_offset, 20),
+                    needle.name,
+                    needle.happiness_level_as_string(),
+                    (write_end_pos - write_start_pos).to_formatted_string(&Locale::en),
+                    match dump_window.is_merged {
+                        true => ", merged with nearby matches",
+                        false => "",
+                    }
+                );
+            }
+        } else if log_this_match && needle.log_format.is_none() {
+            info!(
+                "Offset 0x{}. Needle '{}'. Happiness level {}. Skipping writing to disk.",
+                display_hex_offset(match_start_global_offset, 20),
+                needle.name,
+                needle.happiness_level,
+            );
+        }
+
+        if let (true, Some(log_format)) = (log_this_match, &needle.log_format) {
+            info!(
+                "{}",
+                needle.render_log_format(log_format, match_start_global_offset)
+            );
+        }
+
+        if !search_assignment.defer_jsonl_records {
+            write_jsonl_record(
+                process_data_state,
+                search_assignment,
+                &needle_val_found,
+                needle,
+            );
+        }
+
+        process_data_state.needle_vals_found.push(needle_val_found);
+    }
+
+    // Matches are in priority order, so sort them by offset for the hotspot window.
+    if process_data_state.hotspot_detector.is_some() {
+        let mut match_offsets: Vec<(u64, &str)> = chunk_matches
+            .iter()
+            .map(|chunk_match| {
+                (
+                    haystack_chunk_start_global_offset + chunk_match.pos_in_chunk as u64,
+                    search_assignment.needles[chunk_match.needle_idx]
+                        .name
+                        .as_str(),
+                )
+            })
+            .collect();
+        match_offsets.sort();
+        for (match_start_global_offset, needle_name) in match_offsets {
+            if let Some(hotspot) =
+                process_data_state
+                    .hotspot_detector
+                    .as_mut()
+                    .and_then(|hotspot_detector| {
+                        hotspot_detector.record_match(match_start_global_offset, needle_name)
+                    })
+            {
+                process_data_state.record_hotspot(&hotspot, search_assignment);
+            }
+        }
+    }
+
+    for (needle_name, match_count) in process_data_state.match_log_throttle.finish_chunk() {
+        info!(
+            "Needle '{}' matched {} times in this chunk (at offset 0x{}). Per-match logging was throttled.",
+            needle_name,
+            match_count.to_formatted_string(&Locale::en),
+            display_hex_offset(haystack_chunk_start_global_offset, 20),
+        );
+        process_data_state.error_collector.record(
+            "match_logging_throttled",
+            Some(haystack_chunk_start_global_offset),
+            &format!(
+                "Needle '{}' matched {} times in one chunk. Per-match logging was throttled.",
+                needle_name, match_count
+            ),
+        );
+    }
+}
+
+/// Searches `searched_range` of an uncompressed image file on `thread_count` threads (`--threads`).
+/// The range is split into one run of chunks per thread, and each is searched like a resumed search
+/// (see `ProcessDataState::resume_from_checkpoint`): it reads the carry-forward before its start
+/// again, so matches across the boundary are found, but only by the thread whose sub-range they end
+/// in. Each thread has its own state from `make_process_data_state`. At the end, their matches are
+/// merged into `process_data_state` in stream order (de-duplicated by global offset and needle),
+/// and written to the JSONL files. Dumps are written by the threads as they go, and read past the
+/// end of their sub-range where needed.
+pub fn search_file_in_parallel(
+    input_file_path: &Path,
+    searched_range: Range<u64>,
+    thread_count: usize,
+    make_process_data_state: impl Fn() -> ProcessDataState + Sync,
+    process_data_state: &mut ProcessDataState,
+    search_assignment: &SearchAssignment,
+) -> io::Result<()> {
+    // Split at the same chunk boundaries as a single-threaded search, so every chunk (and so every
+    // dump and log message) is the same. The first chunk reads a full buffer, and the others read
+    // a buffer minus the carry-forward.
+    let thread_count = thread_count.max(1) as u64;
+    let haystack_chunk_buffer_size_bytes = process_data_state.haystack_chunk_buffer.len() as u64;
+    let carry_forward_len_bytes = search_assignment.haystack_carry_forward_len_bytes as u64;
+    let chunk_stride_bytes = haystack_chunk_buffer_size_bytes - carry_forward_len_bytes;
+    let searched_len = searched_range.end - searched_range.start;
+    let chunk_count = 1 + searched_len
+        .saturating_sub(haystack_chunk_buffer_size_bytes)
+        .div_ceil(chunk_stride_bytes);
+    let chunks_per_thread = chunk_count.div_ceil(thread_count);
+    let chunk_start_global_offset = |chunk_idx: u64| match chunk_idx {
+        0 => searched_range.start,
+        _ => (searched_range.start
+            + haystack_chunk_buffer_size_bytes
+            + (chunk_idx - 1) * chunk_stride_bytes)
+            .min(searched_range.end),
+    };
+    let sub_ranges: Vec<Range<u64>> = (0..thread_count)
+        .map(|thread_idx| {
+            chunk_start_global_offset((thread_idx * chunks_per_thread).min(chunk_count))
+                ..chunk_start_global_offset(((thread_idx + 1) * chunks_per_thread).min(chunk_count))
+        })
+        .filter(|sub_range| !sub_range.is_empty())
+        .collect();
+    let lookbehind_capacity = lookbehind_capacity(search_assignment) as u64;
+    let worker_search_assignments: Vec<SearchAssignment> = sub_ranges
+        .iter()
+        .map(|sub_range| SearchAssignment {
+            end_global_offset: Some(sub_range.end),
+            is_searched_past_end: sub_range.end < searched_range.end,
+            checkpoint_interval_sec: None,
+            defer_jsonl_records: true,
+            ..search_assignment.clone()
+        })
+        .collect();
+
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(sub_ranges.len())
+        .build()
+        .map_err(io::Error::other)?;
+    let recorded_match_keys = &process_data_state.recorded_match_keys;
+    let worker_states: Vec<ProcessDataState> = thread_pool.install(|| {
+        sub_ranges
+            .par_iter()
+            .zip(worker_search_assignments.par_iter())
+            .map(|(sub_range, worker_search_assignment)| {
+                let mut worker_state = make_process_data_state();
+                let mut input_file = fs::File::open(input_file_path)?;
+                // the first sub-range starts where the search starts, with nothing to carry forward
+                let carried_len = match sub_range.start == searched_range.start {
+                    true => 0,
+                    false => carry_forward_len_bytes,
+                };
+                // with `scan_window_context`, the stream before the carry-forward is also kept
+                let lookbehind_start_global_offset = (sub_range.start - carried_len)
+                    .saturating_sub(lookbehind_capacity)
+                    .max(searched_range.start);
+                input_file.seek(SeekFrom::Start(lookbehind_start_global_offset))?;
+                input_file
+                    .by_ref()
+                    .take(sub_range.start - carried_len - lookbehind_start_global_offset)
+                    .read_to_end(&mut worker_state.lookbehind_bytes)?;
+                // with `dedup`, the matches recorded before this search (when resuming) are skipped
+                worker_state.recorded_match_keys = recorded_match_keys.clone();
+                worker_state.resume_from_checkpoint(
+                    &mut input_file,
+                    sub_range.start,
+                    carried_len as usize,
+                )?;
+                search_stream(
+                    &mut input_file,
+                    &mut worker_state,
+                    worker_search_assignment,
+                    |_, _| {},
+                );
+                // dumps of matches near the end of the sub-range continue into the next one
+                worker_state.read_pending_dumps_from_file(&mut input_file, searched_range.end)?;
+                worker_state.finish_dump_writes();
+                Ok(worker_state)
+            })
+            .collect::<io::Result<Vec<ProcessDataState>>>()
+    })?;
+
+    for worker_state in worker_states {
+        process_data_state
+            .needle_vals_found
+            .extend(worker_state.needle_vals_found);
+        process_data_state.chunk_count += worker_state.chunk_count;
+        process_data_state.carved_file_excluded_match_count +=
+            worker_state.carved_file_excluded_match_count;
+        process_data_state.dedup_skipped_match_count += worker_state.dedup_skipped_match_count;
+        process_data_state
+            .recorded_match_keys
+            .extend(worker_state.recorded_match_keys);
+        process_data_state.dump_writing_disabled |= worker_state.dump_writing_disabled;
+        process_data_state.interrupted |= worker_state.interrupted;
+        process_data_state
+            .error_collector
+            .merge(worker_state.error_collector);
+    }
+    process_data_state.total_haystack_bytes_read = searched_range.end;
+
+    process_data_state.needle_vals_found.sort_by(|a, b| {
+        a.match_start_global_offset
+            .cmp(&b.match_start_global_offset)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    process_data_state.needle_vals_found.dedup_by(|a, b| {
+        a.match_start_global_offset == b.match_start_global_offset && a.name == b.name
+    });
+    let needle_vals_found = std::mem::take(&mut process_data_state.needle_vals_found);
+    for needle_val_found in &needle_vals_found {
+        if let Some(needle) = search_assignment
+            .needles
+            .iter()
+            .find(|needle| needle.name == needle_val_found.name)
+        {
+            write_jsonl_record(
+                process_data_state,
+                search_assignment,
+                needle_val_found,
+                needle,
+            );
+        }
+    }
+    process_data_state.needle_vals_found = needle_vals_found;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_summary::ErrorSummaryEntry;
+    use crate::found_needle::{log_polars_summary, write_summary_csv_file};
+    use crate::multi_frame_lz4::tests::lz4_compress;
+    use crate::multi_frame_lz4::MultiFrameLz4Decoder;
+    use crate::needle::{override_write_to_file, sort_needles_by_priority};
+    use crate::partition_table::read_partitions;
+    use crate::partition_table::tests::make_mbr_disk;
+    use std::io::Write;
+    use std::path::Path;
+
+    fn make_needle(name: &str, val: &[u8]) -> Needle {
+        Needle {
+            name: name.to_string(),
+            val: val.to_vec(),
+            happiness_level: 1,
+            write_to_file: true,
+            byte_count_before_match: 1024,
+            byte_count_after_match: 1024,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_matches_in_chunk_sub_range_boundaries() {
+        let mut haystack = vec![0u8; 1000];
+        // 4 sub-ranges of 250 bytes: one needle spans the 250 boundary, one starts exactly on 500,
+        // and one ends exactly on 750
+        haystack[248..253].copy_from_slice(b"ABCDE");
+        haystack[500..505].copy_from_slice(b"FGHIJ");
+        haystack[745..750].copy_from_slice(b"KLMNO");
+        let needles = vec![
+            make_needle("spans", b"ABCDE"),
+            make_needle("starts", b"FGHIJ"),
+            make_needle("ends", b"KLMNO"),
+            make_needle("absent", b"ZZZZZ"),
+        ];
+
+        let expected_matches = vec![
+            ChunkMatch {
+                needle_idx: 0,
+                pos_in_chunk: 248,
+                match_len: 5,
+            },
+            ChunkMatch {
+                needle_idx: 1,
+                pos_in_chunk: 500,
+                match_len: 5,
+            },
+            ChunkMatch {
+                needle_idx: 2,
+                pos_in_chunk: 745,
+                match_len: 5,
+            },
+        ];
+        for sub_range_count in [1, 2, 3, 4, 7, 1000, 5000] {
+            assert_eq!(
+                find_matches_in_chunk(
+                    &haystack,
+                    &needles,
+                    &NeedleMatcher::new(&needles).unwrap(),
+                    sub_range_count,
+                    0
+                ),
+                expected_matches,
+                "sub_range_count: {}",
+                sub_range_count
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_matches_in_chunk_finds_every_match() {
+        let mut haystack = vec![0u8; 1000];
+        haystack[600..603].copy_from_slice(b"abc");
+        haystack[100..103].copy_from_slice(b"abc");
+        haystack[249..252].copy_from_slice(b"abc"); // spans the first sub-range boundary
+        let needles = vec![make_needle("abc", b"abc")];
+
+        for sub_range_count in [1, 4] {
+            assert_eq!(
+                find_matches_in_chunk(
+                    &haystack,
+                    &needles,
+                    &NeedleMatcher::new(&needles).unwrap(),
+                    sub_range_count,
+                    0
+                ),
+                [100, 249, 600]
+                    .into_iter()
+                    .map(|pos_in_chunk| ChunkMatch {
+                        needle_idx: 0,
+                        pos_in_chunk,
+                        match_len: 3,
+                    })
+                    .collect::<Vec<ChunkMatch>>()
+            );
+        }
+
+        // overlapping matches are each found
+        let needles = vec![make_needle("zeros", &[0, 0])];
+        assert_eq!(
+            find_matches_in_chunk(
+                &[0, 0, 0, 1],
+                &needles,
+                &NeedleMatcher::new(&needles).unwrap(),
+                1,
+                0
+            ),
+            vec![
+                ChunkMatch {
+                    needle_idx: 0,
+                    pos_in_chunk: 0,
+                    match_len: 2,
+                },
+                ChunkMatch {
+                    needle_idx: 0,
+                    pos_in_chunk: 1,
+                    match_len: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_matches_in_chunk_wildcard_needle() {
+        let mut haystack = vec![0u8; 1000];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"Hxlyo");
+        haystack[300..305].copy_from_slice(b"Jello"); // first byte differs
+        haystack[400..405].copy_from_slice(b"Hell!"); // last byte differs
+        let needles = vec![Needle {
+            mask: Some(vec![0xFF, 0x00, 0xFF, 0x00, 0xFF]),
+            ..make_needle("wild", b"H\x00l\x00o")
+        }];
+
+        let found_positions: Vec<usize> = find_matches_in_chunk(
+            &haystack,
+            &needles,
+            &NeedleMatcher::new(&needles).unwrap(),
+            1,
+            0,
+        )
+        .iter()
+        .map(|chunk_match| chunk_match.pos_in_chunk)
+        .collect();
+        assert_eq!(found_positions, vec![100, 200]);
+    }
+
+    fn make_search_assignment(output_dir_path: &Path, needles: Vec<Needle>) -> SearchAssignment {
+        SearchAssignment {
+            input_file_path: PathBuf::from("/images/disk.img"),
+            output_dir_path: output_dir_path.to_path_buf(),
+            jsonl_output_log_file_path: Some(output_dir_path.join("00_all_output_record.jsonl")),
+            needle_matcher: NeedleMatcher::new(&needles).unwrap(),
+            needles,
+            chunk_search_threads: 1,
+            happiness_dir_prefixes: HashMap::new(),
+            haystack_carry_forward_len_bytes: DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES,
+            sample_every_nth_chunk: None,
+            exclude_carved_files: false,
+            merge_dump_distance_bytes: None,
+            disk_geometry: None,
+            text_regions_min_run_len: None,
+            jsonl_line_ending: LineEnding::Lf,
+            jsonl_val_array: false,
+            jsonl_context_hexdump: false,
+            scan_window_context: false,
+            max_matches_per_chunk: None,
+            jsonl_max_part_size_bytes: None,
+            jsonl_max_part_records: None,
+            dump_file_name_template: DumpFileNameTemplate::default(),
+            deterministic: false,
+            checkpoint_interval_sec: None,
+            config_hash: String::new(),
+            defer_jsonl_records: false,
+            dry_run: false,
+            dedup: false,
+            stop_requested: Arc::new(AtomicBool::new(false)),
+            end_global_offset: None,
+            is_searched_past_end: false,
+            record_match_time: false,
+        }
+    }
+
+    fn make_state_with_haystack(haystack: &[u8]) -> ProcessDataState {
+        let mut process_data_state = ProcessDataState::new(haystack.len(), 100, 4);
+        process_data_state
+            .haystack_chunk_buffer
+            .copy_from_slice(haystack);
+        process_data_state.haystack_chunk_len = haystack.len();
+        process_data_state
+    }
+
+    #[test]
+    fn test_do_search_uses_happiness_dir_prefix() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"World");
+        let mut critical_needle = make_needle("hello", b"Hello");
+        critical_needle.happiness_level = 9;
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![critical_needle, make_needle("world", b"World")],
+        );
+        search_assignment.happiness_dir_prefixes =
+            parse_happiness_dir_prefixes("9=critical, 5=interesting").unwrap();
+
+        do_search(&mut make_state_with_haystack(&haystack), &search_assignment);
+
+        assert!(tmp_dir.path().join("critical_hello").is_dir());
+        assert!(tmp_dir.path().join("1_world").is_dir()); // no prefix configured for level 1
+    }
+
+    #[test]
+    fn test_do_search_reports_every_match_of_a_needle() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        for pos in [100, 1500, 3000] {
+            haystack[pos..(pos + 5)].copy_from_slice(b"Hello");
+        }
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.haystack_chunk_start_global_offset = 0x10_0000;
+
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![0x10_0064, 0x10_05dc, 0x10_0bb8]);
+        let jsonl = fs::read_to_string(tmp_dir.path().join("00_all_output_record.jsonl")).unwrap();
+        assert_eq!(jsonl.lines().count(), 3);
+        let dump_count = fs::read_dir(tmp_dir.path().join("1_hello"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().unwrap() == "bin")
+            .count();
+        assert_eq!(dump_count, 3);
+    }
+
+    #[test]
+    fn test_check_haystack_chunk_buffer_size() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("long", &[0xAB; 100]),
+                make_needle("hello", b"Hello"),
+            ],
+        );
+        // 1024-byte carry-forward, plus the 100-byte needle
+        assert!(search_assignment
+            .check_haystack_chunk_buffer_size(8 * 1024 * 1024)
+            .is_ok());
+        assert!(search_assignment
+            .check_haystack_chunk_buffer_size(1125)
+            .is_ok());
+        assert_eq!(
+            search_assignment.check_haystack_chunk_buffer_size(1124),
+            Err("Chunk size of 1124 bytes is too small. It must be over 1124 bytes: the carry-forward (1024 bytes) plus the longest needle (100 bytes).".to_string())
+        );
+        assert!(search_assignment
+            .check_haystack_chunk_buffer_size(512)
+            .is_err());
+
+        // a regex needle's pattern length doesn't count
+        let regex_needles = vec![Needle {
+            regex: Some(regex::bytes::Regex::new("[0-9]+").unwrap()),
+            ..make_needle("digits", &[b'x'; 3000])
+        }];
+        assert_eq!(
+            haystack_carry_forward_len_bytes_for(&regex_needles),
+            DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES
+        );
+        assert!(check_haystack_chunk_buffer_size(4096, 1024, &regex_needles).is_ok());
+    }
+
+    #[test]
+    fn test_max_needle_len_bytes_for_chunk_size() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        for chunk_size_bytes in [1000, 2000, 2049, 2050, 2051, 4096] {
+            let max_needle_len_bytes = max_needle_len_bytes_for_chunk_size(chunk_size_bytes);
+            for (needle_len, fits) in [
+                (max_needle_len_bytes, true),
+                (max_needle_len_bytes + 1, false),
+            ] {
+                if needle_len == 0 {
+                    continue;
+                }
+                let needles = vec![make_needle("needle", &vec![0xAB; needle_len])];
+                let search_assignment = SearchAssignment {
+                    haystack_carry_forward_len_bytes: haystack_carry_forward_len_bytes_for(
+                        &needles,
+                    ),
+                    ..make_search_assignment(tmp_dir.path(), needles)
+                };
+                assert_eq!(
+                    search_assignment
+                        .check_haystack_chunk_buffer_size(chunk_size_bytes)
+                        .is_ok(),
+                    fits,
+                    "{} bytes, {}-byte needle",
+                    chunk_size_bytes,
+                    needle_len
+                );
+            }
+        }
+        assert_eq!(max_needle_len_bytes_for_chunk_size(4096), 2048);
+        assert_eq!(max_needle_len_bytes_for_chunk_size(2000), 975);
+    }
+
+    #[test]
+    fn test_do_search_survives_dump_write_error() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        // simulate an unwritable needle directory: a file is in the way
+        fs::write(tmp_dir.path().join("1_hello"), b"not a dir").unwrap();
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        // dumping stops, but the match is still counted and recorded in the overall JSONL
+        assert!(process_data_state.dump_writing_disabled);
+        assert_eq!(process_data_state.needle_vals_found.len(), 1);
+        let jsonl_contents = fs::read_to_string(
+            search_assignment
+                .jsonl_output_log_file_path
+                .as_ref()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(jsonl_contents.lines().count(), 1);
+
+        // later chunks keep searching without trying to dump
+        do_search(&mut process_data_state, &search_assignment);
+        assert_eq!(process_data_state.needle_vals_found.len(), 2);
+    }
+
+    #[test]
+    fn test_do_search_excludes_matches_inside_carved_jpeg() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let jpeg = crate::carved_files::tests::make_jpeg(b"xx Hello xx");
+        let mut haystack = vec![0u8; 4096];
+        haystack[1000..(1000 + jpeg.len())].copy_from_slice(&jpeg);
+        haystack[3000..3005].copy_from_slice(b"World"); // outside the JPEG
+
+        let mut opted_in_needle = make_needle("hello_opted_in", b"Hello");
+        opted_in_needle.allow_inside_carved_files = true;
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("hello", b"Hello"),
+                opted_in_needle,
+                make_needle("world", b"World"),
+            ],
+        );
+        search_assignment.exclude_carved_files = true;
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let found_names: Vec<&str> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.name.as_str())
+            .collect();
+        assert_eq!(found_names, vec!["hello_opted_in", "world"]);
+        assert_eq!(process_data_state.carved_file_excluded_match_count, 1);
+    }
+
+    #[test]
+    fn test_do_search_merges_nearby_dumps() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack: Vec<u8> = (0..4096).map(|i| (i % 200) as u8 + 50).collect();
+        haystack[1000..1005].copy_from_slice(b"Hello");
+        haystack[1100..1105].copy_from_slice(b"World");
+        haystack[3000..3005].copy_from_slice(b"Later");
+
+        let needles: Vec<Needle> = [
+            ("hello", b"Hello"),
+            ("world", b"World"),
+            ("later", b"Later"),
+        ]
+        .into_iter()
+        .map(|(name, val)| {
+            let mut needle = make_needle(name, val);
+            needle.byte_count_before_match = 16;
+            needle.byte_count_after_match = 16;
+            needle
+        })
+        .collect();
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), needles);
+        search_assignment.merge_dump_distance_bytes = Some(100);
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        // "Hello" and "World" share one dump: from 16 bytes before "Hello" to 16 bytes after "World"
+        let merged_dump_file_paths: Vec<PathBuf> =
+            fs::read_dir(search_assignment.merged_dump_dir_path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+        assert_eq!(merged_dump_file_paths.len(), 1);
+        assert_eq!(
+            fs::read(&merged_dump_file_paths[0]).unwrap(),
+            &haystack[984..1121]
+        );
+        let dump_file_paths: Vec<String> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.dump_file_path.clone().unwrap())
+            .collect();
+        assert_eq!(
+            dump_file_paths[0],
+            merged_dump_file_paths[0].display().to_string()
+        );
+        assert_eq!(dump_file_paths[1], dump_file_paths[0]);
+
+        // the faraway match keeps its own dump
+        assert_ne!(dump_file_paths[2], dump_file_paths[0]);
+        assert_eq!(
+            fs::read(&dump_file_paths[2]).unwrap(),
+            &haystack[2984..3021]
+        );
+    }
+
+    #[test]
+    fn test_do_search_capture_block_size() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack: Vec<u8> = (0..16384).map(|i| (i % 200) as u8 + 50).collect();
+        haystack[5000..5005].copy_from_slice(b"Hello");
+        // crosses the block boundary at 8192, with its after-match context
+        haystack[8080..8085].copy_from_slice(b"World");
+
+        let needles: Vec<Needle> = [("hello", b"Hello"), ("world", b"World")]
+            .into_iter()
+            .map(|(name, val)| {
+                let mut needle = make_needle(name, val);
+                needle.byte_count_before_match = 16;
+                needle.byte_count_after_match = 16;
+                needle.capture_block_size = Some(4096);
+                needle
+            })
+            .collect();
+        let search_assignment = make_search_assignment(tmp_dir.path(), needles);
+
+        // the chunk isn't block-aligned (like after a carry-forward), but the blocks are
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.haystack_chunk_start_global_offset = 100;
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        let dump_file_paths: Vec<String> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.dump_file_path.clone().unwrap())
+            .collect();
+        // "Hello" at 5100 is in the block at 4096
+        assert!(dump_file_paths[0].ends_with("_startat_0x3EC.bin"));
+        assert_eq!(
+            fs::read(&dump_file_paths[0]).unwrap(),
+            &haystack[(4096 - 100)..(8192 - 100)]
+        );
+        // "World" at 8180 and its context span the blocks at 4096 and 8192
+        assert!(dump_file_paths[1].ends_with("_startat_0xFF4.bin"));
+        assert_eq!(
+            fs::read(&dump_file_paths[1]).unwrap(),
+            &haystack[(4096 - 100)..(12288 - 100)]
+        );
+        assert_eq!(process_data_state.error_collector.total_count(), 0);
+    }
+
+    #[test]
+    fn test_do_search_reports_in_priority_order() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"First");
+        haystack[2000..2005].copy_from_slice(b"Later");
+
+        let mut needles = vec![
+            make_needle("first", b"First"),
+            make_needle("later", b"Later"),
+        ];
+        needles[1].priority = 10;
+        sort_needles_by_priority(&mut needles);
+        let search_assignment = make_search_assignment(tmp_dir.path(), needles);
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        // the high-priority needle is reported first, even though its match is later in the chunk
+        let found_names: Vec<&str> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.name.as_str())
+            .collect();
+        assert_eq!(found_names, vec!["later", "first"]);
+    }
+
+    #[test]
+    fn test_do_search_without_global_jsonl() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"World");
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("hello", b"Hello"),
+                make_needle("world", b"World"),
+            ],
+        );
+        search_assignment.jsonl_output_log_file_path = None;
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        assert!(!tmp_dir.path().join("00_all_output_record.jsonl").exists());
+        let jsonl_file_paths = search_assignment.all_jsonl_file_paths();
+        assert_eq!(jsonl_file_paths.len(), 2);
+        for jsonl_file_path in &jsonl_file_paths {
+            assert_eq!(
+                fs::read_to_string(jsonl_file_path).unwrap().lines().count(),
+                1
+            );
+        }
+        assert!(log_polars_summary(&jsonl_file_paths).is_ok());
+    }
+
+    #[test]
+    fn test_do_search_splits_global_jsonl_into_parts() {
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"World");
+        haystack[300..305].copy_from_slice(b"Again");
+        let needles = vec![
+            make_needle("hello", b"Hello"),
+            make_needle("world", b"World"),
+            make_needle("again", b"Again"),
+        ];
+
+        // by record count: 2 records, then a second part with the third
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), needles.clone());
+        search_assignment.jsonl_max_part_records = Some(2);
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let jsonl_file_paths = search_assignment.all_jsonl_file_paths();
+        assert_eq!(
+            jsonl_file_paths,
+            vec![
+                tmp_dir.path().join("00_all_output_record.jsonl"),
+                tmp_dir.path().join("00_all_output_record_part002.jsonl"),
+            ]
+        );
+        let part_line_counts: Vec<usize> = jsonl_file_paths
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap().lines().count())
+            .collect();
+        assert_eq!(part_line_counts, vec![2, 1]);
+        assert!(log_polars_summary(&jsonl_file_paths).is_ok());
+
+        // by size: every record is over 1 byte, so each gets its own part
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), needles);
+        search_assignment.jsonl_max_part_size_bytes = Some(1);
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let jsonl_file_paths = search_assignment.all_jsonl_file_paths();
+        assert_eq!(jsonl_file_paths.len(), 3);
+        assert!(tmp_dir
+            .path()
+            .join("00_all_output_record_part003.jsonl")
+            .exists());
+        assert_eq!(process_data_state.jsonl_part_number, 4);
+    }
+
+    #[test]
+    fn test_max_matches_counts_earlier_matches_when_resuming() {
+        let mut haystack = vec![0u8; 4096];
+        for pos in [100, 200, 300] {
+            haystack[pos..(pos + 5)].copy_from_slice(b"Hello");
+        }
+        let needle = Needle {
+            max_matches: Some(2),
+            ..make_needle("hello", b"Hello")
+        };
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment = make_search_assignment(tmp_dir.path(), vec![needle.clone()]);
+
+        // one match was recorded before the resume, so only one more is
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.count_earlier_matches(
+            &[
+                NeedleValFound::from_needle_val_in_stream(&needle, 10),
+                NeedleValFound::from_needle_val_in_stream(&make_needle("other", b"Other"), 20),
+            ],
+            &search_assignment.needles,
+        );
+        do_search(&mut process_data_state, &search_assignment);
+
+        assert_eq!(
+            process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|needle_val_found| needle_val_found.match_start_global_offset)
+                .collect::<Vec<u64>>(),
+            vec![100]
+        );
+        assert_eq!(process_data_state.capped_match_counts["hello"], 2);
+        assert!(!process_data_state.needle_match_counts.contains_key("other"));
+    }
+
+    #[test]
+    fn test_continue_jsonl_parts_when_resuming() {
+        let mut haystack = vec![0u8; 4096];
+        for pos in [100, 200, 300] {
+            haystack[pos..(pos + 5)].copy_from_slice(b"Hello");
+        }
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.jsonl_max_part_records = Some(2);
+
+        // the first run writes 2 records to part 1 and 1 to part 2
+        do_search(&mut make_state_with_haystack(&haystack), &search_assignment);
+
+        // the resumed run continues in part 2, then part 3
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.haystack_chunk_start_global_offset = 4096;
+        process_data_state
+            .continue_jsonl_parts(&search_assignment)
+            .unwrap();
+        assert_eq!(process_data_state.jsonl_part_number, 2);
+        assert_eq!(process_data_state.jsonl_part_record_count, 1);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let part_offsets: Vec<Vec<u64>> = search_assignment
+            .all_jsonl_file_paths()
+            .iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .unwrap()
+                    .lines()
+                    .map(|line| {
+                        serde_json::from_str::<NeedleValFound>(line)
+                            .unwrap()
+                            .match_start_global_offset
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            part_offsets,
+            vec![vec![100, 200], vec![300, 4196], vec![4296, 4396]]
+        );
+    }
+
+    #[test]
+    fn test_do_search_min_preceding_run() {
+        let mut haystack: Vec<u8> = (0..4096).map(|i| (i % 200) as u8 + 50).collect();
+        haystack[100..104].copy_from_slice(b"REC1");
+        haystack[1488..2000].fill(0x00);
+        haystack[2000..2004].copy_from_slice(b"REC1");
+
+        for (min_preceding_run, expected_offsets) in [
+            (None, vec![100, 2000]),
+            (Some(512), vec![2000]),
+            (Some(513), vec![]),
+        ] {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let mut needle = make_needle("record", b"REC1");
+            needle.min_preceding_run = min_preceding_run;
+            let search_assignment = make_search_assignment(tmp_dir.path(), vec![needle]);
+
+            let mut process_data_state = make_state_with_haystack(&haystack);
+            do_search(&mut process_data_state, &search_assignment);
+
+            let found_offsets: Vec<u64> = process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect();
+            assert_eq!(found_offsets, expected_offsets);
+        }
+    }
+
+    #[test]
+    fn test_do_search_max_matches_per_chunk() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // every needle matches once
+        let mut haystack = vec![0u8; 4096];
+        let needles: Vec<Needle> = (1..=20)
+            .map(|needle_number| {
+                let needle_val = format!("needle_{:02}", needle_number);
+                let pos = needle_number * 100;
+                haystack[pos..(pos + needle_val.len())].copy_from_slice(needle_val.as_bytes());
+                make_needle(&needle_val, needle_val.as_bytes())
+            })
+            .collect();
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), needles);
+        search_assignment.max_matches_per_chunk = Some(5);
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let found_names: Vec<&str> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.name.as_str())
+            .collect();
+        assert_eq!(
+            found_names,
+            vec![
+                "needle_01",
+                "needle_02",
+                "needle_03",
+                "needle_04",
+                "needle_05"
+            ]
+        );
+        let entries: Vec<&ErrorSummaryEntry> =
+            process_data_state.error_collector.entries().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, "max_matches_per_chunk_exceeded");
+        assert_eq!(entries[0].count, 15);
+
+        // the next chunk is searched as usual
+        do_search(&mut process_data_state, &search_assignment);
+        assert_eq!(process_data_state.needle_vals_found.len(), 10);
+    }
+
+    #[test]
+    fn test_do_search_needle_alignment() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // the chunk starts at global offset 1000, so position 512 in it isn't aligned
+        let mut haystack = vec![0u8; 4096];
+        haystack[24..29].copy_from_slice(b"Hello");
+        haystack[512..517].copy_from_slice(b"Hello");
+        let mut aligned_needle = make_needle("hello", b"Hello");
+        aligned_needle.alignment = Some(512);
+        let search_assignment = make_search_assignment(tmp_dir.path(), vec![aligned_needle]);
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.haystack_chunk_start_global_offset = 1000;
+        do_search(&mut process_data_state, &search_assignment);
+
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![1024]);
+    }
+
+    #[test]
+    fn test_do_search_suppress_if_covered_by() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        // the short needles alone, then inside the long one
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"world");
+        haystack[1000..1012].copy_from_slice(b"Hello, world");
+        let mut short_needle = make_needle("hello", b"Hello");
+        short_needle.suppress_if_covered_by = vec!["hello_world".to_string()];
+        let mut inner_needle = make_needle("world", b"world");
+        inner_needle.suppress_if_covered_by = vec!["hello_world".to_string()];
+        let long_needle = make_needle("hello_world", b"Hello, world");
+        let search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![short_needle, inner_needle, long_needle],
+        );
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let found: Vec<(&str, u64)> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| (found.name.as_str(), found.match_start_global_offset))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("hello", 100), ("world", 200), ("hello_world", 1000)]
+        );
+    }
+
+    #[test]
+    fn test_do_search_dedup() {
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[2000..2005].copy_from_slice(b"Hello");
+
+        for (dedup, expected_match_count) in [(false, 4), (true, 2)] {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let mut search_assignment =
+                make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+            search_assignment.dedup = dedup;
+
+            // the same chunk, searched twice
+            let mut process_data_state = make_state_with_haystack(&haystack);
+            do_search(&mut process_data_state, &search_assignment);
+            do_search(&mut process_data_state, &search_assignment);
+
+            assert_eq!(
+                process_data_state.needle_vals_found.len(),
+                expected_match_count
+            );
+            let jsonl = fs::read_to_string(
+                search_assignment
+                    .jsonl_output_log_file_path
+                    .as_ref()
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(jsonl.lines().count(), expected_match_count);
+            match dedup {
+                true => {
+                    assert_eq!(process_data_state.dedup_skipped_match_count, 2);
+                    assert_eq!(process_data_state.recorded_match_keys.len(), 2);
+                    assert!(
+                        process_data_state.recorded_match_keys_memory_bytes()
+                            >= 2 * (std::mem::size_of::<(String, u64)>() + 1 + "hello".len())
+                    );
+                }
+                false => assert!(process_data_state.recorded_match_keys.is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_do_search_writes_strings_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[1000..1012].copy_from_slice(b"user=alice\r\n");
+        haystack[1100..1105].copy_from_slice(b"Hello");
+        haystack[1200..1214].copy_from_slice(b"C:\\wallet.dat\x00");
+        let mut strings_needle = make_needle("hello", b"Hello");
+        strings_needle.strings = true;
+        let search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![strings_needle, make_needle("wallet", b"wallet.dat")],
+        );
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.haystack_chunk_start_global_offset = 0x10000;
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        let dump_file_path = PathBuf::from(
+            process_data_state.needle_vals_found[0]
+                .dump_file_path
+                .as_ref()
+                .unwrap(),
+        );
+        assert_eq!(
+            fs::read_to_string(strings_file_path(&dump_file_path)).unwrap(),
+            "0x0000_0001_03E8: user=alice\n0x0000_0001_044C: Hello\n0x0000_0001_04B0: C:\\wallet.dat\n"
+        );
+        // only for the needles with `strings: true`
+        let dump_file_path = PathBuf::from(
+            process_data_state.needle_vals_found[1]
+                .dump_file_path
+                .as_ref()
+                .unwrap(),
+        );
+        assert!(dump_file_path.exists());
+        assert!(!strings_file_path(&dump_file_path).exists());
+    }
+
+    #[test]
+    fn test_do_search_needle_max_matches() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        for pos in [100, 1100, 2100] {
+            haystack[pos..(pos + 5)].copy_from_slice(b"Hello");
+        }
+        haystack[3000..3005].copy_from_slice(b"World");
+        let mut capped_needle = make_needle("hello", b"Hello");
+        capped_needle.max_matches = Some(2);
+        let search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![capped_needle, make_needle("world", b"World")],
+        );
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        let found: Vec<(&str, u64)> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| (found.name.as_str(), found.match_start_global_offset))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("hello", 100), ("hello", 1100), ("world", 3000)]
+        );
+        let dump_file_count = fs::read_dir(tmp_dir.path().join("1_hello"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("bin".as_ref()))
+            .count();
+        assert_eq!(dump_file_count, 2);
+        let jsonl = fs::read_to_string(tmp_dir.path().join("00_all_output_record.jsonl")).unwrap();
+        assert_eq!(jsonl.lines().count(), 3);
+        assert_eq!(
+            process_data_state.capped_match_counts,
+            BTreeMap::from([("hello".to_string(), 1)])
+        );
+        let entries: Vec<&ErrorSummaryEntry> =
+            process_data_state.error_collector.entries().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, "needle_max_matches_reached");
+        assert_eq!(entries[0].example_offsets, vec![2100]);
+
+        // later matches are only counted
+        do_search(&mut process_data_state, &search_assignment);
+        assert_eq!(process_data_state.needle_vals_found.len(), 4);
+        assert_eq!(process_data_state.capped_match_counts["hello"], 4);
+    }
+
+    #[test]
+    fn test_do_search_dry_run() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[2000..2005].copy_from_slice(b"Hello");
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.dry_run = true;
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        // only the global JSONL file, with both matches
+        let file_names: Vec<String> = fs::read_dir(tmp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(file_names, vec!["00_all_output_record.jsonl"]);
+        let jsonl = fs::read_to_string(tmp_dir.path().join("00_all_output_record.jsonl")).unwrap();
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(process_data_state
+            .needle_vals_found
+            .iter()
+            .all(|found| found.dump_file_path.is_none()));
+    }
+
+    #[test]
+    fn test_do_search_writes_sqlite_records() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        for pos in [100, 1100, 2100] {
+            haystack[pos..(pos + 5)].copy_from_slice(b"Hello");
+        }
+        haystack[3000..3005].copy_from_slice(b"World");
+        let search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("hello", b"Hello"),
+                make_needle("world", b"World"),
+            ],
+        );
+        let db_file_path = tmp_dir.path().join("finds.sqlite");
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        process_data_state.sqlite_writer = Some(SqliteWriter::open(&db_file_path).unwrap());
+        do_search(&mut process_data_state, &search_assignment);
+        process_data_state.finish_sqlite_writes();
+
+        let connection = rusqlite::Connection::open(&db_file_path).unwrap();
+        let row_count: u64 = connection
+            .query_row("SELECT COUNT(*) FROM needle_finds", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(row_count, 4);
+
+        let query = "SELECT match_start_global_offset FROM needle_finds WHERE name = ?1 ORDER BY match_start_global_offset";
+        let query_plan: String = connection
+            .query_row(&format!("EXPLAIN QUERY PLAN {}", query), ["hello"], |row| {
+                row.get(3)
+            })
+            .unwrap();
+        assert!(query_plan.contains("idx_needle_finds_name"));
+        let hello_offsets: Vec<u64> = connection
+            .prepare(query)
+            .unwrap()
+            .query_map(["hello"], |row| row.get(0))
+            .unwrap()
+            .map(|offset| offset.unwrap())
+            .collect();
+        assert_eq!(hello_offsets, vec![100, 1100, 2100]);
+    }
+
+    #[test]
+    fn test_do_search_write_to_file_override() {
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"World");
+        let mut world_needle = make_needle("world", b"World");
+        world_needle.write_to_file = false;
+        let needles = vec![make_needle("hello", b"Hello"), world_needle];
+
+        for (write_to_file, expected_dump_count) in [(None, 1), (Some(false), 0), (Some(true), 2)] {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let search_assignment = make_search_assignment(
+                tmp_dir.path(),
+                override_write_to_file(needles.clone(), write_to_file),
+            );
+
+            let mut process_data_state = make_state_with_haystack(&haystack);
+            do_search(&mut process_data_state, &search_assignment);
+            process_data_state.finish_dump_writes();
+
+            assert_eq!(process_data_state.needle_vals_found.len(), 2);
+            let dump_file_paths: Vec<String> = process_data_state
+                .needle_vals_found
+                .iter()
+                .filter_map(|found| found.dump_file_path.clone())
+                .collect();
+            assert_eq!(dump_file_paths.len(), expected_dump_count);
+            for dump_file_path in &dump_file_paths {
+                assert!(Path::new(dump_file_path).is_file());
+            }
+        }
+    }
+
+    #[test]
+    fn test_do_search_jsonl_crlf_line_ending() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[200..205].copy_from_slice(b"World");
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("hello", b"Hello"),
+                make_needle("world", b"World"),
+            ],
+        );
+        search_assignment.jsonl_line_ending = LineEnding::CrLf;
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let jsonl_contents = fs::read_to_string(
+            search_assignment
+                .jsonl_output_log_file_path
+                .as_ref()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(jsonl_contents.matches("}\r\n{").count(), 1);
+        assert!(jsonl_contents.ends_with("}\r\n"));
+        assert_eq!(jsonl_contents.matches('\n').count(), 2);
+        // still readable by the summary
+        assert!(log_polars_summary(&search_assignment.all_jsonl_file_paths()).is_ok());
+    }
+
+    #[test]
+    fn test_do_search_jsonl_val_array() {
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+
+        for (jsonl_val_array, expected_val_json) in [
+            (false, r#""val_hex":"48656c6c6f""#),
+            (true, r#""val":[72,101,108,108,111]"#),
+        ] {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let mut search_assignment =
+                make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+            search_assignment.jsonl_val_array = jsonl_val_array;
+
+            do_search(&mut make_state_with_haystack(&haystack), &search_assignment);
+
+            let jsonl = fs::read_to_string(
+                search_assignment
+                    .jsonl_output_log_file_path
+                    .as_ref()
+                    .unwrap(),
+            )
+            .unwrap();
+            assert!(jsonl.contains(expected_val_json));
+            let record: NeedleValFound = serde_json::from_str(jsonl.trim_end()).unwrap();
+            assert_eq!(record.val.bytes(), b"Hello");
+            assert!(log_polars_summary(&search_assignment.all_jsonl_file_paths()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_do_search_jsonl_context_hexdump() {
+        let mut haystack = vec![b'.'; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut needle = make_needle("hello", b"Hello");
+        needle.byte_count_before_match = 20;
+        needle.byte_count_after_match = 11;
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), vec![needle]);
+        search_assignment.jsonl_context_hexdump = true;
+
+        do_search(&mut make_state_with_haystack(&haystack), &search_assignment);
+
+        let jsonl = fs::read_to_string(
+            search_assignment
+                .jsonl_output_log_file_path
+                .as_ref()
+                .unwrap(),
+        )
+        .unwrap();
+        let record: NeedleValFound = serde_json::from_str(jsonl.trim_end()).unwrap();
+        // 20 bytes before and 11 after the match: 0x50 to 0x74
+        let context_hexdump = record.context_hexdump.unwrap();
+        let lines: Vec<&str> = context_hexdump.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0x0000_0000_0050: 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e 2e |................|",
+                "0x0000_0000_0060: 2e 2e 2e 2e 48 65 6c 6c 6f 2e 2e 2e 2e 2e 2e 2e |....Hello.......|",
+                "0x0000_0000_0070: 2e 2e 2e 2e                                     |....|",
+            ]
+        );
+        // the match at 0x64 is the 5th byte of its line
+        let hex_column = "0x0000_0000_0060: ".len() + 4 * "2e ".len();
+        assert_eq!(&lines[1][hex_column..(hex_column + 14)], "48 65 6c 6c 6f");
+
+        // not in the record without the flag
+        search_assignment.jsonl_context_hexdump = false;
+        fs::remove_file(
+            search_assignment
+                .jsonl_output_log_file_path
+                .as_ref()
+                .unwrap(),
+        )
+        .unwrap();
+        do_search(&mut make_state_with_haystack(&haystack), &search_assignment);
+        let jsonl = fs::read_to_string(
+            search_assignment
+                .jsonl_output_log_file_path
+                .as_ref()
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(!jsonl.contains("context_hexdump"));
+    }
+
+    #[test]
+    fn test_do_search_text_regions_only() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // binary noise, with the keyword in it first, then a text region with the keyword
+        let mut haystack: Vec<u8> = (0..4096).map(|i| (i % 7) as u8 + 0xF0).collect();
+        haystack[500..508].copy_from_slice(b"password");
+        let text_region = b"username=admin password=hunter2";
+        haystack[3000..(3000 + text_region.len())].copy_from_slice(text_region);
+        haystack[1000..1002].copy_from_slice(&[0xF1, 0xF2]);
+
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("password", b"password"),
+                make_needle("binary", &[0xF1, 0xF2]),
+            ],
+        );
+        search_assignment.text_regions_min_run_len = Some(16);
+        assert!(search_assignment.needles[0].is_text());
+        assert!(!search_assignment.needles[1].is_text());
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let found: Vec<(&str, u64)> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| (found.name.as_str(), found.match_start_global_offset))
+            .collect();
+        // the binary needle still matches anywhere (in the noise every 7 bytes, from 1)
+        assert_eq!(found[0], ("password", 3015));
+        assert_eq!(found[1], ("binary", 1));
+        assert!(found[1..].iter().all(|(name, _)| *name == "binary"));
+        assert!(found.len() > 500);
+    }
+
+    #[test]
+    fn test_do_search_text_regions_only_binary_regex() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // a JPEG start in binary noise, outside any text region
+        let mut haystack: Vec<u8> = (0..4096).map(|i| (i % 7) as u8 + 0xF0).collect();
+        haystack[1000..1003].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+
+        let pattern = r"(?-u)\xFF\xD8\xFF";
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![Needle {
+                regex: Some(regex::bytes::Regex::new(pattern).unwrap()),
+                ..make_needle("jpeg", pattern.as_bytes())
+            }],
+        );
+        search_assignment.text_regions_min_run_len = Some(16);
+        // the pattern text is printable, but the needle isn't a text needle
+        assert!(!search_assignment.needles[0].is_text());
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        assert_eq!(
+            process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect::<Vec<u64>>(),
+            vec![1000]
+        );
+    }
+
+    #[test]
+    fn test_do_search_records_truncated_dumps_in_error_summary() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut haystack = vec![0u8; 8192];
+        haystack[100..105].copy_from_slice(b"Start"); // before the context at the start of the stream
+        haystack[4000..4005].copy_from_slice(b"Fits!"); // full context
+        haystack[8000..8005].copy_from_slice(b"Close"); // after-context cut off by the end of the chunk
+        let search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("start", b"Start"),
+                make_needle("fits", b"Fits!"),
+                make_needle("close", b"Close"),
+            ],
+        );
+
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+
+        let entries: Vec<&ErrorSummaryEntry> =
+            process_data_state.error_collector.entries().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, "dump_context_truncated");
+        assert_eq!(entries[0].count, 1);
+        assert_eq!(entries[0].example_offsets, vec![8000]);
+    }
+
+    /// Runs `search_stream` over an in-memory stream, with 4 KiB chunks (3 KiB fresh + 1 KiB carry-forward).
+    fn search_test_stream(stream: &[u8], search_assignment: &SearchAssignment) -> ProcessDataState {
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        search_stream(
+            &mut io::Cursor::new(stream),
+            &mut process_data_state,
+            search_assignment,
+            |_, _| {},
+        );
+        process_data_state
+    }
+
+    #[test]
+    fn test_search_stream_reads_all_concatenated_members() {
+        // the needle is only in the second member
+        let mut first_member = vec![0x11u8; 10_000];
+        first_member[5000..5005].copy_from_slice(b"First");
+        let mut second_member = vec![0x22u8; 10_000];
+        second_member[3000..3005].copy_from_slice(b"Hello");
+
+        let xz_compress = |data: &[u8]| {
+            let mut xz_encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+            xz_encoder.write_all(data).unwrap();
+            xz_encoder.finish().unwrap()
+        };
+        let mut concatenated_xz = xz_compress(&first_member);
+        concatenated_xz.extend(xz_compress(&second_member));
+        let mut concatenated_lz4 = lz4_compress(&first_member);
+        concatenated_lz4.extend(lz4_compress(&second_member));
+
+        let decoders: Vec<Box<dyn Read>> = vec![
+            Box::new(xz2::read::XzDecoder::new_multi_decoder(io::Cursor::new(
+                concatenated_xz,
+            ))),
+            Box::new(MultiFrameLz4Decoder::new(io::Cursor::new(concatenated_lz4))),
+        ];
+        for mut decoder in decoders {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let search_assignment = make_search_assignment(
+                tmp_dir.path(),
+                vec![
+                    make_needle("first", b"First"),
+                    make_needle("hello", b"Hello"),
+                ],
+            );
+            let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+            search_stream(
+                &mut decoder,
+                &mut process_data_state,
+                &search_assignment,
+                |_, _| {},
+            );
+
+            assert_eq!(process_data_state.total_haystack_bytes_read, 20_000);
+            let found: Vec<(&str, u64)> = process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| (found.name.as_str(), found.match_start_global_offset))
+                .collect();
+            assert_eq!(found, vec![("first", 5000), ("hello", 13_000)]);
+        }
+    }
+
+    /// Returns at most `max_read_len` bytes per read, like a decoder that returns short reads.
+    struct ShortReader<R: Read> {
+        reader: R,
+        max_read_len: usize,
+    }
+
+    impl<R: Read> Read for ShortReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read_len = buf.len().min(self.max_read_len);
+            self.reader.read(&mut buf[..read_len])
+        }
+    }
+
+    #[test]
+    fn test_search_stream_xz_with_short_reads() {
+        let mut stream: Vec<u8> = (0..20_000).map(|i| (i % 7) as u8 + b'a').collect();
+        for pos in [3000, 4093, 15_000] {
+            stream[pos..(pos + 5)].copy_from_slice(b"Hello"); // the second spans chunks 0 and 1
+        }
+        let mut xz_encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz_encoder.write_all(&stream).unwrap();
+        let compressed = xz_encoder.finish().unwrap();
+
+        let mut process_data_states: Vec<ProcessDataState> = Vec::new();
+        for max_read_len in [usize::MAX, 1000, 1] {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let search_assignment =
+                make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+            let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+            search_stream(
+                &mut ShortReader {
+                    reader: xz2::read::XzDecoder::new(io::Cursor::new(&compressed)),
+                    max_read_len,
+                },
+                &mut process_data_state,
+                &search_assignment,
+                |_, _| {},
+            );
+            process_data_states.push(process_data_state);
+        }
+
+        for process_data_state in &process_data_states {
+            let found_offsets: Vec<u64> = process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect();
+            assert_eq!(found_offsets, vec![3000, 4093, 15_000]);
+            assert_eq!(process_data_state.total_haystack_bytes_read, 20_000);
+            // short reads are filled out, so the chunks are the same as for an uncompressed file
+            assert_eq!(process_data_state.chunk_count, 7);
+            assert_eq!(process_data_state.partial_chunk_read_count, 1);
+        }
+    }
+
+    /// Requests a stop (like Ctrl-C) as soon as it's read from.
+    struct StopRequestingReader<R: Read> {
+        reader: R,
+        stop_requested: Arc<AtomicBool>,
+    }
+
+    impl<R: Read> Read for StopRequestingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.stop_requested.store(true, Ordering::Relaxed);
+            self.reader.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_search_stream_stops_when_requested() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut stream = vec![0x11u8; 20_000];
+        stream[1000..1005].copy_from_slice(b"Hello"); // in the first chunk
+        stream[10_000..10_005].copy_from_slice(b"Hello");
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        search_stream(
+            &mut StopRequestingReader {
+                reader: io::Cursor::new(&stream),
+                stop_requested: search_assignment.stop_requested.clone(),
+            },
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+        process_data_state.finish_dump_writes();
+
+        // the first chunk is finished, and its match recorded and dumped
+        assert!(process_data_state.interrupted);
+        assert_eq!(process_data_state.chunk_count, 1);
+        assert_eq!(process_data_state.total_haystack_bytes_read, 4096);
+        assert_eq!(process_data_state.needle_vals_found.len(), 1);
+        assert!(PathBuf::from(
+            process_data_state.needle_vals_found[0]
+                .dump_file_path
+                .as_ref()
+                .unwrap()
+        )
+        .exists());
+
+        // and the summary covers it
+        let summary_csv_file_path = tmp_dir.path().join("03_summary.csv");
+        write_summary_csv_file(
+            &search_assignment.all_jsonl_file_paths(),
+            &summary_csv_file_path,
+        )
+        .unwrap();
+        let summary_csv = fs::read_to_string(&summary_csv_file_path).unwrap();
+        assert_eq!(summary_csv.lines().count(), 2, "{}", summary_csv);
+        assert!(summary_csv.lines().nth(1).unwrap().starts_with("hello,1,"));
+    }
+
+    #[test]
+    fn test_search_stream_records_hotspot() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut stream = vec![0x11u8; 64 * 1024];
+        // isolated matches, before and after a dense cluster (which crosses a chunk boundary)
+        stream[2000..2004].copy_from_slice(b"KeyA");
+        for (i, val) in [b"KeyA", b"KeyB", b"KeyC", b"KeyD", b"KeyE"]
+            .iter()
+            .enumerate()
+        {
+            let offset = 20_000 + i * 100;
+            stream[offset..(offset + 4)].copy_from_slice(*val);
+        }
+        stream[60_000..60_004].copy_from_slice(b"KeyA");
+        let needles: Vec<Needle> = ["KeyA", "KeyB", "KeyC", "KeyD", "KeyE"]
+            .iter()
+            .map(|name| make_needle(name, name.as_bytes()))
+            .collect();
+        let search_assignment = make_search_assignment(tmp_dir.path(), needles);
+
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state.hotspot_detector = Some(HotspotDetector::new(1000, 4));
+        search_stream(
+            &mut io::Cursor::new(&stream),
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+
+        assert_eq!(process_data_state.needle_vals_found.len(), 7);
+        let hotspots: Vec<Hotspot> =
+            fs::read_to_string(search_assignment.hotspots_jsonl_file_path())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+        assert_eq!(
+            hotspots,
+            vec![Hotspot {
+                first_match_global_offset: 20_000,
+                last_match_global_offset: 20_400,
+                match_count: 5,
+                needle_names: ["KeyA", "KeyB", "KeyC", "KeyD", "KeyE"]
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_search_stream_sample_every_nth_chunk() {
+        // one needle in the fresh data of each of chunks 0 to 3
+        let mut stream: Vec<u8> = (0..(3072 * 4)).map(|i| (i % 200) as u8).collect();
+        let needles: Vec<Needle> = (0..4)
+            .map(|chunk_idx| {
+                let val = format!("NEEDLE_{}", chunk_idx).into_bytes();
+                let pos = chunk_idx * 3072 + 2000;
+                stream[pos..(pos + val.len())].copy_from_slice(&val);
+                make_needle(&format!("chunk_{}", chunk_idx), &val)
+            })
+            .collect();
+
+        let full_tmp_dir = tempfile::tempdir().unwrap();
+        let full_state = search_test_stream(
+            &stream,
+            &make_search_assignment(full_tmp_dir.path(), needles.clone()),
+        );
+
+        let sampled_tmp_dir = tempfile::tempdir().unwrap();
+        let mut sampled_search_assignment =
+            make_search_assignment(sampled_tmp_dir.path(), needles.clone());
+        sampled_search_assignment.sample_every_nth_chunk = Some(2);
+        let sampled_state = search_test_stream(&stream, &sampled_search_assignment);
+
+        // only chunks 0 and 2 were searched
+        let sampled_names: Vec<&str> = sampled_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.name.as_str())
+            .collect();
+        assert_eq!(sampled_names, vec!["chunk_0", "chunk_2"]);
+        assert_eq!(sampled_state.chunk_count, full_state.chunk_count);
+
+        // offsets are the true stream offsets, and the same as in the full scan
+        assert_eq!(
+            sampled_state.needle_vals_found[0].match_start_global_offset,
+            2000
+        );
+        assert_eq!(
+            sampled_state.needle_vals_found[1].match_start_global_offset,
+            2 * 3072 + 2000
+        );
+        for sampled_found in &sampled_state.needle_vals_found {
+            let full_found = full_state
+                .needle_vals_found
+                .iter()
+                .find(|found| found.name == sampled_found.name)
+                .unwrap();
+            assert_eq!(
+                sampled_found.match_start_global_offset,
+                full_found.match_start_global_offset
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_stream_min_preceding_run_across_chunks() {
+        let mut stream: Vec<u8> = (0..20_000).map(|i| (i % 200) as u8 + 1).collect();
+        // a 2048-byte run that crosses the chunk 0/1 boundary (at 4096), then the record
+        stream[3000..5048].fill(0x00);
+        stream[5048..5052].copy_from_slice(b"REC1");
+        // a run that's too short
+        stream[12_000..13_000].fill(0x00);
+        stream[13_000..13_004].copy_from_slice(b"REC1");
+        let mut needle = make_needle("record", b"REC1");
+        needle.min_preceding_run = Some(2048);
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let needles = vec![needle];
+        let search_assignment = SearchAssignment {
+            haystack_carry_forward_len_bytes: haystack_carry_forward_len_bytes_for(&needles),
+            ..make_search_assignment(tmp_dir.path(), needles)
+        };
+        assert_eq!(search_assignment.haystack_carry_forward_len_bytes, 2051);
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![5048]);
+    }
+
+    #[test]
+    fn test_search_stream_match_spanning_carry_forward_boundary() {
+        // Chunk 0 is stream[0..4096]. Chunk 1 carries forward stream[3072..4096], then reads fresh
+        // data from 4096. Place the needle so `split` of its bytes are in the carry-forward.
+        let needle_val = b"0123456789ABCDEF";
+        for split in 0..=needle_val.len() {
+            let mut stream: Vec<u8> = (0..12000).map(|i| (i % 7) as u8 + b'a').collect();
+            let needle_pos = 4096 - split;
+            stream[needle_pos..(needle_pos + needle_val.len())].copy_from_slice(needle_val);
+
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let search_assignment =
+                make_search_assignment(tmp_dir.path(), vec![make_needle("spanning", needle_val)]);
+            let process_data_state = search_test_stream(&stream, &search_assignment);
+
+            let found_offsets: Vec<u64> = process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect();
+            assert_eq!(found_offsets, vec![needle_pos as u64], "split: {}", split);
+        }
+    }
+
+    #[test]
+    fn test_search_stream_counts_each_chunk_once() {
+        // chunk 0 is stream[0..4096], and chunk 1 has fresh data from 4096
+        let mut stream: Vec<u8> = (0..7168).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[1000..1005].copy_from_slice(b"Hello");
+        stream[5000..5005].copy_from_slice(b"Hello");
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+
+        assert_eq!(process_data_state.chunk_count, 2);
+        assert_eq!(process_data_state.total_haystack_bytes_read, 7168);
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![1000, 5000]);
+    }
+
+    #[test]
+    fn test_search_stream_long_needle_across_chunk_boundary() {
+        let needle_val: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        let needles = vec![make_needle("long", &needle_val)];
+        assert_eq!(haystack_carry_forward_len_bytes_for(&needles), 1999);
+        assert_eq!(
+            haystack_carry_forward_len_bytes_for(&[make_needle("short", b"Hello")]),
+            DEFAULT_HAYSTACK_CARRY_FORWARD_LEN_BYTES
+        );
+
+        // chunk 0 is stream[0..8192], and the needle starts 1500 bytes before its end
+        let mut stream: Vec<u8> = (0..30_000).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[6692..(6692 + needle_val.len())].copy_from_slice(&needle_val);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), needles);
+        search_assignment.haystack_carry_forward_len_bytes =
+            haystack_carry_forward_len_bytes_for(&search_assignment.needles);
+        let mut process_data_state = ProcessDataState::new(8192, 100, 4);
+        search_stream(
+            &mut io::Cursor::new(&stream),
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![6692]);
+    }
+
+    #[test]
+    fn test_is_uniform_chunk() {
+        let mut chunk = vec![0u8; 8 * 1024 * 1024];
+        assert!(is_uniform_chunk(&chunk));
+        assert!(is_uniform_chunk(&[0xFF; 100]));
+        assert!(is_uniform_chunk(&[]));
+
+        // differing at byte 1, in a later block, and in the partial block at the end
+        chunk[1] = 1;
+        assert!(!is_uniform_chunk(&chunk));
+        chunk[1] = 0;
+        chunk[5000] = 1;
+        assert!(!is_uniform_chunk(&chunk));
+        assert!(!is_uniform_chunk(&chunk[..5001]));
+        assert!(is_uniform_chunk(&chunk[..5000]));
+    }
+
+    #[test]
+    fn test_search_stream_finds_uniform_needle_in_uniform_chunks() {
+        // all zeros, except a marker in the last chunk (so the last chunk isn't uniform)
+        let mut stream = vec![0u8; 12000];
+        stream[11000..11005].copy_from_slice(b"Hello");
+
+        let needles = vec![
+            make_needle("zeros", &[0x00; 8]),
+            make_needle("hello", b"Hello"),
+        ];
+        assert_eq!(needles[0].uniform_byte_vals(), vec![0x00]);
+        assert!(needles[1].uniform_byte_vals().is_empty());
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment = make_search_assignment(tmp_dir.path(), needles);
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+        let found: Vec<(&str, u64)> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| (found.name.as_str(), found.match_start_global_offset))
+            .collect();
+        // the first chunk is all zeros, and still searched
+        assert_eq!(found[0], ("zeros", 0));
+        assert!(found.contains(&("hello", 11000)));
+    }
+
+    #[test]
+    fn test_search_stream_resume_from_checkpoint_matches_uninterrupted_run() {
+        let mut stream: Vec<u8> = (0..30000).map(|i| (i % 7) as u8 + b'a').collect();
+        // the run is interrupted after 3 chunks (at 10240): matches before, in the carry-forward
+        // of the resumed run, across the interruption, and after
+        for offset in [500, 7000, 9500, 10238, 20000] {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        let interrupted_len = 4096 + 3072 * 2;
+        let search = |input_reader: &mut io::Cursor<&[u8]>,
+                      process_data_state: &mut ProcessDataState,
+                      search_assignment: &SearchAssignment| {
+            search_stream(
+                input_reader,
+                process_data_state,
+                search_assignment,
+                |_, _| {},
+            );
+            process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect::<Vec<u64>>()
+        };
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.checkpoint_interval_sec = Some(0.0);
+        search_assignment.config_hash = "config".to_string();
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        let mut found_offsets = search(
+            &mut io::Cursor::new(&stream[..interrupted_len]),
+            &mut process_data_state,
+            &search_assignment,
+        );
+        assert_eq!(process_data_state.chunk_count, 3);
+
+        let checkpoint =
+            Checkpoint::read_from_json_file(&search_assignment.checkpoint_file_path()).unwrap();
+        assert_eq!(
+            checkpoint.searched_end_global_offset,
+            interrupted_len as u64
+        );
+        assert!(checkpoint.check_resumable("config").is_ok());
+
+        // resume, appending to the same results
+        let carried_len = search_assignment.haystack_carry_forward_len_bytes;
+        let mut input_reader = io::Cursor::new(&stream[..]);
+        input_reader.set_position(checkpoint.searched_end_global_offset - carried_len as u64);
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state
+            .resume_from_checkpoint(
+                &mut input_reader,
+                checkpoint.searched_end_global_offset,
+                carried_len,
+            )
+            .unwrap();
+        found_offsets.extend(search(
+            &mut input_reader,
+            &mut process_data_state,
+            &search_assignment,
+        ));
+
+        let uninterrupted_tmp_dir = tempfile::tempdir().unwrap();
+        let uninterrupted_search_assignment = make_search_assignment(
+            uninterrupted_tmp_dir.path(),
+            vec![make_needle("hello", b"Hello")],
+        );
+        let uninterrupted_found_offsets = search(
+            &mut io::Cursor::new(&stream[..]),
+            &mut ProcessDataState::new(4096, 100, 4),
+            &uninterrupted_search_assignment,
+        );
+        assert_eq!(
+            uninterrupted_found_offsets,
+            vec![500, 7000, 9500, 10238, 20000]
+        );
+        assert_eq!(found_offsets, uninterrupted_found_offsets);
+
+        // the same records in the JSONL output
+        let count_jsonl_records = |dir: &Path| {
+            fs::read_to_string(dir.join("00_all_output_record.jsonl"))
+                .unwrap()
+                .lines()
+                .count()
+        };
+        assert_eq!(count_jsonl_records(tmp_dir.path()), 5);
+        assert_eq!(count_jsonl_records(uninterrupted_tmp_dir.path()), 5);
+    }
+
+    #[test]
+    fn test_search_file_in_parallel_matches_single_threaded_search() {
+        let mut stream: Vec<u8> = (0..40_000).map(|i| (i % 7) as u8 + b'a').collect();
+        for offset in (0..(stream.len() - 5)).step_by(997) {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        // across chunk boundaries (at 4096, then every 3072 bytes), some at the threads' ones
+        for offset in [4094, 10238, 16382, 28670] {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let input_file_path = tmp_dir.path().join("disk.img");
+        fs::write(&input_file_path, &stream).unwrap();
+        let read_output = |output_dir_path: &Path| {
+            let mut dump_file_names: Vec<String> = fs::read_dir(output_dir_path.join("1_hello"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                .filter(|file_name| file_name.ends_with(".bin"))
+                .collect();
+            dump_file_names.sort();
+            let jsonl =
+                fs::read_to_string(output_dir_path.join("00_all_output_record.jsonl")).unwrap();
+            (dump_file_names, jsonl)
+        };
+
+        let single_threaded_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(
+            single_threaded_dir.path(),
+            vec![make_needle("hello", b"Hello")],
+        );
+        search_assignment.deterministic = true;
+        let mut single_threaded_state = ProcessDataState::new(4096, 100, 4);
+        search_stream(
+            &mut io::Cursor::new(&stream[..]),
+            &mut single_threaded_state,
+            &search_assignment,
+            |_, _| {},
+        );
+        single_threaded_state.finish_dump_writes();
+
+        let parallel_dir = tempfile::tempdir().unwrap();
+        search_assignment.output_dir_path = parallel_dir.path().to_path_buf();
+        search_assignment.jsonl_output_log_file_path =
+            Some(parallel_dir.path().join("00_all_output_record.jsonl"));
+        let mut parallel_state = ProcessDataState::new(4096, 100, 4);
+        search_file_in_parallel(
+            &input_file_path,
+            0..(stream.len() as u64),
+            4,
+            || ProcessDataState::new(4096, 100, 4),
+            &mut parallel_state,
+            &search_assignment,
+        )
+        .unwrap();
+        parallel_state.finish_dump_writes();
+
+        let found_offsets = |process_data_state: &ProcessDataState| {
+            process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect::<Vec<u64>>()
+        };
+        assert_eq!(found_offsets(&single_threaded_state).len(), 45);
+        assert_eq!(
+            found_offsets(&parallel_state),
+            found_offsets(&single_threaded_state)
+        );
+        assert_eq!(
+            parallel_state.chunk_count,
+            single_threaded_state.chunk_count
+        );
+        assert_eq!(
+            parallel_state.total_haystack_bytes_read,
+            stream.len() as u64
+        );
+        assert_eq!(
+            read_output(parallel_dir.path()),
+            read_output(single_threaded_dir.path())
+        );
+    }
+
+    #[test]
+    fn test_search_file_in_parallel_regex_match_across_threads() {
+        let mut stream: Vec<u8> = (0..40_000).map(|i| (i % 7) as u8 + b'a').collect();
+        // across the first threads' boundaries (chunks 4 and 8, at 13312 and 25600)
+        stream[13308..13316].copy_from_slice(b"01234567");
+        stream[25598..25604].copy_from_slice(b"012345");
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let input_file_path = tmp_dir.path().join("disk.img");
+        fs::write(&input_file_path, &stream).unwrap();
+
+        let output_tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment = make_search_assignment(
+            output_tmp_dir.path(),
+            vec![Needle {
+                regex: Some(regex::bytes::Regex::new("[0-9]+").unwrap()),
+                ..make_needle("digits", b"[0-9]+")
+            }],
+        );
+        let mut parallel_state = ProcessDataState::new(4096, 100, 4);
+        search_file_in_parallel(
+            &input_file_path,
+            0..(stream.len() as u64),
+            4,
+            || ProcessDataState::new(4096, 100, 4),
+            &mut parallel_state,
+            &search_assignment,
+        )
+        .unwrap();
+        parallel_state.finish_dump_writes();
+
+        // each reported once, by the thread after the boundary
+        assert_eq!(
+            parallel_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect::<Vec<u64>>(),
+            vec![13308, 25598]
+        );
+        assert_eq!(
+            fs::read_dir(output_tmp_dir.path().join("1_digits"))
+                .unwrap()
+                .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("bin".as_ref()))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_search_mapped_file_matches_stream_search() {
+        let mut stream: Vec<u8> = (0..20_000).map(|i| (i % 7) as u8 + b'a').collect();
+        // across chunk boundaries (at 4096, then every 3072 bytes), and at the start and end
+        for offset in [0, 2000, 4094, 7166, 10238, 15000, 19995] {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let input_file_path = tmp_dir.path().join("disk.img");
+        fs::write(&input_file_path, &stream).unwrap();
+
+        let search = |mapped_haystack: Option<MappedHaystack>| {
+            let output_tmp_dir = tempfile::tempdir().unwrap();
+            let mut search_assignment =
+                make_search_assignment(output_tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+            search_assignment.scan_window_context = true;
+            let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+            match mapped_haystack {
+                Some(mapped_haystack) => {
+                    process_data_state.mapped_haystack = Some(mapped_haystack);
+                    search_mapped_file(&mut process_data_state, &search_assignment, |_| {});
+                }
+                None => search_stream(
+                    &mut io::Cursor::new(&stream[..]),
+                    &mut process_data_state,
+                    &search_assignment,
+                    |_, _| {},
+                ),
+            }
+            process_data_state.finish_dump_writes();
+            let found = process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| {
+                    let dump_file_path = found.dump_file_path.as_ref().unwrap();
+                    (
+                        found.match_start_global_offset,
+                        fs::read(dump_file_path).unwrap(),
+                    )
+                })
+                .collect::<Vec<(u64, Vec<u8>)>>();
+            (found, process_data_state.chunk_count)
+        };
+
+        let (stream_found, stream_chunk_count) = search(None);
+        let mapped_haystack = MappedHaystack {
+            mmap: unsafe { Mmap::map(&fs::File::open(&input_file_path).unwrap()) }.unwrap(),
+            global_start_file_offset: 0,
+            window_len: 4096,
+        };
+        let (mapped_found, mapped_chunk_count) = search(Some(mapped_haystack));
+        assert_eq!(
+            stream_found
+                .iter()
+                .map(|(offset, _)| *offset)
+                .collect::<Vec<u64>>(),
+            vec![0, 2000, 4094, 7166, 10238, 15000, 19995]
+        );
+        assert_eq!(mapped_found, stream_found);
+        assert_eq!(mapped_chunk_count, stream_chunk_count);
+    }
+
+    #[test]
+    fn test_search_stream_start_and_end_offsets() {
+        let mut stream: Vec<u8> = (0..16000).map(|i| (i % 7) as u8 + b'a').collect();
+        // before the start, inside the range, across the end, and after the end
+        for offset in [1000, 6000, 8997, 12000] {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.end_global_offset = Some(9000);
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state.resume_from_offset(3000);
+        let mut input_reader = io::Cursor::new(&stream);
+        input_reader.set_position(3000);
+        search_stream(
+            &mut input_reader,
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+
+        // only the match inside the range, at its absolute offset
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![6000]);
+        assert_eq!(process_data_state.total_haystack_bytes_read, 9000);
+    }
+
+    #[test]
+    fn test_search_stream_resume_from_offset() {
+        let mut stream: Vec<u8> = (0..12000).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[1000..1005].copy_from_slice(b"Hello");
+        stream[9000..9005].copy_from_slice(b"Hello");
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state.coverage_verifier = Some(CoverageVerifier::new(1024));
+        process_data_state.resume_from_offset(5000);
+
+        // the caller positions the stream (e.g., by seeking the file)
+        let mut input_reader = io::Cursor::new(&stream);
+        input_reader.set_position(5000);
+        search_stream(
+            &mut input_reader,
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+
+        // only the later match, at its absolute offset
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![9000]);
+        assert_eq!(process_data_state.total_haystack_bytes_read, 12000);
+
+        let mut coverage_verifier = process_data_state.coverage_verifier.take().unwrap();
+        coverage_verifier.finish();
+        assert_eq!(coverage_verifier.problems, vec![]);
+    }
+
+    #[test]
+    fn test_search_stream_scan_window_context_across_chunk_boundaries() {
+        let mut stream: Vec<u8> = (0..12000).map(|i| (i % 251) as u8).collect();
+        // chunks: 0..4096, 3072..7168, 6144..10240, 9216..12000
+        stream[4000..4005].copy_from_slice(b"Hello"); // after-context runs into the next chunk
+        stream[5000..5005].copy_from_slice(b"World"); // before-context starts in the previous chunk
+        stream[11900..11905].copy_from_slice(b"Final"); // after-context runs past the end of the stream
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![
+                make_needle("hello", b"Hello"),
+                Needle {
+                    byte_count_before_match: 3000,
+                    byte_count_after_match: 16,
+                    ..make_needle("world", b"World")
+                },
+                make_needle("final", b"Final"),
+            ],
+        );
+        search_assignment.scan_window_context = true;
+        let mut process_data_state = search_test_stream(&stream, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        let dump_by_name: HashMap<&str, Vec<u8>> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| {
+                (
+                    found.name.as_str(),
+                    fs::read(found.dump_file_path.as_ref().unwrap()).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(dump_by_name["hello"], stream[(4000 - 1024)..(4005 + 1024)]);
+        assert_eq!(dump_by_name["world"], stream[(5000 - 3000)..(5005 + 16)]);
+        assert_eq!(dump_by_name["final"], stream[(11900 - 1024)..]);
+        // the file name's offset is the match's position within the dump
+        assert!(process_data_state.needle_vals_found[1]
+            .dump_file_path
+            .as_ref()
+            .unwrap()
+            .ends_with("_startat_0xBB8.bin"));
+    }
+
+    #[test]
+    fn test_search_stream_final_chunk_sizes() {
+        // (stream length, expected chunk count). After the first 4096-byte chunk, each chunk
+        // has 3072 bytes of fresh data.
+        for (stream_len, expected_chunk_count) in [
+            (4096 + 2 * 3072, 3),     // exactly fills the last chunk
+            (4096 + 2 * 3072 + 1, 4), // one byte over: the last chunk is the carry-forward + 1 byte
+            (1000, 1),                // smaller than one chunk
+        ] {
+            let mut stream: Vec<u8> = (0..stream_len).map(|i| (i % 7) as u8 + b'a').collect();
+            stream[0..5].copy_from_slice(b"Start");
+            stream[(stream_len - 5)..].copy_from_slice(b"Final");
+
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let search_assignment = make_search_assignment(
+                tmp_dir.path(),
+                vec![
+                    make_needle("start", b"Start"),
+                    make_needle("final", b"Final"),
+                ],
+            );
+            let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+            process_data_state.coverage_verifier = Some(CoverageVerifier::new(1024));
+            search_stream(
+                &mut io::Cursor::new(&stream),
+                &mut process_data_state,
+                &search_assignment,
+                |_, _| {},
+            );
+
+            assert_eq!(
+                process_data_state.total_haystack_bytes_read,
+                stream_len as u64
+            );
+            assert_eq!(
+                process_data_state.chunk_count, expected_chunk_count,
+                "stream_len: {}",
+                stream_len
+            );
+            let found_offsets: Vec<u64> = process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|found| found.match_start_global_offset)
+                .collect();
+            assert_eq!(found_offsets, vec![0, (stream_len - 5) as u64]);
+
+            // every byte searched, none searched twice (beyond the carry-forward)
+            let mut coverage_verifier = process_data_state.coverage_verifier.take().unwrap();
+            coverage_verifier.finish();
+            assert_eq!(coverage_verifier.problems, vec![]);
+        }
+    }
+
+    #[test]
+    fn test_search_stream_records_complete_after_clean_finish() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // matches in the first chunk, across a chunk boundary, and near the end of the final
+        // (partial) chunk, whose after-match context runs past the end of the stream
+        let mut stream: Vec<u8> = (0..10000).map(|i| (i % 200) as u8 + 50).collect();
+        let match_offsets: Vec<usize> = vec![100, 4094, 9990];
+        for &offset in &match_offsets {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.scan_window_context = true;
+
+        let mut process_data_state = search_test_stream(&stream, &search_assignment);
+        process_data_state.finish_dump_writes();
+
+        assert_eq!(process_data_state.needle_vals_found.len(), 3);
+        for jsonl_file_path in [
+            search_assignment
+                .jsonl_output_log_file_path
+                .clone()
+                .unwrap(),
+            search_assignment.needle_jsonl_file_path(&search_assignment.needles[0]),
+        ] {
+            let records: Vec<NeedleValFound> = fs::read_to_string(&jsonl_file_path)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(
+                records
+                    .iter()
+                    .map(|record| record.match_start_global_offset as usize)
+                    .collect::<Vec<usize>>(),
+                match_offsets
+            );
+        }
+        for found in &process_data_state.needle_vals_found {
+            let offset = found.match_start_global_offset as usize;
+            assert_eq!(
+                fs::read(found.dump_file_path.as_ref().unwrap()).unwrap(),
+                &stream[offset.saturating_sub(1024)..(offset + 5 + 1024).min(stream.len())]
+            );
+        }
+        assert!(log_polars_summary(&search_assignment.all_jsonl_file_paths()).is_ok());
+    }
+
+    #[test]
+    fn test_search_stream_record_match_time() {
+        let mut stream: Vec<u8> = (0..(3072 * 4)).map(|i| (i % 200) as u8 + 50).collect();
+        for chunk_idx in 0..4 {
+            let pos = chunk_idx * 3072 + 2000;
+            stream[pos..(pos + 5)].copy_from_slice(b"Hello");
+        }
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.record_match_time = true;
+
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+
+        let elapsed_secs: Vec<f64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.elapsed_since_start_sec.unwrap())
+            .collect();
+        assert_eq!(elapsed_secs.len(), 4);
+        assert!(elapsed_secs
+            .windows(2)
+            .all(|elapsed_pair| elapsed_pair[0] < elapsed_pair[1]));
+        // e.g., "2026-01-02T03:04:05.123456Z"
+        let found_timestamp_utc = process_data_state.needle_vals_found[0]
+            .found_timestamp_utc
+            .as_ref()
+            .unwrap();
+        assert_eq!(found_timestamp_utc.len(), 27);
+        assert!(found_timestamp_utc.ends_with('Z'));
+
+        // not recorded by default
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+        assert!(process_data_state
+            .needle_vals_found
+            .iter()
+            .all(|found| found.elapsed_since_start_sec.is_none()));
+    }
+
+    #[test]
+    fn test_search_stream_deterministic_output_is_identical_across_runs() {
+        let mut stream: Vec<u8> = (0..(3072 * 4)).map(|i| (i % 200) as u8 + 50).collect();
+        stream[5000..5005].copy_from_slice(b"Hello");
+        stream[9000..9005].copy_from_slice(b"Hello");
+
+        let jsonl_outputs: Vec<String> = (0..2)
+            .map(|_| {
+                let tmp_dir = tempfile::tempdir().unwrap();
+                let mut search_assignment =
+                    make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+                search_assignment.deterministic = true;
+                search_test_stream(&stream, &search_assignment);
+                std::fs::read_to_string(tmp_dir.path().join("00_all_output_record.jsonl")).unwrap()
+            })
+            .collect();
+
+        assert_eq!(jsonl_outputs[0].lines().count(), 2);
+        assert_eq!(jsonl_outputs[0], jsonl_outputs[1]);
+        let found: NeedleValFound =
+            serde_json::from_str(jsonl_outputs[0].lines().next().unwrap()).unwrap();
+        assert_eq!(found.found_timestamp_utc, None);
+        assert!(Path::new(found.dump_file_path.as_ref().unwrap()).is_relative());
+    }
+
+    #[test]
+    fn test_search_stream_partition_2_of_synthetic_disk() {
+        // partition 1 at sectors 2-19, partition 2 at sectors 20-59, with a match in each, and one
+        // just past the end of partition 2
+        let mut disk = make_mbr_disk(80, &[(0x83, 2, 18), (0x07, 20, 40)]);
+        for offset in [2 * 512 + 100, 20 * 512 + 4000, 60 * 512 - 5, 60 * 512 + 10] {
+            disk[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        let partitions = read_partitions(&mut io::Cursor::new(&disk)).unwrap();
+        let partition = partitions
+            .iter()
+            .find(|partition| partition.number == 2)
+            .unwrap();
+
+        // absolute offsets: search from the partition start, as if resuming there
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.end_global_offset = Some(partition.end_offset());
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state.resume_from_offset(partition.start_offset);
+        let mut input_reader = io::Cursor::new(&disk);
+        input_reader.set_position(partition.start_offset);
+        search_stream(
+            &mut input_reader,
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![20 * 512 + 4000, 60 * 512 - 5]);
+        assert_eq!(
+            process_data_state.total_haystack_bytes_read,
+            partition.end_offset()
+        );
+
+        // partition-relative offsets: the partition is the whole stream
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        search_assignment.end_global_offset = Some(partition.len_bytes);
+        let mut input_reader = io::Cursor::new(&disk);
+        input_reader.set_position(partition.start_offset);
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        search_stream(
+            &mut input_reader,
+            &mut process_data_state,
+            &search_assignment,
+            |_, _| {},
+        );
+        let found_offsets: Vec<u64> = process_data_state
+            .needle_vals_found
+            .iter()
+            .map(|found| found.match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![4000, 40 * 512 - 5]);
+    }
+
+    #[test]
+    fn test_search_stream_match_within_carry_forward_reported_once() {
+        let mut stream: Vec<u8> = (0..12000).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[3500..3505].copy_from_slice(b"Hello"); // in chunk 0, and in chunk 1's carry-forward
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment =
+            make_search_assignment(tmp_dir.path(), vec![make_needle("hello", b"Hello")]);
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+
+        assert_eq!(process_data_state.needle_vals_found.len(), 1);
+        assert_eq!(
+            process_data_state.needle_vals_found[0].match_start_global_offset,
+            3500
+        );
+    }
+
+    #[test]
+    fn test_search_stream_regex_match_across_chunk_boundary() {
+        let digits_needle = Needle {
+            regex: Some(regex::bytes::Regex::new("[0-9]+").unwrap()),
+            ..make_needle("digits", b"[0-9]+")
+        };
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let search_assignment = make_search_assignment(tmp_dir.path(), vec![digits_needle]);
+
+        // Across the first chunk boundary (at 4096), and running to the end of a stream that ends
+        // right after a full chunk (4096 + 3072 bytes), with nothing more to read.
+        let mut stream: Vec<u8> = (0..(4096 + 3072)).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[4090..4100].copy_from_slice(b"0123456789");
+        stream[7160..7168].copy_from_slice(b"01234567");
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+
+        assert_eq!(
+            process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|needle_val_found| needle_val_found.match_start_global_offset)
+                .collect::<Vec<u64>>(),
+            vec![4090, 7160]
+        );
+    }
+
+    #[test]
+    fn test_do_search_dedup_only_records_kept_matches() {
+        let mut haystack = vec![0u8; 4096];
+        haystack[100..105].copy_from_slice(b"Hello");
+        haystack[2000..2005].copy_from_slice(b"Hello");
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(
+            tmp_dir.path(),
+            vec![Needle {
+                max_matches: Some(1),
+                ..make_needle("hello", b"Hello")
+            }],
+        );
+        search_assignment.dedup = true;
+
+        // the match at 2000 is over `max_matches`, so it isn't recorded, even when searched again
+        let mut process_data_state = make_state_with_haystack(&haystack);
+        do_search(&mut process_data_state, &search_assignment);
+        do_search(&mut process_data_state, &search_assignment);
+
+        assert_eq!(
+            process_data_state.recorded_match_keys,
+            HashSet::from([("hello".to_string(), 100)])
+        );
+        assert_eq!(process_data_state.dedup_skipped_match_count, 1);
+        assert_eq!(process_data_state.capped_match_counts["hello"], 2);
+    }
+
+    #[test]
+    fn test_recorded_match_keys_table_bytes() {
+        let key_bytes = std::mem::size_of::<(String, u64)>() + 1;
+        assert_eq!(recorded_match_keys_table_bytes(0), 0);
+        assert_eq!(recorded_match_keys_table_bytes(3), 4 * key_bytes);
+        assert_eq!(recorded_match_keys_table_bytes(7), 8 * key_bytes);
+        assert_eq!(recorded_match_keys_table_bytes(14), 16 * key_bytes);
+        assert_eq!(
+            recorded_match_keys_table_bytes(917_504),
+            1_048_576 * key_bytes
+        );
+
+        // matches the capacity that the set reports
+        let recorded_match_keys: HashSet<(String, u64)> =
+            (0..1000).map(|i| ("hello".to_string(), i)).collect();
+        let bucket_count =
+            recorded_match_keys_table_bytes(recorded_match_keys.capacity()) / key_bytes;
+        assert!(bucket_count.is_power_of_two());
+        assert!(bucket_count >= recorded_match_keys.len());
+    }
+
+    #[test]
+    fn test_search_stream_regex_match_at_end_of_searched_range() {
+        let digits_needle = Needle {
+            regex: Some(regex::bytes::Regex::new("[0-9]+").unwrap()),
+            ..make_needle("digits", b"[0-9]+")
+        };
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let mut search_assignment = make_search_assignment(tmp_dir.path(), vec![digits_needle]);
+        // like a partition that ends right after the second (full) chunk
+        search_assignment.end_global_offset = Some(4096 + 3072);
+
+        let mut stream: Vec<u8> = (0..12000).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[7160..7168].copy_from_slice(b"01234567");
+        stream[8000..8004].copy_from_slice(b"0123"); // past the end
+        let process_data_state = search_test_stream(&stream, &search_assignment);
+
+        assert_eq!(
+            process_data_state
+                .needle_vals_found
+                .iter()
+                .map(|needle_val_found| needle_val_found.match_start_global_offset)
+                .collect::<Vec<u64>>(),
+            vec![7160]
+        );
+    }
+
+    #[test]
+    fn test_parse_happiness_dir_prefixes() {
+        let happiness_dir_prefixes = parse_happiness_dir_prefixes("9=critical,0=info").unwrap();
+        assert_eq!(happiness_dir_prefixes.len(), 2);
+        assert_eq!(happiness_dir_prefixes[&9], "critical");
+        assert_eq!(happiness_dir_prefixes[&0], "info");
+
+        assert!(parse_happiness_dir_prefixes("").unwrap().is_empty());
+        assert!(parse_happiness_dir_prefixes("critical").is_err());
+        assert!(parse_happiness_dir_prefixes("x=critical").is_err());
+        assert!(parse_happiness_dir_prefixes("9=../escape").is_err());
+    }
+
+    #[test]
+    fn test_match_log_throttle() {
+        let mut throttle = MatchLogThrottle::new(3);
+
+        // a very common needle, and a rare one
+        let logged_common_count = (0..4000)
+            .filter(|_| throttle.record_match("common"))
+            .count();
+        assert_eq!(logged_common_count, 3);
+        assert!(throttle.record_match("rare"));
+
+        assert_eq!(throttle.finish_chunk(), vec![("common".to_string(), 4000)]);
+
+        // counts reset for the next chunk
+        assert!(throttle.record_match("common"));
+        assert_eq!(throttle.finish_chunk(), vec![]);
     }
 }
@@ -0,0 +1,83 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+use std::io::{self, Read};
+
+/// A progress bar of the input file read so far (`--progress-bar`), with the throughput, percent
+/// complete, and ETA. Without a known input size (stdin), it's a spinner with just the bytes read
+/// and the throughput.
+pub fn make_progress_bar(input_file_size_bytes: Option<u64>) -> ProgressBar {
+    let (progress_bar, template) = match input_file_size_bytes {
+        Some(input_file_size_bytes) => (
+            ProgressBar::new(input_file_size_bytes),
+            "{elapsed_precise} [{wide_bar}] {percent}% {binary_bytes}/{binary_total_bytes} ({binary_bytes_per_sec}, ETA {eta})",
+        ),
+        None => (
+            ProgressBar::no_length(),
+            "{elapsed_precise} {spinner} {binary_bytes} ({binary_bytes_per_sec})",
+        ),
+    };
+    progress_bar.set_style(
+        ProgressStyle::with_template(template)
+            .expect("Invalid progress bar template")
+            .progress_chars("=> "),
+    );
+    progress_bar
+}
+
+/// Wraps a reader, and moves a progress bar to `total_in` of the inner reader (the compressed
+/// bytes read so far) after every read.
+pub struct ProgressBarReader<R: Read> {
+    inner: R,
+    progress_bar: Option<ProgressBar>,
+    total_in: fn(&R) -> u64,
+}
+
+impl<R: Read> ProgressBarReader<R> {
+    /// When `progress_bar` is None, this is a plain pass-through reader.
+    pub fn new(inner: R, progress_bar: Option<ProgressBar>, total_in: fn(&R) -> u64) -> Self {
+        Self {
+            inner,
+            progress_bar,
+            total_in,
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: Read> Read for ProgressBarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        if let Some(progress_bar) = &self.progress_bar {
+            progress_bar.set_position((self.total_in)(&self.inner));
+        }
+        Ok(bytes_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_bar_follows_total_in() {
+        let data = vec![7u8; 10_000];
+        // not drawn, so the test doesn't need a terminal, but the position is still tracked
+        let progress_bar = ProgressBar::hidden();
+        let mut reader = ProgressBarReader::new(
+            io::Cursor::new(&data),
+            Some(progress_bar.clone()),
+            |cursor| cursor.position(),
+        );
+
+        let mut buf = vec![0u8; 4096];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(progress_bar.position(), 4096);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(progress_bar.position(), data.len() as u64);
+        assert_eq!(reader.inner().position(), data.len() as u64);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::process_data::ProcessDataState;
+
+use chrono::Utc;
+use num_format::{Locale, ToFormattedString as _};
+use num_traits::AsPrimitive;
+use serde::{Deserialize, Serialize};
+
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The numbers behind one progress tick. Logged as a message, and optionally appended to an
+/// NDJSON progress log (`--progress-json-to`), to plot throughput over time after a run.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProgressStats {
+    pub timestamp: String,
+    pub elapsed_sec: f32,
+
+    // compressed (input-side) stats
+    pub compressed_bytes_read: u64,
+    /// None when the size isn't known, like when reading from stdin (`-i -`).
+    pub input_source_file_size: Option<u64>,
+
+    // uncompressed (output-side) stats
+    pub uncompressed_bytes_searched: u64,
+    /// None when neither the stream nor the input size tells how big it is (like stdin).
+    pub total_uncompressed_size: Option<f32>,
+    /// True if `total_uncompressed_size` was declared in the stream, false if it's estimated from the ratio so far.
+    pub total_uncompressed_size_is_declared: bool,
+
+    // other
+    pub expected_time_remaining_sec: Option<f32>,
+    pub compression_ratio: f32,
+    pub chunk_count: u64,
+    pub match_count: u64,
+    /// Matches recorded per second of the run so far, to spot a pathologically broad needle.
+    #[serde(default)]
+    pub matches_per_sec: f32,
+}
+
+impl ProgressStats {
+    pub fn new(
+        total_in: u64,
+        total_out: u64,
+        input_source_file_size: Option<u64>,
+        declared_uncompressed_size: Option<u64>,
+        process_data_state: &ProcessDataState,
+    ) -> Self {
+        let compression_ratio = total_in as f32 / total_out as f32;
+        let elapsed_sec = process_data_state.start_time.elapsed().as_secs_f32();
+        let uncompressed_bytes_searched = process_data_state.total_haystack_bytes_read;
+        let match_count = process_data_state.needle_vals_found.len() as u64;
+
+        // Prefer the uncompressed size declared in the stream, if any. Otherwise, estimate it from
+        // the ratio so far. Without an input size either, there's no estimate.
+        let (total_uncompressed_size, expected_time_remaining_sec) =
+            match (declared_uncompressed_size, input_source_file_size) {
+                (Some(declared_uncompressed_size), _) => (
+                    Some(declared_uncompressed_size as f32),
+                    Some(
+                        elapsed_sec
+                            * (declared_uncompressed_size as f32
+                                - uncompressed_bytes_searched as f32)
+                                .max(0.0)
+                            / uncompressed_bytes_searched as f32,
+                    ),
+                ),
+                (None, Some(input_source_file_size)) => (
+                    Some((input_source_file_size as f32) / compression_ratio),
+                    Some(elapsed_sec * (input_source_file_size as f32) / total_in as f32),
+                ),
+                (None, None) => (None, None),
+            };
+
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            elapsed_sec,
+            compressed_bytes_read: total_in,
+            input_source_file_size,
+            uncompressed_bytes_searched,
+            total_uncompressed_size,
+            total_uncompressed_size_is_declared: declared_uncompressed_size.is_some(),
+            expected_time_remaining_sec,
+            compression_ratio,
+            chunk_count: process_data_state.chunk_count,
+            match_count,
+            matches_per_sec: match elapsed_sec > 0.0 {
+                true => match_count as f32 / elapsed_sec,
+                false => 0.0,
+            },
+        }
+    }
+
+    /// The log line for this progress tick. Throughput and percentages are shown with `precision`
+    /// decimal places (`--stats-precision`); sizes are in whole MiB.
+    /// Unknown sizes (like when reading from stdin) are shown as "unknown".
+    pub fn to_message(&self, precision: usize) -> String {
+        let format_mib = |size_bytes: f32| {
+            ((size_bytes / 1024.0 / 1024.0).round() as u64).to_formatted_string(&Locale::en)
+        };
+        let format_percent = |done_bytes: u64, total_bytes: Option<f32>| match total_bytes {
+            Some(total_bytes) => {
+                format!("{:.*}", precision, done_bytes as f32 / total_bytes * 100.0)
+            }
+            None => "unknown".to_string(),
+        };
+        format!("{} elapsed, {}MiB / {}MiB decompressed ({}% complete), {} MiB/{} MiB searched ({}% complete), {} remaining, {:.*} MiB/s out, ratio: {:.*}%, {} chunks, {} matches ({:.*} matches/s)",
+            format_duration(self.elapsed_sec.round()),
+
+            // compressed (input-side) stats
+            format_mib(self.compressed_bytes_read as f32),
+            self.input_source_file_size.map_or("unknown ".to_string(), |size| format_mib(size as f32)),
+            format_percent(self.compressed_bytes_read, self.input_source_file_size.map(|size| size as f32)),
+
+            // uncompressed (output-side) stats
+            format_mib(self.uncompressed_bytes_searched as f32),
+            self.total_uncompressed_size.map_or("unknown".to_string(), format_mib),
+            format_percent(self.uncompressed_bytes_searched, self.total_uncompressed_size),
+
+            // other
+            self.expected_time_remaining_sec.map_or("unknown time".to_string(), |sec| format_duration(sec.round())),
+            precision,
+            self.uncompressed_bytes_searched as f32 / self.elapsed_sec / 1024.0 / 1024.0,
+            precision,
+            self.compression_ratio * 100.0,
+            self.chunk_count,
+            self.match_count.to_formatted_string(&Locale::en),
+            precision,
+            self.matches_per_sec
+        )
+    }
+
+    pub fn append_to_ndjson_file(&self, ndjson_file_path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(ndjson_file_path)?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", serde_json::to_string(self)?)?;
+        writer.flush()
+    }
+}
+
+fn format_duration<T: AsPrimitive<u64>>(seconds: T) -> String {
+    let secs = seconds.as_();
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    let seconds = secs % 60;
+
+    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_append_to_ndjson_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let ndjson_file_path = tmp_dir.path().join("progress.ndjson");
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+
+        for tick in 1..=3u64 {
+            process_data_state.total_haystack_bytes_read = tick * 1000;
+            process_data_state.chunk_count = tick;
+            ProgressStats::new(
+                tick * 500,
+                tick * 1000,
+                Some(10_000),
+                None,
+                &process_data_state,
+            )
+            .append_to_ndjson_file(&ndjson_file_path)
+            .unwrap();
+        }
+
+        let lines: Vec<ProgressStats> = fs::read_to_string(&ndjson_file_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2].uncompressed_bytes_searched, 3000);
+        assert_eq!(lines[2].chunk_count, 3);
+        assert_eq!(lines[2].total_uncompressed_size, Some(20_000.0));
+        assert!(!lines[2].total_uncompressed_size_is_declared);
+    }
+
+    #[test]
+    fn test_to_message_precision() {
+        // a slow scan: 3 MiB in 10 s, of a 10 GiB image
+        let progress_stats = ProgressStats {
+            timestamp: Utc::now().to_rfc3339(),
+            elapsed_sec: 10.0,
+            compressed_bytes_read: 3 * 1024 * 1024,
+            input_source_file_size: Some(10 * 1024 * 1024 * 1024),
+            uncompressed_bytes_searched: 3 * 1024 * 1024,
+            total_uncompressed_size: Some(10.0 * 1024.0 * 1024.0 * 1024.0),
+            total_uncompressed_size_is_declared: false,
+            expected_time_remaining_sec: Some(34123.0),
+            compression_ratio: 1.0,
+            chunk_count: 1,
+            match_count: 0,
+            matches_per_sec: 0.0,
+        };
+
+        let message = progress_stats.to_message(0);
+        assert!(message.contains("(0% complete)"));
+        assert!(message.contains(" 0 MiB/s out"));
+
+        let message = progress_stats.to_message(2);
+        assert!(message.contains("(0.03% complete)"));
+        assert!(message.contains(" 0.30 MiB/s out"));
+        assert!(message.contains("ratio: 100.00%"));
+        assert!(message.starts_with("00:00:10 elapsed, 3MiB / 10,240MiB decompressed"));
+        assert!(message.contains("09:28:43 remaining"));
+        assert!(message.ends_with(", 1 chunks, 0 matches (0.00 matches/s)"));
+    }
+
+    #[test]
+    fn test_to_message_match_rate() {
+        // a broad needle: 2.5 million matches in the first 100 s
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state.total_haystack_bytes_read = 1024 * 1024 * 1024;
+        process_data_state.chunk_count = 128;
+        let mut progress_stats = ProgressStats::new(
+            1024 * 1024 * 1024,
+            1024 * 1024 * 1024,
+            Some(4 * 1024 * 1024 * 1024),
+            None,
+            &process_data_state,
+        );
+        assert_eq!(progress_stats.match_count, 0);
+        progress_stats.elapsed_sec = 100.0;
+        progress_stats.match_count = 2_500_000;
+        progress_stats.matches_per_sec = 25_000.0;
+
+        let message = progress_stats.to_message(1);
+        assert!(message.contains(" 10.2 MiB/s out"));
+        assert!(message.ends_with(", 128 chunks, 2,500,000 matches (25000.0 matches/s)"));
+    }
+
+    #[test]
+    fn test_unknown_input_size() {
+        // like stdin: no input size, and no declared uncompressed size
+        let mut process_data_state = ProcessDataState::new(4096, 100, 4);
+        process_data_state.total_haystack_bytes_read = 5 * 1024 * 1024;
+        let progress_stats = ProgressStats::new(
+            5 * 1024 * 1024,
+            5 * 1024 * 1024,
+            None,
+            None,
+            &process_data_state,
+        );
+        assert_eq!(progress_stats.total_uncompressed_size, None);
+        assert_eq!(progress_stats.expected_time_remaining_sec, None);
+
+        let message = progress_stats.to_message(0);
+        assert!(message.contains(
+            "5MiB / unknown MiB decompressed (unknown% complete), 5 MiB/unknown MiB searched (unknown% complete), unknown time remaining"
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{self, Read};
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+
+/// Wraps a reader, and retries reads that fail with a transient error (e.g., a flaky network
+/// filesystem), waiting `retry_delay` before the first retry, and doubling it for each one after.
+///
+/// End of stream (`Ok(0)`) is passed through as usual. Errors that retrying can't fix (corrupt
+/// compressed data, a truncated stream, etc.) are returned right away.
+pub struct RetryingReader<R: Read> {
+    inner: R,
+    max_retries: u32,
+    retry_delay: Duration,
+
+    /// Total retries over the whole run, for the error summary.
+    pub retried_read_count: u64,
+    pub first_retried_read_error: Option<String>,
+}
+
+impl<R: Read> RetryingReader<R> {
+    pub fn new(inner: R, max_retries: u32, retry_delay: Duration) -> Self {
+        Self {
+            inner,
+            max_retries,
+            retry_delay,
+            retried_read_count: 0,
+            first_retried_read_error: None,
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+fn is_transient_read_error(e: &io::Error) -> bool {
+    !matches!(
+        e.kind(),
+        io::ErrorKind::InvalidData
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::Unsupported
+    )
+}
+
+impl<R: Read> Read for RetryingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retry_count: u32 = 0;
+        loop {
+            match self.inner.read(buf) {
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e) if is_transient_read_error(&e) && retry_count < self.max_retries => {
+                    let delay = self.retry_delay * 2u32.saturating_pow(retry_count);
+                    retry_count += 1;
+                    self.retried_read_count += 1;
+                    if self.first_retried_read_error.is_none() {
+                        self.first_retried_read_error = Some(e.to_string());
+                    }
+                    warn!(
+                        "Read failed ({}). Retrying in {} (retry {}/{})...",
+                        e,
+                        humantime::format_duration(delay),
+                        retry_count,
+                        self.max_retries
+                    );
+                    thread::sleep(delay);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Fails the first `failures_left` reads with `error_kind`, then reads from `inner`.
+    struct FlakyReader {
+        inner: Cursor<Vec<u8>>,
+        failures_left: u32,
+        error_kind: io::ErrorKind,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(io::Error::new(self.error_kind, "flaky"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    fn make_flaky_reader(data: &[u8], failures: u32, error_kind: io::ErrorKind) -> FlakyReader {
+        FlakyReader {
+            inner: Cursor::new(data.to_vec()),
+            failures_left: failures,
+            error_kind,
+        }
+    }
+
+    #[test]
+    fn test_retrying_reader_recovers_from_transient_errors() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 256) as u8).collect();
+        let mut retrying_reader = RetryingReader::new(
+            make_flaky_reader(&data, 2, io::ErrorKind::TimedOut),
+            3,
+            Duration::ZERO,
+        );
+
+        // the whole stream is read, through to EOF
+        let mut read_data = Vec::new();
+        let mut buf = vec![0; 4096];
+        loop {
+            match retrying_reader.read(&mut buf).unwrap() {
+                0 => break,
+                bytes_read => read_data.extend_from_slice(&buf[..bytes_read]),
+            }
+        }
+        assert_eq!(read_data, data);
+        assert_eq!(retrying_reader.retried_read_count, 2);
+    }
+
+    #[test]
+    fn test_retrying_reader_gives_up() {
+        let mut buf = vec![0; 16];
+
+        // too many transient errors
+        let mut retrying_reader = RetryingReader::new(
+            make_flaky_reader(b"data", 3, io::ErrorKind::TimedOut),
+            2,
+            Duration::ZERO,
+        );
+        assert!(retrying_reader.read(&mut buf).is_err());
+
+        // not transient, so not retried
+        let mut retrying_reader = RetryingReader::new(
+            make_flaky_reader(b"data", 1, io::ErrorKind::InvalidData),
+            2,
+            Duration::ZERO,
+        );
+        assert_eq!(
+            retrying_reader.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
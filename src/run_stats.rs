@@ -0,0 +1,47 @@
+use crate::hashing_reader::HashAlgo;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Stats about a whole run, written to the output directory at the end of the run.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RunStats {
+    pub drive_image_searcher_version: String,
+    pub input_file_path: String,
+    pub total_haystack_bytes_read: u64,
+    pub chunk_count: u64,
+    pub match_count: u64,
+    pub elapsed_sec: f32,
+
+    /// True if haystack chunk dumps stopped partway through, due to a write error (e.g., disk full).
+    pub dump_writing_disabled: bool,
+
+    /// Set for sampled scans (`--sample-every-nth-chunk`), where most of the image was NOT searched.
+    pub sample_every_nth_chunk: Option<u64>,
+
+    /// Hash algorithm of `input_stream_hash` (`--hash-algo`).
+    pub input_stream_hash_algo: Option<HashAlgo>,
+    /// Hash of the whole uncompressed input stream, i.e., of the image itself (if `--hash-stream` was used).
+    pub input_stream_hash: Option<String>,
+
+    /// Matches past a needle's `max_matches`, per needle. They were counted, but not recorded.
+    #[serde(default)]
+    pub capped_match_counts: BTreeMap<String, u64>,
+
+    /// True if the run was stopped early (Ctrl-C), so only part of the input was searched.
+    #[serde(default)]
+    pub interrupted: bool,
+}
+
+impl RunStats {
+    pub fn write_to_json_file(&self, json_file_path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(json_file_path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
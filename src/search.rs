@@ -0,0 +1,385 @@
+use crate::found_needle::NeedleValFound;
+use crate::needle::Needle;
+use crate::needle_matcher::NeedleMatcher;
+use crate::partition_table::read_full;
+use crate::process_data::{
+    cap_needle_matches, check_haystack_chunk_buffer_size, defer_regex_matches_at_chunk_end,
+    filter_needle_matches, find_matches_in_chunk, haystack_carry_forward_len_bytes_for,
+};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{self, Read};
+
+/// Options for the library `search`.
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    /// Size of each haystack chunk, including the carry-forward from the previous chunk.
+    pub chunk_size_bytes: usize,
+    /// Amount from the end of each chunk to carry forward into the next, so needles crossing a
+    /// chunk boundary are found. None = enough for the longest needle (at least 1024 bytes).
+    pub carry_forward_len_bytes: Option<usize>,
+    /// Global offset of the first byte of the reader, like when the caller has already skipped
+    /// part of the stream. Reported offsets are absolute (from the start of the whole stream).
+    pub start_offset: u64,
+    /// Stop searching at this global offset (exclusive). None = search to the end of the stream.
+    pub end_offset: Option<u64>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size_bytes: 8 * 1024 * 1024, // 8 MiB
+            carry_forward_len_bytes: None,
+            start_offset: 0,
+            end_offset: None,
+        }
+    }
+}
+
+/// Searches `reader` for `needles`, chunk by chunk, like the command-line search, but without
+/// writing anything to disk (no JSONL, no dumps). The matches of each chunk are yielded in stream
+/// order, and a read error ends the search.
+pub fn search<R: Read>(
+    reader: R,
+    needles: &[Needle],
+    opts: SearchOptions,
+) -> Result<Search<'_, R>, String> {
+    let carry_forward_len_bytes = opts
+        .carry_forward_len_bytes
+        .unwrap_or_else(|| haystack_carry_forward_len_bytes_for(needles));
+    check_haystack_chunk_buffer_size(opts.chunk_size_bytes, carry_forward_len_bytes, needles)?;
+    Ok(Search {
+        reader,
+        needles,
+        needle_matcher: NeedleMatcher::new(needles)?,
+        carry_forward_len_bytes,
+        end_offset: opts.end_offset,
+        chunk_buffer: vec![0; opts.chunk_size_bytes],
+        chunk_len: 0,
+        next_global_offset: opts.start_offset,
+        is_last_chunk: false,
+        found_in_chunk: VecDeque::new(),
+        needle_match_counts: HashMap::new(),
+        capped_match_counts: BTreeMap::new(),
+        is_finished: false,
+    })
+}
+
+/// Iterator over the matches of a `search`.
+pub struct Search<'a, R: Read> {
+    reader: R,
+    needles: &'a [Needle],
+    needle_matcher: NeedleMatcher,
+    carry_forward_len_bytes: usize,
+    end_offset: Option<u64>,
+    chunk_buffer: Vec<u8>,
+    /// Number of valid bytes at the start of `chunk_buffer`.
+    chunk_len: usize,
+    /// Global offset of the next byte to read.
+    next_global_offset: u64,
+    /// True if the last chunk ran to the end of the stream.
+    is_last_chunk: bool,
+    /// Matches of the last chunk that haven't been yielded yet.
+    found_in_chunk: VecDeque<NeedleValFound>,
+    /// Matches so far, per needle with `max_matches`.
+    needle_match_counts: HashMap<String, u64>,
+    /// Matches past each needle's `max_matches`, which aren't yielded.
+    capped_match_counts: BTreeMap<String, u64>,
+    is_finished: bool,
+}
+
+impl<R: Read> Search<'_, R> {
+    /// Matches past a needle's `max_matches` so far, per needle. They were counted, but not
+    /// yielded.
+    pub fn capped_match_counts(&self) -> &BTreeMap<String, u64> {
+        &self.capped_match_counts
+    }
+
+    /// Reads the next chunk (after the carry-forward), and queues its matches.
+    fn search_next_chunk(&mut self) -> io::Result<()> {
+        let carried_len = self.chunk_len.min(self.carry_forward_len_bytes);
+        self.chunk_buffer
+            .copy_within((self.chunk_len - carried_len)..self.chunk_len, 0);
+        let chunk_start_global_offset = self.next_global_offset - carried_len as u64;
+
+        let read_len = match self.end_offset {
+            Some(end_offset) => ((self.chunk_buffer.len() - carried_len) as u64)
+                .min(end_offset.saturating_sub(self.next_global_offset))
+                as usize,
+            None => self.chunk_buffer.len() - carried_len,
+        };
+        let bytes_read = read_full(
+            &mut self.reader,
+            &mut self.chunk_buffer[carried_len..(carried_len + read_len)],
+        )?;
+        let was_last_chunk = self.is_last_chunk;
+        self.is_last_chunk = bytes_read < read_len
+            || self.end_offset == Some(self.next_global_offset + bytes_read as u64);
+        if bytes_read == 0 {
+            self.is_finished = true;
+            // The stream ended right after a full chunk: search its carry-forward again as the
+            // last chunk, for the regex matches it left for the next one.
+            let may_have_deferred_matches = self.is_last_chunk
+                && !was_last_chunk
+                && carried_len > 0
+                && self.needles.iter().any(|needle| needle.regex.is_some());
+            if !may_have_deferred_matches {
+                return Ok(());
+            }
+        }
+        self.chunk_len = carried_len + bytes_read;
+        self.next_global_offset += bytes_read as u64;
+
+        let mut chunk_matches = find_matches_in_chunk(
+            &self.chunk_buffer[..self.chunk_len],
+            self.needles,
+            &self.needle_matcher,
+            1,
+            carried_len,
+        );
+        if !self.is_last_chunk {
+            defer_regex_matches_at_chunk_end(&mut chunk_matches, self.needles, self.chunk_len);
+        }
+        filter_needle_matches(&mut chunk_matches, self.needles, chunk_start_global_offset);
+        cap_needle_matches(
+            &mut chunk_matches,
+            self.needles,
+            chunk_start_global_offset,
+            &mut self.needle_match_counts,
+            &mut self.capped_match_counts,
+        );
+        chunk_matches.sort_by_key(|chunk_match| (chunk_match.pos_in_chunk, chunk_match.needle_idx));
+        self.found_in_chunk
+            .extend(chunk_matches.iter().map(|chunk_match| {
+                NeedleValFound::from_needle_val_in_stream(
+                    &self.needles[chunk_match.needle_idx],
+                    chunk_start_global_offset + chunk_match.pos_in_chunk as u64,
+                )
+            }));
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Search<'_, R> {
+    type Item = io::Result<NeedleValFound>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(found) = self.found_in_chunk.pop_front() {
+                return Some(Ok(found));
+            }
+            if self.is_finished {
+                return None;
+            }
+            if let Err(e) = self.search_next_chunk() {
+                self.is_finished = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn make_needle(name: &str, val: &[u8]) -> Needle {
+        Needle {
+            name: name.to_string(),
+            val: val.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_search_in_memory_stream() {
+        let mut stream: Vec<u8> = (0..20000).map(|i| (i % 7) as u8 + b'a').collect();
+        // including one across the first chunk boundary (at 4096)
+        for offset in [100, 4094, 15000] {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        stream[9002..9007].copy_from_slice(b"World");
+        let needles = vec![
+            make_needle("hello", b"Hello"),
+            make_needle("world", b"World"),
+        ];
+        let opts = SearchOptions {
+            chunk_size_bytes: 4096,
+            ..Default::default()
+        };
+
+        let found: Vec<(String, u64)> = search(Cursor::new(stream.clone()), &needles, opts.clone())
+            .unwrap()
+            .map(|found| {
+                let found = found.unwrap();
+                (found.name, found.match_start_global_offset)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("hello".to_string(), 100),
+                ("hello".to_string(), 4094),
+                ("world".to_string(), 9002),
+                ("hello".to_string(), 15000),
+            ]
+        );
+
+        // a reader that starts partway through the stream, up to an end offset
+        let found_offsets: Vec<u64> = search(
+            Cursor::new(stream[1000..].to_vec()),
+            &needles,
+            SearchOptions {
+                start_offset: 1000,
+                end_offset: Some(15004),
+                ..opts
+            },
+        )
+        .unwrap()
+        .map(|found| found.unwrap().match_start_global_offset)
+        .collect();
+        assert_eq!(found_offsets, vec![4094, 9002]);
+    }
+
+    #[test]
+    fn test_search_alignment() {
+        let mut stream = vec![0u8; 40_000];
+        for offset in [1024, 10_000, 20_480] {
+            stream[offset..(offset + 3)].copy_from_slice(b"REC");
+        }
+        let needles = vec![Needle {
+            alignment: Some(512),
+            ..make_needle("record", b"REC")
+        }];
+        // the same at any chunk size: only the sector-aligned ones
+        for chunk_size_bytes in [4096, 8192, 16384, 32768] {
+            let found_offsets: Vec<u64> = search(
+                Cursor::new(stream.clone()),
+                &needles,
+                SearchOptions {
+                    chunk_size_bytes,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .map(|found| found.unwrap().match_start_global_offset)
+            .collect();
+            assert_eq!(found_offsets, vec![1024, 20_480], "{}", chunk_size_bytes);
+        }
+    }
+
+    #[test]
+    fn test_search_suppress_if_covered_by() {
+        let mut stream = vec![0u8; 10_000];
+        stream[1000..1012].copy_from_slice(b"Hello, world");
+        stream[5000..5005].copy_from_slice(b"Hello");
+        let needles = vec![
+            Needle {
+                suppress_if_covered_by: vec!["hello_world".to_string()],
+                ..make_needle("hello", b"Hello")
+            },
+            make_needle("hello_world", b"Hello, world"),
+        ];
+
+        let found: Vec<(String, u64)> = search(
+            Cursor::new(stream),
+            &needles,
+            SearchOptions {
+                chunk_size_bytes: 4096,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .map(|found| {
+            let found = found.unwrap();
+            (found.name, found.match_start_global_offset)
+        })
+        .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("hello_world".to_string(), 1000),
+                ("hello".to_string(), 5000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_max_matches() {
+        let mut stream = vec![0u8; 20_000];
+        for offset in [100, 2000, 6000, 12_000, 18_000] {
+            stream[offset..(offset + 5)].copy_from_slice(b"Hello");
+        }
+        let needles = vec![Needle {
+            max_matches: Some(3),
+            ..make_needle("hello", b"Hello")
+        }];
+
+        // capped across chunks, not per chunk
+        let mut found_search = search(
+            Cursor::new(stream),
+            &needles,
+            SearchOptions {
+                chunk_size_bytes: 4096,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let found_offsets: Vec<u64> = found_search
+            .by_ref()
+            .map(|found| found.unwrap().match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![100, 2000, 6000]);
+        assert_eq!(
+            found_search.capped_match_counts(),
+            &BTreeMap::from([("hello".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_search_regex_match_across_chunk_boundary() {
+        let mut stream: Vec<u8> = (0..(4096 + 3072)).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[4090..4100].copy_from_slice(b"0123456789");
+        stream[7160..7168].copy_from_slice(b"01234567");
+        let needles = vec![Needle {
+            regex: Some(regex::bytes::Regex::new("[0-9]+").unwrap()),
+            ..make_needle("digits", b"[0-9]+")
+        }];
+        let opts = SearchOptions {
+            chunk_size_bytes: 4096,
+            ..Default::default()
+        };
+
+        let found_offsets: Vec<u64> = search(Cursor::new(stream), &needles, opts)
+            .unwrap()
+            .map(|found| found.unwrap().match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![4090, 7160]);
+
+        // and at the end of the searched range, with the stream going on after it
+        let mut stream: Vec<u8> = (0..12000).map(|i| (i % 7) as u8 + b'a').collect();
+        stream[7160..7168].copy_from_slice(b"01234567");
+        let opts = SearchOptions {
+            chunk_size_bytes: 4096,
+            end_offset: Some(7168),
+            ..Default::default()
+        };
+        let found_offsets: Vec<u64> = search(Cursor::new(stream), &needles, opts)
+            .unwrap()
+            .map(|found| found.unwrap().match_start_global_offset)
+            .collect();
+        assert_eq!(found_offsets, vec![7160]);
+    }
+
+    #[test]
+    fn test_search_rejects_too_small_chunk() {
+        let needles = vec![make_needle("hello", b"Hello")];
+        let opts = SearchOptions {
+            chunk_size_bytes: 1024,
+            ..Default::default()
+        };
+        assert!(search(Cursor::new(Vec::new()), &needles, opts).is_err());
+    }
+}
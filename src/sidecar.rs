@@ -0,0 +1,212 @@
+use crate::display_hex::display_hex_offset;
+use crate::found_needle::NeedleValFound;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+
+/// Formats for the match sidecar file, for interop with other forensic tools.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SidecarFormat {
+    /// The Sleuth Kit "bodyfile" (3.x) format, which is consumed by `mactime` and most timeline tools.
+    Bodyfile,
+    /// Plain CSV with one row per match.
+    Csv,
+}
+
+impl SidecarFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            SidecarFormat::Bodyfile => "body",
+            SidecarFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for SidecarFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "bodyfile" => Ok(SidecarFormat::Bodyfile),
+            "csv" => Ok(SidecarFormat::Csv),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn write_sidecar_file(
+    needle_vals_found: &[NeedleValFound],
+    sidecar_format: SidecarFormat,
+    sidecar_file_path: &Path,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(sidecar_file_path)?);
+
+    if sidecar_format == SidecarFormat::Csv {
+        writeln!(
+            writer,
+            "offset,offset_hex,label,length,happiness_level,found_timestamp_utc,description_notes"
+        )?;
+    }
+
+    for needle_val_found in needle_vals_found {
+        let line = match sidecar_format {
+            SidecarFormat::Bodyfile => make_bodyfile_line(needle_val_found),
+            SidecarFormat::Csv => make_csv_line(needle_val_found),
+        };
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()
+}
+
+/// Label for a match, like "Example Needle 1 @ 0x0000_0000_0000_0000_1000".
+fn make_label(needle_val_found: &NeedleValFound) -> String {
+    format!(
+        "{} @ 0x{}",
+        needle_val_found.name,
+        display_hex_offset(needle_val_found.match_start_global_offset, 20)
+    )
+}
+
+/// Bodyfile columns: MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+/// The match timestamp is used for all four times, so matches show up in the timeline in discovery order.
+fn make_bodyfile_line(needle_val_found: &NeedleValFound) -> String {
+    let found_timestamp = needle_val_found
+        .found_timestamp_utc
+        .as_ref()
+        .and_then(|found_timestamp_utc| {
+            // with `--record-match-time`, it has microseconds and a trailing "Z"
+            ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M:%S%.fZ"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(found_timestamp_utc, format).ok())
+        })
+        .map(|t| t.and_utc().timestamp())
+        .unwrap_or(0);
+
+    format!(
+        "0|{}|{}|r/r---------|0|0|{}|{}|{}|{}|{}",
+        make_label(needle_val_found).replace('|', "_"),
+        needle_val_found.match_start_global_offset,
+        needle_val_found.val.bytes().len(),
+        found_timestamp,
+        found_timestamp,
+        found_timestamp,
+        found_timestamp,
+    )
+}
+
+fn make_csv_line(needle_val_found: &NeedleValFound) -> String {
+    format!(
+        "{},0x{},{},{},{},{},{}",
+        needle_val_found.match_start_global_offset,
+        display_hex_offset(needle_val_found.match_start_global_offset, 1),
+        escape_csv_field(&make_label(needle_val_found)),
+        needle_val_found.val.bytes().len(),
+        needle_val_found.happiness_level,
+        needle_val_found
+            .found_timestamp_utc
+            .as_deref()
+            .unwrap_or_default(),
+        escape_csv_field(&needle_val_found.description_notes),
+    )
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::needle::Needle;
+    use std::fs;
+
+    fn make_needle_val_found(name: &str, offset: u64) -> NeedleValFound {
+        let needle = Needle {
+            name: name.to_string(),
+            val: b"Hello".to_vec(),
+            description_notes: "Says \"hi\", politely".to_string(),
+            happiness_level: 3,
+            write_to_file: true,
+            byte_count_before_match: 1024,
+            byte_count_after_match: 1024,
+            ..Default::default()
+        };
+        NeedleValFound::from_needle_val(&needle, offset, Path::new("/images/disk.img"))
+    }
+
+    #[test]
+    fn test_write_sidecar_file_bodyfile() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let sidecar_file_path = tmp_dir.path().join("matches.body");
+        let records = vec![
+            make_needle_val_found("Needle|A", 0x1000),
+            make_needle_val_found("Needle B", 42),
+        ];
+
+        write_sidecar_file(&records, SidecarFormat::Bodyfile, &sidecar_file_path).unwrap();
+
+        let contents = fs::read_to_string(&sidecar_file_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert_eq!(line.split('|').count(), 11);
+        }
+        let fields: Vec<&str> = lines[0].split('|').collect();
+        assert_eq!(fields[0], "0");
+        assert_eq!(fields[1], "Needle_A @ 0x0000_0000_0000_0000_1000");
+        assert_eq!(fields[2], "4096");
+        assert_eq!(fields[6], "5");
+        assert!(fields[7].parse::<i64>().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_write_sidecar_file_bodyfile_with_record_match_time() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let sidecar_file_path = tmp_dir.path().join("matches.body");
+        // as recorded with `--record-match-time`
+        let records = vec![NeedleValFound {
+            found_timestamp_utc: Some("2024-05-01T12:34:56.123456Z".to_string()),
+            ..make_needle_val_found("Needle A", 0x1000)
+        }];
+
+        write_sidecar_file(&records, SidecarFormat::Bodyfile, &sidecar_file_path).unwrap();
+
+        let contents = fs::read_to_string(&sidecar_file_path).unwrap();
+        let fields: Vec<&str> = contents.trim_end().split('|').collect();
+        assert_eq!(fields[7..], ["1714566896"; 4]);
+    }
+
+    #[test]
+    fn test_write_sidecar_file_csv() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let sidecar_file_path = tmp_dir.path().join("matches.csv");
+        let records = vec![make_needle_val_found("Needle A", 0x1000)];
+
+        write_sidecar_file(&records, SidecarFormat::Csv, &sidecar_file_path).unwrap();
+
+        let contents = fs::read_to_string(&sidecar_file_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("offset,offset_hex,label,"));
+        assert!(lines[1].starts_with("4096,0x1000,Needle A @ 0x0000_0000_0000_0000_1000,5,3,"));
+        assert!(lines[1].ends_with(",\"Says \"\"hi\"\", politely\""));
+    }
+
+    #[test]
+    fn test_sidecar_format_from_str() {
+        assert_eq!(
+            SidecarFormat::from_str("bodyfile"),
+            Ok(SidecarFormat::Bodyfile)
+        );
+        assert_eq!(SidecarFormat::from_str("CSV"), Ok(SidecarFormat::Csv));
+        assert!(SidecarFormat::from_str("ewf").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::found_needle::NeedleValFound;
+
+use rusqlite::{params, Connection};
+
+use std::path::Path;
+
+/// Records inserted per transaction. One transaction per record would make each insert wait for
+/// the disk.
+const SQLITE_BATCH_RECORD_COUNT: u64 = 1000;
+
+/// Writes the match records to a SQLite database (`--sqlite`), as they're found, to query with
+/// SQL instead of grepping the JSONL files. Records are inserted in batches, in one transaction
+/// each. Call `finish` at the end of the run to commit the last batch.
+pub struct SqliteWriter {
+    connection: Connection,
+    /// Records inserted in the current (uncommitted) transaction.
+    batch_record_count: u64,
+}
+
+impl SqliteWriter {
+    /// Opens (or creates) the database at `db_file_path`, with the `needle_finds` table and its
+    /// indexes. Records from an earlier run into the same database are kept.
+    pub fn open(db_file_path: &Path) -> rusqlite::Result<Self> {
+        let connection = Connection::open(db_file_path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS needle_finds (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                match_start_global_offset INTEGER NOT NULL,
+                val_hex TEXT NOT NULL,
+                val_as_str TEXT NOT NULL,
+                description_notes TEXT NOT NULL,
+                happiness_level INTEGER NOT NULL,
+                found_timestamp_utc TEXT,
+                haystack_file_path TEXT,
+                dump_file_path TEXT,
+                chs_address TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_needle_finds_name ON needle_finds (name);
+            CREATE INDEX IF NOT EXISTS idx_needle_finds_match_start_global_offset
+                ON needle_finds (match_start_global_offset);",
+        )?;
+        Ok(Self {
+            connection,
+            batch_record_count: 0,
+        })
+    }
+
+    pub fn insert(&mut self, needle_val_found: &NeedleValFound) -> rusqlite::Result<()> {
+        if self.connection.is_autocommit() {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection
+            .prepare_cached(
+                "INSERT INTO needle_finds (name, match_start_global_offset, val_hex, val_as_str,
+                    description_notes, happiness_level, found_timestamp_utc, haystack_file_path,
+                    dump_file_path, chs_address)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?
+            .execute(params![
+                needle_val_found.name,
+                needle_val_found.match_start_global_offset as i64,
+                hex::encode(needle_val_found.val.bytes()),
+                needle_val_found.val_as_str,
+                needle_val_found.description_notes,
+                needle_val_found.happiness_level,
+                needle_val_found.found_timestamp_utc,
+                needle_val_found.haystack_file_path,
+                needle_val_found.dump_file_path,
+                needle_val_found.chs_address,
+            ])?;
+        self.batch_record_count += 1;
+        if self.batch_record_count >= SQLITE_BATCH_RECORD_COUNT {
+            self.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the records inserted so far.
+    pub fn finish(&mut self) -> rusqlite::Result<()> {
+        if !self.connection.is_autocommit() {
+            self.connection.execute_batch("COMMIT")?;
+        }
+        self.batch_record_count = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::display_hex::display_hex_offset;
+
+use std::path::{Path, PathBuf};
+
+/// Shortest run of printable characters that's listed, like the `strings` tool's default.
+pub const MIN_PRINTABLE_STRING_LEN: usize = 4;
+
+/// The printable character (ASCII or UTF-8, or a tab) at the start of `bytes`, and its length in
+/// bytes. None for a control character or invalid UTF-8.
+fn printable_char_at(bytes: &[u8]) -> Option<(char, usize)> {
+    let char_len = match bytes.first()? {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let c = std::str::from_utf8(bytes.get(..char_len)?)
+        .ok()?
+        .chars()
+        .next()?;
+    (c == '\t' || !c.is_control()).then_some((c, char_len))
+}
+
+/// Finds the runs of at least `min_len` printable characters (ASCII or UTF-8) in `bytes`, with the
+/// position of each run's first byte. A newline ends a run, so each line is its own string.
+pub fn find_printable_strings(bytes: &[u8], min_len: usize) -> Vec<(usize, String)> {
+    let mut printable_strings = Vec::new();
+    let mut run_start = 0;
+    let mut run = String::new();
+    let mut run_char_count = 0;
+    let mut pos = 0;
+    // up to and including the end, where the empty rest of the bytes ends the last run
+    while pos <= bytes.len() {
+        match printable_char_at(&bytes[pos..]) {
+            Some((c, char_len)) => {
+                if run.is_empty() {
+                    run_start = pos;
+                }
+                run.push(c);
+                run_char_count += 1;
+                pos += char_len;
+            }
+            None => {
+                match run_char_count >= min_len {
+                    true => printable_strings.push((run_start, std::mem::take(&mut run))),
+                    false => run.clear(),
+                }
+                run_char_count = 0;
+                pos += 1;
+            }
+        }
+    }
+    printable_strings
+}
+
+/// Lists the printable strings in the dumped `bytes`, one per line, each after its global offset,
+/// like "0x0000_0000_1000: some text".
+pub fn format_printable_strings(bytes: &[u8], start_global_offset: u64) -> String {
+    find_printable_strings(bytes, MIN_PRINTABLE_STRING_LEN)
+        .iter()
+        .map(|(pos, printable_string)| {
+            format!(
+                "0x{}: {}\n",
+                display_hex_offset(start_global_offset + *pos as u64, 12),
+                printable_string
+            )
+        })
+        .collect()
+}
+
+/// The strings file written next to a dump (`strings: true`), like `found_....strings.txt` for
+/// `found_....bin`.
+pub fn strings_file_path(dump_file_path: &Path) -> PathBuf {
+    dump_file_path.with_extension("strings.txt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_printable_strings() {
+        let bytes = "\x00\x01abc\x02wallet.dat\nüber-key\t1\u{7f}\u{7f}end!".as_bytes();
+        assert_eq!(
+            find_printable_strings(bytes, 4),
+            vec![
+                (6, "wallet.dat".to_string()),
+                (17, "über-key\t1".to_string()),
+                (30, "end!".to_string()),
+            ]
+        );
+        // invalid UTF-8 ends a run
+        assert_eq!(
+            find_printable_strings(b"abcd\xFFefgh\xE2\x82", 4),
+            vec![(0, "abcd".to_string()), (5, "efgh".to_string())]
+        );
+        assert_eq!(
+            strings_file_path(Path::new("out/1_key/found_g_0x10.bin")),
+            Path::new("out/1_key/found_g_0x10.strings.txt")
+        );
+    }
+}
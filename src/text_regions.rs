@@ -0,0 +1,71 @@
+use std::ops::Range;
+
+/// Printable ASCII, plus the whitespace that shows up in text files.
+pub fn is_text_byte(b: u8) -> bool {
+    b.is_ascii_graphic() || matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Finds the runs of at least `min_run_len` text bytes (human-readable areas) in the haystack.
+pub fn find_text_runs(haystack: &[u8], min_run_len: usize) -> Vec<Range<usize>> {
+    let mut text_runs = Vec::new();
+    let mut run_start: Option<usize> = None;
+    for (pos, &b) in haystack.iter().enumerate() {
+        match (is_text_byte(b), run_start) {
+            (true, None) => run_start = Some(pos),
+            (false, Some(start)) => {
+                if pos - start >= min_run_len {
+                    text_runs.push(start..pos);
+                }
+                run_start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(start) = run_start {
+        if haystack.len() - start >= min_run_len {
+            text_runs.push(start..haystack.len());
+        }
+    }
+    text_runs
+}
+
+/// Returns a copy of the haystack with everything outside the text runs zeroed (`--text-regions-only`).
+/// Text needles searched in it can only match inside a text run, because their bytes are all
+/// non-zero text bytes. Positions are the same as in the haystack.
+pub fn mask_non_text_regions(haystack: &[u8], min_run_len: usize) -> Vec<u8> {
+    let mut masked_haystack = vec![0u8; haystack.len()];
+    for text_run in find_text_runs(haystack, min_run_len) {
+        masked_haystack[text_run.clone()].copy_from_slice(&haystack[text_run]);
+    }
+    masked_haystack
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_text_runs() {
+        let haystack = b"\x00\x01short\xFFa longer line of text\n\x02\x03the end";
+        let text_runs = find_text_runs(haystack, 10);
+        assert_eq!(
+            text_runs
+                .iter()
+                .map(|text_run| &haystack[text_run.clone()])
+                .collect::<Vec<&[u8]>>(),
+            vec![&b"a longer line of text\n"[..]]
+        );
+
+        // a run at the very end counts too
+        assert_eq!(find_text_runs(haystack, 5).len(), 3);
+        assert!(find_text_runs(b"", 5).is_empty());
+    }
+
+    #[test]
+    fn test_mask_non_text_regions() {
+        assert_eq!(
+            mask_non_text_regions(b"\xFFab\xFFlong text\xFF", 5),
+            b"\x00\x00\x00\x00long text\x00"
+        );
+    }
+}
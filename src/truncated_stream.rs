@@ -0,0 +1,200 @@
+use std::io::{self, Read};
+
+use log::{error, warn};
+
+/// How a decompression error ended the stream early.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamEndErrorKind {
+    /// The compressed input ran out (e.g., a partly copied image), so the end is just missing.
+    TrailingTruncation,
+    /// Corrupt compressed data partway through the input (only ends the stream with `--skip-read-errors`).
+    MidStreamCorruption,
+}
+
+impl StreamEndErrorKind {
+    /// Short snake_case name, for the error summary.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamEndErrorKind::TrailingTruncation => "truncated_compressed_stream",
+            StreamEndErrorKind::MidStreamCorruption => "corrupt_compressed_stream",
+        }
+    }
+}
+
+/// The decompression error that ended the stream early.
+#[derive(Debug)]
+pub struct StreamEndError {
+    pub kind: StreamEndErrorKind,
+    pub message: String,
+}
+
+/// Wraps a decompressing reader, so a truncated or corrupt compressed image still yields results
+/// for the part that could be read.
+///
+/// A decompression error (bad CRC, premature end of the compressed data, etc.) after all the
+/// compressed input was read is trailing truncation, so it's treated as the end of the stream,
+/// with a warning. One with compressed input left over is mid-stream corruption, which is
+/// returned as an error, unless `skip_read_errors` is set (then it ends the stream too, because
+/// the decompressor can't pick up again after it).
+pub struct TruncationTolerantReader<R: Read> {
+    inner: R,
+    /// True if all the compressed input has been read.
+    is_input_exhausted: fn(&R) -> bool,
+    skip_read_errors: bool,
+
+    pub stream_end_error: Option<StreamEndError>,
+}
+
+impl<R: Read> TruncationTolerantReader<R> {
+    pub fn new(inner: R, is_input_exhausted: fn(&R) -> bool, skip_read_errors: bool) -> Self {
+        Self {
+            inner,
+            is_input_exhausted,
+            skip_read_errors,
+            stream_end_error: None,
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+fn is_decompression_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+    )
+}
+
+impl<R: Read> Read for TruncationTolerantReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.stream_end_error.is_some() {
+            return Ok(0);
+        }
+        match self.inner.read(buf) {
+            Ok(bytes_read) => Ok(bytes_read),
+            Err(e) if is_decompression_error(&e) => {
+                let kind = match (self.is_input_exhausted)(&self.inner) {
+                    true => StreamEndErrorKind::TrailingTruncation,
+                    false => StreamEndErrorKind::MidStreamCorruption,
+                };
+                match kind {
+                    StreamEndErrorKind::TrailingTruncation => warn!(
+                        "Decompression error at the end of the compressed image ({}). It looks truncated, so treating this as the end of the stream.",
+                        e
+                    ),
+                    StreamEndErrorKind::MidStreamCorruption if self.skip_read_errors => error!(
+                        "Corrupt compressed data partway through the image ({}). Skipping the rest of the stream (--skip-read-errors).",
+                        e
+                    ),
+                    StreamEndErrorKind::MidStreamCorruption => return Err(e),
+                }
+                self.stream_end_error = Some(StreamEndError {
+                    kind,
+                    message: e.to_string(),
+                });
+                Ok(0)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Cursor, Write};
+    use xz2::read::XzDecoder;
+    use xz2::write::XzEncoder;
+
+    /// Incompressible bytes, so the compressed stream is about as long as the data.
+    fn make_noise(len: usize) -> Vec<u8> {
+        let mut state: u32 = 12345;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn xz_compress(data: &[u8]) -> Vec<u8> {
+        let mut xz_encoder = XzEncoder::new(Vec::new(), 6);
+        xz_encoder.write_all(data).unwrap();
+        xz_encoder.finish().unwrap()
+    }
+
+    fn is_xz_input_exhausted(xz_decoder: &XzDecoder<Cursor<Vec<u8>>>) -> bool {
+        let cursor = xz_decoder.get_ref();
+        cursor.position() >= cursor.get_ref().len() as u64
+    }
+
+    #[test]
+    fn test_truncated_xz_stream_ends_with_warning() {
+        let data = make_noise(1024 * 1024);
+        let mut compressed = xz_compress(&data);
+        compressed.truncate(compressed.len() / 2);
+
+        let mut reader = TruncationTolerantReader::new(
+            XzDecoder::new(Cursor::new(compressed)),
+            is_xz_input_exhausted,
+            false,
+        );
+        let mut read_data = Vec::new();
+        reader.read_to_end(&mut read_data).unwrap();
+
+        // the part before the truncation is read as usual
+        assert!(read_data.len() > data.len() / 4);
+        assert_eq!(read_data, data[..read_data.len()]);
+        assert_eq!(
+            reader.stream_end_error.as_ref().unwrap().kind,
+            StreamEndErrorKind::TrailingTruncation
+        );
+        // and it stays ended
+        assert_eq!(reader.read(&mut [0u8; 16]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_corrupt_xz_stream_is_fatal_unless_skipping_read_errors() {
+        let data = make_noise(1024 * 1024);
+        let mut compressed = xz_compress(&data);
+        // corrupt the block header (after the 12-byte stream header), with most of the input left
+        compressed[13] ^= 0xFF;
+
+        let mut reader = TruncationTolerantReader::new(
+            XzDecoder::new(Cursor::new(compressed.clone())),
+            is_xz_input_exhausted,
+            false,
+        );
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+
+        let mut reader = TruncationTolerantReader::new(
+            XzDecoder::new(Cursor::new(compressed)),
+            is_xz_input_exhausted,
+            true,
+        );
+        let mut read_data = Vec::new();
+        reader.read_to_end(&mut read_data).unwrap();
+        assert!(read_data.len() < data.len());
+        assert_eq!(
+            reader.stream_end_error.unwrap().kind,
+            StreamEndErrorKind::MidStreamCorruption
+        );
+    }
+
+    #[test]
+    fn test_complete_stream_has_no_stream_end_error() {
+        let data = make_noise(100_000);
+        let mut reader = TruncationTolerantReader::new(
+            XzDecoder::new(Cursor::new(xz_compress(&data))),
+            is_xz_input_exhausted,
+            false,
+        );
+        let mut read_data = Vec::new();
+        reader.read_to_end(&mut read_data).unwrap();
+        assert_eq!(read_data, data);
+        assert!(reader.stream_end_error.is_none());
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+const XZ_STREAM_HEADER_LEN: u64 = 12;
+const XZ_STREAM_FOOTER_LEN: u64 = 12;
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Reads the uncompressed size that some compressed formats record in the file itself, so progress
+/// stats can use the true total instead of estimating it from the compression ratio so far.
+/// Returns None if the format doesn't record it, or it can't be read.
+pub fn read_declared_uncompressed_size(file: &mut File, compression_format: &str) -> Option<u64> {
+    let result = match compression_format {
+        "xz" => read_xz_uncompressed_size(file),
+        "zstd" => {
+            let mut frame_header = [0u8; 18]; // max frame header length
+            file.rewind()
+                .and_then(|_| file.read(&mut frame_header))
+                .map(|bytes_read| parse_zstd_frame_content_size(&frame_header[..bytes_read]))
+        }
+        _ => Ok(None),
+    };
+    // leave the file where the decoder expects it
+    file.rewind().ok()?;
+    result.ok().flatten()
+}
+
+/// Sums the uncompressed sizes of all blocks in the xz file's indexes. Each index is found by
+/// seeking to its stream footer, from the end of the file back, so files of several concatenated
+/// streams (like `cat a.xz b.xz`, with or without stream padding) are summed too.
+fn read_xz_uncompressed_size<R: Read + Seek>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut stream_end = reader.seek(SeekFrom::End(0))?;
+    let mut stream_count = 0;
+    let mut total_uncompressed_size: u64 = 0;
+    while stream_end > 0 {
+        // skip stream padding (null bytes, in multiples of 4)
+        let mut last_word = [0u8; 4];
+        if stream_end < 4 {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(stream_end - 4))?;
+        reader.read_exact(&mut last_word)?;
+        if last_word == [0u8; 4] {
+            stream_end -= 4;
+            continue;
+        }
+        if stream_end < XZ_STREAM_HEADER_LEN + XZ_STREAM_FOOTER_LEN {
+            return Ok(None);
+        }
+
+        // Stream footer: CRC32 (4), Backward Size (4), Stream Flags (2), Footer Magic "YZ" (2)
+        let mut footer = [0u8; XZ_STREAM_FOOTER_LEN as usize];
+        reader.seek(SeekFrom::Start(stream_end - XZ_STREAM_FOOTER_LEN))?;
+        reader.read_exact(&mut footer)?;
+        if &footer[10..12] != b"YZ" {
+            return Ok(None);
+        }
+        let backward_size = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as u64;
+        let index_len = (backward_size + 1) * 4;
+        if index_len + XZ_STREAM_HEADER_LEN + XZ_STREAM_FOOTER_LEN > stream_end {
+            return Ok(None);
+        }
+
+        let mut index = vec![0u8; index_len as usize];
+        reader.seek(SeekFrom::Start(
+            stream_end - XZ_STREAM_FOOTER_LEN - index_len,
+        ))?;
+        reader.read_exact(&mut index)?;
+        let (blocks_len, uncompressed_size) = match parse_xz_index(&index) {
+            Some(sizes) => sizes,
+            None => return Ok(None),
+        };
+        let stream_len = XZ_STREAM_HEADER_LEN + blocks_len + index_len + XZ_STREAM_FOOTER_LEN;
+        if stream_len > stream_end {
+            return Ok(None);
+        }
+        total_uncompressed_size = match total_uncompressed_size.checked_add(uncompressed_size) {
+            Some(total_uncompressed_size) => total_uncompressed_size,
+            None => return Ok(None),
+        };
+        stream_end -= stream_len;
+        stream_count += 1;
+    }
+    match stream_count {
+        0 => Ok(None),
+        _ => Ok(Some(total_uncompressed_size)),
+    }
+}
+
+/// Index: Indicator (0x00), Number of Records, then (Unpadded Size, Uncompressed Size) per record,
+/// all as xz multibyte integers. Returns the length of the stream's blocks (each padded to a
+/// multiple of 4), and their total uncompressed size.
+fn parse_xz_index(index: &[u8]) -> Option<(u64, u64)> {
+    if index.first() != Some(&0x00) {
+        return None;
+    }
+    let mut pos = 1;
+    let record_count = read_xz_multibyte_int(index, &mut pos)?;
+    let mut blocks_len: u64 = 0;
+    let mut total_uncompressed_size: u64 = 0;
+    for _ in 0..record_count {
+        let unpadded_size = read_xz_multibyte_int(index, &mut pos)?;
+        blocks_len = blocks_len.checked_add(unpadded_size.div_ceil(4) * 4)?;
+        total_uncompressed_size =
+            total_uncompressed_size.checked_add(read_xz_multibyte_int(index, &mut pos)?)?;
+    }
+    Some((blocks_len, total_uncompressed_size))
+}
+
+fn read_xz_multibyte_int(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut val: u64 = 0;
+    for i in 0..9 {
+        let byte = *buf.get(*pos)?;
+        *pos += 1;
+        val |= ((byte & 0x7F) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some(val);
+        }
+    }
+    None
+}
+
+/// Reads the Frame_Content_Size field from the header of the first zstd frame, if it's present.
+pub fn parse_zstd_frame_content_size(frame: &[u8]) -> Option<u64> {
+    if frame.len() < 5 || frame[0..4] != ZSTD_MAGIC {
+        return None;
+    }
+    let frame_header_descriptor = frame[4];
+    let fcs_flag = frame_header_descriptor >> 6;
+    let single_segment = (frame_header_descriptor >> 5) & 1 == 1;
+    let dictionary_id_len = match frame_header_descriptor & 0b11 {
+        0 => 0,
+        1 => 1,
+        2 => 2,
+        _ => 4,
+    };
+    let fcs_len = match (fcs_flag, single_segment) {
+        (0, false) => return None, // content size not declared
+        (0, true) => 1,
+        (1, _) => 2,
+        (2, _) => 4,
+        _ => 8,
+    };
+
+    let fcs_start = 5 + (if single_segment { 0 } else { 1 }) + dictionary_id_len;
+    let fcs_bytes = frame.get(fcs_start..(fcs_start + fcs_len))?;
+    let mut le_bytes = [0u8; 8];
+    le_bytes[..fcs_len].copy_from_slice(fcs_bytes);
+    let fcs = u64::from_le_bytes(le_bytes);
+    match fcs_len {
+        2 => Some(fcs + 256), // the 2-byte form is offset by 256
+        _ => Some(fcs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn test_parse_zstd_frame_content_size() {
+        // A complete zstd frame: single segment with a 1-byte content size, then one raw block with "Hello"
+        let mut frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0x20, 5];
+        frame.extend_from_slice(&[(5 << 3) | 1, 0x00, 0x00]);
+        frame.extend_from_slice(b"Hello");
+        assert_eq!(parse_zstd_frame_content_size(&frame), Some(5));
+
+        // 2-byte content size field (offset by 256)
+        let frame = [0x28, 0xB5, 0x2F, 0xFD, 0x40, 0x00, 0x10, 0x27];
+        assert_eq!(parse_zstd_frame_content_size(&frame), Some(0x2710 + 256));
+
+        // 8-byte content size field, with a window descriptor
+        let mut frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0xC0, 0x58];
+        frame.extend_from_slice(&(10_000_000_000u64).to_le_bytes());
+        assert_eq!(parse_zstd_frame_content_size(&frame), Some(10_000_000_000));
+
+        // content size not declared
+        let frame = [0x28, 0xB5, 0x2F, 0xFD, 0x00, 0x58];
+        assert_eq!(parse_zstd_frame_content_size(&frame), None);
+
+        // not zstd
+        assert_eq!(parse_zstd_frame_content_size(b"\x1F\x8B\x08\x00\x00"), None);
+    }
+
+    #[test]
+    fn test_read_xz_uncompressed_size() {
+        let data: Vec<u8> = (0..300_000).map(|i| (i % 97) as u8).collect();
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            read_xz_uncompressed_size(&mut Cursor::new(compressed.clone())).unwrap(),
+            Some(data.len() as u64)
+        );
+        assert_eq!(
+            read_xz_uncompressed_size(&mut Cursor::new(b"not an xz file".to_vec())).unwrap(),
+            None
+        );
+
+        // concatenated streams, with stream padding between them
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(&data[..1000]).unwrap();
+        let mut concatenated = encoder.finish().unwrap();
+        concatenated.extend_from_slice(&[0u8; 8]);
+        concatenated.extend_from_slice(&compressed);
+        assert_eq!(
+            read_xz_uncompressed_size(&mut Cursor::new(concatenated)).unwrap(),
+            Some(1000 + data.len() as u64)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut gz_encoder = GzEncoder::new(Vec::new(), Compression::default());
+    gz_encoder.write_all(bytes).unwrap();
+    gz_encoder.finish().unwrap()
+}
+
+#[test]
+fn test_search_gzip_image() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let needle_config_path = tmp_dir.path().join("needle_config.yaml");
+    fs::write(
+        &needle_config_path,
+        "- name: \"hello\"\n  val: \"Hello\"\n  val_format: ascii\n  description_notes: \"test\"\n  happiness_level: 1\n",
+    )
+    .unwrap();
+    let output_dir = tmp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+
+    // two gzip members (like `cat a.gz b.gz`), with one match across the first chunk boundary
+    // (4 MiB) and one in the second member
+    let mut image: Vec<u8> = (0..6_000_000).map(|i| (i % 7) as u8 + b'a').collect();
+    image[4_194_302..4_194_307].copy_from_slice(b"Hello");
+    image[5_500_000..5_500_005].copy_from_slice(b"Hello");
+    let mut gzip_image = gzip(&image[..5_000_000]);
+    gzip_image.extend(gzip(&image[5_000_000..]));
+    let image_path = tmp_dir.path().join("disk.img.gz");
+    fs::write(&image_path, &gzip_image).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_drive-image-searcher"))
+        .args(["-c", "gzip", "--deterministic"])
+        .arg("-i")
+        .arg(&image_path)
+        .arg("-n")
+        .arg(&needle_config_path)
+        .arg("-o")
+        .arg(&output_dir)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let results_dir = output_dir.join("results__disk.img.gz");
+    let jsonl = fs::read_to_string(results_dir.join("00_all_output_record.jsonl")).unwrap();
+    let found_offsets: Vec<u64> = jsonl
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["name"], "hello");
+            record["match_start_global_offset"].as_u64().unwrap()
+        })
+        .collect();
+    assert_eq!(found_offsets, vec![4_194_302, 5_500_000]);
+
+    // the whole image was decompressed
+    let run_stats: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(results_dir.join("04_run_stats.json")).unwrap())
+            .unwrap();
+    assert_eq!(run_stats["total_haystack_bytes_read"], 6_000_000);
+}
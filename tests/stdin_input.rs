@@ -0,0 +1,46 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_search_image_piped_to_stdin() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let needle_config_path = tmp_dir.path().join("needle_config.yaml");
+    fs::write(
+        &needle_config_path,
+        "- name: \"hello\"\n  val: \"Hello\"\n  val_format: ascii\n  description_notes: \"test\"\n  happiness_level: 1\n",
+    )
+    .unwrap();
+    let output_dir = tmp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+
+    let mut image: Vec<u8> = (0..3_000_000).map(|i| (i % 7) as u8 + b'a').collect();
+    image[2_500_000..2_500_005].copy_from_slice(b"Hello");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_drive-image-searcher"))
+        .args(["-c", "none", "-i", "-", "--deterministic"])
+        .arg("-n")
+        .arg(&needle_config_path)
+        .arg("-o")
+        .arg(&output_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&image).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let jsonl = fs::read_to_string(
+        output_dir
+            .join("results__stdin")
+            .join("00_all_output_record.jsonl"),
+    )
+    .unwrap();
+    let records: Vec<serde_json::Value> = jsonl
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["name"], "hello");
+    assert_eq!(records[0]["match_start_global_offset"], 2_500_000);
+}